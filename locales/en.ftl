@@ -0,0 +1,157 @@
+# Menu entries
+
+system-diagnostics = Diagnostics
+system-info = Connection info
+system-edit-config = Edit network-dmenu config
+system-edit-connections = Edit connections
+system-start-hotspot = Start Wi-Fi hotspot
+system-stop-hotspot = Stop Wi-Fi hotspot
+
+tailscale-disable-exit-node = Disable exit-node
+tailscale-login = Login / Reauthenticate
+tailscale-logout = Logout
+tailscale-hostname = Hostname: { $hostname }
+tailscale-set-operator = Allow { $user } to manage Tailscale without root
+tailscale-operator-set = { $user } can now manage Tailscale without root
+tailscale-suggested-exit-node = Suggested exit node
+tailscale-enable = Enable tailscale
+tailscale-enable-key-expiry = Enable tailscale (key expires { $date })
+tailscale-ping-peer = Ping { $peer }
+tailscale-drive-share = Drive share { $name }
+tailscale-advertise-route = Advertise route { $route }
+tailscale-start-funnel = Start funnel preset { $name }
+tailscale-stop-serve = Stop serve on port { $port } ({ $protocol })
+tailscale-stop-funnel = Stop funnel on port { $port } ({ $protocol })
+mullvad-country = { $country } ({ $nodes } nodes)
+mullvad-random-node = Random node
+mullvad-random-node-in = Random node in { $country }
+mullvad-filter = Server filter: { $filter }
+mullvad-filter-all = All servers
+mullvad-filter-owned = Owned servers only
+
+mullvad-app-connect = Connect Mullvad
+mullvad-app-disconnect = Disconnect Mullvad ({ $relay })
+mullvad-app-country = Mullvad { $country } ({ $cities } cities)
+mullvad-app-city = Mullvad { $city } ({ $code })
+mullvad-app-any-city = Mullvad { $country } (any city)
+
+tor-check-circuit = Check Tor circuit
+tor-service = Tor service
+dns-flush-cache = Flush DNS cache
+kill-switch = Kill switch
+
+wifi-disconnect = Disconnect
+wifi-connect = Connect
+wifi-connect-access-point = Connect to specific access point…
+wifi-connect-hidden = Connect to hidden network…
+wifi-connect-wps = Connect via WPS push button
+wifi-forget = Forget network
+wifi-rescan = Rescan Wi-Fi
+wifi-saved-networks = Saved networks…
+wifi-out-of-range = { $name } (out of range)
+wifi-interface = Interface { $interface }…
+wifi-share = Share current network
+wifi-history = Connection history
+wifi-policy = Policy of { $ssid }: { $policy }
+wifi-failed-recently = ⚠ failed { $count }× recently
+entry-stale = ⏳ stale
+history-summary = { $ssid }: connected { $connections }×, { $duration } in total, failed { $failures }× recently
+wifi-autoconnect = Autoconnect
+wifi-autoconnect-priority = Autoconnect priority ({ $priority })
+wifi-connect-when-available = Connect when available
+wifi-activate-profile = Activate profile now
+
+bluetooth-pair = Pair new device…
+bluetooth-profile = Profile { $profile } - { $card }
+bluetooth-remove = Remove { $name } - { $address }
+
+toggle-accept-dns = Accept DNS
+toggle-accept-routes = Accept routes
+toggle-airplane-mode = Airplane mode
+toggle-bluetooth-power = Power
+toggle-bluetooth-radio = Radio bluetooth
+toggle-exit-node-allow-lan = Allow LAN access while using exit node
+toggle-ipv6 = IPv6 on the active connection
+toggle-metered = Metered active connection
+toggle-shields = Shields up
+toggle-wifi-power-save = Power save
+toggle-wifi-radio = Radio wifi
+
+# Prompts and confirmations
+
+confirm-yes = Yes, { $operation }
+confirm-no = No
+confirm-remove-device = remove { $address }
+confirm-remember-password = remember password for { $ssid }
+confirm-logout = log out of Tailscale
+confirm-tailscale-operator = make { $user } the Tailscale operator
+prompt-hidden-ssid = Hidden SSID
+prompt-tailscale-hostname = New hostname (now { $hostname })
+prompt-autoconnect-priority = Autoconnect priority
+prompt-identity = { $ssid } identity
+prompt-wifi-password = Enter { $ssid } password
+prompt-bluetooth-pin = Enter PIN for { $address }
+prompt-vpn-password = Password for { $name }
+prompt-vpn-otp = One-time password for { $name }
+
+# Menu status
+
+status-wifi = Wi-Fi: { $ssid }
+status-exit-node = Exit node: { $node }
+tray-open-menu = Open menu
+
+# Notifications
+
+action-failed = Failed: { $action }
+batch-applied = { $applied } of { $total } actions applied
+config-invalid = Invalid config
+wifi = Wi-Fi
+wifi-connected = Connected to { $ssid }
+wifi-scanning = Scanning…
+wifi-press-wps = Press the WPS button on your router
+wifi-connect-once-in-range = { $name } will be connected once in range
+wifi-connection-failed = Could not connect to { $ssid }: { $reason }
+wifi-hotspot = Wi-Fi hotspot
+wifi-credentials =
+    SSID: { $ssid }
+    Password: { $password }
+wifi-no-password = none
+connection-info = Connection info
+connection-info-copied = Connection info (copied)
+connectivity-location =
+    IP: { $ip }
+    Location: { $city }, { $country }
+    Provider: { $provider }
+connection-status = Connected Status
+exit-node-not-in-effect = Exit node not in effect
+exit-node-unexpected-location =
+    Expected { $expected }, got:
+    { $location }
+diagnostics = Diagnostics
+security = Wi-Fi security
+security-open-network = { $ssid } is an open network, its traffic is not encrypted
+security-open-protected = { $ssid } is an open network, traffic now goes through { $name }
+security-protection-reverted = Back on a secured network, open network protection disabled
+policy-applied = { $ssid }: { $policy }
+policy-disable-exit-node = exit node disabled
+policy-exit-node = exit node { $node }
+policy-mullvad = Mullvad exit node in { $country }
+policy-trusted = trusted network
+tailscale = Tailscale
+tailscale-peer-online = { $peer } is online
+tailscale-peer-offline = { $peer } is offline
+tailscale-exit-node-offline = Exit node went offline
+tailscale-authenticate = Authenticate at { $url }
+tailscale-no-exit-node = No exit node available
+tailscale-no-suggestion = No exit node suggestion available
+tailscale-peer-ip = { $peer } IP { $ip }
+tailscale-peer-ip-copied = Copied { $peer } IP { $ip }
+tailscale-ping-result = { $peer }: { $summary }
+tailscale-no-pong = { $peer }: no pong
+tor-not-in-effect = Tor not in effect
+tor-not-in-effect-body = Traffic through { $proxy } does not leave through Tor
+tor-connected = Connected through Tor
+tor-exit = Exit IP: { $ip }
+tor-exit-location =
+    Exit IP: { $ip }
+    Exit location: { $city }, { $country }
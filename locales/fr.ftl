@@ -0,0 +1,157 @@
+# Entrées du menu
+
+system-diagnostics = Diagnostic
+system-info = Informations de connexion
+system-edit-config = Modifier la configuration de network-dmenu
+system-edit-connections = Modifier les connexions
+system-start-hotspot = Démarrer le point d'accès Wi-Fi
+system-stop-hotspot = Arrêter le point d'accès Wi-Fi
+
+tailscale-disable-exit-node = Désactiver le nœud de sortie
+tailscale-login = Se connecter / Se réauthentifier
+tailscale-logout = Se déconnecter
+tailscale-hostname = Nom d'hôte : { $hostname }
+tailscale-set-operator = Permettre à { $user } de gérer Tailscale sans root
+tailscale-operator-set = { $user } peut désormais gérer Tailscale sans root
+tailscale-suggested-exit-node = Nœud de sortie suggéré
+tailscale-enable = Activer tailscale
+tailscale-enable-key-expiry = Activer tailscale (la clé expire le { $date })
+tailscale-ping-peer = Ping { $peer }
+tailscale-drive-share = Partage drive { $name }
+tailscale-advertise-route = Annoncer la route { $route }
+tailscale-start-funnel = Démarrer le funnel { $name }
+tailscale-stop-serve = Arrêter le serve du port { $port } ({ $protocol })
+tailscale-stop-funnel = Arrêter le funnel du port { $port } ({ $protocol })
+mullvad-country = { $country } ({ $nodes } nœuds)
+mullvad-random-node = Nœud aléatoire
+mullvad-random-node-in = Nœud aléatoire en { $country }
+mullvad-filter = Filtre des serveurs : { $filter }
+mullvad-filter-all = Tous les serveurs
+mullvad-filter-owned = Serveurs possédés uniquement
+
+mullvad-app-connect = Connecter Mullvad
+mullvad-app-disconnect = Déconnecter Mullvad ({ $relay })
+mullvad-app-country = Mullvad { $country } ({ $cities } villes)
+mullvad-app-city = Mullvad { $city } ({ $code })
+mullvad-app-any-city = Mullvad { $country } (toute ville)
+
+tor-check-circuit = Vérifier le circuit Tor
+tor-service = Service Tor
+dns-flush-cache = Vider le cache DNS
+kill-switch = Kill switch
+
+wifi-disconnect = Se déconnecter
+wifi-connect = Se connecter
+wifi-connect-access-point = Se connecter à un point d'accès précis…
+wifi-connect-hidden = Se connecter à un réseau masqué…
+wifi-connect-wps = Se connecter par bouton WPS
+wifi-forget = Oublier un réseau
+wifi-rescan = Rechercher les réseaux Wi-Fi
+wifi-saved-networks = Réseaux enregistrés…
+wifi-out-of-range = { $name } (hors de portée)
+wifi-interface = Interface { $interface }…
+wifi-share = Partager le réseau actuel
+wifi-history = Historique des connexions
+wifi-policy = Règle de { $ssid } : { $policy }
+wifi-failed-recently = ⚠ échec { $count }× récemment
+entry-stale = ⏳ obsolète
+history-summary = { $ssid } : connecté { $connections }×, { $duration } au total, échec { $failures }× récemment
+wifi-autoconnect = Connexion automatique
+wifi-autoconnect-priority = Priorité de connexion automatique ({ $priority })
+wifi-connect-when-available = Se connecter dès que disponible
+wifi-activate-profile = Activer le profil maintenant
+
+bluetooth-pair = Appairer un nouvel appareil…
+bluetooth-profile = Profil { $profile } - { $card }
+bluetooth-remove = Supprimer { $name } - { $address }
+
+toggle-accept-dns = Accepter le DNS
+toggle-accept-routes = Accepter les routes
+toggle-airplane-mode = Mode avion
+toggle-bluetooth-power = Alimentation
+toggle-bluetooth-radio = Radio bluetooth
+toggle-exit-node-allow-lan = Autoriser le réseau local avec le nœud de sortie
+toggle-ipv6 = IPv6 sur la connexion active
+toggle-metered = Connexion active limitée
+toggle-shields = Boucliers levés
+toggle-wifi-power-save = Économie d'énergie
+toggle-wifi-radio = Radio wifi
+
+# Invites et confirmations
+
+confirm-yes = Oui, { $operation }
+confirm-no = Non
+confirm-remove-device = supprimer { $address }
+confirm-remember-password = retenir le mot de passe de { $ssid }
+confirm-logout = se déconnecter de Tailscale
+confirm-tailscale-operator = faire de { $user } l'opérateur Tailscale
+prompt-hidden-ssid = SSID masqué
+prompt-tailscale-hostname = Nouveau nom d'hôte (actuellement { $hostname })
+prompt-autoconnect-priority = Priorité de connexion automatique
+prompt-identity = Identité pour { $ssid }
+prompt-wifi-password = Mot de passe de { $ssid }
+prompt-bluetooth-pin = Code PIN de { $address }
+prompt-vpn-password = Mot de passe de { $name }
+prompt-vpn-otp = Mot de passe à usage unique de { $name }
+
+# Menu status
+
+status-wifi = Wi-Fi : { $ssid }
+status-exit-node = Nœud de sortie : { $node }
+tray-open-menu = Ouvrir le menu
+
+# Notifications
+
+action-failed = Échec : { $action }
+batch-applied = { $applied } actions sur { $total } appliquées
+config-invalid = Configuration invalide
+wifi = Wi-Fi
+wifi-connected = Connecté à { $ssid }
+wifi-scanning = Recherche en cours…
+wifi-press-wps = Appuyez sur le bouton WPS de votre routeur
+wifi-connect-once-in-range = { $name } sera connecté une fois à portée
+wifi-connection-failed = Impossible de se connecter à { $ssid } : { $reason }
+wifi-hotspot = Point d'accès Wi-Fi
+wifi-credentials =
+    SSID : { $ssid }
+    Mot de passe : { $password }
+wifi-no-password = aucun
+connection-info = Informations de connexion
+connection-info-copied = Informations de connexion (copiées)
+connectivity-location =
+    IP : { $ip }
+    Emplacement : { $city }, { $country }
+    Fournisseur : { $provider }
+connection-status = État de la connexion
+exit-node-not-in-effect = Nœud de sortie inactif
+exit-node-unexpected-location =
+    { $expected } attendu, obtenu :
+    { $location }
+diagnostics = Diagnostic
+security = Sécurité Wi-Fi
+security-open-network = { $ssid } est un réseau ouvert, son trafic n’est pas chiffré
+security-open-protected = { $ssid } est un réseau ouvert, le trafic passe désormais par { $name }
+security-protection-reverted = De retour sur un réseau sécurisé, protection des réseaux ouverts désactivée
+policy-applied = { $ssid } : { $policy }
+policy-disable-exit-node = nœud de sortie désactivé
+policy-exit-node = nœud de sortie { $node }
+policy-mullvad = nœud de sortie Mullvad en { $country }
+policy-trusted = réseau de confiance
+tailscale = Tailscale
+tailscale-peer-online = { $peer } est en ligne
+tailscale-peer-offline = { $peer } est hors ligne
+tailscale-exit-node-offline = Le nœud de sortie est hors ligne
+tailscale-authenticate = Authentifiez-vous sur { $url }
+tailscale-no-exit-node = Aucun nœud de sortie disponible
+tailscale-no-suggestion = Aucune suggestion de nœud de sortie
+tailscale-peer-ip = IP de { $peer } : { $ip }
+tailscale-peer-ip-copied = IP de { $peer } copiée : { $ip }
+tailscale-ping-result = { $peer } : { $summary }
+tailscale-no-pong = { $peer } : pas de réponse
+tor-not-in-effect = Tor inactif
+tor-not-in-effect-body = Le trafic passant par { $proxy } ne sort pas par Tor
+tor-connected = Connecté via Tor
+tor-exit = IP de sortie : { $ip }
+tor-exit-location =
+    IP de sortie : { $ip }
+    Emplacement de sortie : { $city }, { $country }
@@ -1,12 +1,32 @@
-use crate::command::{read_output_lines, CommandRunner};
-use crate::format_entry;
+use crate::bluetooth_dbus::{
+    get_bluez_connected_devices, get_bluez_paired_devices, is_bluez_dbus_available,
+    set_bluez_device_connected,
+};
+use crate::columns::pad;
+use crate::command::{is_command_installed, read_output_lines, CommandRunner};
+use crate::i18n::tr_args;
+use crate::utils::prompt_with_pinentry;
+use crate::{confirm, format_entry, select_from_menu, Config};
 use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::error::Error;
-use std::process::Output;
+use std::io::{ErrorKind, Read, Write};
+use std::process::{Command, Output, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+use tracing::{debug, warn};
+
+/// Maximum time to wait for bluetoothctl output while pairing.
+const PAIRING_TIMEOUT: Duration = Duration::from_secs(30);
 
 /// Represents actions that can be performed on Bluetooth devices.
-#[derive(Debug)]
+#[derive(Debug, Deserialize, Serialize)]
 pub enum BluetoothAction {
+    Pair,
+    Remove(String),
+    SetProfile(String, String),
     ToggleConnect(String),
 }
 
@@ -14,7 +34,11 @@ pub enum BluetoothAction {
 pub fn get_paired_bluetooth_devices(
     command_runner: &dyn CommandRunner,
 ) -> Result<Vec<BluetoothAction>, Box<dyn Error>> {
-    let output = command_runner.run_command("bluetoothctl", &["devices"])?;
+    if is_bluez_dbus_available() {
+        return get_bluez_paired_devices();
+    }
+
+    let output = command_runner.run_query("bluetoothctl", &["devices"])?;
     let connected_devices = get_connected_devices(command_runner)?;
 
     if output.status.success() {
@@ -34,12 +58,16 @@ fn parse_bluetooth_devices(
     let devices = reader
         .into_iter()
         .filter_map(|line| parse_bluetooth_device(line, connected_devices))
+        .flatten()
         .collect();
     Ok(devices)
 }
 
-/// Parses a line of Bluetooth device information and returns a `BluetoothAction` if valid.
-fn parse_bluetooth_device(line: String, connected_devices: &[String]) -> Option<BluetoothAction> {
+/// Parses a line of Bluetooth device information and returns its `BluetoothAction`s if valid.
+fn parse_bluetooth_device(
+    line: String,
+    connected_devices: &[String],
+) -> Option<Vec<BluetoothAction>> {
     // Define a regex pattern for matching MAC addresses and device names
     // Check if the line matches the pattern and extract captures
     Regex::new(r"([0-9A-Fa-f]{2}(:[0-9A-Fa-f]{2}){5})\s+(.*)")
@@ -56,12 +84,15 @@ fn parse_bluetooth_device(line: String, connected_devices: &[String]) -> Option<
                     // Check if the device is active
                     let is_active = connected_devices.contains(&addr);
 
-                    // Return the appropriate BluetoothAction
-                    BluetoothAction::ToggleConnect(format_entry(
-                        "bluetooth",
-                        if is_active { "✅" } else { " " },
-                        &format!("{nm:<25} - {addr}"),
-                    ))
+                    // Return the appropriate BluetoothActions
+                    vec![
+                        BluetoothAction::ToggleConnect(format_entry(
+                            "bluetooth",
+                            if is_active { "✅" } else { " " },
+                            &format!("{} - {addr}", pad(&nm, 25)),
+                        )),
+                        remove_device_action(&nm, &addr),
+                    ]
                 })
             })
         })
@@ -69,11 +100,17 @@ fn parse_bluetooth_device(line: String, connected_devices: &[String]) -> Option<
 
 /// Handles a Bluetooth action, such as connecting or disconnecting a device.
 pub fn handle_bluetooth_action(
+    config: &Config,
     action: &BluetoothAction,
     connected_devices: &[String],
     command_runner: &dyn CommandRunner,
 ) -> Result<bool, Box<dyn Error>> {
     match action {
+        BluetoothAction::Pair => pair_new_device(config, command_runner),
+        BluetoothAction::Remove(device) => remove_device(config, device, command_runner),
+        BluetoothAction::SetProfile(card, profile) => {
+            set_audio_profile(card, profile, command_runner)
+        }
         BluetoothAction::ToggleConnect(device) => {
             connect_to_bluetooth_device(device, connected_devices, command_runner)
         }
@@ -89,8 +126,12 @@ fn connect_to_bluetooth_device(
     if let Some(address) = extract_device_address(device) {
         let is_active = connected_devices.contains(&address);
         let action = if is_active { "disconnect" } else { "connect" };
-        #[cfg(debug_assertions)]
-        println!("Connect to Bluetooth device: {address}");
+        debug!("Connect to Bluetooth device: {address}");
+
+        if is_bluez_dbus_available() {
+            return set_bluez_device_connected(&address, !is_active);
+        }
+
         let status = command_runner
             .run_command("bluetoothctl", &[action, &address])?
             .status;
@@ -98,8 +139,7 @@ fn connect_to_bluetooth_device(
         if status.success() {
             Ok(true)
         } else {
-            #[cfg(debug_assertions)]
-            eprintln!("Failed to connect to Bluetooth device: {address}");
+            warn!("Failed to connect to Bluetooth device: {address}");
             Ok(false)
         }
     } else {
@@ -107,8 +147,167 @@ fn connect_to_bluetooth_device(
     }
 }
 
+/// Builds the action removing the pairing of a device.
+pub fn remove_device_action(name: &str, address: &str) -> BluetoothAction {
+    BluetoothAction::Remove(format_entry(
+        "bluetooth",
+        "🗑️",
+        &tr_args(
+            "bluetooth-remove",
+            &[("name", &pad(name, 18)), ("address", address)],
+        ),
+    ))
+}
+
+/// Removes the pairing of a device after confirmation.
+fn remove_device(
+    config: &Config,
+    device: &str,
+    command_runner: &dyn CommandRunner,
+) -> Result<bool, Box<dyn Error>> {
+    let Some(address) = extract_device_address(device) else {
+        return Ok(false);
+    };
+
+    if !confirm(
+        config,
+        &tr_args("confirm-remove-device", &[("address", &address)]),
+    )? {
+        return Ok(false);
+    }
+
+    let status = command_runner
+        .run_command("bluetoothctl", &["remove", &address])?
+        .status;
+    Ok(status.success())
+}
+
+/// Scans for nearby devices, then pairs, trusts and connects the selected one.
+fn pair_new_device(
+    config: &Config,
+    command_runner: &dyn CommandRunner,
+) -> Result<bool, Box<dyn Error>> {
+    let timeout = config.bluetooth_scan_timeout.to_string();
+    command_runner.run_command("bluetoothctl", &["--timeout", &timeout, "scan", "on"])?;
+
+    let paired = get_device_addresses(command_runner, &["devices", "Paired"])?;
+    let output = command_runner.run_query("bluetoothctl", &["devices"])?;
+    let devices: Vec<String> = read_output_lines(&output)?
+        .into_iter()
+        .filter_map(|line| {
+            let mut parts = line.splitn(3, ' ');
+            parts.next().filter(|kind| *kind == "Device")?;
+            let address = parts.next()?;
+            let name = parts.next().unwrap_or(address);
+            (!paired.iter().any(|paired| paired == address))
+                .then(|| format!("{} - {address}", pad(name, 25)))
+        })
+        .collect();
+
+    if devices.is_empty() {
+        return Ok(false);
+    }
+
+    let selection = select_from_menu(config, &devices)?;
+    let Some(address) = extract_device_address(&selection) else {
+        return Ok(false);
+    };
+
+    if !pair_device(config, &address)? {
+        warn!("Failed to pair Bluetooth device: {address}");
+        return Ok(false);
+    }
+
+    command_runner.run_command("bluetoothctl", &["trust", &address])?;
+    let status = command_runner
+        .run_command("bluetoothctl", &["connect", &address])?
+        .status;
+    Ok(status.success())
+}
+
+/// Pairs a device in an interactive bluetoothctl session, answering agent prompts.
+fn pair_device(config: &Config, address: &str) -> Result<bool, Box<dyn Error>> {
+    let mut child = Command::new("bluetoothctl")
+        .env("LC_ALL", "C")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    let mut stdin = child.stdin.take().ok_or("Failed to open stdin")?;
+    let mut stdout = child.stdout.take().ok_or("Failed to open stdout")?;
+
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let mut buffer = [0u8; 1024];
+        while let Ok(read) = stdout.read(&mut buffer) {
+            if read == 0
+                || sender
+                    .send(String::from_utf8_lossy(&buffer[..read]).to_string())
+                    .is_err()
+            {
+                break;
+            }
+        }
+    });
+
+    writeln!(stdin, "agent KeyboardDisplay")?;
+    writeln!(stdin, "default-agent")?;
+    writeln!(stdin, "pair {address}")?;
+
+    let passkey_regex = Regex::new(r"Confirm passkey (\d+)")?;
+    let mut transcript = String::new();
+    let paired = loop {
+        let Ok(chunk) = receiver.recv_timeout(PAIRING_TIMEOUT) else {
+            break false;
+        };
+        transcript.push_str(&chunk);
+
+        if transcript.contains("Pairing successful") {
+            break true;
+        } else if transcript.contains("Failed to pair") {
+            break false;
+        } else if transcript.contains("Enter PIN code") || transcript.contains("Enter passkey") {
+            let pin = prompt_with_pinentry(
+                config,
+                &tr_args("prompt-bluetooth-pin", &[("address", address)]),
+            )?;
+            writeln!(stdin, "{pin}")?;
+            transcript.clear();
+        } else if let Some(caps) = passkey_regex.captures(&transcript) {
+            let confirm = format!("yes - confirm passkey {}", &caps[1]);
+            let answer = select_from_menu(config, &[confirm.clone(), "no".to_string()])?;
+            writeln!(stdin, "{}", if answer == confirm { "yes" } else { "no" })?;
+            transcript.clear();
+        } else if transcript.contains("Authorize service") {
+            writeln!(stdin, "yes")?;
+            transcript.clear();
+        }
+    };
+
+    writeln!(stdin, "quit").ok();
+    child.wait()?;
+    Ok(paired)
+}
+
+/// Retrieves the MAC addresses listed by a bluetoothctl command, none when bluetoothctl is
+/// not installed.
+fn get_device_addresses(
+    command_runner: &dyn CommandRunner,
+    args: &[&str],
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let output = match command_runner.run_query("bluetoothctl", args) {
+        Ok(output) => output,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err.into()),
+    };
+    Ok(read_output_lines(&output)?
+        .into_iter()
+        .filter(|line| line.starts_with("Device "))
+        .filter_map(|line| line.split_whitespace().nth(1).map(|s| s.to_string()))
+        .collect())
+}
+
 /// Extracts the MAC address from the given device string.
-fn extract_device_address(device: &str) -> Option<String> {
+pub fn extract_device_address(device: &str) -> Option<String> {
     Regex::new(r"([0-9A-Fa-f]{2}(:[0-9A-Fa-f]{2}){5})$")
         .ok()?
         .captures(device)
@@ -120,11 +319,266 @@ fn extract_device_address(device: &str) -> Option<String> {
 pub fn get_connected_devices(
     command_runner: &dyn CommandRunner,
 ) -> Result<Vec<String>, Box<dyn Error>> {
-    let output = command_runner.run_command("bluetoothctl", &["info"])?;
-    let mac_addresses = read_output_lines(&output)?
-        .into_iter()
-        .filter(|line| line.starts_with("Device "))
-        .filter_map(|line| line.split_whitespace().nth(1).map(|s| s.to_string()))
+    if is_bluez_dbus_available() {
+        return get_bluez_connected_devices();
+    }
+
+    get_device_addresses(command_runner, &["info"])
+}
+
+/// Checks if the default Bluetooth adapter is powered on.
+pub fn is_bluetooth_powered(command_runner: &dyn CommandRunner) -> Result<bool, Box<dyn Error>> {
+    let output = command_runner.run_query("bluetoothctl", &["show"])?;
+    Ok(read_output_lines(&output)?
+        .iter()
+        .any(|line| line.trim() == "Powered: yes"))
+}
+
+/// Retrieves the audio profile switching actions of connected Bluetooth devices.
+///
+/// Uses `pactl`, which talks to both PulseAudio and PipeWire (through pipewire-pulse), or
+/// else `wpctl` and `pw-dump` on PipeWire alone.
+pub fn get_audio_profile_actions(
+    connected_devices: &[String],
+    command_runner: &dyn CommandRunner,
+) -> Result<Vec<BluetoothAction>, Box<dyn Error>> {
+    let cards: Vec<String> = connected_devices
+        .iter()
+        .map(|address| format!("bluez_card.{}", address.replace(':', "_")))
         .collect();
-    Ok(mac_addresses)
+    if cards.is_empty() {
+        Ok(Vec::new())
+    } else if is_command_installed("pactl") {
+        get_pactl_profile_actions(&cards, command_runner)
+    } else if is_command_installed("wpctl") && is_command_installed("pw-dump") {
+        Ok(get_pipewire_cards(command_runner)?
+            .into_iter()
+            .filter(|card| cards.contains(&card.name))
+            .flat_map(|card| {
+                let PipeWireCard {
+                    name,
+                    active,
+                    profiles,
+                    ..
+                } = card;
+                profiles
+                    .into_iter()
+                    .filter(move |(_, profile)| Some(profile) != active.as_ref())
+                    .map(move |(_, profile)| BluetoothAction::SetProfile(name.clone(), profile))
+            })
+            .collect())
+    } else {
+        Ok(Vec::new())
+    }
+}
+
+/// Retrieves the audio profile switching actions of the given cards with `pactl`.
+fn get_pactl_profile_actions(
+    cards: &[String],
+    command_runner: &dyn CommandRunner,
+) -> Result<Vec<BluetoothAction>, Box<dyn Error>> {
+    let output = command_runner.run_query("pactl", &["list", "cards"])?;
+    if !output.status.success() {
+        return Ok(Vec::new());
+    }
+
+    let mut actions = Vec::new();
+    let mut card: Option<String> = None;
+    let mut profiles = Vec::new();
+    let mut in_profiles = false;
+
+    for line in read_output_lines(&output)? {
+        let trimmed = line.trim();
+        if let Some(name) = trimmed.strip_prefix("Name: ") {
+            card = cards.iter().find(|card| *card == name).cloned();
+            profiles.clear();
+        } else if trimmed == "Profiles:" {
+            in_profiles = true;
+        } else if let Some(active) = trimmed.strip_prefix("Active Profile: ") {
+            in_profiles = false;
+            if let Some(card) = &card {
+                actions.extend(
+                    profiles
+                        .drain(..)
+                        .filter(|profile: &String| profile != active)
+                        .map(|profile| BluetoothAction::SetProfile(card.clone(), profile)),
+                );
+            }
+        } else if in_profiles {
+            let profile = trimmed.split(':').next().unwrap_or_default();
+            if is_audio_profile(profile) && trimmed.contains("available: yes") {
+                profiles.push(profile.to_string());
+            }
+        }
+    }
+
+    Ok(actions)
+}
+
+/// Bluetooth audio device of PipeWire, with its available A2DP and HSP/HFP profiles.
+struct PipeWireCard {
+    id: u64,
+    name: String,
+    active: Option<String>,
+    profiles: Vec<(u64, String)>,
+}
+
+/// Retrieves the Bluetooth audio devices of PipeWire from `pw-dump`.
+fn get_pipewire_cards(
+    command_runner: &dyn CommandRunner,
+) -> Result<Vec<PipeWireCard>, Box<dyn Error>> {
+    let output = command_runner.run_query("pw-dump", &[])?;
+    if !output.status.success() {
+        return Ok(Vec::new());
+    }
+    parse_pipewire_cards(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Parses the Bluetooth audio devices out of the objects dumped by `pw-dump`.
+fn parse_pipewire_cards(dump: &str) -> Result<Vec<PipeWireCard>, Box<dyn Error>> {
+    let objects: Vec<Value> = serde_json::from_str(dump)?;
+    Ok(objects
+        .iter()
+        .filter(|object| object["type"] == "PipeWire:Interface:Device")
+        .filter(|object| object["info"]["props"]["device.api"] == "bluez5")
+        .filter_map(|object| {
+            let params = &object["info"]["params"];
+            let profiles = params["EnumProfile"]
+                .as_array()?
+                .iter()
+                .filter(|profile| profile["available"] != "no")
+                .filter_map(|profile| {
+                    let name = profile["name"].as_str()?;
+                    let index = profile["index"].as_u64()?;
+                    is_audio_profile(name).then(|| (index, name.to_string()))
+                })
+                .collect();
+            Some(PipeWireCard {
+                id: object["id"].as_u64()?,
+                name: object["info"]["props"]["device.name"].as_str()?.to_string(),
+                active: params["Profile"][0]["name"].as_str().map(str::to_string),
+                profiles,
+            })
+        })
+        .collect())
+}
+
+/// Switches the profile of an audio card with `pactl`, or else `wpctl`.
+fn set_audio_profile(
+    card: &str,
+    profile: &str,
+    command_runner: &dyn CommandRunner,
+) -> Result<bool, Box<dyn Error>> {
+    let status = if is_command_installed("pactl") {
+        command_runner
+            .run_command("pactl", &["set-card-profile", card, profile])?
+            .status
+    } else {
+        // wpctl takes the ids of the device and profile, which change across reconnections
+        let Some((id, index)) = get_pipewire_cards(command_runner)?
+            .into_iter()
+            .filter(|pipewire_card| pipewire_card.name == card)
+            .find_map(|pipewire_card| {
+                let (index, _) = pipewire_card
+                    .profiles
+                    .into_iter()
+                    .find(|(_, name)| name == profile)?;
+                Some((pipewire_card.id, index))
+            })
+        else {
+            return Ok(false);
+        };
+        command_runner
+            .run_command(
+                "wpctl",
+                &["set-profile", &id.to_string(), &index.to_string()],
+            )?
+            .status
+    };
+    Ok(status.success())
+}
+
+/// Checks if a card profile is an A2DP or HSP/HFP profile.
+fn is_audio_profile(profile: &str) -> bool {
+    profile.starts_with("a2dp")
+        || profile.starts_with("headset")
+        || profile.starts_with("handsfree")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{fixture, output, MockCommandRunner};
+
+    #[test]
+    fn parses_paired_devices() {
+        let devices = parse_bluetooth_devices(
+            &output(0, &fixture("bluetoothctl/devices.txt")),
+            &["00:1B:66:AA:BB:CC".to_string()],
+        )
+        .unwrap();
+
+        let toggles: Vec<_> = devices
+            .iter()
+            .filter_map(|device| match device {
+                BluetoothAction::ToggleConnect(entry) => Some(entry.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(toggles.len(), 3);
+        assert!(toggles[0].contains("✅") && toggles[0].contains("WH-1000XM4"));
+        assert!(!toggles[1].contains("✅") && toggles[1].contains("Logitech K380"));
+        assert_eq!(
+            toggles
+                .iter()
+                .filter_map(|entry| extract_device_address(entry))
+                .collect::<Vec<_>>(),
+            [
+                "00:1B:66:AA:BB:CC",
+                "5C:F3:70:11:22:33",
+                "7A:4C:21:9E:0D:F1"
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_device_addresses() {
+        let runner = MockCommandRunner::new()
+            .expect_fixture("bluetoothctl", &["info"], "bluetoothctl/info.txt")
+            .expect_missing("bluetoothctl", &["info"]);
+        assert_eq!(
+            get_device_addresses(&runner, &["info"]).unwrap(),
+            ["00:1B:66:AA:BB:CC"]
+        );
+        assert!(get_device_addresses(&runner, &["info"]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn reads_power_state() {
+        let runner = MockCommandRunner::new()
+            .expect_fixture("bluetoothctl", &["show"], "bluetoothctl/show.txt")
+            .expect_fixture(
+                "bluetoothctl",
+                &["show"],
+                "bluetoothctl/show-powered-off.txt",
+            );
+        assert!(is_bluetooth_powered(&runner).unwrap());
+        assert!(!is_bluetooth_powered(&runner).unwrap());
+    }
+
+    #[test]
+    fn parses_pipewire_cards() {
+        let cards = parse_pipewire_cards(&fixture("pw-dump/devices.json")).unwrap();
+        assert_eq!(cards.len(), 1);
+        assert_eq!(cards[0].id, 77);
+        assert_eq!(cards[0].name, "bluez_card.AA_BB_CC_DD_EE_FF");
+        assert_eq!(cards[0].active.as_deref(), Some("a2dp-sink"));
+        assert_eq!(
+            cards[0].profiles,
+            [
+                (1, "a2dp-sink".to_string()),
+                (2, "headset-head-unit".to_string())
+            ]
+        );
+    }
 }
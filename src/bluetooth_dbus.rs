@@ -0,0 +1,146 @@
+use crate::bluetooth::{remove_device_action, BluetoothAction};
+use crate::columns::pad;
+use crate::format_entry;
+use std::collections::HashMap;
+use std::error::Error;
+use zbus::blocking::{Connection, Proxy};
+use zbus::zvariant::{OwnedObjectPath, OwnedValue};
+
+const BLUEZ_SERVICE: &str = "org.bluez";
+const BLUEZ_DEVICE_INTERFACE: &str = "org.bluez.Device1";
+const BLUEZ_BATTERY_INTERFACE: &str = "org.bluez.Battery1";
+const OBJECT_MANAGER_INTERFACE: &str = "org.freedesktop.DBus.ObjectManager";
+
+type ManagedObjects = HashMap<OwnedObjectPath, HashMap<String, HashMap<String, OwnedValue>>>;
+
+/// Bluetooth device as reported by BlueZ.
+#[derive(Debug)]
+struct BluezDevice {
+    path: OwnedObjectPath,
+    address: String,
+    name: String,
+    paired: bool,
+    connected: bool,
+    battery: Option<u8>,
+    rssi: Option<i16>,
+}
+
+/// Checks if BlueZ is reachable on the system bus.
+pub fn is_bluez_dbus_available() -> bool {
+    Connection::system()
+        .and_then(|connection| get_managed_objects(&connection))
+        .is_ok()
+}
+
+/// Retrieves paired Bluetooth devices from BlueZ over D-Bus.
+pub fn get_bluez_paired_devices() -> Result<Vec<BluetoothAction>, Box<dyn Error>> {
+    let connection = Connection::system()?;
+    let devices = get_bluez_devices(&connection)?
+        .into_iter()
+        .filter(|device| device.paired)
+        .flat_map(|device| {
+            [
+                BluetoothAction::ToggleConnect(format_entry(
+                    "bluetooth",
+                    if device.connected { "✅" } else { " " },
+                    &format!(
+                        "{} {} - {}",
+                        pad(&device.name, 25),
+                        pad(&device_details(&device), 12),
+                        device.address
+                    ),
+                )),
+                remove_device_action(&device.name, &device.address),
+            ]
+        })
+        .collect();
+    Ok(devices)
+}
+
+/// Retrieves the addresses of connected Bluetooth devices from BlueZ over D-Bus.
+pub fn get_bluez_connected_devices() -> Result<Vec<String>, Box<dyn Error>> {
+    let connection = Connection::system()?;
+    Ok(get_bluez_devices(&connection)?
+        .into_iter()
+        .filter(|device| device.connected)
+        .map(|device| device.address)
+        .collect())
+}
+
+/// Connects or disconnects a Bluetooth device through BlueZ over D-Bus.
+pub fn set_bluez_device_connected(address: &str, connect: bool) -> Result<bool, Box<dyn Error>> {
+    let connection = Connection::system()?;
+    let Some(device) = get_bluez_devices(&connection)?
+        .into_iter()
+        .find(|device| device.address == address)
+    else {
+        return Ok(false);
+    };
+
+    let proxy = Proxy::new(
+        &connection,
+        BLUEZ_SERVICE,
+        device.path.as_ref(),
+        BLUEZ_DEVICE_INTERFACE,
+    )?;
+    let result = proxy.call_method(if connect { "Connect" } else { "Disconnect" }, &());
+    Ok(result.is_ok())
+}
+
+/// Formats battery percentage and RSSI of a device for display.
+fn device_details(device: &BluezDevice) -> String {
+    let battery = device
+        .battery
+        .map(|percentage| format!("🔋{percentage}%"))
+        .unwrap_or_default();
+    let rssi = device
+        .rssi
+        .map(|rssi| format!("{rssi}dBm"))
+        .unwrap_or_default();
+    format!("{battery} {rssi}").trim().to_string()
+}
+
+/// Retrieves all Bluetooth devices known to BlueZ.
+fn get_bluez_devices(connection: &Connection) -> Result<Vec<BluezDevice>, Box<dyn Error>> {
+    let mut devices: Vec<BluezDevice> = get_managed_objects(connection)?
+        .into_iter()
+        .filter_map(|(path, interfaces)| {
+            let device = interfaces.get(BLUEZ_DEVICE_INTERFACE)?;
+            let address: String = get_value(device, "Address")?;
+            let name = get_value::<String>(device, "Alias")
+                .or_else(|| get_value(device, "Name"))
+                .unwrap_or_else(|| address.clone());
+            let battery = interfaces
+                .get(BLUEZ_BATTERY_INTERFACE)
+                .and_then(|battery| get_value(battery, "Percentage"));
+            Some(BluezDevice {
+                paired: get_value(device, "Paired").unwrap_or(false),
+                connected: get_value(device, "Connected").unwrap_or(false),
+                rssi: get_value(device, "RSSI"),
+                path,
+                address,
+                name,
+                battery,
+            })
+        })
+        .collect();
+    devices.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(devices)
+}
+
+/// Retrieves all objects exported by BlueZ.
+fn get_managed_objects(connection: &Connection) -> zbus::Result<ManagedObjects> {
+    Proxy::new(connection, BLUEZ_SERVICE, "/", OBJECT_MANAGER_INTERFACE)?
+        .call("GetManagedObjects", &())
+}
+
+/// Extracts a typed property from a D-Bus property map.
+fn get_value<T>(properties: &HashMap<String, OwnedValue>, key: &str) -> Option<T>
+where
+    T: TryFrom<OwnedValue>,
+{
+    properties
+        .get(key)
+        .and_then(|value| value.try_clone().ok())
+        .and_then(|value| T::try_from(value).ok())
+}
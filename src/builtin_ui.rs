@@ -0,0 +1,222 @@
+use fuzzy_matcher::skim::SkimMatcherV2;
+use fuzzy_matcher::FuzzyMatcher;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style};
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use std::error::Error;
+use unicode_width::UnicodeWidthStr;
+
+/// Row of the selector, a section header or the index of an entry.
+#[derive(Debug, PartialEq)]
+pub enum Row {
+    Header(String),
+    Entry(usize),
+}
+
+/// State of the selector: the typed query and the highlighted match.
+struct Selector<'a> {
+    title: &'a str,
+    entries: &'a [String],
+    toggles: &'a [Option<bool>],
+    query: String,
+    selected: usize,
+}
+
+/// Shows the entries in an embedded terminal selector and returns the selected one.
+///
+/// Entries with a toggle state are shown with a checkbox. Without entries, the selector
+/// is a prompt returning the typed text, which is also returned when nothing matches it.
+pub fn run_builtin_menu(
+    title: &str,
+    entries: &[String],
+    toggles: &[Option<bool>],
+) -> Result<String, Box<dyn Error>> {
+    let mut selector = Selector {
+        title,
+        entries,
+        toggles,
+        query: String::new(),
+        selected: 0,
+    };
+    let mut terminal = ratatui::init();
+    let result = selector.run(&mut terminal);
+    ratatui::restore();
+    result
+}
+
+impl Selector<'_> {
+    /// Handles the keys until an entry is selected or the selector is closed.
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> Result<String, Box<dyn Error>> {
+        loop {
+            let rows = group_by_section(self.entries, &filter_entries(self.entries, &self.query));
+            // Entries are selected in display order, across the sections
+            let matches: Vec<usize> = rows
+                .iter()
+                .filter_map(|row| match row {
+                    Row::Entry(index) => Some(*index),
+                    Row::Header(_) => None,
+                })
+                .collect();
+            self.selected = self.selected.min(matches.len().saturating_sub(1));
+            terminal.draw(|frame| self.draw(frame, &rows, matches.get(self.selected)))?;
+
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            let control = key.modifiers.contains(KeyModifiers::CONTROL);
+            match key.code {
+                KeyCode::Esc => return Ok(String::new()),
+                KeyCode::Char('c') if control => return Ok(String::new()),
+                KeyCode::Enter => {
+                    return Ok(matches
+                        .get(self.selected)
+                        .map_or_else(|| self.query.clone(), |&index| self.entries[index].clone()))
+                }
+                KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+                KeyCode::Char('p') if control => self.selected = self.selected.saturating_sub(1),
+                KeyCode::Down => self.selected += 1,
+                KeyCode::Char('n') if control => self.selected += 1,
+                KeyCode::Backspace => {
+                    self.query.pop();
+                    self.selected = 0;
+                }
+                KeyCode::Char(c) => {
+                    self.query.push(c);
+                    self.selected = 0;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Draws the query input above the matches grouped by section.
+    fn draw(&self, frame: &mut Frame, rows: &[Row], selected: Option<&usize>) {
+        let [input_area, list_area] =
+            Layout::vertical([Constraint::Length(3), Constraint::Min(1)]).areas(frame.area());
+
+        let input =
+            Paragraph::new(format!("> {}", self.query)).block(Block::bordered().title(self.title));
+        frame.render_widget(input, input_area);
+        frame.set_cursor_position((
+            input_area.x + 3 + self.query.width() as u16,
+            input_area.y + 1,
+        ));
+
+        let mut state = ListState::default();
+        let items: Vec<ListItem> = rows
+            .iter()
+            .enumerate()
+            .map(|(position, row)| match row {
+                Row::Header(section) => ListItem::new(section.as_str())
+                    .style(Style::new().add_modifier(Modifier::BOLD | Modifier::UNDERLINED)),
+                Row::Entry(index) => {
+                    if selected == Some(index) {
+                        state.select(Some(position));
+                    }
+                    // The section is shown by the header
+                    let entry = &self.entries[*index];
+                    let text = entry
+                        .split_once("- ")
+                        .map_or(entry.as_str(), |(_, text)| text);
+                    match self.toggles.get(*index).copied().flatten() {
+                        Some(enabled) => ListItem::new(format!(
+                            "  [{}] {}",
+                            if enabled { "x" } else { " " },
+                            toggle_label(text)
+                        )),
+                        None => ListItem::new(format!("  {text}")),
+                    }
+                }
+            })
+            .collect();
+        let list = List::new(items).highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(list, list_area, &mut state);
+    }
+}
+
+/// Keeps the entries matching the query, best matches first.
+pub fn filter_entries(entries: &[String], query: &str) -> Vec<usize> {
+    if query.is_empty() {
+        return (0..entries.len()).collect();
+    }
+    let matcher = SkimMatcherV2::default();
+    let mut matches: Vec<(i64, usize)> = entries
+        .iter()
+        .enumerate()
+        .filter_map(|(index, entry)| Some((matcher.fuzzy_match(entry, query)?, index)))
+        .collect();
+    matches.sort_by_key(|&(score, _)| std::cmp::Reverse(score));
+    matches.into_iter().map(|(_, index)| index).collect()
+}
+
+/// Strips the icon of a toggle entry, whose state is shown by a switch instead.
+pub fn toggle_label(text: &str) -> &str {
+    text.split_once(' ').map_or(text, |(_, label)| label)
+}
+
+/// Gets the section of an entry, its prefix before the dash.
+fn section(entry: &str) -> &str {
+    entry
+        .split_once("- ")
+        .map_or("", |(prefix, _)| prefix.trim())
+}
+
+/// Groups the matches under a header per section, in the order sections first appear.
+pub fn group_by_section(entries: &[String], matches: &[usize]) -> Vec<Row> {
+    let mut sections: Vec<&str> = Vec::new();
+    for &index in matches {
+        let section = section(&entries[index]);
+        if !sections.contains(&section) {
+            sections.push(section);
+        }
+    }
+
+    let mut rows = Vec::new();
+    for name in sections {
+        if !name.is_empty() {
+            rows.push(Row::Header(name.to_string()));
+        }
+        rows.extend(
+            matches
+                .iter()
+                .filter(|&&index| section(&entries[index]) == name)
+                .map(|&index| Row::Entry(index)),
+        );
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filters_and_groups_entries() {
+        let entries: Vec<String> = [
+            "wifi      - 📶 Home",
+            "system    - 🩺 Diagnostics",
+            "wifi      - 📶 Office",
+        ]
+        .map(str::to_string)
+        .to_vec();
+
+        let matches = filter_entries(&entries, "");
+        assert_eq!(
+            group_by_section(&entries, &matches),
+            vec![
+                Row::Header("wifi".to_string()),
+                Row::Entry(0),
+                Row::Entry(2),
+                Row::Header("system".to_string()),
+                Row::Entry(1),
+            ]
+        );
+        assert_eq!(filter_entries(&entries, "ofc"), vec![2]);
+        assert!(filter_entries(&entries, "zzz").is_empty());
+    }
+}
@@ -0,0 +1,95 @@
+use crate::bluetooth_dbus::is_bluez_dbus_available;
+use crate::command::is_command_installed;
+use crate::launcher::Launcher;
+use crate::networkmanager_dbus::is_nm_dbus_available;
+use crate::utils::get_pinentry_command;
+use crate::{Args, Config};
+use thiserror::Error;
+use tracing::warn;
+
+/// Errors raised when a tool required by the menu is missing.
+#[derive(Debug, Error)]
+pub enum CapabilityError {
+    #[error("menu command `{0}` not found, install it or set dmenu_cmd in the config")]
+    MissingMenu(String),
+    #[error("no pinentry program found, install one or set pinentry_cmd in the config")]
+    MissingPinentry,
+}
+
+/// Optional tools detected at startup.
+#[derive(Debug)]
+pub struct Capabilities {
+    pub pinentry: bool,
+    pub wifi: bool,
+    pub bluetooth: bool,
+    pub tailscale: bool,
+}
+
+/// Checks that the menu command is installed, needed only when the menu is shown.
+pub fn check_menu_command(config: &Config) -> Result<(), CapabilityError> {
+    if !Launcher::detect(config).is_embedded() && !is_command_installed(&config.dmenu_cmd) {
+        return Err(CapabilityError::MissingMenu(config.dmenu_cmd.clone()));
+    }
+    Ok(())
+}
+
+/// Detects the optional tools of each section.
+pub fn detect_capabilities(config: &Config) -> Capabilities {
+    Capabilities {
+        pinentry: get_pinentry_command(config).is_ok_and(|command| {
+            command
+                .split_whitespace()
+                .next()
+                .is_some_and(is_command_installed)
+        }),
+        wifi: is_nm_dbus_available()
+            || is_command_installed("nmcli")
+            || is_command_installed("iwctl"),
+        bluetooth: is_bluez_dbus_available() || is_command_installed("bluetoothctl"),
+        tailscale: is_command_installed("tailscale"),
+    }
+}
+
+/// Prints a warning on stderr for each missing tool of an enabled section.
+pub fn warn_missing_tools(args: &Args, capabilities: &Capabilities) {
+    if !args.no_wifi && !capabilities.wifi {
+        warn!("neither NetworkManager nor iwctl found, Wi-Fi entries disabled");
+    }
+    if !args.no_wifi && capabilities.wifi && !capabilities.pinentry {
+        warn!(
+            "{}, only open and known Wi-Fi networks are shown",
+            CapabilityError::MissingPinentry
+        );
+    }
+    if !args.no_bluetooth && !capabilities.bluetooth {
+        warn!("neither BlueZ nor bluetoothctl found, Bluetooth entries disabled");
+    }
+    if !args.no_tailscale && !capabilities.tailscale {
+        warn!("tailscale not found, Tailscale entries disabled");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requires_the_menu_command_only_to_show_the_menu() {
+        let config: Config = toml::from_str(
+            r#"
+            dmenu_cmd = "network-dmenu-missing-launcher"
+            pinentry_cmd = "network-dmenu-missing-pinentry"
+            "#,
+        )
+        .unwrap();
+        let capabilities = detect_capabilities(&config);
+        assert!(!capabilities.pinentry);
+        assert!(matches!(
+            check_menu_command(&config),
+            Err(CapabilityError::MissingMenu(command)) if command == config.dmenu_cmd
+        ));
+
+        let config: Config = toml::from_str(r#"dmenu_cmd = "builtin""#).unwrap();
+        assert!(check_menu_command(&config).is_ok());
+    }
+}
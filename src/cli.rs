@@ -0,0 +1,207 @@
+use crate::bluetooth::BluetoothAction;
+use crate::columns::pad;
+use crate::command::CommandRunner;
+use crate::connectivity::check_connectivity;
+use crate::context::Context;
+use crate::daemon::{send_request, Request};
+use crate::doctor::run_doctor;
+use crate::tailscale::TailscaleAction;
+use crate::tor::check_tor;
+use crate::{
+    get_action_devices, get_wifi_networks, parse_wifi_action, set_action, ActionType, Config,
+    WifiAction,
+};
+use clap::Subcommand;
+use serde_json::{json, Value};
+use std::error::Error;
+
+/// Non-interactive subcommands bypassing the menu.
+#[derive(Subcommand, Debug)]
+pub enum Commands {
+    /// Manage the Tailscale exit node
+    ExitNode {
+        #[command(subcommand)]
+        command: ExitNodeCommand,
+    },
+    /// Manage Wi-Fi networks
+    Wifi {
+        #[command(subcommand)]
+        command: WifiCommand,
+    },
+    /// Manage Bluetooth devices
+    Bluetooth {
+        #[command(subcommand)]
+        command: BluetoothCommand,
+    },
+    /// Query and control a running daemon
+    Ctl {
+        #[command(subcommand)]
+        command: CtlCommand,
+    },
+    /// Check the optional tools, services and permissions, printing what to fix
+    Doctor,
+    /// Check the public IP geolocation, run detached after network changes
+    #[command(hide = true)]
+    CheckConnectivity {
+        #[arg(long)]
+        expected_country: Option<String>,
+    },
+    /// Check the traffic leaves through Tor, run detached after starting it
+    #[command(hide = true)]
+    CheckTor,
+}
+
+/// Exit node subcommands.
+#[derive(Subcommand, Debug)]
+pub enum ExitNodeCommand {
+    /// Use the given exit node (name or IP address)
+    Set { node: String },
+    /// Disable the exit node
+    Off,
+}
+
+/// Wi-Fi subcommands.
+#[derive(Subcommand, Debug)]
+pub enum WifiCommand {
+    /// Connect to the given SSID
+    Connect { ssid: String },
+    /// List available networks
+    List {
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+/// Bluetooth subcommands.
+#[derive(Subcommand, Debug)]
+pub enum BluetoothCommand {
+    /// Connect or disconnect the device with the given MAC address
+    Toggle { mac: String },
+}
+
+/// Daemon control subcommands.
+#[derive(Subcommand, Debug)]
+pub enum CtlCommand {
+    /// Print the network state and the available action ids as JSON
+    Status,
+    /// Apply the action with the given id, as listed by `ctl status`
+    Apply { id: String },
+}
+
+/// Runs a subcommand and returns whether it succeeded.
+pub async fn run_cli_command(
+    command: &Commands,
+    context: &Context<'_>,
+) -> Result<bool, Box<dyn Error>> {
+    let Context {
+        args,
+        config,
+        command_runner,
+        ..
+    } = *context;
+    let action = match command {
+        Commands::ExitNode { command } => match command {
+            ExitNodeCommand::Set { node } => {
+                ActionType::Tailscale(TailscaleAction::SetExitNode(node.clone()))
+            }
+            ExitNodeCommand::Off => ActionType::Tailscale(TailscaleAction::DisableExitNode),
+        },
+        Commands::Wifi { command } => match command {
+            WifiCommand::Connect { ssid } => {
+                let network =
+                    get_wifi_networks(config, &args.wifi_interface, false, command_runner)?
+                        .into_iter()
+                        .find(|network| match network {
+                            WifiAction::Network(display) => {
+                                parse_wifi_action(display).is_ok_and(|(name, _)| name == ssid)
+                            }
+                            _ => false,
+                        })
+                        .ok_or_else(|| format!("Wi-Fi network {ssid} not found"))?;
+                ActionType::Wifi(network)
+            }
+            WifiCommand::List { json } => {
+                print_wifi_networks(config, &args.wifi_interface, *json, command_runner)?;
+                return Ok(true);
+            }
+        },
+        Commands::Bluetooth { command } => match command {
+            BluetoothCommand::Toggle { mac } => {
+                ActionType::Bluetooth(BluetoothAction::ToggleConnect(mac.clone()))
+            }
+        },
+        Commands::Ctl { command } => return run_ctl_command(command),
+        Commands::Doctor => return Ok(run_doctor(config, command_runner)),
+        Commands::CheckConnectivity { expected_country } => {
+            check_connectivity(config, expected_country.as_deref()).await?;
+            return Ok(true);
+        }
+        Commands::CheckTor => {
+            check_tor(config).await?;
+            return Ok(true);
+        }
+    };
+
+    let connected_devices = get_action_devices(&action, command_runner)?;
+    set_action(
+        config,
+        &args.wifi_interface,
+        &action,
+        &connected_devices,
+        command_runner,
+    )
+    .await
+}
+
+/// Forwards a control subcommand to the daemon and prints its answer.
+fn run_ctl_command(command: &CtlCommand) -> Result<bool, Box<dyn Error>> {
+    let request = match command {
+        CtlCommand::Status => Request::Status,
+        CtlCommand::Apply { id } => Request::Apply { id: id.clone() },
+    };
+    let answer: Value = serde_json::from_str(
+        &send_request(&request, None).map_err(|err| format!("daemon not reachable: {err}"))?,
+    )?;
+    println!("{}", serde_json::to_string_pretty(&answer)?);
+    Ok(answer["success"].as_bool().unwrap_or(true))
+}
+
+/// Prints the available Wi-Fi networks as text or JSON.
+fn print_wifi_networks(
+    config: &Config,
+    wifi_interface: &str,
+    json: bool,
+    command_runner: &dyn CommandRunner,
+) -> Result<(), Box<dyn Error>> {
+    let networks = get_wifi_networks(config, wifi_interface, false, command_runner)?;
+    let networks: Vec<_> = networks
+        .iter()
+        .filter_map(|network| match network {
+            WifiAction::Network(display) => {
+                let (ssid, security) = parse_wifi_action(display).ok()?;
+                Some((ssid, security, display.starts_with('✅')))
+            }
+            _ => None,
+        })
+        .collect();
+
+    if json {
+        let networks: Vec<_> = networks
+            .iter()
+            .map(|(ssid, security, connected)| {
+                json!({ "ssid": ssid, "security": security, "connected": connected })
+            })
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&networks)?);
+    } else {
+        for (ssid, security, connected) in networks {
+            println!(
+                "{} {} {security}",
+                if connected { "*" } else { " " },
+                pad(ssid, 25)
+            );
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,84 @@
+use crate::Config;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use unicode_width::UnicodeWidthStr;
+
+/// How the columns of the entries are laid out for a launcher.
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ColumnLayout {
+    /// Columns padded with spaces, aligned in monospace fonts.
+    #[default]
+    Pad,
+    /// Padded columns in a monospace pango span, for launchers rendering markup.
+    Markup,
+    /// Columns separated by single spaces, for proportional fonts.
+    Plain,
+}
+
+/// Pads a text with spaces to the given display width, counting wide characters twice.
+pub fn pad(text: &str, width: usize) -> String {
+    let padding = width.saturating_sub(text.width());
+    format!("{text}{}", " ".repeat(padding))
+}
+
+/// Gets the column layout configured for the launcher, by the file name of its command.
+pub fn get_column_layout(config: &Config) -> ColumnLayout {
+    let launcher = Path::new(&config.dmenu_cmd)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    config
+        .menu
+        .column_layouts
+        .get(&launcher)
+        .copied()
+        .unwrap_or_default()
+}
+
+/// Lays out the columns of an entry, which are padded and may be separated by tabs.
+pub fn layout_entry(entry: &str, layout: ColumnLayout) -> String {
+    let padded = entry.replace('\t', "  ");
+    match layout {
+        ColumnLayout::Pad => padded,
+        ColumnLayout::Markup => format!(
+            "<span font_family=\"monospace\">{}</span>",
+            escape_markup(&padded)
+        ),
+        ColumnLayout::Plain => padded.split_whitespace().collect::<Vec<_>>().join(" "),
+    }
+}
+
+/// Escapes the characters of a text having a meaning in pango markup.
+pub fn escape_markup(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lays_out_columns_by_display_width() {
+        assert_eq!(pad("café", 6), "café  ");
+        assert_eq!(pad("東京", 6), "東京  ");
+        assert_eq!(pad("too long", 4), "too long");
+
+        let entry = format!("📶 {}\tWPA2\t▂▄▆_", pad("A&B", 6));
+        assert_eq!(
+            layout_entry(&entry, ColumnLayout::Pad),
+            "📶 A&B     WPA2  ▂▄▆_"
+        );
+        assert_eq!(
+            layout_entry(&entry, ColumnLayout::Plain),
+            "📶 A&B WPA2 ▂▄▆_"
+        );
+        assert_eq!(
+            layout_entry(&entry, ColumnLayout::Markup),
+            "<span font_family=\"monospace\">📶 A&amp;B     WPA2  ▂▄▆_</span>"
+        );
+    }
+}
@@ -1,25 +1,360 @@
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::error::Error;
-use std::io::{BufRead, BufReader};
-use std::process::{Command, Output, Stdio};
+use std::fs;
+use std::io::{BufRead, BufReader, ErrorKind};
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::process::ExitStatusExt;
+use std::process::{Command, ExitStatus, Output, Stdio};
+use std::sync::{mpsc, Mutex, OnceLock};
+use std::thread;
+use std::time::Duration;
+use tracing::warn;
+
+/// Commands always failing without root, escalated right away by default.
+const ROOT_COMMANDS: [&str; 5] = ["nft", "openconnect", "sh", "tee", "wg-quick"];
+
+/// Escalation tools waiting for the user to authenticate, never timed out by default.
+const INTERACTIVE_COMMANDS: [&str; 3] = ["doas", "pkexec", "sudo"];
+
+/// Time given to a killed command to exit and release its output.
+const KILL_GRACE: Duration = Duration::from_secs(1);
+
+/// Messages of the commands failing for lack of privileges.
+const PERMISSION_ERRORS: [&str; 5] = [
+    "permission denied",
+    "operation not permitted",
+    "access denied",
+    "not authorized",
+    "interactive authentication required",
+];
 
 /// Trait for running shell commands.
 pub trait CommandRunner {
     /// Runs a shell command with the specified arguments.
     fn run_command(&self, command: &str, args: &[&str]) -> Result<Output, std::io::Error>;
+
+    /// Runs a command only reading the state, e.g. to list the menu entries, killed if it
+    /// hangs so that the menu still shows up.
+    fn run_query(&self, command: &str, args: &[&str]) -> Result<Output, std::io::Error> {
+        self.run_command(command, args)
+    }
+
+    /// Runs a command needing root, escalated according to the configured policy.
+    fn run_privileged(&self, command: &str, args: &[&str]) -> Result<Output, std::io::Error> {
+        self.run_command(command, args)
+    }
+
+    /// Returns and clears the errors of the commands that failed so far, if recorded.
+    fn take_failures(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+/// Tool running commands as root.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EscalationMethod {
+    /// The first installed of pkexec, sudo and doas.
+    #[default]
+    Auto,
+    Pkexec,
+    Sudo,
+    Doas,
+}
+
+/// When a privileged command is run as root.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum EscalationPolicy {
+    Always,
+    OnFailure,
+    Never,
+}
+
+/// Configuration of the privilege escalation, with a policy per command.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct EscalationConfig {
+    #[serde(default)]
+    pub method: EscalationMethod,
+    #[serde(default)]
+    pub policies: HashMap<String, EscalationPolicy>,
+}
+
+impl EscalationConfig {
+    /// Returns the policy of a command, escalating the others only on permission errors.
+    fn policy(&self, command: &str) -> EscalationPolicy {
+        self.policies.get(command).copied().unwrap_or_else(|| {
+            if ROOT_COMMANDS.contains(&command) {
+                EscalationPolicy::Always
+            } else {
+                EscalationPolicy::OnFailure
+            }
+        })
+    }
+
+    /// Returns the escalation tool and its arguments, never prompting on a terminal.
+    fn tool(&self) -> Option<(&'static str, &'static [&'static str])> {
+        let tools: [(&str, &[&str]); 3] = [("pkexec", &[]), ("sudo", &["-n"]), ("doas", &["-n"])];
+        match self.method {
+            EscalationMethod::Auto => tools
+                .into_iter()
+                .find(|(tool, _)| is_command_installed(tool)),
+            EscalationMethod::Pkexec => Some(tools[0]),
+            EscalationMethod::Sudo => Some(tools[1]),
+            EscalationMethod::Doas => Some(tools[2]),
+        }
+    }
+
+    /// Prefixes a command with the escalation tool.
+    fn wrap(&self, command: &str, args: &[&str]) -> Result<Vec<String>, std::io::Error> {
+        let (tool, tool_args) = self.tool().ok_or_else(|| {
+            std::io::Error::new(
+                ErrorKind::PermissionDenied,
+                format!("{command} needs root, but none of pkexec, sudo or doas is installed"),
+            )
+        })?;
+        let mut command_line = vec![tool.to_string()];
+        command_line.extend(tool_args.iter().map(|arg| arg.to_string()));
+        command_line.push(command.to_string());
+        command_line.extend(args.iter().map(|arg| arg.to_string()));
+        Ok(command_line)
+    }
+}
+
+/// Timeouts of the external commands in seconds, `0` waiting for them indefinitely.
+///
+/// Queries listing the state are timed out by default, the other commands are not as
+/// they may wait for the user, e.g. to authenticate a VPN.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct TimeoutConfig {
+    #[serde(default)]
+    pub default: u64,
+    #[serde(default = "default_query_timeout")]
+    pub queries: u64,
+    #[serde(default)]
+    pub commands: HashMap<String, u64>,
+}
+
+impl Default for TimeoutConfig {
+    fn default() -> Self {
+        Self {
+            default: 0,
+            queries: default_query_timeout(),
+            commands: HashMap::new(),
+        }
+    }
+}
+
+impl TimeoutConfig {
+    /// Returns the timeout of a command, if any, given the one of its kind.
+    fn timeout(&self, command: &str, seconds: u64) -> Option<Duration> {
+        let seconds = self.commands.get(command).copied().unwrap_or_else(|| {
+            if INTERACTIVE_COMMANDS.contains(&command) {
+                0
+            } else {
+                seconds
+            }
+        });
+        (seconds > 0).then(|| Duration::from_secs(seconds))
+    }
+}
+
+/// Returns the default timeout of the queries in seconds.
+fn default_query_timeout() -> u64 {
+    30
+}
+
+/// Wraps a command needing root with the configured escalation tool.
+///
+/// The command is returned as is when running as root or when its policy is `never`.
+pub fn escalate(
+    config: &EscalationConfig,
+    command: &str,
+    args: &[&str],
+) -> Result<Vec<String>, std::io::Error> {
+    if config.policy(command) == EscalationPolicy::Never || is_root() {
+        let mut command_line = vec![command.to_string()];
+        command_line.extend(args.iter().map(|arg| arg.to_string()));
+        return Ok(command_line);
+    }
+    config.wrap(command, args)
+}
+
+/// Checks if the process runs as root, from the owner of its `/proc` entry.
+fn is_root() -> bool {
+    fs::metadata("/proc/self").is_ok_and(|metadata| metadata.uid() == 0)
+}
+
+/// Checks if a command failed for lack of privileges.
+fn is_permission_error(output: &Output) -> bool {
+    let stderr = String::from_utf8_lossy(&output.stderr).to_lowercase();
+    !output.status.success() && PERMISSION_ERRORS.iter().any(|error| stderr.contains(error))
 }
 
 /// Struct for running real shell commands.
-pub struct RealCommandRunner;
+///
+/// The standard error of failed commands, queries aside, is kept to report why an action
+/// failed, and every command is printed with its output in verbose mode.
+#[derive(Default)]
+pub struct RealCommandRunner {
+    verbose: bool,
+    escalation: EscalationConfig,
+    timeouts: TimeoutConfig,
+    failures: RefCell<Vec<String>>,
+}
+
+impl RealCommandRunner {
+    /// Creates a command runner, printing a transcript of the commands if verbose.
+    pub fn new(verbose: bool) -> Self {
+        Self {
+            verbose,
+            ..Self::default()
+        }
+    }
+
+    /// Sets how the privileged commands are escalated.
+    pub fn with_escalation(mut self, escalation: EscalationConfig) -> Self {
+        self.escalation = escalation;
+        self
+    }
+
+    /// Sets the time after which hanging commands are killed.
+    pub fn with_timeouts(mut self, timeouts: TimeoutConfig) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    /// Runs a command, killing it once the timeout, if any, elapses, and recording its
+    /// failure if asked.
+    fn run(
+        &self,
+        command: &str,
+        args: &[&str],
+        timeout: Option<Duration>,
+        record_failure: bool,
+    ) -> Result<Output, std::io::Error> {
+        let mut process = Command::new(command);
+        process.args(args).env("LC_ALL", "C");
+        let output = match timeout {
+            Some(timeout) => output_with_timeout(process, timeout)?,
+            None => process.output()?,
+        };
+        let command_line = format!("{command} {}", args.join(" "));
+
+        if self.verbose {
+            eprintln!("$ {command_line}");
+            eprint!("{}", String::from_utf8_lossy(&output.stdout));
+            eprint!("{}", String::from_utf8_lossy(&output.stderr));
+            eprintln!("[{}]", output.status);
+        }
+
+        if record_failure && !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            self.failures
+                .borrow_mut()
+                .push(format!("{command_line}: {}", stderr.trim()));
+        }
+
+        Ok(output)
+    }
+}
 
 impl CommandRunner for RealCommandRunner {
     fn run_command(&self, command: &str, args: &[&str]) -> Result<Output, std::io::Error> {
-        Command::new(command).args(args).env("LC_ALL", "C").output()
+        self.run(
+            command,
+            args,
+            self.timeouts.timeout(command, self.timeouts.default),
+            true,
+        )
+    }
+
+    fn run_query(&self, command: &str, args: &[&str]) -> Result<Output, std::io::Error> {
+        self.run(
+            command,
+            args,
+            self.timeouts.timeout(command, self.timeouts.queries),
+            // Queries only gather the entries, e.g. again and again in the daemon
+            false,
+        )
+    }
+
+    fn run_privileged(&self, command: &str, args: &[&str]) -> Result<Output, std::io::Error> {
+        let command_line = match self.escalation.policy(command) {
+            EscalationPolicy::Never => return self.run_command(command, args),
+            _ if is_root() => return self.run_command(command, args),
+            EscalationPolicy::OnFailure => {
+                let output = self.run_command(command, args)?;
+                if !is_permission_error(&output) {
+                    return Ok(output);
+                }
+                // The failure is reported by the escalated command instead.
+                self.failures.borrow_mut().pop();
+                self.escalation.wrap(command, args)?
+            }
+            EscalationPolicy::Always => self.escalation.wrap(command, args)?,
+        };
+        let args: Vec<&str> = command_line[1..].iter().map(String::as_str).collect();
+        self.run_command(&command_line[0], &args)
+    }
+
+    fn take_failures(&self) -> Vec<String> {
+        self.failures.take()
     }
 }
 
+/// Runs a command, killing it once the timeout elapses.
+///
+/// A killed command fails with the timeout as its error output, so that the callers carry
+/// on as with any failure instead of hanging.
+fn output_with_timeout(mut command: Command, timeout: Duration) -> Result<Output, std::io::Error> {
+    let child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let pid = child.id();
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || sender.send(child.wait_with_output()));
+    if let Ok(output) = receiver.recv_timeout(timeout) {
+        return output;
+    }
+
+    let program = command.get_program().to_string_lossy().into_owned();
+    warn!(
+        "{program} timed out after {}s, killing it",
+        timeout.as_secs()
+    );
+    execute_command("kill", &["-KILL", &pid.to_string()]);
+    let mut output = receiver.recv_timeout(KILL_GRACE).unwrap_or_else(|_| {
+        // Output still held open, e.g. by a child of the command.
+        Ok(Output {
+            status: ExitStatus::from_raw(9),
+            stdout: Vec::new(),
+            stderr: Vec::new(),
+        })
+    })?;
+    output.stderr = format!("timed out after {}s", timeout.as_secs()).into_bytes();
+    Ok(output)
+}
+
+/// Commands looked up in `PATH`, by name, with whether they were found.
+static INSTALLED_COMMANDS: OnceLock<Mutex<HashMap<String, bool>>> = OnceLock::new();
+
 /// Checks if a command is installed on the system.
+///
+/// `PATH` is searched once per command and process; a daemon sees tools installed
+/// after it started once restarted, as with the capabilities detected at startup.
 pub fn is_command_installed(cmd: &str) -> bool {
-    which::which(cmd).is_ok()
+    let mut installed = INSTALLED_COMMANDS
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    *installed
+        .entry(cmd.to_string())
+        .or_insert_with(|| which::which(cmd).is_ok())
 }
 
 /// Reads the output of a command and returns it as a vector of lines.
@@ -37,5 +372,57 @@ pub fn execute_command(command: &str, args: &[&str]) -> bool {
         .stdout(Stdio::null())
         .stderr(Stdio::null())
         .status()
-        .map_or(false, |status| status.success())
+        .is_ok_and(|status| status.success())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escalates_according_to_policy() {
+        let config: EscalationConfig = toml::from_str(
+            r#"
+            method = "sudo"
+            policies = { rfkill = "always", nft = "never" }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.policy("rfkill"), EscalationPolicy::Always);
+        assert_eq!(config.policy("nft"), EscalationPolicy::Never);
+        assert_eq!(config.policy("wg-quick"), EscalationPolicy::Always);
+        assert_eq!(config.policy("resolvectl"), EscalationPolicy::OnFailure);
+        assert_eq!(
+            config.wrap("rfkill", &["block", "wlan"]).unwrap(),
+            ["sudo", "-n", "rfkill", "block", "wlan"]
+        );
+    }
+
+    #[test]
+    fn kills_commands_on_timeout() {
+        let timeouts: TimeoutConfig = toml::from_str("commands = { sleep = 1 }").unwrap();
+        assert_eq!(timeouts.timeout("pkexec", timeouts.queries), None);
+        assert_eq!(timeouts.timeout("nmcli", timeouts.default), None);
+        assert_eq!(
+            timeouts.timeout("nmcli", timeouts.queries),
+            Some(Duration::from_secs(30))
+        );
+        let runner = RealCommandRunner::new(false).with_timeouts(timeouts);
+
+        let output = runner.run_command("sleep", &["10"]).unwrap();
+        assert!(!output.status.success());
+        assert_eq!(output.stderr, b"timed out after 1s");
+        assert_eq!(runner.take_failures(), ["sleep 10: timed out after 1s"]);
+    }
+
+    #[test]
+    fn records_failures_of_commands_only() {
+        let runner = RealCommandRunner::new(false);
+        assert!(!runner.run_query("false", &[]).unwrap().status.success());
+        assert!(runner.take_failures().is_empty());
+
+        assert!(!runner.run_command("false", &[]).unwrap().status.success());
+        assert_eq!(runner.take_failures(), ["false : "]);
+        assert!(runner.take_failures().is_empty());
+    }
 }
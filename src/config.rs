@@ -0,0 +1,498 @@
+use crate::columns::ColumnLayout;
+use crate::command::{EscalationConfig, TimeoutConfig};
+use crate::dns::DnsPreset;
+use crate::firewall::UfwProfile;
+use crate::hooks::{HooksConfig, HOOK_ENV_PREFIX};
+use crate::i18n::tr;
+use crate::icons::IconTheme;
+use crate::ipprofile::IpProfile;
+use crate::killswitch::default_kill_switch_interfaces;
+use crate::logging::LOG_ENV;
+use crate::mullvad_relays::MullvadFilter;
+use crate::netmap::NetmapWatchConfig;
+use crate::notifications::{new_notification, show_notification, NotificationConfig};
+use crate::profiles::Profile;
+use crate::proxy::ProxyProfile;
+use crate::security::SecurityConfig;
+use crate::tailscale::{DriveShare, FunnelPreset};
+use crate::tor::{default_tor_proxy, default_tor_service};
+use crate::tunnels::TunnelPreset;
+use crate::utils::open_in_terminal;
+use crate::vpn::VpnSession;
+use dirs::config_dir;
+use notify_rust::Urgency;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml::Value;
+
+/// Prefix of the environment variables overriding top-level settings, e.g.
+/// `NETWORK_DMENU_DMENU_CMD` for `dmenu_cmd`.
+const ENV_PREFIX: &str = "NETWORK_DMENU_";
+
+/// Configuration structure for the application.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Config {
+    #[serde(default)]
+    pub actions: Vec<CustomAction>,
+    #[serde(default)]
+    pub exclude_exit_node: Vec<String>,
+    #[serde(default = "default_true")]
+    pub mullvad_by_country: bool,
+    #[serde(default)]
+    pub mullvad_filter: MullvadFilter,
+    #[serde(default)]
+    pub exit_node_allow_lan_access: Option<bool>,
+    #[serde(default = "default_dmenu_cmd")]
+    pub dmenu_cmd: String,
+    #[serde(default)]
+    pub dmenu_args: String,
+    #[serde(default = "default_recency_weight")]
+    pub recency_weight: f64,
+    #[serde(default = "default_true")]
+    pub wifi_dedup: bool,
+    #[serde(default = "default_true")]
+    pub wifi_sort_by_signal: bool,
+    #[serde(default)]
+    pub wifi_advanced: bool,
+    #[serde(default)]
+    pub wifi_out_of_range: bool,
+    #[serde(default = "default_wifi_scan_timeout")]
+    pub wifi_scan_timeout: u64,
+    #[serde(default)]
+    pub iwd_8021x_template: Option<String>,
+    #[serde(default = "default_bluetooth_scan_timeout")]
+    pub bluetooth_scan_timeout: u64,
+    #[serde(default = "default_daemon_refresh_interval")]
+    pub daemon_refresh_interval: u64,
+    #[serde(default)]
+    pub pinentry_cmd: Option<String>,
+    #[serde(default)]
+    pub hotspot_ssid: Option<String>,
+    #[serde(default)]
+    pub hotspot_password: Option<String>,
+    #[serde(default = "default_true")]
+    pub connectivity_check: bool,
+    #[serde(default = "default_connectivity_url")]
+    pub connectivity_url: String,
+    #[serde(default = "default_connectivity_timeout")]
+    pub connectivity_timeout: u64,
+    #[serde(default)]
+    pub tailscale_login_server: Option<String>,
+    #[serde(default)]
+    pub tailscale_auth_key_cmd: Option<String>,
+    #[serde(default)]
+    pub dns_presets: Vec<DnsPreset>,
+    #[serde(default)]
+    pub proxy_profiles: Vec<ProxyProfile>,
+    #[serde(default)]
+    pub ip_profiles: Vec<IpProfile>,
+    #[serde(default)]
+    pub profiles: Vec<Profile>,
+    #[serde(default)]
+    pub ufw_profiles: Vec<UfwProfile>,
+    #[serde(default)]
+    pub vpn_sessions: Vec<VpnSession>,
+    #[serde(default)]
+    pub tunnels: Vec<TunnelPreset>,
+    #[serde(default)]
+    pub kill_switch: bool,
+    #[serde(default = "default_kill_switch_interfaces")]
+    pub kill_switch_interfaces: Vec<String>,
+    #[serde(default)]
+    pub kill_switch_endpoints: Vec<String>,
+    #[serde(default)]
+    pub tor: bool,
+    #[serde(default = "default_tor_service")]
+    pub tor_service: String,
+    #[serde(default = "default_tor_proxy")]
+    pub tor_proxy: String,
+    #[serde(default)]
+    pub funnel_presets: Vec<FunnelPreset>,
+    #[serde(default)]
+    pub drive_shares: Vec<DriveShare>,
+    #[serde(default)]
+    pub advertised_routes: Vec<String>,
+    #[serde(default)]
+    pub escalation: EscalationConfig,
+    #[serde(default)]
+    pub timeouts: TimeoutConfig,
+    #[serde(default)]
+    pub notifications: NotificationConfig,
+    #[serde(default)]
+    pub hooks: HooksConfig,
+    #[serde(default)]
+    pub netmap_watch: NetmapWatchConfig,
+    #[serde(default)]
+    pub security: SecurityConfig,
+    #[serde(default)]
+    pub menu: MenuConfig,
+    #[serde(default)]
+    pub icons: IconTheme,
+    #[serde(default)]
+    pub icon_overrides: HashMap<String, String>,
+    #[serde(default)]
+    pub locale: Option<String>,
+}
+
+/// Menu layout configuration.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct MenuConfig {
+    #[serde(default = "default_menu_sections")]
+    pub sections: Vec<MenuSection>,
+    #[serde(default)]
+    pub prefixes: HashMap<String, String>,
+    #[serde(default)]
+    pub column_layouts: HashMap<String, ColumnLayout>,
+    /// Shows the menu before gathering the entries, adding each section once gathered.
+    #[serde(default)]
+    pub stream: bool,
+    /// Lets several entries be selected at once, applied one after the other.
+    #[serde(default)]
+    pub multi_select: bool,
+}
+
+impl Default for MenuConfig {
+    fn default() -> Self {
+        Self {
+            sections: default_menu_sections(),
+            prefixes: HashMap::new(),
+            column_layouts: HashMap::new(),
+            stream: false,
+            multi_select: false,
+        }
+    }
+}
+
+/// Sections of the menu, listed in display order.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum MenuSection {
+    Bluetooth,
+    Custom,
+    Dns,
+    Firewall,
+    Ip,
+    Peers,
+    Power,
+    Profiles,
+    Proxy,
+    System,
+    Tailscale,
+    Vpn,
+    Wifi,
+}
+
+impl MenuSection {
+    /// Ranks the section by the time taken to gather it, from the configuration only to
+    /// the Tailscale section reading the mullvad exit nodes.
+    pub fn gather_cost(self) -> u8 {
+        match self {
+            MenuSection::Profiles | MenuSection::Custom | MenuSection::Proxy => 0,
+            MenuSection::System
+            | MenuSection::Power
+            | MenuSection::Dns
+            | MenuSection::Ip
+            | MenuSection::Firewall => 1,
+            MenuSection::Wifi | MenuSection::Peers | MenuSection::Bluetooth => 2,
+            MenuSection::Vpn => 3,
+            MenuSection::Tailscale => 4,
+        }
+    }
+}
+
+/// Returns the default menu sections and their order.
+fn default_menu_sections() -> Vec<MenuSection> {
+    vec![
+        MenuSection::Profiles,
+        MenuSection::Custom,
+        MenuSection::Wifi,
+        MenuSection::System,
+        MenuSection::Power,
+        MenuSection::Dns,
+        MenuSection::Ip,
+        MenuSection::Proxy,
+        MenuSection::Firewall,
+        MenuSection::Vpn,
+        MenuSection::Tailscale,
+        MenuSection::Peers,
+        MenuSection::Bluetooth,
+    ]
+}
+
+/// Returns the default menu command.
+fn default_dmenu_cmd() -> String {
+    "dmenu".to_string()
+}
+
+/// Returns the default weight of recency versus frequency in the menu ordering.
+fn default_recency_weight() -> f64 {
+    0.5
+}
+
+/// Returns true, for boolean settings enabled by default.
+pub fn default_true() -> bool {
+    true
+}
+
+/// Returns the default timeout of the connectivity check in seconds.
+fn default_connectivity_timeout() -> u64 {
+    5
+}
+
+/// Returns the default time waited for the iwd scan results, in seconds.
+fn default_wifi_scan_timeout() -> u64 {
+    5
+}
+
+/// Returns the default endpoint queried for the public IP geolocation.
+fn default_connectivity_url() -> String {
+    "https://ipinfo.io/json".to_string()
+}
+
+/// Returns the default duration of the Bluetooth discovery scan in seconds.
+fn default_bluetooth_scan_timeout() -> u64 {
+    10
+}
+
+/// Returns the default interval between two refreshes of the daemon cache in seconds.
+fn default_daemon_refresh_interval() -> u64 {
+    30
+}
+
+/// Custom action structure for user-defined actions.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct CustomAction {
+    pub display: String,
+    pub cmd: String,
+    #[serde(default)]
+    pub prompt: Option<String>,
+}
+
+/// Returns the default configuration as a string.
+fn get_default_config() -> &'static str {
+    r#"
+dmenu_cmd = "dmenu"
+dmenu_args = "--no-multi"
+
+exclude_exit_node = ["exit1", "exit2"]
+
+[[actions]]
+display = "🛡️ Example"
+cmd = "notify-send 'hello' 'world'"
+"#
+}
+
+/// Gets the configuration file path.
+pub fn get_config_path() -> Result<PathBuf, Box<dyn Error>> {
+    let config_dir = config_dir().ok_or("Failed to find config directory")?;
+    Ok(config_dir.join("network-dmenu").join("config.toml"))
+}
+
+/// Creates a default configuration file if it doesn't exist.
+pub fn create_default_config_if_missing() -> Result<(), Box<dyn Error>> {
+    let config_path = get_config_path()?;
+
+    if !config_path.exists() {
+        if let Some(parent) = config_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        fs::write(&config_path, get_default_config())?;
+    }
+    Ok(())
+}
+
+/// Reads the configuration and applies the environment overrides.
+///
+/// Returns the configuration along with warnings about the keys that are not recognized.
+pub fn get_config() -> Result<(Config, Vec<String>), Box<dyn Error>> {
+    let config_path = get_config_path()?;
+    let config_content = fs::read_to_string(&config_path)?;
+    let (config, mut warnings) = parse_config(&config_path, &config_content)?;
+    let (config, env_warnings) = apply_env_overrides(config, std::env::vars())?;
+    warnings.extend(env_warnings);
+    Ok((config, warnings))
+}
+
+/// Parses the configuration, collecting the unknown keys instead of ignoring them silently.
+fn parse_config(path: &Path, content: &str) -> Result<(Config, Vec<String>), String> {
+    let mut unknown = Vec::new();
+    let config = serde_ignored::deserialize(toml::Deserializer::new(content), |key| {
+        unknown.push(format!("{}: unknown key {key}", path.display()))
+    })
+    .map_err(|err| format_config_error(path, content, &err))?;
+    Ok((config, unknown))
+}
+
+/// Overrides top-level settings from the `NETWORK_DMENU_*` environment variables.
+///
+/// Values are read as TOML, e.g. `true` or `["wifi", "vpn"]`, and else as strings.
+fn apply_env_overrides(
+    config: Config,
+    variables: impl Iterator<Item = (String, String)>,
+) -> Result<(Config, Vec<String>), Box<dyn Error>> {
+    let overrides: Vec<(String, String)> = variables
+        .filter(|(name, _)| name != LOG_ENV && !name.starts_with(HOOK_ENV_PREFIX))
+        .filter_map(|(name, value)| Some((name.strip_prefix(ENV_PREFIX)?.to_lowercase(), value)))
+        .collect();
+    if overrides.is_empty() {
+        return Ok((config, Vec::new()));
+    }
+
+    let Value::Table(mut table) = Value::try_from(&config)? else {
+        return Err("Failed to serialize the configuration".into());
+    };
+    for (key, value) in overrides {
+        let value = toml::from_str::<toml::Table>(&format!("value = {value}"))
+            .ok()
+            .and_then(|mut parsed| parsed.remove("value"))
+            .unwrap_or(Value::String(value));
+        table.insert(key, value);
+    }
+
+    let mut unknown = Vec::new();
+    let config = serde_ignored::deserialize(Value::Table(table), |key| {
+        unknown.push(format!(
+            "{ENV_PREFIX}{}: unknown key",
+            key.to_string().to_uppercase()
+        ))
+    })
+    .map_err(|err| format!("Invalid {ENV_PREFIX}* override: {}", err.message()))?;
+    Ok((config, unknown))
+}
+
+/// Formats a configuration parsing error as `path:line: message`.
+fn format_config_error(path: &Path, content: &str, err: &toml::de::Error) -> String {
+    let line = err
+        .span()
+        .map(|span| content[..span.start].matches('\n').count() + 1)
+        .unwrap_or(1);
+    format!("{}:{line}: {}", path.display(), err.message())
+}
+
+/// Checks the configuration file, returning its problems and unknown keys.
+pub fn check_config() -> Result<Vec<String>, Box<dyn Error>> {
+    let config_path = get_config_path()?;
+    let config_content = fs::read_to_string(&config_path)?;
+    match parse_config(&config_path, &config_content) {
+        Ok((config, mut problems)) => {
+            problems.extend(validate_config(&config));
+            Ok(problems)
+        }
+        Err(err) => Ok(vec![err]),
+    }
+}
+
+/// Checks what parsing cannot: commands of custom actions and names looked up by the menu.
+fn validate_config(config: &Config) -> Vec<String> {
+    let mut problems: Vec<String> = config
+        .actions
+        .iter()
+        .filter(|action| action.display.trim().is_empty() || action.cmd.trim().is_empty())
+        .map(|action| format!("actions: \"{}\" needs a display and a cmd", action.display))
+        .collect();
+
+    let sections: [(&str, Vec<&str>); 7] = [
+        (
+            "dns_presets",
+            config.dns_presets.iter().map(|p| p.name.as_str()).collect(),
+        ),
+        (
+            "ip_profiles",
+            config.ip_profiles.iter().map(|p| p.name.as_str()).collect(),
+        ),
+        (
+            "profiles",
+            config.profiles.iter().map(|p| p.name.as_str()).collect(),
+        ),
+        (
+            "proxy_profiles",
+            config
+                .proxy_profiles
+                .iter()
+                .map(|p| p.name.as_str())
+                .collect(),
+        ),
+        (
+            "ufw_profiles",
+            config
+                .ufw_profiles
+                .iter()
+                .map(|p| p.name.as_str())
+                .collect(),
+        ),
+        (
+            "vpn_sessions",
+            config
+                .vpn_sessions
+                .iter()
+                .map(|s| s.name.as_str())
+                .collect(),
+        ),
+        (
+            "tunnels",
+            config.tunnels.iter().map(|t| t.name.as_str()).collect(),
+        ),
+    ];
+    for (section, names) in sections {
+        for (index, name) in names.iter().enumerate() {
+            if names[..index].contains(name) {
+                problems.push(format!("{section}: \"{name}\" is defined more than once"));
+            }
+        }
+    }
+    problems
+}
+
+/// Opens the configuration in `$VISUAL` or `$EDITOR` and reports problems once closed.
+pub fn edit_config(config: &Config) -> Result<bool, Box<dyn Error>> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    let config_path = get_config_path()?;
+    let config_path = config_path.to_string_lossy();
+    let mut command: Vec<&str> = editor.split_whitespace().collect();
+    command.push(&config_path);
+    if !open_in_terminal(&command)?.wait()?.success() {
+        return Ok(false);
+    }
+
+    let problems = check_config()?;
+    if !problems.is_empty() {
+        let mut notification =
+            new_notification(config, &tr("config-invalid"), &problems.join("\n"));
+        notification.urgency(Urgency::Critical);
+        show_notification(config, &notification);
+    }
+    Ok(problems.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn applies_defaults_and_overrides() {
+        let path = Path::new("config.toml");
+        let (config, warnings) = parse_config(path, "recency_weigth = 1.0\n").unwrap();
+        assert_eq!(config.dmenu_cmd, "dmenu");
+        assert_eq!(warnings, ["config.toml: unknown key recency_weigth"]);
+
+        let variables = [
+            ("NETWORK_DMENU_DMENU_CMD", "rofi"),
+            ("NETWORK_DMENU_DMENU_ARGS", "-dmenu -i"),
+            ("NETWORK_DMENU_TOR", "true"),
+            ("NETWORK_DMENU_LOG", "debug"),
+            ("HOME", "/home/user"),
+        ]
+        .map(|(name, value)| (name.to_string(), value.to_string()));
+        let (config, warnings) = apply_env_overrides(config, variables.into_iter()).unwrap();
+        assert_eq!(config.dmenu_cmd, "rofi");
+        assert_eq!(config.dmenu_args, "-dmenu -i");
+        assert!(config.tor);
+        assert!(warnings.is_empty());
+    }
+}
@@ -0,0 +1,151 @@
+use crate::i18n::{tr, tr_args};
+use crate::notifications::{new_notification, notify, show_notification};
+use crate::Config;
+use notify_rust::Urgency;
+use reqwest::{Client, Proxy};
+use reqwest_middleware::{ClientBuilder, ClientWithMiddleware};
+use reqwest_retry::{policies::ExponentialBackoff, RetryTransientMiddleware};
+use serde_json::Value;
+use std::error::Error;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+/// Public IP geolocation as reported by the connectivity endpoint.
+#[derive(Debug)]
+pub struct Geolocation {
+    pub ip: String,
+    pub city: String,
+    pub country: String,
+    pub provider: String,
+}
+
+/// Returns the first non-empty string field among the given keys.
+fn get_field(json: &Value, keys: &[&str]) -> String {
+    keys.iter()
+        .find_map(|key| json.get(*key).and_then(Value::as_str))
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// Fetches a JSON document, optionally through a proxy such as `socks5h://127.0.0.1:9050`.
+pub async fn get_json(
+    url: &str,
+    timeout: Duration,
+    proxy: Option<&str>,
+) -> Result<Value, Box<dyn Error>> {
+    // Create a retry policy with exponential backoff
+    let retry_policy = ExponentialBackoff::builder().build_with_max_retries(3);
+
+    let mut builder = Client::builder().timeout(timeout);
+    if let Some(proxy) = proxy {
+        builder = builder.proxy(Proxy::all(proxy)?);
+    }
+
+    // Build a client with retry middleware
+    let client: ClientWithMiddleware = ClientBuilder::new(builder.build()?)
+        .with(RetryTransientMiddleware::new_with_policy(retry_policy))
+        .build();
+
+    Ok(client
+        .get(url)
+        .header("Accept", "application/json")
+        .send()
+        .await?
+        .json()
+        .await?)
+}
+
+/// Queries the connectivity endpoint for the public IP geolocation.
+///
+/// Both ipinfo.io and ifconfig.co style responses are understood.
+pub async fn get_geolocation(
+    url: &str,
+    timeout: Duration,
+    proxy: Option<&str>,
+) -> Result<Geolocation, Box<dyn Error>> {
+    let json = get_json(url, timeout, proxy).await?;
+
+    Ok(Geolocation {
+        ip: get_field(&json, &["ip"]),
+        city: get_field(&json, &["city"]),
+        country: get_field(&json, &["country_iso", "country"]),
+        provider: get_field(&json, &["org", "asn_org"]),
+    })
+}
+
+/// Returns the country code of a Mullvad exit node from its name, e.g. `fr` for
+/// `fr-par-wg-001.mullvad.ts.net`.
+pub fn get_expected_country(node: &str) -> Option<String> {
+    node.split_whitespace()
+        .find(|part| part.contains("mullvad.ts.net"))
+        .and_then(|name| name.split('-').next())
+        .filter(|code| code.len() == 2)
+        .map(str::to_uppercase)
+}
+
+/// Checks the public IP geolocation and sends a notification with the details.
+///
+/// When an expected country is given, warns if the traffic does not leave from it,
+/// i.e. the exit node is not actually in effect.
+pub async fn check_connectivity(
+    config: &Config,
+    expected_country: Option<&str>,
+) -> Result<(), Box<dyn Error>> {
+    let location = get_geolocation(
+        &config.connectivity_url,
+        Duration::from_secs(config.connectivity_timeout),
+        None,
+    )
+    .await?;
+    let body = tr_args(
+        "connectivity-location",
+        &[
+            ("ip", &location.ip),
+            ("city", &location.city),
+            ("country", &location.country),
+            ("provider", &location.provider),
+        ],
+    );
+
+    match expected_country {
+        Some(expected) if !location.country.eq_ignore_ascii_case(expected) => {
+            let mut notification = new_notification(
+                config,
+                &tr("exit-node-not-in-effect"),
+                &tr_args(
+                    "exit-node-unexpected-location",
+                    &[("expected", expected), ("location", &body)],
+                ),
+            );
+            notification.urgency(Urgency::Critical);
+            show_notification(config, &notification);
+        }
+        _ => {
+            notify(config, &tr("connection-status"), &body);
+        }
+    }
+
+    Ok(())
+}
+
+/// Runs the connectivity check in a detached process, so the action returns at once.
+pub fn spawn_connectivity_check(
+    config: &Config,
+    expected_country: Option<&str>,
+) -> Result<(), Box<dyn Error>> {
+    if !config.connectivity_check {
+        return Ok(());
+    }
+
+    let mut command = Command::new(std::env::current_exe()?);
+    command.arg("check-connectivity");
+    if let Some(country) = expected_country {
+        command.arg("--expected-country").arg(country);
+    }
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    Ok(())
+}
@@ -0,0 +1,13 @@
+use crate::capabilities::Capabilities;
+use crate::command::CommandRunner;
+use crate::{Args, Config};
+
+/// State built once at startup and shared by the menu, the daemon and the commands: the
+/// arguments, the parsed configuration, the detected tools and the command runner.
+#[derive(Clone, Copy)]
+pub struct Context<'a> {
+    pub args: &'a Args,
+    pub config: &'a Config,
+    pub capabilities: &'a Capabilities,
+    pub command_runner: &'a dyn CommandRunner,
+}
@@ -0,0 +1,465 @@
+use crate::bluetooth::get_connected_devices;
+use crate::command::CommandRunner;
+use crate::context::Context;
+use crate::dbus_service::{emit_status_changed, serve_dbus, SERVICE_NAME};
+use crate::history::sync_wifi_history;
+use crate::mullvad_relays::{get_mullvad_filter, refresh_relays};
+use crate::netmap::{get_netmap_state, notify_netmap_changes};
+use crate::profiles::apply_actions;
+use crate::tailscale::{get_active_exit_node, TailscaleAction};
+#[cfg(feature = "tray")]
+use crate::tray::spawn_tray;
+use crate::{
+    action_key, action_to_entry, get_actions, parse_wifi_action, ActionType, Config, WifiAction,
+};
+use dirs::runtime_dir;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::error::Error;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+use zbus::blocking::{Connection, MessageIterator};
+use zbus::message::Type;
+use zbus::MatchRule;
+
+/// Time given to the menu to get an answer from the daemon before computing the entries itself.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Delay letting bursts of D-Bus signals settle before refreshing.
+const SIGNAL_DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Request sent by a client to the daemon, one JSON object per line.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(tag = "command", rename_all = "kebab-case")]
+pub enum Request {
+    Actions,
+    Apply { id: String },
+    Refresh,
+    Status,
+}
+
+/// Event handled by the main loop of the daemon.
+pub enum Event {
+    Apply(String, Sender<String>),
+    Refresh,
+}
+
+/// Menu entry along with the id used to apply it.
+#[derive(Debug, Clone, Serialize)]
+pub struct ActionEntry {
+    pub id: String,
+    pub entry: String,
+}
+
+/// Current network state answered to `status` requests.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Status {
+    pub wifi: Option<String>,
+    pub tailscale: Option<bool>,
+    pub exit_node: Option<String>,
+    pub bluetooth: Vec<String>,
+    pub actions: Vec<ActionEntry>,
+}
+
+/// Cached answers of the daemon, serialized once per refresh.
+pub struct Cache {
+    /// Actions, none until gathered for the first time.
+    pub actions: Option<String>,
+    pub status: String,
+    pub entries: Vec<ActionEntry>,
+}
+
+/// Gets the daemon socket path, in the runtime directory private to the user.
+///
+/// There is no fallback to the temporary directory, where any user could create the socket
+/// first and answer the menu with their own entries, custom commands included.
+fn get_socket_path() -> Result<PathBuf, Box<dyn Error>> {
+    runtime_dir()
+        .map(|dir| dir.join("network-dmenu.sock"))
+        .ok_or_else(|| "XDG_RUNTIME_DIR is not set".into())
+}
+
+/// Checks that the socket was created by the current user before trusting its answers.
+fn check_socket_owner(path: &Path) -> Result<(), Box<dyn Error>> {
+    let owner = fs::metadata(path)?.uid();
+    if owner != fs::metadata("/proc/self")?.uid() {
+        return Err(format!("{} is owned by another user", path.display()).into());
+    }
+    Ok(())
+}
+
+/// Sends a request to the daemon and returns its raw answer.
+///
+/// Without timeout, waits until the daemon answers, e.g. once an applied action is done.
+pub fn send_request(
+    request: &Request,
+    timeout: Option<Duration>,
+) -> Result<String, Box<dyn Error>> {
+    let socket_path = get_socket_path()?;
+    check_socket_owner(&socket_path)?;
+    let mut stream = UnixStream::connect(socket_path)?;
+    stream.set_read_timeout(timeout)?;
+    stream.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+    writeln!(stream, "{}", serde_json::to_string(request)?)?;
+
+    let mut answer = String::new();
+    BufReader::new(stream).read_line(&mut answer)?;
+    Ok(answer)
+}
+
+/// Retrieves the cached actions from a running daemon, if any and gathered already.
+pub fn fetch_daemon_actions() -> Option<Vec<ActionType>> {
+    let answer = send_request(&Request::Actions, Some(CLIENT_TIMEOUT))
+        .map_err(|err| debug!("daemon not reachable: {err}"))
+        .ok()?;
+    serde_json::from_str::<Option<Vec<ActionType>>>(&answer)
+        .map_err(|err| warn!("invalid daemon answer: {err}"))
+        .ok()
+        .flatten()
+}
+
+/// Asks a running daemon to refresh its cache, e.g. after an action changed the state.
+pub fn request_daemon_refresh() {
+    if let Err(err) = send_request(&Request::Refresh, Some(CLIENT_TIMEOUT)) {
+        debug!("daemon not reachable: {err}");
+    }
+}
+
+/// Runs the daemon, refreshing the cached actions periodically and on D-Bus signals.
+pub async fn run_daemon(context: &Context<'_>) -> Result<(), Box<dyn Error>> {
+    let Context {
+        args,
+        config,
+        capabilities,
+        command_runner,
+    } = *context;
+    let socket_path = get_socket_path()?;
+    if UnixStream::connect(&socket_path).is_ok() {
+        return Err(format!("a daemon is already listening on {}", socket_path.display()).into());
+    }
+    // A socket nobody listens on is left over from a daemon that did not exit cleanly
+    let _ = fs::remove_file(&socket_path);
+    let listener = UnixListener::bind(&socket_path)?;
+    info!("listening on {}", socket_path.display());
+
+    let cache = Arc::new(Mutex::new(Cache {
+        actions: None,
+        status: serde_json::to_string(&Status::default())?,
+        entries: Vec::new(),
+    }));
+    let (event_sender, event_receiver) = mpsc::channel();
+
+    {
+        let cache = Arc::clone(&cache);
+        let event_sender = event_sender.clone();
+        thread::spawn(move || serve_clients(listener, cache, event_sender));
+    }
+    let dbus = serve_dbus(Arc::clone(&cache), event_sender.clone())
+        .await
+        .map_err(|err| warn!("not registering {SERVICE_NAME} on the session bus: {err}"))
+        .ok();
+    watch_dbus_signals(
+        "org.freedesktop.NetworkManager",
+        "/org/freedesktop/NetworkManager",
+        event_sender.clone(),
+    );
+    #[cfg(feature = "tray")]
+    let tray = if args.tray {
+        spawn_tray(Status::default(), event_sender.clone())
+            .await
+            .map_err(|err| warn!("failed to show the tray icon: {err}"))
+            .ok()
+    } else {
+        None
+    };
+    watch_dbus_signals("org.bluez", "/org/bluez", event_sender);
+
+    let interval = Duration::from_secs(config.daemon_refresh_interval.max(1));
+    let mut actions = Vec::new();
+    let mut netmap = None;
+    loop {
+        // Drops the failures of the previous refresh, only the applied actions report theirs
+        command_runner.take_failures();
+        if !get_mullvad_filter(config).is_empty() {
+            refresh_relays(config).await;
+        }
+        match get_actions(context) {
+            Ok(refreshed) => {
+                actions = refreshed;
+                let status = get_status(config, &actions, command_runner);
+                if capabilities.wifi && !args.no_wifi {
+                    sync_wifi_history(status.wifi.as_deref());
+                }
+                #[cfg(feature = "tray")]
+                if let Some(tray) = &tray {
+                    let status = status.clone();
+                    tray.update(|tray| tray.status = status).await;
+                }
+                let status_json = serde_json::to_string(&status)?;
+                let changed = {
+                    let mut cache = cache.lock().map_err(|_| "cache lock poisoned")?;
+                    let changed = cache.status != status_json;
+                    *cache = Cache {
+                        actions: Some(serde_json::to_string(&actions)?),
+                        status: status_json.clone(),
+                        entries: status.actions,
+                    };
+                    changed
+                };
+                if let (Some(dbus), true) = (&dbus, changed) {
+                    if let Err(err) = emit_status_changed(dbus, &status_json).await {
+                        warn!("failed to signal the status change: {err}");
+                    }
+                }
+                debug!("refreshed cached actions");
+            }
+            Err(err) => warn!("failed to refresh actions: {err}"),
+        }
+
+        if capabilities.tailscale && !args.no_tailscale {
+            let current = get_netmap_state(command_runner);
+            if let (Some(previous), Some(current)) = (&netmap, &current) {
+                notify_netmap_changes(config, previous, current);
+            }
+            netmap = current;
+        }
+
+        match event_receiver.recv_timeout(interval) {
+            Ok(Event::Apply(id, answer_sender)) => {
+                let answer = apply_action(context, &actions, &id).await;
+                let _ = answer_sender.send(answer.to_string());
+            }
+            Ok(Event::Refresh) => {
+                thread::sleep(SIGNAL_DEBOUNCE);
+                // Coalesces the burst of refreshes, still answering the queued applies
+                loop {
+                    match event_receiver.try_recv() {
+                        Ok(Event::Refresh) => {}
+                        Ok(Event::Apply(id, answer_sender)) => {
+                            let answer = apply_action(context, &actions, &id).await;
+                            let _ = answer_sender.send(answer.to_string());
+                        }
+                        Err(_) => break,
+                    }
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return Ok(()),
+        }
+    }
+}
+
+/// Applies the cached action with the given id, profiles step by step, and returns the
+/// JSON answer.
+async fn apply_action(
+    context: &Context<'_>,
+    actions: &[ActionType],
+    id: &str,
+) -> serde_json::Value {
+    let Some(action) = actions.iter().find(|action| action_key(action) == id) else {
+        return json!({ "success": false, "error": format!("unknown action id {id}") });
+    };
+    info!("applying {id}");
+
+    match apply_actions(context, &[action], actions).await {
+        Ok(result) => {
+            let errors: Vec<String> = result
+                .failures
+                .iter()
+                .flat_map(|(entry, errors)| {
+                    errors
+                        .iter()
+                        .map(move |error| format!("{}: {error}", entry.trim()))
+                })
+                .collect();
+            if errors.is_empty() {
+                json!({ "success": true, "cancelled": result.cancelled > 0 })
+            } else {
+                json!({ "success": false, "error": errors.join("\n") })
+            }
+        }
+        Err(err) => json!({ "success": false, "error": err.to_string() }),
+    }
+}
+
+/// Summarizes the network state from the actions and the tools.
+fn get_status(
+    config: &Config,
+    actions: &[ActionType],
+    command_runner: &dyn CommandRunner,
+) -> Status {
+    let wifi = actions.iter().find_map(|action| match action {
+        ActionType::Wifi(WifiAction::Network(display)) if display.starts_with('✅') => {
+            parse_wifi_action(display)
+                .ok()
+                .map(|(ssid, _)| ssid.to_string())
+        }
+        _ => None,
+    });
+    let tailscale = actions.iter().find_map(|action| match action {
+        ActionType::Tailscale(TailscaleAction::SetEnable(enable, _)) => Some(!enable),
+        _ => None,
+    });
+    let exit_node = Some(get_active_exit_node(command_runner))
+        .filter(|node| tailscale == Some(true) && !node.is_empty());
+
+    Status {
+        wifi,
+        tailscale,
+        exit_node,
+        bluetooth: get_connected_devices(command_runner).unwrap_or_default(),
+        actions: action_entries(config, actions),
+    }
+}
+
+/// Lists the menu entries along with their ids.
+pub fn action_entries(config: &Config, actions: &[ActionType]) -> Vec<ActionEntry> {
+    actions
+        .iter()
+        .map(|action| ActionEntry {
+            id: action_key(action),
+            entry: action_to_entry(config, action),
+        })
+        .collect()
+}
+
+/// Answers client requests with the cached state, forwarding the others to the main loop.
+///
+/// Each client is served by its own thread, so that a client waiting for an applied action
+/// does not hold up the menus asking for the cached entries.
+fn serve_clients(listener: UnixListener, cache: Arc<Mutex<Cache>>, event_sender: Sender<Event>) {
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                warn!("failed to accept client: {err}");
+                continue;
+            }
+        };
+        let cache = Arc::clone(&cache);
+        let event_sender = event_sender.clone();
+        thread::spawn(move || {
+            if let Err(err) = handle_client(stream, &cache, &event_sender) {
+                warn!("failed to answer client: {err}");
+            }
+        });
+    }
+}
+
+/// Handles a single client request.
+fn handle_client(
+    mut stream: UnixStream,
+    cache: &Mutex<Cache>,
+    event_sender: &Sender<Event>,
+) -> Result<(), Box<dyn Error>> {
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line)?;
+
+    let answer = match serde_json::from_str(&line)? {
+        // `null` until the first refresh, for the menu to gather the actions itself
+        Request::Actions => cache
+            .lock()
+            .map_err(|_| "cache lock poisoned")?
+            .actions
+            .clone()
+            .unwrap_or_else(|| "null".to_string()),
+        Request::Status => cache
+            .lock()
+            .map_err(|_| "cache lock poisoned")?
+            .status
+            .clone(),
+        Request::Apply { id } => {
+            let (answer_sender, answer_receiver) = mpsc::channel();
+            event_sender.send(Event::Apply(id, answer_sender))?;
+            answer_receiver.recv()?
+        }
+        Request::Refresh => {
+            event_sender.send(Event::Refresh)?;
+            json!({ "success": true }).to_string()
+        }
+    };
+    writeln!(stream, "{answer}")?;
+    Ok(())
+}
+
+/// Triggers a refresh whenever a service on the system bus reports changed properties.
+fn watch_dbus_signals(sender: &'static str, path_namespace: &'static str, refresh: Sender<Event>) {
+    thread::spawn(move || {
+        let result = (|| -> Result<(), Box<dyn Error>> {
+            let connection = Connection::system()?;
+            let rule = MatchRule::builder()
+                .msg_type(Type::Signal)
+                .sender(sender)?
+                .path_namespace(path_namespace)?
+                .interface("org.freedesktop.DBus.Properties")?
+                .member("PropertiesChanged")?
+                .build();
+            for message in MessageIterator::for_match_rule(rule, &connection, None)? {
+                message?;
+                if refresh.send(Event::Refresh).is_err() {
+                    break;
+                }
+            }
+            Ok(())
+        })();
+        if let Err(err) = result {
+            warn!("not watching {sender} signals: {err}");
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Sends a request line to `handle_client` and returns its answer.
+    fn ask(
+        request: &str,
+        cache: &Mutex<Cache>,
+        event_sender: &Sender<Event>,
+    ) -> Result<String, Box<dyn Error>> {
+        let (mut client, server) = UnixStream::pair()?;
+        writeln!(client, "{request}")?;
+        handle_client(server, cache, event_sender)?;
+        let mut answer = String::new();
+        BufReader::new(client).read_line(&mut answer)?;
+        Ok(answer)
+    }
+
+    #[test]
+    fn answers_client_requests() {
+        let cache = Mutex::new(Cache {
+            actions: None,
+            status: r#"{"wifi":"Home"}"#.to_string(),
+            entries: Vec::new(),
+        });
+        let (event_sender, event_receiver) = mpsc::channel();
+
+        let answer = ask(r#"{"command": "actions"}"#, &cache, &event_sender).unwrap();
+        assert_eq!(answer, "null\n");
+        let answer = ask(r#"{"command": "status"}"#, &cache, &event_sender).unwrap();
+        assert_eq!(answer, "{\"wifi\":\"Home\"}\n");
+        let answer = ask(r#"{"command": "refresh"}"#, &cache, &event_sender).unwrap();
+        assert_eq!(answer, "{\"success\":true}\n");
+        assert!(matches!(event_receiver.try_recv(), Ok(Event::Refresh)));
+        assert!(ask(r#"{"command": "reboot"}"#, &cache, &event_sender).is_err());
+
+        let main_loop = thread::spawn(move || match event_receiver.recv() {
+            Ok(Event::Apply(id, answer_sender)) => answer_sender.send(format!("applied {id}")),
+            _ => panic!("expected an apply event"),
+        });
+        let request = r#"{"command": "apply", "id": "toggle:shields"}"#;
+        let answer = ask(request, &cache, &event_sender).unwrap();
+        assert_eq!(answer, "applied toggle:shields\n");
+        main_loop.join().unwrap().unwrap();
+    }
+}
@@ -0,0 +1,95 @@
+use crate::daemon::{Cache, Event};
+use std::error::Error;
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use tokio::sync::oneshot;
+use zbus::object_server::SignalContext;
+use zbus::{fdo, interface, Connection};
+
+/// Well-known name of the daemon on the session bus.
+pub const SERVICE_NAME: &str = "org.networkdmenu.Manager";
+
+/// Object path of the manager interface.
+pub const OBJECT_PATH: &str = "/org/networkdmenu/Manager";
+
+/// Manager interface answering from the daemon cache, applying actions through its main loop.
+struct Manager {
+    cache: Arc<Mutex<Cache>>,
+    event_sender: Sender<Event>,
+}
+
+#[interface(name = "org.networkdmenu.Manager")]
+impl Manager {
+    /// Lists the menu entries along with the id used to apply them.
+    fn list_actions(&self) -> fdo::Result<Vec<(String, String)>> {
+        let cache = self.lock_cache()?;
+        Ok(cache
+            .entries
+            .iter()
+            .map(|action| (action.id.clone(), action.entry.clone()))
+            .collect())
+    }
+
+    /// Applies the entry with the given id, returning whether it succeeded.
+    async fn apply_action(&self, id: String) -> fdo::Result<bool> {
+        let (answer_sender, answer_receiver) = mpsc::channel();
+        self.event_sender
+            .send(Event::Apply(id, answer_sender))
+            .map_err(|err| fdo::Error::Failed(err.to_string()))?;
+
+        // Waited on another thread, to keep answering the other calls meanwhile
+        let (sender, receiver) = oneshot::channel();
+        thread::spawn(move || sender.send(answer_receiver.recv()));
+        let answer = receiver
+            .await
+            .map_err(|err| fdo::Error::Failed(err.to_string()))?
+            .map_err(|err| fdo::Error::Failed(err.to_string()))?;
+
+        let answer: serde_json::Value =
+            serde_json::from_str(&answer).map_err(|err| fdo::Error::Failed(err.to_string()))?;
+        match answer["error"].as_str() {
+            Some(error) => Err(fdo::Error::Failed(error.to_string())),
+            None => Ok(answer["success"].as_bool().unwrap_or_default()),
+        }
+    }
+
+    /// Gets the network state as JSON, as answered to `ctl status`.
+    fn get_status(&self) -> fdo::Result<String> {
+        Ok(self.lock_cache()?.status.clone())
+    }
+
+    /// Emitted with the new network state as JSON whenever it changes.
+    #[zbus(signal)]
+    async fn status_changed(context: &SignalContext<'_>, status: &str) -> zbus::Result<()>;
+}
+
+impl Manager {
+    /// Locks the daemon cache, failing the call if poisoned.
+    fn lock_cache(&self) -> fdo::Result<std::sync::MutexGuard<'_, Cache>> {
+        self.cache
+            .lock()
+            .map_err(|_| fdo::Error::Failed("cache lock poisoned".to_string()))
+    }
+}
+
+/// Registers the manager on the session bus, serving until the connection is dropped.
+pub async fn serve_dbus(
+    cache: Arc<Mutex<Cache>>,
+    event_sender: Sender<Event>,
+) -> Result<Connection, Box<dyn Error>> {
+    let manager = Manager {
+        cache,
+        event_sender,
+    };
+    Ok(zbus::connection::Builder::session()?
+        .name(SERVICE_NAME)?
+        .serve_at(OBJECT_PATH, manager)?
+        .build()
+        .await?)
+}
+
+/// Signals the new network state to the D-Bus clients.
+pub async fn emit_status_changed(connection: &Connection, status: &str) -> zbus::Result<()> {
+    Manager::status_changed(&SignalContext::new(connection, OBJECT_PATH)?, status).await
+}
@@ -0,0 +1,120 @@
+use crate::command::{is_command_installed, read_output_lines, CommandRunner};
+use crate::i18n::tr;
+use crate::notifications::notify;
+use crate::tailscale::get_active_exit_node;
+use crate::utils::{open_in_terminal, write_private_file};
+use crate::Config;
+use regex::Regex;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+/// Runs the connection diagnostics, notifies a summary and opens the full report.
+pub fn run_diagnostics(
+    config: &Config,
+    command_runner: &dyn CommandRunner,
+) -> Result<bool, Box<dyn Error>> {
+    let mut summary = Vec::new();
+    let mut report = String::new();
+
+    if is_command_installed("tailscale") {
+        let output = command_runner.run_command("tailscale", &["netcheck"])?;
+        let netcheck = String::from_utf8_lossy(&output.stdout).to_string();
+        summary.extend(summarize_netcheck(&netcheck));
+        report.push_str(&format!("# tailscale netcheck\n\n{netcheck}\n"));
+
+        let exit_node = get_active_exit_node(command_runner);
+        if !exit_node.is_empty() {
+            let output =
+                command_runner.run_command("tailscale", &["ping", "-c", "3", &exit_node])?;
+            let ping = String::from_utf8_lossy(&output.stdout).to_string();
+            summary.push(format!(
+                "Exit node: {}",
+                ping.lines().last().unwrap_or("no reply")
+            ));
+            report.push_str(&format!("# tailscale ping {exit_node}\n\n{ping}\n"));
+        }
+    }
+
+    if let Some(gateway) = get_default_gateway(command_runner)? {
+        let output = command_runner.run_command("ping", &["-c", "4", "-q", &gateway])?;
+        let ping = String::from_utf8_lossy(&output.stdout).to_string();
+        summary.push(format!(
+            "Gateway {gateway}: {} packet loss",
+            parse_packet_loss(&ping).unwrap_or("unknown")
+        ));
+        report.push_str(&format!("# ping {gateway}\n\n{ping}\n"));
+    } else {
+        summary.push("Gateway: no default route".to_string());
+    }
+
+    notify(config, &tr("diagnostics"), &summary.join("\n"));
+
+    let report_path = write_report(&report)?;
+    open_in_pager(&report_path)?;
+
+    Ok(true)
+}
+
+/// Extracts the DERP latency and NAT type from `tailscale netcheck` output.
+fn summarize_netcheck(netcheck: &str) -> Vec<String> {
+    let mut summary = Vec::new();
+    let latency = Regex::new(r"^\s*-\s*(\S+):\s*(\S+)").unwrap();
+
+    for line in netcheck.lines() {
+        let line = line.trim().trim_start_matches("* ");
+        if let Some(derp) = line.strip_prefix("Nearest DERP:") {
+            summary.push(format!("Nearest DERP: {}", derp.trim()));
+        } else if let Some(varies) = line.strip_prefix("MappingVariesByDestIP:") {
+            let nat = if varies.trim() == "true" {
+                "hard"
+            } else {
+                "easy"
+            };
+            summary.push(format!("NAT type: {nat}"));
+        }
+    }
+
+    if let Some(caps) = netcheck
+        .lines()
+        .find_map(|line| latency.captures(line.trim_end()))
+    {
+        summary.push(format!("DERP latency: {} ({})", &caps[2], &caps[1]));
+    }
+
+    summary
+}
+
+/// Retrieves the default gateway from the routing table.
+pub fn get_default_gateway(
+    command_runner: &dyn CommandRunner,
+) -> Result<Option<String>, Box<dyn Error>> {
+    if !is_command_installed("ip") {
+        return Ok(None);
+    }
+
+    let output = command_runner.run_query("ip", &["route", "show", "default"])?;
+    Ok(read_output_lines(&output)?.into_iter().find_map(|line| {
+        let mut parts = line.split_whitespace();
+        parts.find(|part| *part == "via")?;
+        parts.next().map(|gateway| gateway.to_string())
+    }))
+}
+
+/// Extracts the packet loss percentage from `ping` output.
+fn parse_packet_loss(ping: &str) -> Option<&str> {
+    ping.split(',')
+        .find(|part| part.contains("packet loss"))
+        .and_then(|part| part.split_whitespace().next())
+}
+
+/// Writes the diagnostics report, holding the addresses of the machine, to a private file.
+fn write_report(report: &str) -> Result<PathBuf, Box<dyn Error>> {
+    write_private_file("network-dmenu-diagnostics.txt", report.as_bytes())
+}
+
+/// Opens a file in `$PAGER` inside a terminal.
+fn open_in_pager(path: &Path) -> Result<(), Box<dyn Error>> {
+    let pager = std::env::var("PAGER").unwrap_or_else(|_| "less".to_string());
+    open_in_terminal(&[&pager, &path.to_string_lossy()])?;
+    Ok(())
+}
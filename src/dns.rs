@@ -0,0 +1,111 @@
+use crate::command::{is_command_installed, read_output_lines, CommandRunner};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+
+/// DNS servers preset selectable from the menu.
+///
+/// A preset without servers reverts the link to the servers provided by the network.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct DnsPreset {
+    pub name: String,
+    #[serde(default)]
+    pub servers: Vec<String>,
+}
+
+/// Represents actions that can be performed on the DNS configuration.
+#[derive(Debug, Deserialize, Serialize)]
+pub enum DnsAction {
+    FlushCache,
+    SetPreset(String, bool),
+}
+
+/// Retrieves the interface of the default route, none without `ip`.
+pub fn get_default_link(
+    command_runner: &dyn CommandRunner,
+) -> Result<Option<String>, Box<dyn Error>> {
+    if !is_command_installed("ip") {
+        return Ok(None);
+    }
+    let output = command_runner.run_query("ip", &["route", "show", "default"])?;
+    Ok(read_output_lines(&output)?.into_iter().find_map(|line| {
+        let mut parts = line.split_whitespace();
+        parts.find(|part| *part == "dev")?;
+        parts.next().map(|link| link.to_string())
+    }))
+}
+
+/// Retrieves the DNS servers of a link from `resolvectl dns`.
+pub fn get_link_dns_servers(
+    link: &str,
+    command_runner: &dyn CommandRunner,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let output = command_runner.run_query("resolvectl", &["dns", link])?;
+    Ok(read_output_lines(&output)?
+        .first()
+        .and_then(|line| line.split_once("):"))
+        .map(|(_, servers)| servers.split_whitespace().map(String::from).collect())
+        .unwrap_or_default())
+}
+
+/// Retrieves the DNS actions, marking the preset matching the servers of the default link.
+///
+/// Without `ip`, the link the presets apply to is unknown and no action is offered.
+pub fn get_dns_actions(
+    presets: &[DnsPreset],
+    command_runner: &dyn CommandRunner,
+) -> Result<Vec<DnsAction>, Box<dyn Error>> {
+    if !is_command_installed("ip") {
+        return Ok(Vec::new());
+    }
+    let current = match get_default_link(command_runner)? {
+        Some(link) => get_link_dns_servers(&link, command_runner)?,
+        None => Vec::new(),
+    };
+    let active = presets
+        .iter()
+        .find(|preset| !preset.servers.is_empty() && preset.servers == current)
+        .or_else(|| presets.iter().find(|preset| preset.servers.is_empty()))
+        .map(|preset| preset.name.as_str());
+
+    let mut actions: Vec<_> = presets
+        .iter()
+        .map(|preset| {
+            DnsAction::SetPreset(preset.name.clone(), active == Some(preset.name.as_str()))
+        })
+        .collect();
+    actions.push(DnsAction::FlushCache);
+    Ok(actions)
+}
+
+/// Handles a DNS action.
+pub fn handle_dns_action(
+    action: &DnsAction,
+    presets: &[DnsPreset],
+    command_runner: &dyn CommandRunner,
+) -> Result<bool, Box<dyn Error>> {
+    match action {
+        DnsAction::FlushCache => {
+            let status = command_runner
+                .run_privileged("resolvectl", &["flush-caches"])?
+                .status;
+            Ok(status.success())
+        }
+        DnsAction::SetPreset(name, _) => {
+            let preset = presets
+                .iter()
+                .find(|preset| preset.name == *name)
+                .ok_or("DNS preset not found")?;
+            let link = get_default_link(command_runner)?.ok_or("No default route found")?;
+
+            let status = if preset.servers.is_empty() {
+                command_runner.run_privileged("resolvectl", &["revert", &link])?
+            } else {
+                let mut args = vec!["dns", link.as_str()];
+                args.extend(preset.servers.iter().map(String::as_str));
+                command_runner.run_privileged("resolvectl", &args)?
+            }
+            .status;
+            Ok(status.success())
+        }
+    }
+}
@@ -0,0 +1,306 @@
+use crate::bluetooth_dbus::is_bluez_dbus_available;
+use crate::command::{is_command_installed, CommandRunner};
+use crate::config::check_config;
+use crate::launcher::Launcher;
+use crate::networkmanager::is_nm_running;
+use crate::tailscale::{get_operator_candidate, is_tailscaled_running};
+use crate::utils::get_pinentry_command;
+use crate::Config;
+use std::error::Error;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use zbus::blocking::fdo::DBusProxy;
+use zbus::blocking::Connection;
+use zbus::names::BusName;
+
+/// Optional features and the commands providing them, any one being enough.
+const TOOLS: [(&str, &[&str]); 10] = [
+    ("Wi-Fi", &["nmcli", "iwctl"]),
+    ("Tailscale", &["tailscale"]),
+    ("DNS presets", &["resolvectl"]),
+    ("Wi-Fi power save", &["iw"]),
+    ("airplane mode", &["rfkill"]),
+    ("power profiles", &["powerprofilesctl", "tuned-adm"]),
+    ("firewall", &["firewall-cmd", "ufw"]),
+    ("WireGuard", &["wg-quick"]),
+    ("clipboard", &["wl-copy", "xclip", "xsel"]),
+    ("hotspot QR code", &["qrencode"]),
+];
+
+/// Bus name of the desktop notification daemon.
+const NOTIFICATIONS_NAME: &str = "org.freedesktop.Notifications";
+
+/// Severity of a finding; only errors make the check fail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Severity {
+    Ok,
+    Warning,
+    Error,
+}
+
+/// Result of a single check, with what to do about it.
+#[derive(Debug, PartialEq, Eq)]
+struct Finding {
+    severity: Severity,
+    check: String,
+    detail: String,
+}
+
+impl Finding {
+    fn new(severity: Severity, check: &str, detail: impl Into<String>) -> Self {
+        Self {
+            severity,
+            check: check.to_string(),
+            detail: detail.into(),
+        }
+    }
+}
+
+/// Checks the tools, services and permissions the menu relies on, prints the findings and
+/// returns whether none is an error.
+pub fn run_doctor(config: &Config, command_runner: &dyn CommandRunner) -> bool {
+    let mut findings = vec![check_launcher(config)];
+    findings.extend(check_tools(is_command_installed));
+    findings.extend(check_config_file());
+    findings.extend(check_dbus());
+    if is_command_installed("nmcli") {
+        findings.push(if is_nm_running(command_runner) {
+            Finding::new(Severity::Ok, "NetworkManager", "running")
+        } else {
+            Finding::new(
+                Severity::Warning,
+                "NetworkManager",
+                "not running, start it with `systemctl enable --now NetworkManager`",
+            )
+        });
+    }
+    findings.push(if is_bluez_dbus_available() {
+        Finding::new(Severity::Ok, "BlueZ", "reachable over D-Bus")
+    } else if is_command_installed("bluetoothctl") {
+        Finding::new(Severity::Ok, "BlueZ", "not on D-Bus, using bluetoothctl")
+    } else {
+        Finding::new(
+            Severity::Warning,
+            "BlueZ",
+            "not reachable, install bluez and start `bluetooth.service`",
+        )
+    });
+    if is_command_installed("tailscale") {
+        findings.extend(check_tailscale(command_runner));
+    }
+    findings.push(check_pinentry(config));
+    findings.push(check_notifications(config));
+
+    for finding in &findings {
+        println!("{}", format_finding(finding));
+    }
+    !findings
+        .iter()
+        .any(|finding| finding.severity == Severity::Error)
+}
+
+/// Formats a finding as a line of the report.
+fn format_finding(finding: &Finding) -> String {
+    let severity = match finding.severity {
+        Severity::Ok => "ok",
+        Severity::Warning => "warning",
+        Severity::Error => "error",
+    };
+    format!("{severity:<8}{}: {}", finding.check, finding.detail)
+}
+
+/// Checks the menu command can be run.
+fn check_launcher(config: &Config) -> Finding {
+    if Launcher::detect(config).is_embedded() || is_command_installed(&config.dmenu_cmd) {
+        Finding::new(Severity::Ok, "launcher", &config.dmenu_cmd)
+    } else {
+        Finding::new(
+            Severity::Error,
+            "launcher",
+            format!(
+                "`{}` not found, install it or set dmenu_cmd in the config",
+                config.dmenu_cmd
+            ),
+        )
+    }
+}
+
+/// Checks the commands of the optional features.
+fn check_tools(is_installed: impl Fn(&str) -> bool) -> Vec<Finding> {
+    TOOLS
+        .iter()
+        .map(
+            |(feature, commands)| match commands.iter().find(|command| is_installed(command)) {
+                Some(command) => Finding::new(Severity::Ok, feature, *command),
+                None => Finding::new(
+                    Severity::Warning,
+                    feature,
+                    format!("unavailable, install {}", commands.join(" or ")),
+                ),
+            },
+        )
+        .collect()
+}
+
+/// Reports the problems of the configuration file.
+fn check_config_file() -> Vec<Finding> {
+    match check_config() {
+        Ok(problems) if problems.is_empty() => vec![Finding::new(Severity::Ok, "config", "valid")],
+        Ok(problems) => problems
+            .into_iter()
+            .map(|problem| Finding::new(Severity::Warning, "config", problem))
+            .collect(),
+        Err(err) => vec![Finding::new(Severity::Error, "config", err.to_string())],
+    }
+}
+
+/// Checks the system bus, used for NetworkManager and BlueZ, and the session bus, used
+/// for the notifications and the daemon service.
+fn check_dbus() -> Vec<Finding> {
+    let check = |bus: &str, connection: zbus::Result<Connection>| match connection {
+        Ok(_) => Finding::new(Severity::Ok, bus, "reachable"),
+        Err(err) => Finding::new(Severity::Error, bus, format!("unreachable: {err}")),
+    };
+    vec![
+        check("D-Bus system bus", Connection::system()),
+        check("D-Bus session bus", Connection::session()),
+    ]
+}
+
+/// Checks tailscaled answers and the user may change its settings without root.
+fn check_tailscale(command_runner: &dyn CommandRunner) -> Vec<Finding> {
+    if !is_tailscaled_running(command_runner) {
+        return vec![Finding::new(
+            Severity::Warning,
+            "tailscaled",
+            "not running, start it with `systemctl enable --now tailscaled`",
+        )];
+    }
+    let operator = match get_operator_candidate(command_runner) {
+        Some(user) => Finding::new(
+            Severity::Warning,
+            "Tailscale operator",
+            format!(
+                "{user} is not the operator, changing settings needs root: run \
+                 `sudo tailscale set --operator={user}`"
+            ),
+        ),
+        None => Finding::new(
+            Severity::Ok,
+            "Tailscale operator",
+            "settings can be changed",
+        ),
+    };
+    vec![
+        Finding::new(Severity::Ok, "tailscaled", "running"),
+        operator,
+    ]
+}
+
+/// Checks the pinentry program starts and answers the Assuan protocol.
+fn check_pinentry(config: &Config) -> Finding {
+    let command = match get_pinentry_command(config) {
+        Ok(command) => command,
+        Err(err) => {
+            return Finding::new(
+                Severity::Warning,
+                "pinentry",
+                format!("{err}, only open and known Wi-Fi networks can be joined"),
+            )
+        }
+    };
+    let program = command.split_whitespace().next().unwrap_or_default();
+    let is_pinentry = Path::new(program)
+        .file_name()
+        .is_some_and(|name| name.to_string_lossy().starts_with("pinentry"));
+    if !is_command_installed(program) {
+        return Finding::new(
+            Severity::Error,
+            "pinentry",
+            format!("`{program}` not found, install it or fix pinentry_cmd"),
+        );
+    }
+    if !is_pinentry {
+        return Finding::new(Severity::Ok, "pinentry", command);
+    }
+    match greet_pinentry(program) {
+        Ok(true) => Finding::new(Severity::Ok, "pinentry", command),
+        Ok(false) => Finding::new(
+            Severity::Error,
+            "pinentry",
+            format!("`{program}` does not answer, try another pinentry_cmd"),
+        ),
+        Err(err) => Finding::new(
+            Severity::Error,
+            "pinentry",
+            format!("`{program}` fails to start: {err}"),
+        ),
+    }
+}
+
+/// Starts pinentry and closes it right away, checking it greets with `OK`.
+fn greet_pinentry(program: &str) -> Result<bool, Box<dyn Error>> {
+    let mut child = Command::new(program)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+    child
+        .stdin
+        .take()
+        .ok_or("Failed to open stdin")?
+        .write_all(b"BYE\n")?;
+    let output = child.wait_with_output()?;
+    Ok(String::from_utf8_lossy(&output.stdout).starts_with("OK"))
+}
+
+/// Checks a notification daemon runs, or can be started, on the session bus.
+fn check_notifications(config: &Config) -> Finding {
+    if !config.notifications.enabled {
+        return Finding::new(Severity::Ok, "notifications", "disabled in the config");
+    }
+    let available = (|| -> zbus::Result<bool> {
+        let connection = Connection::session()?;
+        let proxy = DBusProxy::new(&connection)?;
+        let name = BusName::try_from(NOTIFICATIONS_NAME)?;
+        Ok(proxy.name_has_owner(name.clone())?
+            || proxy
+                .list_activatable_names()?
+                .iter()
+                .any(|activatable| activatable.as_ref() == name))
+    })();
+    match available {
+        Ok(true) => Finding::new(Severity::Ok, "notifications", "daemon available"),
+        Ok(false) => Finding::new(
+            Severity::Warning,
+            "notifications",
+            "no notification daemon, install one such as mako or dunst",
+        ),
+        Err(err) => Finding::new(
+            Severity::Warning,
+            "notifications",
+            format!("cannot be checked: {err}"),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_missing_tools() {
+        let findings = check_tools(|command| ["nmcli", "tailscale", "ufw"].contains(&command));
+        assert_eq!(findings[0], Finding::new(Severity::Ok, "Wi-Fi", "nmcli"));
+        assert_eq!(
+            format_finding(&findings[2]),
+            "warning DNS presets: unavailable, install resolvectl"
+        );
+        assert_eq!(format_finding(&findings[6]), "ok      firewall: ufw");
+        assert_eq!(
+            format_finding(&findings[5]),
+            "warning power profiles: unavailable, install powerprofilesctl or tuned-adm"
+        );
+    }
+}
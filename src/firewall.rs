@@ -0,0 +1,306 @@
+use crate::command::{is_command_installed, read_output_lines, CommandRunner};
+use crate::dns::get_default_link;
+use crate::utils::{shell_quote, write_state_file};
+use dirs::state_dir;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+
+/// Represents actions switching the firewall zone or profile.
+#[derive(Debug, Deserialize, Serialize)]
+pub enum FirewallAction {
+    SetUfwProfile(String, bool),
+    SetZone(String, bool),
+}
+
+/// ufw profile, setting the default policy and adding the rules on top of the existing ones.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct UfwProfile {
+    pub name: String,
+    #[serde(default = "default_incoming_policy")]
+    pub incoming: String,
+    #[serde(default)]
+    pub rules: Vec<String>,
+}
+
+/// Returns the default policy of incoming traffic.
+fn default_incoming_policy() -> String {
+    "deny".to_string()
+}
+
+/// Retrieves the firewalld zones or the ufw profiles, marking the current one.
+pub fn get_firewall_actions(
+    ufw_profiles: &[UfwProfile],
+    command_runner: &dyn CommandRunner,
+) -> Result<Vec<FirewallAction>, Box<dyn Error>> {
+    if is_command_installed("firewall-cmd") {
+        return get_zone_actions(command_runner);
+    }
+    if is_command_installed("ufw") {
+        let active = get_active_ufw_profile(ufw_profiles, command_runner);
+        return Ok(ufw_profiles
+            .iter()
+            .map(|profile| {
+                FirewallAction::SetUfwProfile(
+                    profile.name.clone(),
+                    active.as_ref() == Some(&profile.name),
+                )
+            })
+            .collect());
+    }
+    Ok(Vec::new())
+}
+
+/// Retrieves the firewalld zones, marking the zone of the default route interface.
+fn get_zone_actions(
+    command_runner: &dyn CommandRunner,
+) -> Result<Vec<FirewallAction>, Box<dyn Error>> {
+    let output = command_runner.run_query("firewall-cmd", &["--get-zones"])?;
+    if !output.status.success() {
+        return Ok(Vec::new());
+    }
+    let zones: Vec<String> = read_output_lines(&output)?
+        .iter()
+        .flat_map(|line| line.split_whitespace())
+        .map(str::to_string)
+        .collect();
+
+    let current = match get_default_link(command_runner)? {
+        Some(link) => {
+            let output = command_runner.run_query(
+                "firewall-cmd",
+                &[&format!("--get-zone-of-interface={link}")],
+            )?;
+            Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+        }
+        None => None,
+    };
+
+    Ok(zones
+        .into_iter()
+        .map(|zone| {
+            let active = current.as_ref() == Some(&zone);
+            FirewallAction::SetZone(zone, active)
+        })
+        .collect())
+}
+
+/// Gets the path of the file recording the ufw profile applied last.
+fn get_ufw_state_path() -> Option<PathBuf> {
+    Some(state_dir()?.join("network-dmenu").join("ufw-profile"))
+}
+
+/// Reads the name of the ufw profile applied last.
+fn get_recorded_ufw_profile() -> Option<String> {
+    let name = fs::read_to_string(get_ufw_state_path()?).ok()?;
+    Some(name.trim().to_string())
+}
+
+/// Finds the profile ufw is set to, the one applied last when several match.
+///
+/// A profile matches when ufw is enabled with its incoming policy and, if readable without
+/// root, its rules.
+fn get_active_ufw_profile(
+    ufw_profiles: &[UfwProfile],
+    command_runner: &dyn CommandRunner,
+) -> Option<String> {
+    let enabled = fs::read_to_string("/etc/ufw/ufw.conf").ok()?;
+    if !read_config_value(&enabled, "ENABLED")?.eq_ignore_ascii_case("yes") {
+        return None;
+    }
+    let defaults = fs::read_to_string("/etc/default/ufw").ok()?;
+    let incoming = parse_ufw_policy(read_config_value(&defaults, "DEFAULT_INPUT_POLICY")?)?;
+    let rules = get_ufw_rules(command_runner);
+
+    let matching: Vec<&UfwProfile> = ufw_profiles
+        .iter()
+        .filter(|profile| profile.incoming == incoming)
+        .filter(|profile| {
+            rules.as_ref().is_none_or(|rules| {
+                profile
+                    .rules
+                    .iter()
+                    .all(|rule| rules.contains(&normalize_rule(rule)))
+            })
+        })
+        .collect();
+    let recorded = get_recorded_ufw_profile();
+    match matching.as_slice() {
+        [profile] => Some(profile.name.clone()),
+        _ => recorded.filter(|name| matching.iter().any(|profile| &profile.name == name)),
+    }
+}
+
+/// Reads the value of a `KEY=value` line of a ufw configuration file.
+fn read_config_value<'a>(contents: &'a str, key: &str) -> Option<&'a str> {
+    contents.lines().find_map(|line| {
+        let value = line.trim().strip_prefix(key)?.strip_prefix('=')?;
+        Some(value.trim().trim_matches('"'))
+    })
+}
+
+/// Converts an iptables chain policy to the ufw policy it is set with.
+fn parse_ufw_policy(policy: &str) -> Option<&'static str> {
+    match policy {
+        "ACCEPT" => Some("allow"),
+        "DROP" => Some("deny"),
+        "REJECT" => Some("reject"),
+        _ => None,
+    }
+}
+
+/// Lists the rules added to ufw, none when they cannot be read without root.
+fn get_ufw_rules(command_runner: &dyn CommandRunner) -> Option<Vec<String>> {
+    let output = command_runner
+        .run_query("ufw", &["show", "added"])
+        .ok()
+        .filter(|output| output.status.success())?;
+    Some(
+        read_output_lines(&output)
+            .ok()?
+            .iter()
+            .filter_map(|line| line.strip_prefix("ufw "))
+            .map(normalize_rule)
+            .collect(),
+    )
+}
+
+/// Normalizes the spacing of a rule, to compare it with the ones listed by ufw.
+fn normalize_rule(rule: &str) -> String {
+    rule.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Applies the selected zone or profile.
+pub fn handle_firewall_action(
+    action: &FirewallAction,
+    ufw_profiles: &[UfwProfile],
+    command_runner: &dyn CommandRunner,
+) -> Result<bool, Box<dyn Error>> {
+    match action {
+        FirewallAction::SetZone(zone, _) => {
+            let link = get_default_link(command_runner)?.ok_or("No default route interface")?;
+            let status = command_runner
+                .run_privileged(
+                    "firewall-cmd",
+                    &[
+                        &format!("--zone={zone}"),
+                        &format!("--change-interface={link}"),
+                    ],
+                )?
+                .status;
+            Ok(status.success())
+        }
+        FirewallAction::SetUfwProfile(name, _) => {
+            let profile = ufw_profiles
+                .iter()
+                .find(|profile| &profile.name == name)
+                .ok_or_else(|| format!("ufw profile {name} not found"))?;
+            let status = command_runner
+                .run_privileged("sh", &["-c", &ufw_script(profile, ufw_profiles)])?
+                .status;
+            if status.success() {
+                write_state_file(get_ufw_state_path(), &profile.name)?;
+            }
+            Ok(status.success())
+        }
+    }
+}
+
+/// Builds the script switching ufw to a profile, run at once to ask for authorization once.
+///
+/// Only the rules of the other profiles are deleted, so that the rules added by hand stay.
+fn ufw_script(profile: &UfwProfile, ufw_profiles: &[UfwProfile]) -> String {
+    let mut stale: Vec<&String> = Vec::new();
+    for rule in ufw_profiles
+        .iter()
+        .filter(|other| other.name != profile.name)
+        .flat_map(|other| &other.rules)
+    {
+        if !profile.rules.contains(rule) && !stale.contains(&rule) {
+            stale.push(rule);
+        }
+    }
+
+    // Deleting a rule that is not there fails, which does not stop the switch
+    let mut commands: Vec<String> = stale
+        .into_iter()
+        .map(|rule| format!("{{ ufw --force delete {} || true; }}", ufw_args(rule)))
+        .collect();
+    commands.push(format!(
+        "ufw default {} incoming",
+        shell_quote(&profile.incoming)
+    ));
+    commands.push("ufw default allow outgoing".to_string());
+    commands.extend(
+        profile
+            .rules
+            .iter()
+            .map(|rule| format!("ufw {}", ufw_args(rule))),
+    );
+    commands.push("ufw --force enable".to_string());
+    commands.join(" && ")
+}
+
+/// Quotes the arguments of a rule for the ufw script.
+fn ufw_args(rule: &str) -> String {
+    let args: Vec<String> = rule.split_whitespace().map(shell_quote).collect();
+    args.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockCommandRunner;
+
+    #[test]
+    fn builds_ufw_script() {
+        let profiles = [
+            UfwProfile {
+                name: "home".to_string(),
+                incoming: default_incoming_policy(),
+                rules: vec![
+                    "allow from 192.168.1.0/24".to_string(),
+                    "allow 22/tcp".to_string(),
+                ],
+            },
+            UfwProfile {
+                name: "work".to_string(),
+                incoming: default_incoming_policy(),
+                rules: vec!["allow 22/tcp".to_string(), "allow 8080".to_string()],
+            },
+        ];
+        assert_eq!(
+            ufw_script(&profiles[0], &profiles),
+            "{ ufw --force delete 'allow' '8080' || true; } && ufw default 'deny' incoming \
+             && ufw default allow outgoing && ufw 'allow' 'from' '192.168.1.0/24' \
+             && ufw 'allow' '22/tcp' && ufw --force enable"
+        );
+    }
+
+    #[test]
+    fn reads_ufw_state() {
+        assert_eq!(
+            read_config_value(
+                "IPV6=yes\nDEFAULT_INPUT_POLICY=\"DROP\"\n",
+                "DEFAULT_INPUT_POLICY"
+            )
+            .and_then(parse_ufw_policy),
+            Some("deny")
+        );
+
+        let runner = MockCommandRunner::new()
+            .expect(
+                "ufw",
+                &["show", "added"],
+                "Added user rules (see 'ufw status' for running firewall):\n\
+                 ufw allow from 192.168.1.0/24\nufw allow  22/tcp\n",
+            )
+            .expect_failure("ufw", &["show", "added"]);
+        assert_eq!(
+            get_ufw_rules(&runner).unwrap(),
+            ["allow from 192.168.1.0/24", "allow 22/tcp"]
+        );
+        assert_eq!(get_ufw_rules(&runner), None);
+    }
+}
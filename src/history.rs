@@ -0,0 +1,207 @@
+use crate::i18n::tr_args;
+use crate::ordering::now;
+use crate::utils::write_state_file;
+use dirs::state_dir;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use tracing::warn;
+
+/// Failures older than this are not reported next to the networks.
+const RECENT_FAILURES_WINDOW: u64 = 7 * 24 * 3600;
+
+/// Number of failures kept per network.
+const MAX_FAILURES: usize = 20;
+
+/// Connection statistics of a single network.
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct NetworkHistory {
+    connections: u64,
+    /// Timestamps of the last failed connection attempts.
+    failures: Vec<u64>,
+    last_connected: Option<u64>,
+    /// Start of the current connection, if still connected.
+    connected_since: Option<u64>,
+    connected_seconds: u64,
+}
+
+/// Persistent connection history of the Wi-Fi networks, by SSID.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct WifiHistory {
+    #[serde(default)]
+    networks: HashMap<String, NetworkHistory>,
+}
+
+/// Gets the history file path.
+fn get_history_path() -> Option<PathBuf> {
+    Some(state_dir()?.join("network-dmenu").join("wifi-history.json"))
+}
+
+/// Loads the Wi-Fi history, falling back to an empty history.
+pub fn load_wifi_history() -> WifiHistory {
+    get_history_path()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+/// Loads the history, updates it and writes it back, logging failures.
+fn update_wifi_history(update: impl FnOnce(&mut WifiHistory, u64) -> bool) {
+    let mut history = load_wifi_history();
+    if !update(&mut history, now()) {
+        return;
+    }
+    let result = serde_json::to_string(&history)
+        .map_err(Into::into)
+        .and_then(|json| write_state_file(get_history_path(), &json));
+    if let Err(err) = result {
+        warn!("failed to save the Wi-Fi history: {err}");
+    }
+}
+
+/// Records a connection attempt to a network.
+pub fn record_wifi_connection(ssid: &str, success: bool) {
+    update_wifi_history(|history, now| {
+        history.record_attempt(ssid, success, now);
+        true
+    });
+}
+
+/// Records the disconnection of the current network.
+pub fn record_wifi_disconnection() {
+    update_wifi_history(|history, now| history.end_sessions(None, now));
+}
+
+/// Follows the connected network, e.g. as seen by the daemon, to keep the durations right.
+pub fn sync_wifi_history(ssid: Option<&str>) {
+    update_wifi_history(|history, now| history.follow_network(ssid, now));
+}
+
+impl WifiHistory {
+    /// Records a connection attempt, ending the connections to the other networks.
+    fn record_attempt(&mut self, ssid: &str, success: bool, now: u64) {
+        if success {
+            self.end_sessions(Some(ssid), now);
+        }
+        let network = self.networks.entry(ssid.to_string()).or_default();
+        if success {
+            network.connections += 1;
+            network.last_connected = Some(now);
+            network.connected_since.get_or_insert(now);
+        } else {
+            network.failures.push(now);
+            let excess = network.failures.len().saturating_sub(MAX_FAILURES);
+            network.failures.drain(..excess);
+        }
+    }
+
+    /// Ends the connections to every network but the given one, returning if any ended.
+    fn end_sessions(&mut self, except: Option<&str>, now: u64) -> bool {
+        let mut ended = false;
+        for (ssid, network) in &mut self.networks {
+            if Some(ssid.as_str()) == except {
+                continue;
+            }
+            if let Some(since) = network.connected_since.take() {
+                network.connected_seconds += now.saturating_sub(since);
+                ended = true;
+            }
+        }
+        ended
+    }
+
+    /// Updates the connections from the connected network, returning if anything changed.
+    fn follow_network(&mut self, ssid: Option<&str>, now: u64) -> bool {
+        let mut changed = self.end_sessions(ssid, now);
+        if let Some(ssid) = ssid {
+            let network = self.networks.entry(ssid.to_string()).or_default();
+            if network.connected_since.is_none() {
+                network.connected_since = Some(now);
+                network.last_connected = Some(now);
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// Counts the failed connections to a network during the last days.
+    pub fn recent_failures(&self, ssid: &str, now: u64) -> usize {
+        self.networks.get(ssid).map_or(0, |network| {
+            network
+                .failures
+                .iter()
+                .filter(|&&failure| now.saturating_sub(failure) < RECENT_FAILURES_WINDOW)
+                .count()
+        })
+    }
+
+    /// Checks if any network was recorded.
+    pub fn is_empty(&self) -> bool {
+        self.networks.is_empty()
+    }
+
+    /// Summarizes the history, one line per network, the last connected first.
+    pub fn summary(&self, now: u64) -> Vec<String> {
+        let mut networks: Vec<(&String, &NetworkHistory)> = self.networks.iter().collect();
+        networks.sort_by_key(|(ssid, network)| (std::cmp::Reverse(network.last_connected), *ssid));
+        networks
+            .into_iter()
+            .map(|(ssid, network)| {
+                let connected = network.connected_seconds
+                    + network
+                        .connected_since
+                        .map_or(0, |since| now.saturating_sub(since));
+                tr_args(
+                    "history-summary",
+                    &[
+                        ("ssid", ssid),
+                        ("connections", &network.connections.to_string()),
+                        ("duration", &format_duration(connected)),
+                        ("failures", &self.recent_failures(ssid, now).to_string()),
+                    ],
+                )
+            })
+            .collect()
+    }
+}
+
+/// Returns the annotation of a network that failed to connect recently.
+pub fn failures_annotation(failures: usize) -> Option<String> {
+    (failures > 0).then(|| tr_args("wifi-failed-recently", &[("count", &failures.to_string())]))
+}
+
+/// Formats a duration in hours and minutes.
+fn format_duration(seconds: u64) -> String {
+    let minutes = seconds / 60;
+    match (minutes / 60, minutes % 60) {
+        (0, minutes) => format!("{minutes}m"),
+        (hours, minutes) => format!("{hours}h {minutes:02}m"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tracks_connections_and_failures() {
+        let mut history = WifiHistory::default();
+        history.record_attempt("Home", false, 0);
+        history.record_attempt("Home", true, 60);
+        history.record_attempt("Office", true, 3660);
+        assert!(history.follow_network(None, 4260));
+        assert!(!history.follow_network(None, 4300));
+
+        assert_eq!(history.recent_failures("Home", 60), 1);
+        assert_eq!(history.recent_failures("Home", RECENT_FAILURES_WINDOW), 0);
+        assert_eq!(history.recent_failures("Office", 60), 0);
+        assert_eq!(
+            history.summary(4300),
+            [
+                "Office: connected 1×, 10m in total, failed 0× recently",
+                "Home: connected 1×, 1h 00m in total, failed 1× recently",
+            ]
+        );
+    }
+}
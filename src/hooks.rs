@@ -0,0 +1,133 @@
+use crate::command::CommandRunner;
+use crate::tailscale::{get_active_exit_node, TailscaleAction};
+use crate::{action_key, action_to_string, get_current_ssid, ActionType, Config, WifiAction};
+use serde::{Deserialize, Serialize};
+use std::process::{Command, Stdio};
+use tracing::warn;
+
+/// Prefix of the environment variables describing the action to the hooks.
+///
+/// Ignored by the configuration overrides, so that hooks may run network-dmenu again.
+pub const HOOK_ENV_PREFIX: &str = "NETWORK_DMENU_HOOK_";
+
+/// Shell commands run after the actions.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct HooksConfig {
+    #[serde(default)]
+    pub post_action: Option<String>,
+    #[serde(default)]
+    pub post_wifi_connect: Option<String>,
+    #[serde(default)]
+    pub post_exit_node_change: Option<String>,
+}
+
+/// Runs the hooks matching an action once it is handled.
+pub fn run_hooks(
+    config: &Config,
+    wifi_interface: &str,
+    action: &ActionType,
+    success: bool,
+    command_runner: &dyn CommandRunner,
+) {
+    let hooks = &config.hooks;
+    let mut variables = vec![
+        ("ACTION", action_key(action)),
+        ("ENTRY", action_to_string(config, action)),
+        ("SUCCESS", success.to_string()),
+        ("INTERFACE", wifi_interface.to_string()),
+    ];
+    if let Some(command) = &hooks.post_action {
+        run_hook(command, "post_action", &variables);
+    }
+    if !success {
+        return;
+    }
+
+    if let Some(command) = &hooks.post_wifi_connect {
+        if is_wifi_connect(action) {
+            if let Ok(Some(ssid)) = get_current_ssid(config, wifi_interface, command_runner) {
+                variables.push(("SSID", ssid));
+                run_hook(command, "post_wifi_connect", &variables);
+            }
+        }
+    }
+    if let Some(command) = &hooks.post_exit_node_change {
+        if is_exit_node_change(action) {
+            variables.push(("EXIT_NODE", get_active_exit_node(command_runner)));
+            run_hook(command, "post_exit_node_change", &variables);
+        }
+    }
+}
+
+/// Checks if an action may connect to a Wi-Fi network.
+fn is_wifi_connect(action: &ActionType) -> bool {
+    matches!(
+        action,
+        ActionType::Wifi(
+            WifiAction::Connect
+                | WifiAction::ConnectAccessPoint
+                | WifiAction::ConnectHidden
+                | WifiAction::ConnectWps
+                | WifiAction::Network(_)
+                | WifiAction::Rescan
+                | WifiAction::SavedOutOfRange(_)
+                | WifiAction::SelectInterface(_)
+        )
+    )
+}
+
+/// Checks if an action sets or clears the Tailscale exit node.
+fn is_exit_node_change(action: &ActionType) -> bool {
+    matches!(
+        action,
+        ActionType::Tailscale(
+            TailscaleAction::SetExitNode(_)
+                | TailscaleAction::SetSuggestedExitNode
+                | TailscaleAction::SetRandomExitNode(_)
+                | TailscaleAction::SelectMullvadCountry(_)
+                | TailscaleAction::DisableExitNode
+        )
+    )
+}
+
+/// Runs a hook with `sh -c`, waiting for it so that hooks run in order.
+fn run_hook(command: &str, event: &str, variables: &[(&str, String)]) {
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .env(format!("{HOOK_ENV_PREFIX}EVENT"), event)
+        .envs(
+            variables
+                .iter()
+                .map(|(name, value)| (format!("{HOOK_ENV_PREFIX}{name}"), value)),
+        )
+        .stdin(Stdio::null())
+        .status();
+    match status {
+        Ok(status) if status.success() => {}
+        Ok(status) => warn!("{event} hook failed with {status}"),
+        Err(err) => warn!("failed to run {event} hook: {err}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn passes_variables_to_hooks() {
+        let path = std::env::temp_dir().join(format!("network-dmenu-hook-{}", std::process::id()));
+        let command = format!(
+            "printf %s \"$NETWORK_DMENU_HOOK_EVENT $NETWORK_DMENU_HOOK_SSID\" > {}",
+            path.display()
+        );
+        run_hook(
+            &command,
+            "post_wifi_connect",
+            &[("SSID", "Home".to_string())],
+        );
+        assert_eq!(fs::read_to_string(&path).unwrap(), "post_wifi_connect Home");
+        let _ = fs::remove_file(path);
+    }
+}
@@ -0,0 +1,114 @@
+use fluent_bundle::concurrent::FluentBundle;
+use fluent_bundle::{FluentArgs, FluentResource};
+use std::sync::OnceLock;
+use tracing::warn;
+use unic_langid::LanguageIdentifier;
+
+/// Message catalogs embedded in the binary, keyed by language, English being the fallback.
+const CATALOGS: [(&str, &str); 2] = [
+    ("en", include_str!("../locales/en.ftl")),
+    ("fr", include_str!("../locales/fr.ftl")),
+];
+
+/// Environment variables giving the language of the messages, by precedence.
+const LOCALE_VARIABLES: [&str; 3] = ["LC_ALL", "LC_MESSAGES", "LANG"];
+
+/// Bundles looked up in order: the selected language, then English.
+static BUNDLES: OnceLock<Vec<FluentBundle<FluentResource>>> = OnceLock::new();
+
+/// Selects the language of the messages, from the `locale` setting or else the environment.
+///
+/// Languages without a catalog fall back to English.
+pub fn init_locale(locale: Option<&str>) {
+    let locale = locale.map(str::to_string).or_else(|| {
+        LOCALE_VARIABLES
+            .iter()
+            .find_map(|name| std::env::var(name).ok().filter(|value| !value.is_empty()))
+    });
+    let language = locale
+        .as_deref()
+        .and_then(|locale| locale.split(['_', '-', '.', '@']).next())
+        .unwrap_or("en")
+        .to_lowercase();
+    let _ = BUNDLES.set(build_bundles(&language));
+}
+
+/// Builds the bundles of a language and of the English fallback.
+fn build_bundles(language: &str) -> Vec<FluentBundle<FluentResource>> {
+    let mut languages = vec![language];
+    if language != "en" {
+        languages.push("en");
+    }
+    languages
+        .into_iter()
+        .filter_map(|language| {
+            let (_, source) = CATALOGS.iter().find(|(name, _)| *name == language)?;
+            let id: LanguageIdentifier = language.parse().ok()?;
+            let resource = FluentResource::try_new(source.to_string())
+                .map_err(|(_, errors)| warn!("invalid {language} catalog: {errors:?}"))
+                .ok()?;
+            let mut bundle = FluentBundle::new_concurrent(vec![id]);
+            // Isolation marks would end up in the menu entries and notifications.
+            bundle.set_use_isolating(false);
+            bundle.add_resource(resource).ok()?;
+            Some(bundle)
+        })
+        .collect()
+}
+
+/// Translates a message with arguments, e.g. `tr_args("wifi-connected", &[("ssid", ssid)])`.
+pub fn tr_args(id: &str, args: &[(&str, &str)]) -> String {
+    let mut fluent_args = FluentArgs::new();
+    for (name, value) in args {
+        fluent_args.set(*name, *value);
+    }
+    BUNDLES
+        .get_or_init(|| build_bundles("en"))
+        .iter()
+        .find_map(|bundle| {
+            let pattern = bundle.get_message(id)?.value()?;
+            let mut errors = Vec::new();
+            Some(
+                bundle
+                    .format_pattern(pattern, Some(&fluent_args), &mut errors)
+                    .into_owned(),
+            )
+        })
+        .unwrap_or_else(|| {
+            warn!("missing message {id}");
+            id.to_string()
+        })
+}
+
+/// Translates a message without arguments.
+pub fn tr(id: &str) -> String {
+    tr_args(id, &[])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn translates_every_message() {
+        let english = build_bundles("en");
+        let ids: Vec<&str> = CATALOGS[0]
+            .1
+            .lines()
+            .filter_map(|line| line.split_once(" =").map(|(id, _)| id))
+            .filter(|id| !id.is_empty() && !id.starts_with([' ', '#']))
+            .collect();
+        assert!(ids.iter().all(|id| english[0].has_message(id)));
+
+        for (language, _) in &CATALOGS[1..] {
+            let bundles = build_bundles(language);
+            for id in &ids {
+                assert!(bundles[0].has_message(id), "{language} lacks {id}");
+            }
+        }
+        assert_eq!(
+            tr_args("wifi-connected", &[("ssid", "Home")]),
+            "Connected to Home"
+        );
+    }
+}
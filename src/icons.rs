@@ -0,0 +1,149 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Glyph sets available for the menu icons.
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum IconTheme {
+    #[default]
+    Emoji,
+    Nerdfont,
+    Ascii,
+    None,
+}
+
+/// Menu icons as `(name, emoji, nerd-font, ASCII)` glyphs.
+///
+/// Menu entries take their glyphs from here by name. Entries built by the sections, e.g.
+/// the Wi-Fi networks, carry emoji glyphs and are translated when shown.
+const ICONS: &[(&str, &str, &str, &str)] = &[
+    ("active", "✅", "\u{f00c}", "*"),
+    ("inactive", "❌", "\u{f00d}", "x"),
+    ("wifi", "📶", "\u{f1eb}", "~"),
+    ("shield", "🛡️", "\u{f132}", "#"),
+    ("diagnostics", "🩺", "\u{f0f1}", "?"),
+    ("hotspot", "📡", "\u{f2ce}", "^"),
+    ("vpn", "🔒", "\u{f023}", "@"),
+    ("dns", "🌐", "\u{f0ac}", "="),
+    ("share", "🔑", "\u{f084}", "&"),
+    ("logout", "🚪", "\u{f08b}", "<"),
+    ("remove", "🗑️", "\u{f1f8}", "-"),
+    ("add", "➕", "\u{f067}", "+"),
+    ("audio", "🎧", "\u{f025}", "%"),
+    ("battery", "🔋", "\u{f240}", "bat:"),
+    ("exit_node", "🌿", "\u{f06c}", ">"),
+    ("suggested", "⭐", "\u{f005}", "!"),
+    ("random", "🎲", "\u{f074}", "%"),
+    ("unknown", "❓", "\u{f128}", "?"),
+    ("power", "⚡", "\u{f0e7}", "z"),
+    ("bluetooth", "\u{f293}", "\u{f293}", "b"),
+    ("info", "ℹ️", "\u{f129}", "i"),
+    ("settings", "⚙️", "\u{f013}", "cfg:"),
+    ("search", "🔍", "\u{f002}", "/"),
+    ("tag", "🏷️", "\u{f02b}", "tag:"),
+    ("tor", "🧅", "\u{f21b}", "o"),
+    ("profile", "🎬", "\u{f008}", "p"),
+    ("ip", "🖧", "\u{f0e8}", "ip:"),
+    ("history", "📜", "\u{f1da}", "h"),
+    ("rescan", "🔄", "\u{f021}", "r"),
+    ("out_of_range", "💤", "\u{f186}", "zz"),
+    ("waiting", "⏳", "\u{f252}", "..."),
+];
+
+/// Returns the glyph of a menu icon in the given theme, or its override.
+pub fn icon<'a>(name: &str, theme: IconTheme, overrides: &'a HashMap<String, String>) -> &'a str {
+    if let Some(glyph) = overrides.get(name) {
+        return glyph;
+    }
+    let Some((_, emoji, nerdfont, ascii)) = ICONS.iter().find(|(icon, ..)| *icon == name) else {
+        debug_assert!(false, "unknown icon {name}");
+        return "";
+    };
+    match theme {
+        IconTheme::Emoji => emoji,
+        IconTheme::Nerdfont => nerdfont,
+        IconTheme::Ascii => ascii,
+        IconTheme::None => "",
+    }
+}
+
+/// Returns a country flag in the given theme, as its country code outside of the emoji
+/// theme.
+pub fn flag(flag: &str, theme: IconTheme) -> String {
+    match theme {
+        IconTheme::Emoji => flag.to_string(),
+        _ => replace_flags(flag, theme == IconTheme::None),
+    }
+}
+
+/// Translates the icons of a menu entry to the given theme and user overrides.
+///
+/// Overrides are keyed by icon name, e.g. `active` or `wifi`. Country flags are
+/// shown as their country code outside of the emoji theme.
+pub fn apply_icon_theme(
+    entry: &str,
+    theme: IconTheme,
+    overrides: &HashMap<String, String>,
+) -> String {
+    if theme == IconTheme::Emoji && overrides.is_empty() {
+        return entry.to_string();
+    }
+
+    let entry = ICONS
+        .iter()
+        .fold(entry.to_string(), |entry, (name, emoji, ..)| {
+            let glyph = icon(name, theme, overrides);
+            if glyph.is_empty() {
+                entry.replace(&format!("{emoji} "), "").replace(emoji, "")
+            } else {
+                entry.replace(emoji, glyph)
+            }
+        });
+
+    if theme == IconTheme::Emoji {
+        entry
+    } else {
+        replace_flags(&entry, theme == IconTheme::None)
+    }
+}
+
+/// Replaces flag emoji by their country code, or removes them.
+fn replace_flags(entry: &str, remove: bool) -> String {
+    let mut result = String::with_capacity(entry.len());
+    let mut chars = entry.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\u{1f1e6}'..='\u{1f1ff}' => {
+                if !remove {
+                    result.push((b'A' + (c as u32 - 0x1f1e6) as u8) as char);
+                } else if !chars
+                    .peek()
+                    .is_some_and(|next| matches!(next, '\u{1f1e6}'..='\u{1f1ff}'))
+                {
+                    chars.next_if_eq(&' ');
+                }
+            }
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_icons_in_theme() {
+        let overrides = HashMap::from([("active".to_string(), "ON".to_string())]);
+        assert_eq!(icon("active", IconTheme::Ascii, &overrides), "ON");
+        assert_eq!(icon("wifi", IconTheme::Ascii, &overrides), "~");
+        assert_eq!(icon("wifi", IconTheme::None, &overrides), "");
+        assert_eq!(flag("🇫🇷", IconTheme::Nerdfont), "FR");
+        assert_eq!(flag("🇫🇷", IconTheme::None), "");
+        assert_eq!(
+            apply_icon_theme("✅ Home\tWPA2", IconTheme::Nerdfont, &overrides),
+            "ON Home\tWPA2"
+        );
+    }
+}
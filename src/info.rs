@@ -0,0 +1,162 @@
+use crate::command::{is_command_installed, read_output_lines, CommandRunner};
+use crate::connectivity::get_geolocation;
+use crate::diagnostics::get_default_gateway;
+use crate::dns::{get_default_link, get_link_dns_servers};
+use crate::i18n::tr;
+use crate::notifications::notify;
+use crate::tailscale::get_active_exit_node;
+use crate::utils::copy_to_clipboard;
+use crate::Config;
+use std::error::Error;
+use std::fs;
+use std::time::Duration;
+
+/// Gathers a summary of the current connection, one `label: value` line per detail.
+pub async fn get_connection_info(
+    config: &Config,
+    wifi_interface: &str,
+    command_runner: &dyn CommandRunner,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut info = Vec::new();
+
+    if is_command_installed("iw") {
+        if let Some(ssid) = get_ssid(wifi_interface, command_runner)? {
+            info.push(format!("SSID: {ssid}"));
+        }
+    }
+
+    if is_command_installed("ip") {
+        let output = command_runner.run_query("ip", &["-brief", "address", "show"])?;
+        for (interface, addresses) in parse_local_addresses(&read_output_lines(&output)?) {
+            info.push(format!("Local IP: {interface} {}", addresses.join(" ")));
+        }
+        if let Some(gateway) = get_default_gateway(command_runner)? {
+            info.push(format!("Gateway: {gateway}"));
+        }
+    }
+
+    let dns_servers = get_dns_servers(command_runner)?;
+    if !dns_servers.is_empty() {
+        info.push(format!("DNS: {}", dns_servers.join(" ")));
+    }
+
+    let timeout = Duration::from_secs(config.connectivity_timeout);
+    if let Ok(location) = get_geolocation(&config.connectivity_url, timeout, None).await {
+        info.push(format!(
+            "Public IP: {} ({}, {}, {})",
+            location.ip, location.city, location.country, location.provider
+        ));
+    }
+
+    if is_command_installed("tailscale") {
+        let output = command_runner.run_query("tailscale", &["ip", "-4"])?;
+        if output.status.success() {
+            let ip = String::from_utf8_lossy(&output.stdout).trim().to_string();
+            info.push(format!("Tailscale IP: {ip}"));
+        }
+        let exit_node = get_active_exit_node(command_runner);
+        if !exit_node.is_empty() {
+            info.push(format!("Exit node: {exit_node}"));
+        }
+    }
+
+    Ok(info)
+}
+
+/// Notifies the connection summary and copies it to the clipboard.
+pub async fn show_connection_info(
+    config: &Config,
+    wifi_interface: &str,
+    command_runner: &dyn CommandRunner,
+) -> Result<bool, Box<dyn Error>> {
+    let info = get_connection_info(config, wifi_interface, command_runner)
+        .await?
+        .join("\n");
+    let copied = copy_to_clipboard(&info)?;
+    notify(
+        config,
+        &tr(if copied {
+            "connection-info-copied"
+        } else {
+            "connection-info"
+        }),
+        &info,
+    );
+    Ok(true)
+}
+
+/// Retrieves the SSID the interface is associated with from `iw dev <iface> link`.
+fn get_ssid(
+    wifi_interface: &str,
+    command_runner: &dyn CommandRunner,
+) -> Result<Option<String>, Box<dyn Error>> {
+    let output = command_runner.run_query("iw", &["dev", wifi_interface, "link"])?;
+    Ok(read_output_lines(&output)?
+        .iter()
+        .find_map(|line| line.trim().strip_prefix("SSID: ").map(str::to_string)))
+}
+
+/// Parses `ip -brief address show` into the addresses of each interface, leaving out
+/// loopback and link-local addresses.
+fn parse_local_addresses(lines: &[String]) -> Vec<(String, Vec<String>)> {
+    lines
+        .iter()
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            let interface = parts.next()?.to_string();
+            let addresses: Vec<String> = parts
+                .skip(1)
+                .filter(|address| !address.starts_with("fe80:"))
+                .map(str::to_string)
+                .collect();
+            (interface != "lo" && !addresses.is_empty()).then_some((interface, addresses))
+        })
+        .collect()
+}
+
+/// Retrieves the DNS servers of the default link, or else those of `/etc/resolv.conf`.
+fn get_dns_servers(command_runner: &dyn CommandRunner) -> Result<Vec<String>, Box<dyn Error>> {
+    if is_command_installed("resolvectl") {
+        if let Some(link) = get_default_link(command_runner)? {
+            let servers = get_link_dns_servers(&link, command_runner)?;
+            if !servers.is_empty() {
+                return Ok(servers);
+            }
+        }
+    }
+    Ok(fs::read_to_string("/etc/resolv.conf")
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("nameserver"))
+        .map(|server| server.trim().to_string())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_local_addresses() {
+        let lines = [
+            "lo               UNKNOWN        127.0.0.1/8 ::1/128",
+            "wlan0            UP             192.168.1.23/24 fe80::1c2b:3cff:fe4d:5e6f/64",
+            "enp0s31f6        DOWN",
+            "tailscale0       UNKNOWN        100.101.102.103/32 fd7a:115c:a1e0::1/128",
+        ]
+        .map(String::from);
+        assert_eq!(
+            parse_local_addresses(&lines),
+            [
+                ("wlan0".to_string(), vec!["192.168.1.23/24".to_string()]),
+                (
+                    "tailscale0".to_string(),
+                    vec![
+                        "100.101.102.103/32".to_string(),
+                        "fd7a:115c:a1e0::1/128".to_string()
+                    ]
+                ),
+            ]
+        );
+    }
+}
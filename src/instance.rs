@@ -0,0 +1,84 @@
+use crate::command::CommandRunner;
+use dirs::runtime_dir;
+use std::error::Error;
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::{Seek, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::info;
+
+/// Time given to a replaced menu to exit and release the lock.
+const REPLACE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Lock held by the running menu, released when dropped or when the process dies.
+pub struct InstanceLock {
+    _file: File,
+}
+
+/// Gets the path of the file locked by the running menu, holding its pid.
+fn get_lock_path() -> PathBuf {
+    runtime_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("network-dmenu.pid")
+}
+
+/// Reads the pid of the running menu, ignoring pids reused by other programs.
+fn read_pid(path: &Path) -> Option<u32> {
+    let pid: u32 = fs::read_to_string(path).ok()?.trim().parse().ok()?;
+    let command = fs::read_to_string(format!("/proc/{pid}/comm")).ok()?;
+    (command.trim() == env!("CARGO_PKG_NAME")).then_some(pid)
+}
+
+/// Takes the single-instance lock of the menu, an exclusive `flock` on the pid file.
+///
+/// When another menu is open, returns `None` to leave it be, or with `replace` stops it
+/// along with its dmenu and takes over.
+pub fn acquire_instance_lock(
+    replace: bool,
+    command_runner: &dyn CommandRunner,
+) -> Result<Option<InstanceLock>, Box<dyn Error>> {
+    let path = get_lock_path();
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&path)?;
+    match file.try_lock() {
+        Ok(()) => {}
+        Err(TryLockError::WouldBlock) => {
+            let pid = read_pid(&path).ok_or("menu lock held by an unknown process")?;
+            if !replace {
+                info!("menu already open in process {pid}");
+                return Ok(None);
+            }
+            let pid = pid.to_string();
+            let _ = command_runner.run_command("pkill", &["-P", &pid]);
+            command_runner.run_command("kill", &[&pid])?;
+            wait_for_lock(&file)?;
+        }
+        Err(TryLockError::Error(err)) => return Err(err.into()),
+    }
+
+    file.set_len(0)?;
+    file.rewind()?;
+    write!(file, "{}", process::id())?;
+    Ok(Some(InstanceLock { _file: file }))
+}
+
+/// Waits for the replaced menu to exit and release the lock.
+fn wait_for_lock(file: &File) -> Result<(), Box<dyn Error>> {
+    let start = Instant::now();
+    loop {
+        match file.try_lock() {
+            Ok(()) => return Ok(()),
+            Err(TryLockError::WouldBlock) if start.elapsed() < REPLACE_TIMEOUT => {
+                thread::sleep(Duration::from_millis(50));
+            }
+            Err(TryLockError::WouldBlock) => return Err("the open menu did not exit".into()),
+            Err(TryLockError::Error(err)) => return Err(err.into()),
+        }
+    }
+}
@@ -0,0 +1,183 @@
+use crate::command::CommandRunner;
+use crate::networkmanager::{
+    get_nm_connection_setting, get_nm_primary_connection, modify_nm_connection,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+
+/// IPv4 addressing applied to a NetworkManager connection.
+///
+/// The address is given in CIDR notation, e.g. `192.168.1.10/24`; a profile without
+/// one uses DHCP. Profiles without a connection apply to the active one.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct IpProfile {
+    pub name: String,
+    #[serde(default)]
+    pub connection: Option<String>,
+    #[serde(default)]
+    pub address: Option<String>,
+    #[serde(default)]
+    pub gateway: Option<String>,
+    #[serde(default)]
+    pub dns: Vec<String>,
+}
+
+impl IpProfile {
+    /// Returns the `nmcli` settings of the profile, clearing those it leaves unset.
+    fn settings(&self) -> Vec<(&'static str, String)> {
+        let method = if self.address.is_some() {
+            "manual"
+        } else {
+            "auto"
+        };
+        vec![
+            ("ipv4.method", method.to_string()),
+            ("ipv4.addresses", self.address.clone().unwrap_or_default()),
+            ("ipv4.gateway", self.gateway.clone().unwrap_or_default()),
+            ("ipv4.dns", self.dns.join(",")),
+            ("ipv4.ignore-auto-dns", self.ignores_auto_dns().to_string()),
+        ]
+    }
+
+    /// Checks if the DNS servers of the profile replace those obtained by DHCP.
+    fn ignores_auto_dns(&self) -> &'static str {
+        if self.dns.is_empty() {
+            "no"
+        } else {
+            "yes"
+        }
+    }
+
+    /// Checks if the profile matches the current method and addresses of its connection.
+    fn is_applied(&self, method: &str, addresses: &str) -> bool {
+        match &self.address {
+            Some(address) => method == "manual" && addresses.split(',').next() == Some(address),
+            None => method == "auto",
+        }
+    }
+
+    /// Resolves the connection the profile applies to.
+    fn target_connection(
+        &self,
+        command_runner: &dyn CommandRunner,
+    ) -> Result<Option<String>, Box<dyn Error>> {
+        match &self.connection {
+            Some(connection) => Ok(Some(connection.clone())),
+            None => get_nm_primary_connection(command_runner),
+        }
+    }
+}
+
+/// Represents actions applying an IP profile.
+#[derive(Debug, Deserialize, Serialize)]
+pub enum IpProfileAction {
+    Apply(String, bool),
+}
+
+/// Retrieves the IP profile actions, marking those matching their connection.
+pub fn get_ip_profile_actions(
+    profiles: &[IpProfile],
+    command_runner: &dyn CommandRunner,
+) -> Result<Vec<IpProfileAction>, Box<dyn Error>> {
+    if profiles.is_empty() {
+        return Ok(Vec::new());
+    }
+    let primary = get_nm_primary_connection(command_runner)?;
+    // Several profiles usually share a connection, read once
+    let mut current: HashMap<String, (String, String)> = HashMap::new();
+    let mut actions = Vec::new();
+    for profile in profiles {
+        let Some(connection) = profile.connection.as_ref().or(primary.as_ref()) else {
+            continue;
+        };
+        if !current.contains_key(connection) {
+            let method = get_nm_connection_setting(connection, "ipv4.method", command_runner)?;
+            let addresses =
+                get_nm_connection_setting(connection, "ipv4.addresses", command_runner)?;
+            current.insert(connection.clone(), (method, addresses));
+        }
+        let (method, addresses) = &current[connection];
+        actions.push(IpProfileAction::Apply(
+            profile.name.clone(),
+            profile.is_applied(method, addresses),
+        ));
+    }
+    Ok(actions)
+}
+
+/// Applies the selected IP profile to its connection, activating it again.
+pub fn handle_ip_profile_action(
+    action: &IpProfileAction,
+    profiles: &[IpProfile],
+    command_runner: &dyn CommandRunner,
+) -> Result<bool, Box<dyn Error>> {
+    let IpProfileAction::Apply(name, _) = action;
+    let profile = profiles
+        .iter()
+        .find(|profile| &profile.name == name)
+        .ok_or_else(|| format!("IP profile {name} not found"))?;
+    let connection = profile
+        .target_connection(command_runner)?
+        .ok_or("no active connection to apply the IP profile to")?;
+
+    let settings = profile.settings();
+    let settings: Vec<(&str, &str)> = settings
+        .iter()
+        .map(|(setting, value)| (*setting, value.as_str()))
+        .collect();
+    modify_nm_connection(&connection, &settings, command_runner)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockCommandRunner;
+
+    #[test]
+    fn applies_static_and_dhcp_profiles() {
+        let static_profile = IpProfile {
+            name: "Lab".to_string(),
+            connection: Some("Wired".to_string()),
+            address: Some("10.0.0.5/24".to_string()),
+            gateway: Some("10.0.0.1".to_string()),
+            dns: vec!["10.0.0.53".to_string(), "1.1.1.1".to_string()],
+        };
+        let dhcp_profile = IpProfile {
+            name: "DHCP".to_string(),
+            connection: Some("Wired".to_string()),
+            address: None,
+            gateway: None,
+            dns: Vec::new(),
+        };
+        assert!(static_profile.is_applied("manual", "10.0.0.5/24,10.0.0.6/24"));
+        assert!(!static_profile.is_applied("auto", ""));
+        assert!(dhcp_profile.is_applied("auto", ""));
+
+        let runner = MockCommandRunner::new()
+            .expect(
+                "nmcli",
+                &[
+                    "connection",
+                    "modify",
+                    "id",
+                    "Wired",
+                    "ipv4.method",
+                    "manual",
+                    "ipv4.addresses",
+                    "10.0.0.5/24",
+                    "ipv4.gateway",
+                    "10.0.0.1",
+                    "ipv4.dns",
+                    "10.0.0.53,1.1.1.1",
+                    "ipv4.ignore-auto-dns",
+                    "yes",
+                ],
+                "",
+            )
+            .expect("nmcli", &["connection", "up", "id", "Wired"], "");
+        let profiles = [static_profile, dhcp_profile];
+        let action = IpProfileAction::Apply("Lab".to_string(), false);
+        assert!(handle_ip_profile_action(&action, &profiles, &runner).unwrap());
+    }
+}
@@ -1,35 +1,97 @@
-use crate::command::{read_output_lines, CommandRunner};
-use crate::utils::{convert_network_strength, prompt_for_password};
-use crate::{notify_connection, parse_wifi_action, WifiAction};
+use crate::command::{escalate, read_output_lines, CommandRunner};
+use crate::i18n::{tr, tr_args};
+use crate::notifications::Progress;
+use crate::secrets::connect_with_password_retry;
+use crate::utils::prompt_with_pinentry;
+use crate::wifi::{ConnectionOutcome, WifiNetwork};
+use crate::{notify_connection, notify_wps, parse_wifi_action, prompt_from_menu, Config};
 use regex::Regex;
 use std::error::Error;
-use std::io::{BufRead, BufReader};
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
+
+/// Delay between two listings while waiting for the scan results.
+const SCAN_POLL_INTERVAL: Duration = Duration::from_millis(500);
 
 /// Retrieves available Wi-Fi networks using IWD.
+///
+/// When disconnected, a scan is requested and the networks are listed again until the
+/// results change or `wifi_scan_timeout` elapses, as iwd reports them asynchronously.
+/// `notify_scan` shows a "Scanning…" placeholder meanwhile.
 pub fn get_iwd_networks(
+    config: &Config,
     interface: &str,
+    notify_scan: bool,
     command_runner: &dyn CommandRunner,
-) -> Result<Vec<WifiAction>, Box<dyn Error>> {
-    let mut actions = Vec::new();
+) -> Result<Vec<WifiNetwork>, Box<dyn Error>> {
+    let mut wifi_networks = Vec::new();
 
     if let Some(networks) = fetch_iwd_networks(interface, command_runner)? {
-        let has_connected = networks.iter().any(|network| network.starts_with('>'));
+        let has_connected = networks.iter().any(|network| is_connected_line(network));
 
         if !has_connected {
             let rescan_output =
                 command_runner.run_command("iwctl", &["station", interface, "scan"])?;
 
             if rescan_output.status.success() {
-                if let Some(rescan_networks) = fetch_iwd_networks(interface, command_runner)? {
-                    parse_iwd_networks(&mut actions, rescan_networks)?;
+                let progress = notify_scan
+                    .then(|| Progress::show(config, &tr("wifi"), &tr("wifi-scanning")))
+                    .flatten();
+                let rescan_networks = wait_for_scan(
+                    interface,
+                    &networks,
+                    Duration::from_secs(config.wifi_scan_timeout),
+                    command_runner,
+                );
+                if let Some(progress) = progress {
+                    progress.close();
+                }
+                if let Some(rescan_networks) = rescan_networks? {
+                    parse_iwd_networks(&mut wifi_networks, rescan_networks)?;
                 }
             }
         } else {
-            parse_iwd_networks(&mut actions, networks)?;
+            parse_iwd_networks(&mut wifi_networks, networks)?;
         }
     }
 
-    Ok(actions)
+    Ok(wifi_networks)
+}
+
+/// Requests a scan and waits for its results, for up to `wifi_scan_timeout`.
+pub fn rescan_iwd_networks(
+    config: &Config,
+    interface: &str,
+    command_runner: &dyn CommandRunner,
+) -> Result<bool, Box<dyn Error>> {
+    let previous = fetch_iwd_networks(interface, command_runner)?.unwrap_or_default();
+    let output = command_runner.run_command("iwctl", &["station", interface, "scan"])?;
+    if !output.status.success() {
+        return Ok(false);
+    }
+    let timeout = Duration::from_secs(config.wifi_scan_timeout);
+    Ok(wait_for_scan(interface, &previous, timeout, command_runner)?.is_some())
+}
+
+/// Lists the networks until they differ from the ones seen before the scan, or the timeout.
+fn wait_for_scan(
+    interface: &str,
+    previous: &[String],
+    timeout: Duration,
+    command_runner: &dyn CommandRunner,
+) -> Result<Option<Vec<String>>, Box<dyn Error>> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        let networks = fetch_iwd_networks(interface, command_runner)?;
+        if networks.as_deref() != Some(previous) || Instant::now() >= deadline {
+            return Ok(networks);
+        }
+        debug!("Waiting for the iwd scan results on {interface}");
+        thread::sleep(SCAN_POLL_INTERVAL);
+    }
 }
 
 /// Fetches raw Wi-Fi network data from IWD.
@@ -37,7 +99,7 @@ fn fetch_iwd_networks(
     interface: &str,
     command_runner: &dyn CommandRunner,
 ) -> Result<Option<Vec<String>>, Box<dyn Error>> {
-    let output = command_runner.run_command("iwctl", &["station", interface, "get-networks"])?;
+    let output = command_runner.run_query("iwctl", &["station", interface, "get-networks"])?;
 
     if output.status.success() {
         let reader = read_output_lines(&output)?;
@@ -54,59 +116,169 @@ fn fetch_iwd_networks(
 
 /// Parses the raw Wi-Fi network data into a structured format.
 fn parse_iwd_networks(
-    actions: &mut Vec<WifiAction>,
+    wifi_networks: &mut Vec<WifiNetwork>,
     networks: Vec<String>,
 ) -> Result<(), Box<dyn Error>> {
     let ansi_escape = Regex::new(r"\x1B\[[0-9;]*m.*?\x1B\[0m")?;
 
     networks.into_iter().for_each(|network| {
-        let line = ansi_escape.replace_all(&network, "").to_string();
+        let line = ansi_escape.replace_all(&network, "");
+        let line = line.trim_start().trim_start_matches('>');
         let mut parts: Vec<&str> = line.split_whitespace().collect();
         if parts.len() >= 3 {
-            let connected = network.starts_with("\u{1b}[0m");
+            let connected = is_connected_line(&network);
             let signal = parts.pop().unwrap().trim();
             let security = parts.pop().unwrap().trim();
             let ssid = line[..line.find(security).unwrap()].trim();
-            let display = format!(
-                "{} {:<25}\t{:<11}\t{}",
-                if connected { "✅" } else { "📶" },
-                ssid,
-                security.to_uppercase(),
-                convert_network_strength(signal)
-            );
-            actions.push(WifiAction::Network(display));
+            let stars = signal.chars().filter(|&c| c == '*').count() as u8;
+            wifi_networks.push(WifiNetwork {
+                ssid: ssid.to_string(),
+                security: security.to_uppercase(),
+                signal: stars.min(4) * 25,
+                connected,
+            });
         }
     });
 
     Ok(())
 }
 
+/// Checks if a network line of `iwctl station get-networks` has the `>` connected marker,
+/// which may be wrapped in color codes.
+fn is_connected_line(line: &str) -> bool {
+    Regex::new(r"\x1B\[[0-9;]*m")
+        .map(|ansi_code| {
+            ansi_code
+                .replace_all(line, "")
+                .trim_start()
+                .starts_with('>')
+        })
+        .unwrap_or(false)
+}
+
 /// Connects to a Wi-Fi network using IWD.
 pub fn connect_to_iwd_wifi(
+    config: &Config,
     interface: &str,
     action: &str,
     command_runner: &dyn CommandRunner,
 ) -> Result<bool, Box<dyn Error>> {
     let (ssid, security) = parse_wifi_action(action)?;
 
-    #[cfg(debug_assertions)]
-    println!("Connecting to Wi-Fi network: {ssid} with security {security}");
+    debug!("Connecting to Wi-Fi network: {ssid} with security {security}");
 
-    if is_known_network(ssid, command_runner)? || security.is_empty() {
-        attempt_connection(interface, ssid, None, command_runner)
+    let is_known = is_known_network(ssid, command_runner)?;
+    if security == "8021X" {
+        // Enterprise credentials are not a password to prompt again
+        if !is_known {
+            provision_iwd_enterprise(config, ssid)?;
+        }
+        Ok(
+            attempt_connection(config, interface, ssid, None, command_runner)?
+                == ConnectionOutcome::Connected,
+        )
     } else {
-        let password = prompt_for_password(ssid)?;
-        attempt_connection(interface, ssid, Some(&password), command_runner)
+        connect_with_password_retry(
+            config,
+            ssid,
+            is_known || security.is_empty(),
+            |passphrase| attempt_connection(config, interface, ssid, passphrase, command_runner),
+            || {
+                // Only the network added by the failed attempt is forgotten
+                if !is_known {
+                    let _ = forget_iwd_network(ssid, command_runner);
+                }
+            },
+        )
     }
 }
 
+/// Default iwd provisioning file of 802.1X networks, using PEAP with MSCHAPv2.
+const DEFAULT_8021X_TEMPLATE: &str = "[Security]
+EAP-Method=PEAP
+EAP-Identity={identity}
+EAP-PEAP-Phase2-Method=MSCHAPV2
+EAP-PEAP-Phase2-Identity={identity}
+EAP-PEAP-Phase2-Password={password}
+";
+
+/// Returns the iwd network file name of an SSID, hex-encoded when it has special characters.
+fn iwd_network_file_name(ssid: &str, extension: &str) -> String {
+    if ssid
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, ' ' | '_' | '-'))
+    {
+        format!("{ssid}.{extension}")
+    } else {
+        let hex: String = ssid.bytes().map(|byte| format!("{byte:02x}")).collect();
+        format!("={hex}.{extension}")
+    }
+}
+
+/// Writes the iwd `.8021x` provisioning file of a network from the prompted
+/// identity and password, escalated as `/var/lib/iwd` belongs to root.
+fn provision_iwd_enterprise(config: &Config, ssid: &str) -> Result<(), Box<dyn Error>> {
+    let identity = prompt_from_menu(config, &tr_args("prompt-identity", &[("ssid", ssid)]))?;
+    if identity.is_empty() {
+        return Err("No identity given".into());
+    }
+    let password =
+        prompt_with_pinentry(config, &tr_args("prompt-wifi-password", &[("ssid", ssid)]))?;
+
+    let content = config
+        .iwd_8021x_template
+        .as_deref()
+        .unwrap_or(DEFAULT_8021X_TEMPLATE)
+        .replace("{identity}", &identity)
+        .replace("{password}", &password);
+    let path = format!("/var/lib/iwd/{}", iwd_network_file_name(ssid, "8021x"));
+
+    let command_line = escalate(&config.escalation, "tee", &[&path])?;
+    let mut child = Command::new(&command_line[0])
+        .args(&command_line[1..])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()?;
+    child
+        .stdin
+        .take()
+        .ok_or("Failed to open stdin")?
+        .write_all(content.as_bytes())?;
+    if !child.wait()?.success() {
+        return Err(format!("Failed to write {path}").into());
+    }
+    Ok(())
+}
+
+/// Connects to a hidden Wi-Fi network, prompting for its passphrase.
+pub fn connect_to_iwd_hidden(
+    config: &Config,
+    interface: &str,
+    ssid: &str,
+    command_runner: &dyn CommandRunner,
+) -> Result<bool, Box<dyn Error>> {
+    let passphrase =
+        prompt_with_pinentry(config, &tr_args("prompt-wifi-password", &[("ssid", ssid)]))?;
+    let mut command_args = vec!["station", interface, "connect-hidden", ssid];
+    if !passphrase.is_empty() {
+        command_args.extend(["--passphrase", &passphrase]);
+    }
+
+    let status = command_runner.run_command("iwctl", &command_args)?.status;
+    if status.success() {
+        notify_connection(config, ssid);
+    }
+    Ok(status.success())
+}
+
 /// Attempts to connect to a Wi-Fi network, optionally using a password.
 fn attempt_connection(
+    config: &Config,
     interface: &str,
     ssid: &str,
     passphrase: Option<&str>,
     command_runner: &dyn CommandRunner,
-) -> Result<bool, Box<dyn Error>> {
+) -> Result<ConnectionOutcome, Box<dyn Error>> {
     let mut command_args: Vec<&str> = vec!["station", interface, "connect", ssid];
 
     if let Some(pwd) = passphrase {
@@ -114,16 +286,41 @@ fn attempt_connection(
         command_args.push(pwd);
     }
 
-    let status = command_runner.run_command("iwctl", &command_args)?.status;
+    let outcome =
+        ConnectionOutcome::from_output(&command_runner.run_command("iwctl", &command_args)?);
 
-    if status.success() {
-        notify_connection(ssid)?;
-        Ok(true)
+    if outcome == ConnectionOutcome::Connected {
+        notify_connection(config, ssid);
     } else {
-        #[cfg(debug_assertions)]
-        eprintln!("Failed to connect to Wi-Fi network: {ssid}");
-        Ok(false)
+        warn!("Failed to connect to Wi-Fi network: {ssid}");
     }
+    Ok(outcome)
+}
+
+/// Connects to a known network, which iwd only attempts when it is in range.
+pub fn connect_to_iwd_known_network(
+    config: &Config,
+    interface: &str,
+    ssid: &str,
+    command_runner: &dyn CommandRunner,
+) -> Result<bool, Box<dyn Error>> {
+    Ok(
+        attempt_connection(config, interface, ssid, None, command_runner)?
+            == ConnectionOutcome::Connected,
+    )
+}
+
+/// Connects to a Wi-Fi network with WPS push button.
+pub fn connect_to_iwd_wps(
+    config: &Config,
+    interface: &str,
+    command_runner: &dyn CommandRunner,
+) -> Result<bool, Box<dyn Error>> {
+    notify_wps(config);
+    let status = command_runner
+        .run_command("iwctl", &["wsc", interface, "push-button"])?
+        .status;
+    Ok(status.success())
 }
 
 /// Disconnects from a Wi-Fi network.
@@ -137,12 +334,89 @@ pub fn disconnect_iwd_wifi(
     Ok(status.success())
 }
 
+/// Retrieves the names of known Wi-Fi networks from IWD.
+pub fn get_iwd_known_networks(
+    command_runner: &dyn CommandRunner,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let output = command_runner.run_query("iwctl", &["known-networks", "list"])?;
+
+    if !output.status.success() {
+        return Ok(Vec::new());
+    }
+
+    let ansi_escape = Regex::new(r"\x1B\[[0-9;]*m")?;
+    let columns = Regex::new(r"\s{2,}")?;
+    let networks = read_output_lines(&output)?
+        .into_iter()
+        .map(|line| ansi_escape.replace_all(&line, "").to_string())
+        .skip_while(|line| !line.trim_start().starts_with("Name"))
+        .skip(2)
+        .filter_map(|line| {
+            columns
+                .split(line.trim())
+                .next()
+                .filter(|name| !name.is_empty())
+                .map(|name| name.to_string())
+        })
+        .collect();
+    Ok(networks)
+}
+
+/// Forgets a known Wi-Fi network in IWD.
+pub fn forget_iwd_network(
+    name: &str,
+    command_runner: &dyn CommandRunner,
+) -> Result<bool, Box<dyn Error>> {
+    let status = command_runner
+        .run_command("iwctl", &["known-networks", name, "forget"])?
+        .status;
+    Ok(status.success())
+}
+
+/// Retrieves the autoconnect flag of a known network.
+pub fn get_iwd_autoconnect(
+    name: &str,
+    command_runner: &dyn CommandRunner,
+) -> Result<bool, Box<dyn Error>> {
+    let output = command_runner.run_query("iwctl", &["known-networks", name, "show"])?;
+
+    if !output.status.success() {
+        return Err(format!("Failed to read known network {name}").into());
+    }
+
+    Ok(read_output_lines(&output)?.iter().any(|line| {
+        let mut parts = line.split_whitespace();
+        parts.next() == Some("AutoConnect") && parts.next() == Some("yes")
+    }))
+}
+
+/// Enables or disables automatic connection to a known network.
+pub fn set_iwd_autoconnect(
+    name: &str,
+    autoconnect: bool,
+    command_runner: &dyn CommandRunner,
+) -> Result<bool, Box<dyn Error>> {
+    let status = command_runner
+        .run_command(
+            "iwctl",
+            &[
+                "known-networks",
+                name,
+                "set-property",
+                "AutoConnect",
+                if autoconnect { "yes" } else { "no" },
+            ],
+        )?
+        .status;
+    Ok(status.success())
+}
+
 /// Checks if IWD is currently connected to a network.
 pub fn is_iwd_connected(
     command_runner: &dyn CommandRunner,
     interface: &str,
 ) -> Result<bool, Box<dyn Error>> {
-    let output = command_runner.run_command("iwctl", &["station", interface, "show"])?;
+    let output = command_runner.run_query("iwctl", &["station", interface, "show"])?;
     if output.status.success() {
         for line in read_output_lines(&output)? {
             if line.contains("Connected") {
@@ -158,7 +432,7 @@ pub fn is_known_network(
     ssid: &str,
     command_runner: &dyn CommandRunner,
 ) -> Result<bool, Box<dyn Error>> {
-    let output = command_runner.run_command("iwctl", &["known-networks", "list"])?;
+    let output = command_runner.run_query("iwctl", &["known-networks", "list"])?;
     if output.status.success() {
         let reader = BufReader::new(output.stdout.as_slice());
         let ssid_pattern = format!(r"\b{}\b", regex::escape(ssid));
@@ -172,3 +446,93 @@ pub fn is_known_network(
     }
     Ok(false)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockCommandRunner;
+
+    fn test_config() -> Config {
+        toml::from_str("wifi_scan_timeout = 0").unwrap()
+    }
+
+    #[test]
+    fn parses_iwd_networks() {
+        let runner = MockCommandRunner::new().expect_fixture(
+            "iwctl",
+            &["station", "wlan0", "get-networks"],
+            "iwctl/get-networks.txt",
+        );
+        let networks = get_iwd_networks(&test_config(), "wlan0", false, &runner).unwrap();
+
+        let summary: Vec<_> = networks
+            .iter()
+            .map(|network| {
+                (
+                    network.ssid.as_str(),
+                    network.security.as_str(),
+                    network.signal,
+                    network.connected,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("Home", "PSK", 100, true),
+                ("Cafe Wifi", "OPEN", 50, false),
+                ("Office", "8021X", 75, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn detects_uncolored_connected_marker() {
+        let runner = MockCommandRunner::new().expect_fixture(
+            "iwctl",
+            &["station", "wlan0", "get-networks"],
+            "iwctl/get-networks-no-color.txt",
+        );
+        let networks = get_iwd_networks(&test_config(), "wlan0", false, &runner).unwrap();
+
+        assert_eq!(networks.len(), 2);
+        assert_eq!(networks[0].ssid, "Home");
+        assert!(networks[0].connected && !networks[1].connected);
+    }
+
+    #[test]
+    fn rescans_when_disconnected() {
+        let runner = MockCommandRunner::new()
+            .expect_fixture(
+                "iwctl",
+                &["station", "wlan0", "get-networks"],
+                "iwctl/get-networks-disconnected.txt",
+            )
+            .expect("iwctl", &["station", "wlan0", "scan"], "")
+            .expect_fixture(
+                "iwctl",
+                &["station", "wlan0", "get-networks"],
+                "iwctl/get-networks-disconnected.txt",
+            );
+        let networks = get_iwd_networks(&test_config(), "wlan0", false, &runner).unwrap();
+
+        assert_eq!(networks.len(), 1);
+        assert_eq!(networks[0].signal, 75);
+        assert!(!networks[0].connected);
+    }
+
+    #[test]
+    fn waits_for_scan_results() {
+        let args = &["station", "wlan0", "get-networks"];
+        let runner = MockCommandRunner::new()
+            .expect_fixture("iwctl", args, "iwctl/get-networks-disconnected.txt")
+            .expect("iwctl", &["station", "wlan0", "scan"], "")
+            .expect_fixture("iwctl", args, "iwctl/get-networks-disconnected.txt")
+            .expect_fixture("iwctl", args, "iwctl/get-networks-no-color.txt");
+        let mut config = test_config();
+        config.wifi_scan_timeout = 5;
+        let networks = get_iwd_networks(&config, "wlan0", false, &runner).unwrap();
+
+        assert_eq!(networks.len(), 2);
+    }
+}
@@ -0,0 +1,191 @@
+use crate::command::CommandRunner;
+use crate::tailscale::TailscaleAction;
+use crate::vpn::VpnAction;
+use crate::wireguard::WireguardAction;
+use crate::{ActionType, Config};
+use dirs::runtime_dir;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+
+/// nftables table holding the kill switch rules.
+const TABLE: &str = "inet network_dmenu_killswitch";
+
+/// Firewall marks of the packets sent by tailscaled and wg-quick, which carry the tunnels.
+const TUNNEL_MARKS: [&str; 2] = [
+    "meta mark & 0x00ff0000 == 0x00080000",
+    "meta mark 0x0000ca6c",
+];
+
+/// Private ranges reached when LAN access is allowed.
+const LAN_RANGES: [&str; 2] = [
+    "ip daddr { 10.0.0.0/8, 172.16.0.0/12, 192.168.0.0/16, 169.254.0.0/16 }",
+    "ip6 daddr { fc00::/7, fe80::/10 }",
+];
+
+/// Represents actions arming and disarming the kill switch.
+#[derive(Debug, Deserialize, Serialize)]
+pub enum KillSwitchAction {
+    SetArmed(bool),
+}
+
+/// Returns the default interfaces traffic may leave through while the kill switch is armed.
+pub fn default_kill_switch_interfaces() -> Vec<String> {
+    vec![
+        "tailscale0".to_string(),
+        "wg*".to_string(),
+        "tun*".to_string(),
+    ]
+}
+
+/// Gets the path of the ruleset loaded last, present while the kill switch is armed.
+///
+/// The runtime directory is cleared on reboot, like the rules which are not persisted.
+fn get_ruleset_path() -> PathBuf {
+    runtime_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("network-dmenu-killswitch.nft")
+}
+
+/// Checks if the kill switch is armed, as listing the rules requires root.
+pub fn is_armed() -> bool {
+    get_ruleset_path().exists()
+}
+
+/// Retrieves the action arming or disarming the kill switch.
+pub fn get_kill_switch_actions() -> Vec<KillSwitchAction> {
+    vec![KillSwitchAction::SetArmed(!is_armed())]
+}
+
+/// Handles a kill switch action.
+pub fn handle_kill_switch_action(
+    config: &Config,
+    action: &KillSwitchAction,
+    command_runner: &dyn CommandRunner,
+) -> Result<bool, Box<dyn Error>> {
+    let KillSwitchAction::SetArmed(arm) = action;
+    set_armed(config, *arm, command_runner)
+}
+
+/// Arms the kill switch when a tunnel is selected, and disarms it when the tunnel is left.
+pub fn follow_tunnel(
+    config: &Config,
+    action: &ActionType,
+    command_runner: &dyn CommandRunner,
+) -> Result<(), Box<dyn Error>> {
+    let arm = match action {
+        ActionType::Tailscale(
+            TailscaleAction::SetExitNode(_)
+            | TailscaleAction::SetSuggestedExitNode
+            | TailscaleAction::SetRandomExitNode(_)
+            | TailscaleAction::SelectMullvadCountry(_),
+        )
+        | ActionType::Vpn(VpnAction::Connect(_) | VpnAction::StartSession(_))
+        | ActionType::Wireguard(WireguardAction::Up(_)) => true,
+        ActionType::Tailscale(TailscaleAction::DisableExitNode)
+        | ActionType::Vpn(VpnAction::Disconnect(_) | VpnAction::StopSession(_))
+        | ActionType::Wireguard(WireguardAction::Down(_)) => false,
+        _ => return Ok(()),
+    };
+    if arm != is_armed() {
+        set_armed(config, arm, command_runner)?;
+    }
+    Ok(())
+}
+
+/// Loads or deletes the kill switch table as root.
+///
+/// `nft` is escalated as the other root commands, through the `[escalation]` tool, rather
+/// than a bundled helper with its own polkit policy, which `cargo install` cannot install.
+fn set_armed(
+    config: &Config,
+    arm: bool,
+    command_runner: &dyn CommandRunner,
+) -> Result<bool, Box<dyn Error>> {
+    let path = get_ruleset_path();
+    if !arm {
+        let status = command_runner
+            .run_privileged("nft", &["delete", "table", TABLE])?
+            .status;
+        // The rules are still loaded when deleting them failed, e.g. on a cancelled prompt
+        if status.success() {
+            let _ = fs::remove_file(path);
+        }
+        return Ok(status.success());
+    }
+
+    fs::write(&path, ruleset(config))?;
+    let status = command_runner
+        .run_privileged("nft", &["-f", &path.to_string_lossy()])?
+        .status;
+    if !status.success() {
+        let _ = fs::remove_file(path);
+    }
+    Ok(status.success())
+}
+
+/// Builds the ruleset dropping the output traffic not going through a tunnel.
+///
+/// The table is declared and deleted first, so that loading it again replaces the rules.
+fn ruleset(config: &Config) -> String {
+    let mut rules = vec!["oifname \"lo\" accept".to_string()];
+    rules.extend(
+        config
+            .kill_switch_interfaces
+            .iter()
+            .map(|interface| format!("oifname \"{interface}\" accept")),
+    );
+    rules.extend(TUNNEL_MARKS.iter().map(|mark| format!("{mark} accept")));
+    rules.push("udp dport { 67, 68 } accept".to_string());
+    rules.push(
+        "icmpv6 type { nd-router-solicit, nd-neighbor-solicit, nd-neighbor-advert } accept"
+            .to_string(),
+    );
+    if config.exit_node_allow_lan_access.unwrap_or(true) {
+        rules.extend(LAN_RANGES.iter().map(|range| format!("{range} accept")));
+    }
+    rules.extend(config.kill_switch_endpoints.iter().map(|endpoint| {
+        let family = if endpoint.contains(':') { "ip6" } else { "ip" };
+        format!("{family} daddr {endpoint} accept")
+    }));
+
+    let mut ruleset = format!("table {TABLE}\ndelete table {TABLE}\n");
+    ruleset.push_str(&format!("table {TABLE} {{\n    chain output {{\n"));
+    ruleset.push_str("        type filter hook output priority 0; policy drop;\n");
+    for rule in rules {
+        ruleset.push_str(&format!("        {rule}\n"));
+    }
+    ruleset.push_str("    }\n}\n");
+    ruleset
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_ruleset_from_config() {
+        let mut config: Config = toml::from_str(
+            r#"
+            dmenu_cmd = "dmenu"
+            dmenu_args = ""
+            exit_node_allow_lan_access = false
+            kill_switch_interfaces = ["tailscale0"]
+            kill_switch_endpoints = ["198.51.100.7", "2001:db8::1"]
+            "#,
+        )
+        .unwrap();
+        let rules = ruleset(&config);
+        assert!(rules.starts_with(&format!("table {TABLE}\ndelete table {TABLE}\n")));
+        assert!(rules.contains("policy drop;\n        oifname \"lo\" accept\n"));
+        assert!(rules.contains("oifname \"tailscale0\" accept\n"));
+        assert!(!rules.contains("oifname \"wg*\""));
+        assert!(rules.contains("ip daddr 198.51.100.7 accept\n"));
+        assert!(rules.contains("ip6 daddr 2001:db8::1 accept\n"));
+        assert!(!rules.contains("192.168.0.0/16"));
+
+        config.exit_node_allow_lan_access = None;
+        assert!(ruleset(&config).contains("192.168.0.0/16"));
+    }
+}
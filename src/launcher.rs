@@ -0,0 +1,495 @@
+use crate::builtin_ui::run_builtin_menu;
+use crate::columns::{escape_markup, get_column_layout, layout_entry, ColumnLayout};
+use crate::command::is_command_installed;
+#[cfg(feature = "gui")]
+use crate::popup::run_popup_menu;
+use crate::Config;
+use std::error::Error;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use tracing::warn;
+
+/// Separates the entry shown by fzf and skim from the details in their preview.
+const FIELD_SEPARATOR: char = '\x1f';
+
+/// Command name selecting the embedded selector instead of an external launcher.
+pub const BUILTIN_LAUNCHER: &str = "builtin";
+
+/// Command name selecting the popup window instead of an external launcher.
+#[cfg(feature = "gui")]
+pub const POPUP_LAUNCHER: &str = "popup";
+
+/// Launcher showing the menu, detected from the file name of its command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Launcher {
+    Builtin,
+    Dmenu,
+    Fzf,
+    #[cfg(feature = "gui")]
+    Popup,
+    Rofi,
+    Skim,
+}
+
+impl Launcher {
+    /// Detects the launcher run by the configured command.
+    pub fn detect(config: &Config) -> Self {
+        match Path::new(&config.dmenu_cmd)
+            .file_name()
+            .and_then(|name| name.to_str())
+        {
+            Some("rofi") => Launcher::Rofi,
+            Some("fzf") => Launcher::Fzf,
+            Some("sk") => Launcher::Skim,
+            Some(BUILTIN_LAUNCHER) => Launcher::Builtin,
+            #[cfg(feature = "gui")]
+            Some(POPUP_LAUNCHER) => Launcher::Popup,
+            _ => Launcher::Dmenu,
+        }
+    }
+
+    /// Checks if the launcher runs in the current terminal.
+    pub fn is_terminal(self) -> bool {
+        matches!(self, Launcher::Fzf | Launcher::Skim)
+    }
+
+    /// Checks if entries can be added while the launcher is shown, through its input.
+    ///
+    /// dmenu and the like read their whole input before showing up.
+    pub fn is_streamable(self) -> bool {
+        matches!(self, Launcher::Rofi | Launcher::Fzf | Launcher::Skim)
+    }
+
+    /// Checks if the menu is shown by network-dmenu itself rather than a command.
+    pub fn is_embedded(self) -> bool {
+        match self {
+            Launcher::Builtin => true,
+            #[cfg(feature = "gui")]
+            Launcher::Popup => true,
+            _ => false,
+        }
+    }
+}
+
+/// Switches the configuration to fzf, or skim, to run the menu in the current terminal.
+pub fn use_terminal_launcher(config: &mut Config) -> Result<(), Box<dyn Error>> {
+    config.dmenu_cmd = ["fzf", "sk"]
+        .into_iter()
+        .find(|command| is_command_installed(command))
+        .ok_or("--tui requires fzf or skim")?
+        .to_string();
+    config.dmenu_args = "--reverse --no-sort".to_string();
+    Ok(())
+}
+
+/// Switches the configuration to the embedded selector.
+pub fn use_builtin_launcher(config: &mut Config) {
+    config.dmenu_cmd = BUILTIN_LAUNCHER.to_string();
+    config.dmenu_args = String::new();
+}
+
+/// Switches the configuration to the popup window.
+#[cfg(feature = "gui")]
+pub fn use_popup_launcher(config: &mut Config) {
+    config.dmenu_cmd = POPUP_LAUNCHER.to_string();
+    config.dmenu_args = String::new();
+}
+
+/// Entry of the menu, with the metadata shown by the launchers supporting it.
+pub struct MenuItem<'a> {
+    pub text: &'a str,
+    /// Icon name of the row, from the icon theme.
+    pub icon: Option<&'static str>,
+    /// Description of the entry, previewed by the terminal launchers.
+    pub details: Option<String>,
+    /// Stable id returned instead of the text once selected, so that the selection does
+    /// not depend on how the entry is displayed.
+    pub id: Option<String>,
+    /// Current state of a toggle entry, shown as a switch by the embedded menus.
+    pub toggle: Option<bool>,
+}
+
+impl MenuItem<'_> {
+    /// Returns what selecting the item returns: its id, or else its text.
+    fn value(&self) -> String {
+        self.id.clone().unwrap_or_else(|| self.text.to_string())
+    }
+}
+
+/// Shows the items in the launcher and returns the id or else the text of the selected one.
+///
+/// The message is shown above the entries by launchers having a message bar or header.
+/// Text typed instead of selecting an entry is returned as is.
+pub fn run_menu(
+    config: &Config,
+    items: &[MenuItem],
+    message: Option<&str>,
+) -> Result<String, Box<dyn Error>> {
+    Ok(show_menu(config, items, message, false)?
+        .into_iter()
+        .next()
+        .unwrap_or_default())
+}
+
+/// Shows the items in the launcher, letting several be selected at once with
+/// `rofi -multi-select`, `fzf --multi` or a patched dmenu, and returns the selected ones.
+pub fn run_multi_menu(
+    config: &Config,
+    items: &[MenuItem],
+    message: Option<&str>,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    show_menu(config, items, message, true)
+}
+
+/// Shows the items in the launcher and returns the ids or else the texts of the selected
+/// ones, in their output order.
+fn show_menu(
+    config: &Config,
+    items: &[MenuItem],
+    message: Option<&str>,
+    multi: bool,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let launcher = Launcher::detect(config);
+    let layout = get_column_layout(config);
+    let shown: Vec<String> = items
+        .iter()
+        .map(|item| render_item(item, launcher, layout))
+        .collect();
+
+    let args = menu_args(
+        config,
+        launcher,
+        message,
+        items.iter().any(|item| item.icon.is_some()),
+        items.iter().any(|item| item.details.is_some()),
+        multi,
+    );
+    let title = message.unwrap_or(env!("CARGO_PKG_NAME"));
+    let toggles: Vec<Option<bool>> = items.iter().map(|item| item.toggle).collect();
+    let output = match launcher {
+        Launcher::Builtin => run_builtin_menu(title, &shown, &toggles)?,
+        #[cfg(feature = "gui")]
+        Launcher::Popup => run_popup_menu(title, &shown, &toggles)?,
+        _ => spawn_launcher(config, &args, &shown)?,
+    };
+    let values: Vec<String> = items.iter().map(MenuItem::value).collect();
+    Ok(find_selections(&output, &shown, &values))
+}
+
+/// Launcher shown right away, fed with the entries as they are gathered.
+pub struct StreamingMenu {
+    child: Child,
+    launcher: Launcher,
+    layout: ColumnLayout,
+    shown: Vec<String>,
+    values: Vec<String>,
+}
+
+impl StreamingMenu {
+    /// Spawns the launcher, without any entry yet, letting several be selected if `multi`.
+    pub fn spawn(
+        config: &Config,
+        message: Option<&str>,
+        multi: bool,
+    ) -> Result<Self, Box<dyn Error>> {
+        let launcher = Launcher::detect(config);
+        // Rows to come may have icons and details, known only once gathered
+        let args = menu_args(
+            config,
+            launcher,
+            message,
+            true,
+            launcher.is_terminal(),
+            multi,
+        );
+        let child = Command::new(&config.dmenu_cmd)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        Ok(Self {
+            child,
+            launcher,
+            layout: get_column_layout(config),
+            shown: Vec::new(),
+            values: Vec::new(),
+        })
+    }
+
+    /// Adds items to the launcher, returning false once it was closed, e.g. by a selection.
+    pub fn push(&mut self, items: &[MenuItem]) -> bool {
+        if items.is_empty() {
+            return true;
+        }
+        if self.child.try_wait().ok().flatten().is_some() {
+            return false;
+        }
+        let Some(stdin) = self.child.stdin.as_mut() else {
+            return false;
+        };
+        let mut lines = String::new();
+        for item in items {
+            let line = render_item(item, self.launcher, self.layout);
+            lines.push_str(&line);
+            lines.push('\n');
+            self.shown.push(line);
+            self.values.push(item.value());
+        }
+        stdin
+            .write_all(lines.as_bytes())
+            .and_then(|_| stdin.flush())
+            .is_ok()
+    }
+
+    /// Waits for the selection once every entry was added.
+    pub fn finish(mut self) -> Result<Vec<String>, Box<dyn Error>> {
+        drop(self.child.stdin.take());
+        let output = self.child.wait_with_output()?;
+        let output = String::from_utf8_lossy(&output.stdout);
+        Ok(find_selections(&output, &self.shown, &self.values))
+    }
+}
+
+/// Launcher shown with a message but no entry, such as a placeholder while scanning, closed
+/// once dropped.
+pub struct MessageWindow {
+    child: Child,
+}
+
+impl MessageWindow {
+    /// Shows the message in the message bar of the launcher, if it draws one without
+    /// entries, as rofi does.
+    pub fn show(config: &Config, message: &str) -> Option<Self> {
+        let launcher = Launcher::detect(config);
+        if launcher != Launcher::Rofi {
+            return None;
+        }
+        let args = menu_args(config, launcher, Some(message), false, false, false);
+        // The input is left open so that the launcher waits for entries
+        Command::new(&config.dmenu_cmd)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()
+            .map_err(|err| warn!("failed to show the launcher message: {err}"))
+            .ok()
+            .map(|child| Self { child })
+    }
+}
+
+impl Drop for MessageWindow {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Builds the launcher arguments, enabling its row icons, previews, message bar and
+/// multiple selection.
+fn menu_args(
+    config: &Config,
+    launcher: Launcher,
+    message: Option<&str>,
+    icons: bool,
+    details: bool,
+    multi: bool,
+) -> Vec<String> {
+    let mut args = launcher_args(config);
+    match launcher {
+        Launcher::Rofi => {
+            add_flag(&mut args, "-markup-rows");
+            if multi {
+                add_flag(&mut args, "-multi-select");
+            }
+            if icons {
+                add_flag(&mut args, "-show-icons");
+            }
+            if let Some(message) = message {
+                args.extend(["-mesg".to_string(), escape_markup(message)]);
+            }
+        }
+        Launcher::Fzf | Launcher::Skim => {
+            if multi {
+                add_flag(&mut args, "--multi");
+            }
+            if details {
+                args.extend(["--delimiter".to_string(), FIELD_SEPARATOR.to_string()]);
+                args.extend(
+                    [
+                        "--with-nth",
+                        "1",
+                        "--preview",
+                        "printf '%b' {2}",
+                        "--preview-window",
+                        "down:40%:wrap",
+                    ]
+                    .map(str::to_string),
+                );
+            }
+            if let Some(message) = message {
+                args.extend(["--header".to_string(), message.to_string()]);
+            }
+        }
+        _ => {}
+    }
+    args
+}
+
+/// Maps each line of the launcher output back to the value of the selected entry, or the
+/// typed text.
+fn find_selections(output: &str, shown: &[String], values: &[String]) -> Vec<String> {
+    output
+        .lines()
+        .map(|line| displayed(line).trim())
+        .filter(|selection| !selection.is_empty())
+        .map(|selection| {
+            shown
+                .iter()
+                .position(|line| displayed(line).trim() == selection)
+                .map_or_else(|| selection.to_string(), |index| values[index].clone())
+        })
+        .collect()
+}
+
+/// Runs the launcher command with the given input lines and returns its output.
+fn spawn_launcher(
+    config: &Config,
+    args: &[String],
+    lines: &[String],
+) -> Result<String, Box<dyn Error>> {
+    let mut child = Command::new(&config.dmenu_cmd)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+
+    {
+        let stdin = child.stdin.as_mut().ok_or("Failed to open stdin")?;
+        write!(stdin, "{}", lines.join("\n"))?;
+    }
+
+    let output = child.wait_with_output()?;
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Asks for free-form input in the launcher, showing the given prompt.
+pub fn run_prompt(config: &Config, prompt: &str) -> Result<String, Box<dyn Error>> {
+    let launcher = Launcher::detect(config);
+    match launcher {
+        Launcher::Builtin => return run_builtin_menu(prompt, &[], &[]),
+        #[cfg(feature = "gui")]
+        Launcher::Popup => return run_popup_menu(prompt, &[], &[]),
+        _ => {}
+    }
+    let mut args = launcher_args(config);
+    if launcher.is_terminal() {
+        args.extend([
+            "--print-query".to_string(),
+            "--prompt".to_string(),
+            format!("{prompt}: "),
+        ]);
+    } else {
+        args.extend(["-p".to_string(), prompt.to_string()]);
+    }
+
+    let mut child = Command::new(&config.dmenu_cmd)
+        .args(&args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    drop(child.stdin.take());
+
+    let output = child.wait_with_output()?;
+    let output = String::from_utf8_lossy(&output.stdout);
+    // The query printed by the terminal launchers comes first, before any match
+    Ok(output.lines().next().unwrap_or_default().trim().to_string())
+}
+
+/// Gets the configured arguments of the launcher.
+fn launcher_args(config: &Config) -> Vec<String> {
+    config
+        .dmenu_args
+        .split_whitespace()
+        .map(str::to_string)
+        .collect()
+}
+
+/// Renders an item as a line of the launcher input.
+fn render_item(item: &MenuItem, launcher: Launcher, layout: ColumnLayout) -> String {
+    let line = layout_entry(item.text, layout);
+    match launcher {
+        Launcher::Rofi => {
+            // Rows are parsed as markup, which the markup layout already is
+            let mut line = if layout == ColumnLayout::Markup {
+                line
+            } else {
+                escape_markup(&line)
+            };
+            if let Some(icon) = item.icon {
+                line.push_str(&format!("\0icon\x1f{icon}"));
+            }
+            line
+        }
+        Launcher::Fzf | Launcher::Skim => match &item.details {
+            // Kept on one line, the details are expanded back by printf in the preview
+            Some(details) => format!(
+                "{line}{FIELD_SEPARATOR}{}",
+                details.replace('\\', "\\\\").replace('\n', "\\n")
+            ),
+            None => line,
+        },
+        _ => line,
+    }
+}
+
+/// Returns the part of a launcher line shown as the entry.
+fn displayed(line: &str) -> &str {
+    line.split(['\0', FIELD_SEPARATOR])
+        .next()
+        .unwrap_or_default()
+}
+
+/// Adds a flag to the launcher arguments unless already given.
+fn add_flag(args: &mut Vec<String>, flag: &str) {
+    if !args.iter().any(|arg| arg == flag) {
+        args.push(flag.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_launcher_rows() {
+        let item = MenuItem {
+            text: "wifi      - 📶 A&B\tWPA2",
+            icon: Some("network-wireless-signal-good-symbolic"),
+            details: Some("SSID: A&B\nSecurity: WPA2".to_string()),
+            id: Some("wifi:A&B".to_string()),
+            toggle: None,
+        };
+        assert_eq!(
+            render_item(&item, Launcher::Rofi, ColumnLayout::Pad),
+            "wifi      - 📶 A&amp;B  WPA2\0icon\x1fnetwork-wireless-signal-good-symbolic"
+        );
+        assert_eq!(
+            render_item(&item, Launcher::Dmenu, ColumnLayout::Pad),
+            "wifi      - 📶 A&B  WPA2"
+        );
+        let line = render_item(&item, Launcher::Fzf, ColumnLayout::Pad);
+        assert_eq!(
+            line,
+            "wifi      - 📶 A&B  WPA2\x1fSSID: A&B\\nSecurity: WPA2"
+        );
+        assert_eq!(displayed(&line), "wifi      - 📶 A&B  WPA2");
+
+        let shown = [line];
+        let values = [item.value()];
+        assert_eq!(
+            find_selections("wifi      - 📶 A&B  WPA2\ntyped\n\n", &shown, &values),
+            ["wifi:A&B", "typed"]
+        );
+    }
+}
@@ -0,0 +1,37 @@
+use dirs::state_dir;
+use std::error::Error;
+use std::fs::{self, OpenOptions};
+use std::sync::Mutex;
+use tracing_subscriber::EnvFilter;
+
+/// Environment variable overriding the log level, e.g. `debug` or `network_dmenu=trace`.
+pub const LOG_ENV: &str = "NETWORK_DMENU_LOG";
+
+/// Initializes logging to stderr, or to the log file under the XDG state directory.
+///
+/// The level comes from `--log-level`, then `NETWORK_DMENU_LOG`, defaulting to `warn`.
+pub fn init_logging(log_level: Option<&str>, log_file: bool) -> Result<(), Box<dyn Error>> {
+    let filter = match log_level {
+        Some(level) => EnvFilter::try_new(level)?,
+        None => EnvFilter::try_from_env(LOG_ENV).unwrap_or_else(|_| EnvFilter::new("warn")),
+    };
+    let subscriber = tracing_subscriber::fmt().with_env_filter(filter);
+
+    if log_file {
+        let log_dir = state_dir()
+            .ok_or("Failed to find state directory")?
+            .join("network-dmenu");
+        fs::create_dir_all(&log_dir)?;
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(log_dir.join("network-dmenu.log"))?;
+        subscriber
+            .with_ansi(false)
+            .with_writer(Mutex::new(file))
+            .init();
+    } else {
+        subscriber.with_writer(std::io::stderr).init();
+    }
+    Ok(())
+}
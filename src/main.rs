@@ -1,33 +1,159 @@
 use crate::command::CommandRunner;
-use clap::Parser;
-use dirs::config_dir;
-use notify_rust::Notification;
+use crate::context::Context;
+use clap::parser::ValueSource;
+use clap::{CommandFactory, FromArgMatches, Parser};
+use clap_complete::Shell;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::error::Error;
-use std::fs;
-use std::io::Write;
-use std::path::PathBuf;
-use std::process::{Command, Stdio};
+use std::io::IsTerminal;
+use std::process::Command;
+use std::time::Instant;
+use tracing::warn;
 
 mod bluetooth;
+mod bluetooth_dbus;
+mod builtin_ui;
+mod capabilities;
+mod cli;
+mod columns;
 mod command;
+mod config;
+mod connectivity;
+mod context;
+mod daemon;
+mod dbus_service;
+mod diagnostics;
+mod dns;
+mod doctor;
+mod firewall;
+mod history;
+mod hooks;
+mod i18n;
+mod icons;
+mod info;
+mod instance;
+mod ipprofile;
 mod iwd;
+mod killswitch;
+mod launcher;
+mod logging;
+mod mullvad;
+mod mullvad_relays;
+mod netmap;
 mod networkmanager;
+mod networkmanager_dbus;
+mod notifications;
+mod ordering;
+#[cfg(feature = "gui")]
+mod popup;
+mod power;
+mod profiles;
+mod profiling;
+mod proxy;
+mod rfkill;
+mod secrets;
+mod security;
+mod snapshot;
 mod tailscale;
+#[cfg(test)]
+mod testing;
+mod toggle;
+mod tor;
+#[cfg(feature = "tray")]
+mod tray;
+mod tunnels;
 mod utils;
+mod vpn;
+mod waybar;
+mod wifi;
+mod wireguard;
 
 use bluetooth::{
-    get_connected_devices, get_paired_bluetooth_devices, handle_bluetooth_action, BluetoothAction,
+    extract_device_address, get_audio_profile_actions, get_connected_devices,
+    get_paired_bluetooth_devices, handle_bluetooth_action, BluetoothAction,
 };
+use capabilities::{check_menu_command, detect_capabilities, warn_missing_tools, Capabilities};
+use cli::{run_cli_command, Commands};
+use columns::pad;
 use command::{is_command_installed, RealCommandRunner};
-use iwd::{connect_to_iwd_wifi, disconnect_iwd_wifi, get_iwd_networks, is_iwd_connected};
+use config::{
+    check_config, create_default_config_if_missing, edit_config, get_config, get_config_path,
+    Config, CustomAction, MenuSection,
+};
+use connectivity::spawn_connectivity_check;
+use daemon::{action_entries, fetch_daemon_actions, request_daemon_refresh, run_daemon};
+use diagnostics::run_diagnostics;
+use dns::{get_dns_actions, handle_dns_action, DnsAction};
+use firewall::{get_firewall_actions, handle_firewall_action, FirewallAction};
+use history::{
+    failures_annotation, load_wifi_history, record_wifi_connection, record_wifi_disconnection,
+};
+use hooks::run_hooks;
+use i18n::{init_locale, tr, tr_args};
+use icons::{apply_icon_theme, flag, icon};
+use info::{get_connection_info, show_connection_info};
+use instance::acquire_instance_lock;
+use ipprofile::{get_ip_profile_actions, handle_ip_profile_action, IpProfileAction};
+use iwd::{
+    connect_to_iwd_hidden, connect_to_iwd_known_network, connect_to_iwd_wifi, connect_to_iwd_wps,
+    disconnect_iwd_wifi, forget_iwd_network, get_iwd_autoconnect, get_iwd_known_networks,
+    get_iwd_networks, is_iwd_connected, rescan_iwd_networks, set_iwd_autoconnect,
+};
+use killswitch::{
+    follow_tunnel, get_kill_switch_actions, handle_kill_switch_action, KillSwitchAction,
+};
+#[cfg(feature = "gui")]
+use launcher::use_popup_launcher;
+use launcher::{
+    run_menu, run_multi_menu, run_prompt, use_builtin_launcher, use_terminal_launcher, Launcher,
+    MenuItem, StreamingMenu,
+};
+use logging::init_logging;
+use mullvad::{get_mullvad_actions, handle_mullvad_action, MullvadAction};
+use mullvad_relays::{describe_mullvad_filter, get_mullvad_filter, spawn_relays_refresh};
 use networkmanager::{
-    connect_to_nm_wifi, disconnect_nm_wifi, get_nm_wifi_networks, is_nm_connected,
+    activate_nm_wifi_connection, connect_to_nm_access_point, connect_to_nm_dbus_wifi,
+    connect_to_nm_hidden, connect_to_nm_wifi, connect_to_nm_wps, disconnect_nm_wifi,
+    forget_nm_network, get_nm_active_wifi_connection, get_nm_autoconnect,
+    get_nm_saved_network_ssids, get_nm_saved_networks, get_nm_vpn_actions, get_nm_wifi_credentials,
+    get_nm_wifi_networks, is_nm_connected, is_nm_hotspot_active, rescan_nm_wifi,
+    set_nm_autoconnect, set_nm_autoconnect_priority, start_nm_hotspot, stop_nm_hotspot,
 };
+use networkmanager_dbus::{
+    connect_nm_dbus_device, disconnect_nm_dbus_wifi, get_nm_dbus_wifi_networks,
+    is_nm_dbus_available, is_nm_dbus_connected,
+};
+use notifications::{new_notification, notify, show_notification, Progress};
+use ordering::{load_usage_state, now, record_usage, sort_by_usage};
+use power::{get_power_actions, handle_power_action, PowerAction};
+use profiles::{apply_actions, BatchResult};
+use profiling::profile_startup;
+use proxy::{get_proxy_actions, handle_proxy_action, ProxyAction};
+use security::{
+    apply_network_policy, apply_wifi_security, describe_network_policy, network_policy,
+};
+use snapshot::{get_stale_actions, save_snapshot, Backend};
 use tailscale::{
-    check_mullvad, get_mullvad_actions, handle_tailscale_action, is_exit_node_active,
-    is_tailscale_enabled, TailscaleAction,
+    extract_node_ip, get_active_exit_node, get_advertised_routes, get_allowed_exit_nodes,
+    get_drive_shares, get_flag, get_key_expiry, get_operator_candidate, get_peers, get_serve_ports,
+    get_tailscale_hostname, group_mullvad_countries, handle_tailscale_action, is_exit_node_active,
+    is_operator_error, is_tailscale_enabled, offer_operator_fix, random_node_entry,
+    TailscaleAction,
+};
+use toggle::{
+    get_toggle_actions, handle_toggle_action, toggle_entry, ToggleAction, ToggleContext, ToggleId,
 };
+use tor::{get_tor_actions, handle_tor_action, TorAction};
+use tunnels::{get_tunnel_actions, handle_tunnel_action, TunnelAction};
+use utils::{generate_password, generate_wifi_qr_code, shell_quote};
+use vpn::{get_vpn_session_actions, handle_vpn_action, VpnAction};
+use waybar::print_waybar_status;
+use wifi::{
+    dedup_and_sort_networks, default_wifi_interface, get_out_of_range_networks,
+    get_selected_wifi_interface, get_wifi_interfaces, save_selected_wifi_interface, WifiNetwork,
+};
+use wireguard::{get_wireguard_actions, handle_wireguard_action, WireguardAction};
 
 /// Command-line arguments structure for the application.
 #[derive(Parser, Debug)]
@@ -41,375 +167,1634 @@ struct Args {
     no_bluetooth: bool,
     #[arg(long)]
     no_tailscale: bool,
-}
-
-/// Configuration structure for the application.
-#[derive(Debug, Deserialize, Serialize)]
-struct Config {
-    #[serde(default)]
-    actions: Vec<CustomAction>,
-    #[serde(default)]
-    exclude_exit_node: Vec<String>,
-    dmenu_cmd: String,
-    dmenu_args: String,
-}
-
-/// Custom action structure for user-defined actions.
-#[derive(Debug, Deserialize, Serialize, Clone)]
-struct CustomAction {
-    display: String,
-    cmd: String,
+    #[arg(short, long)]
+    verbose: bool,
+    #[arg(long)]
+    log_level: Option<String>,
+    #[arg(long)]
+    log_file: bool,
+    #[arg(long)]
+    daemon: bool,
+    #[arg(long)]
+    waybar: bool,
+    #[arg(long)]
+    info: bool,
+    #[arg(long)]
+    profile_startup: bool,
+    #[arg(long)]
+    list_json: bool,
+    #[arg(long, value_name = "ID")]
+    apply: Option<String>,
+    #[arg(long)]
+    rescan: bool,
+    #[arg(long)]
+    replace: bool,
+    #[arg(long)]
+    tui: bool,
+    #[arg(long)]
+    builtin_ui: bool,
+    #[cfg(feature = "gui")]
+    #[arg(long)]
+    popup: bool,
+    #[cfg(feature = "tray")]
+    #[arg(long)]
+    tray: bool,
+    #[arg(long)]
+    check_config: bool,
+    #[arg(long, value_name = "SHELL")]
+    generate_completions: Option<Shell>,
+    #[arg(long)]
+    generate_manpage: bool,
+    #[command(subcommand)]
+    command: Option<Commands>,
 }
 
 /// Enum representing different types of actions that can be performed.
-#[derive(Debug)]
+#[derive(Debug, Deserialize, Serialize)]
 enum ActionType {
     Bluetooth(BluetoothAction),
     Custom(CustomAction),
+    Dns(DnsAction),
+    Firewall(FirewallAction),
+    IpProfile(IpProfileAction),
+    KillSwitch(KillSwitchAction),
+    Mullvad(MullvadAction),
+    Power(PowerAction),
+    Profile(String),
+    Proxy(ProxyAction),
+    /// Last known entry of a section whose daemon is unreachable.
+    Stale(Box<ActionType>),
     System(SystemAction),
     Tailscale(TailscaleAction),
+    Toggle(ToggleAction),
+    Tor(TorAction),
+    Tunnel(TunnelAction),
+    Vpn(VpnAction),
     Wifi(WifiAction),
+    Wireguard(WireguardAction),
 }
 
 /// Enum representing system-related actions.
-#[derive(Debug)]
+#[derive(Debug, Deserialize, Serialize)]
 enum SystemAction {
+    Diagnostics,
+    EditConfig,
     EditConnections,
-    RfkillBlock,
-    RfkillUnblock,
+    Info,
+    StartHotspot,
+    StopHotspot,
 }
 
 /// Enum representing Wi-Fi-related actions.
-#[derive(Debug)]
+#[derive(Debug, Deserialize, Serialize)]
 enum WifiAction {
     Connect,
+    ConnectAccessPoint,
+    ConnectHidden,
+    ConnectWps,
     Disconnect,
+    Forget,
+    History,
     Network(String),
+    Policy(String, String),
+    Rescan,
+    SavedNetworks,
+    SavedOutOfRange(String),
+    SelectInterface(String),
+    ShareNetwork,
 }
 
 /// Formats an entry for display in the menu.
 pub fn format_entry(action: &str, icon: &str, text: &str) -> String {
     if icon.is_empty() {
-        format!("{action:<10}- {text}")
+        format!("{}- {text}", pad(action, 10))
     } else {
-        format!("{action:<10}- {icon} {text}")
+        format!("{}- {icon} {text}", pad(action, 10))
     }
 }
 
-/// Returns the default configuration as a string.
-fn get_default_config() -> &'static str {
-    r#"
-dmenu_cmd = "dmenu"
-dmenu_args = "--no-multi"
-
-exclude_exit_node = ["exit1", "exit2"]
-
-[[actions]]
-display = "🛡️ Example"
-cmd = "notify-send 'hello' 'world'"
-"#
-}
-
 /// Main function for the application.
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
-    let args = Args::parse();
+    let matches = Args::command().get_matches();
+    let mut args = Args::from_arg_matches(&matches)?;
+    init_logging(args.log_level.as_deref(), args.log_file)?;
+
+    if let Some(shell) = args.generate_completions {
+        let mut command = Args::command();
+        let name = command.get_name().to_string();
+        clap_complete::generate(shell, &mut command, name, &mut std::io::stdout());
+        return Ok(());
+    }
+    if args.generate_manpage {
+        clap_mangen::Man::new(Args::command()).render(&mut std::io::stdout())?;
+        return Ok(());
+    }
 
     create_default_config_if_missing()?;
 
-    let config = get_config()?; // Load the configuration once
+    if args.check_config {
+        let problems = check_config()?;
+        for problem in &problems {
+            eprintln!("{problem}");
+        }
+        if problems.is_empty() {
+            println!("{}: OK", get_config_path()?.display());
+        }
+        std::process::exit(if problems.is_empty() { 0 } else { 1 });
+    }
 
-    check_required_commands(&config)?;
+    // Load the configuration once
+    let (mut config, warnings) = get_config().unwrap_or_else(|err| {
+        eprintln!("error: {err}");
+        std::process::exit(1);
+    });
+    for warning in warnings {
+        eprintln!("warning: {warning}");
+    }
+    init_locale(config.locale.as_deref());
+    #[cfg(feature = "gui")]
+    if args.popup {
+        use_popup_launcher(&mut config);
+    }
+    if args.builtin_ui {
+        use_builtin_launcher(&mut config);
+    } else if args.tui {
+        use_terminal_launcher(&mut config)?;
+    } else if !Launcher::detect(&config).is_embedded()
+        && !is_command_installed(&config.dmenu_cmd)
+        && std::io::stdout().is_terminal()
+    {
+        eprintln!(
+            "warning: {} not found, using the built-in selector",
+            config.dmenu_cmd
+        );
+        use_builtin_launcher(&mut config);
+    }
+    let command_runner = RealCommandRunner::new(args.verbose)
+        .with_escalation(config.escalation.clone())
+        .with_timeouts(config.timeouts.clone());
+    if matches.value_source("wifi_interface") != Some(ValueSource::CommandLine) && !args.no_wifi {
+        select_default_wifi_interface(&mut args, &command_runner)?;
+    }
 
-    let command_runner = RealCommandRunner;
-    let actions = get_actions(&args, &config, &command_runner)?; // Use the loaded config
-    let action = select_action_from_menu(&config, &actions)?;
+    let detection_start = Instant::now();
+    let capabilities = detect_capabilities(&config);
+    warn_missing_tools(&args, &capabilities);
+    let context = Context {
+        args: &args,
+        config: &config,
+        capabilities: &capabilities,
+        command_runner: &command_runner,
+    };
 
-    if !action.is_empty() {
-        let selected_action = find_selected_action(&action, &actions)?;
-        let connected_devices = get_connected_devices(&command_runner)?;
+    if let Some(command) = &args.command {
+        let success = run_cli_command(command, &context).await?;
+        if !success {
+            for failure in command_runner.take_failures() {
+                eprintln!("error: {failure}");
+            }
+        }
+        std::process::exit(if success { 0 } else { 1 });
+    }
 
-        set_action(
-            &args.wifi_interface,
-            selected_action,
-            &connected_devices,
-            &command_runner,
-        )
-        .await?;
+    if args.waybar {
+        return print_waybar_status(&context);
+    }
+
+    if args.info {
+        let info = get_connection_info(&config, &args.wifi_interface, &command_runner).await?;
+        println!("{}", info.join("\n"));
+        return Ok(());
+    }
+
+    if args.profile_startup {
+        return profile_startup(&context, detection_start.elapsed());
+    }
+
+    if args.list_json {
+        let actions = get_current_actions(&context).await?;
+        println!(
+            "{}",
+            serde_json::to_string(&action_entries(&config, &actions))?
+        );
+        return Ok(());
+    }
+
+    if let Some(id) = &args.apply {
+        let actions = get_current_actions(&context).await?;
+        let action = actions
+            .iter()
+            .find(|action| action_key(action) == *id)
+            .ok_or_else(|| format!("unknown action id {id}"))?;
+        let result = apply_actions(&context, &[action], &actions).await?;
+        request_daemon_refresh();
+        for (entry, errors) in &result.failures {
+            for error in errors {
+                eprintln!("error: {}: {error}", entry.trim());
+            }
+        }
+        std::process::exit(if result.failures.is_empty() { 0 } else { 1 });
     }
 
-    debug_tailscale_status_if_installed()?;
+    if args.daemon {
+        return run_daemon(&context).await;
+    }
+
+    // The other modes print or apply actions without a menu, so only it needs the launcher
+    if let Err(err) = check_menu_command(&config) {
+        eprintln!("error: {err}");
+        std::process::exit(1);
+    }
+
+    let Some(_lock) = acquire_instance_lock(args.replace, &command_runner)? else {
+        return Ok(());
+    };
+
+    if args.rescan && capabilities.wifi && !args.no_wifi {
+        rescan_wifi(&config, &args.wifi_interface, &command_runner)?;
+    }
+    let cached_actions = if args.rescan {
+        None
+    } else {
+        fetch_daemon_actions()
+    };
+    if cached_actions.is_none() && !get_mullvad_filter(&config).is_empty() {
+        spawn_relays_refresh(&config);
+    }
+    let (actions, selections) = match cached_actions {
+        None if config.menu.stream && Launcher::detect(&config).is_streamable() => {
+            stream_actions_to_menu(&context)?
+        }
+        cached_actions => {
+            let mut actions = match cached_actions {
+                Some(actions) => actions,
+                None => get_actions(&context)?, // Use the loaded config
+            };
+            sort_by_usage(
+                &mut actions,
+                &load_usage_state(),
+                config.recency_weight,
+                action_key,
+            );
+            let selections =
+                select_action_from_menu(&config, &args.wifi_interface, &actions, &command_runner)?;
+            (actions, selections)
+        }
+    };
+
+    let mut selected = Vec::new();
+    for selection in &selections {
+        let selected_action = find_selected_action(&config, selection, &actions)?;
+        // The usage only orders the menu, so failing to save it does not stop the action
+        if let Err(err) = record_usage(&action_key(selected_action)) {
+            warn!("failed to record the usage of the selected entry: {err}");
+        }
+        selected.push(selected_action);
+    }
+    // Several selected actions or profile steps are applied one after the other, reported
+    // together
+    let result = apply_actions(&context, &selected, &actions).await?;
+    if result.total > 0 {
+        request_daemon_refresh();
+    }
+    if result.total > 1 {
+        report_batch(&config, &result);
+    } else if let Some((entry, errors)) = result.failures.first() {
+        report_failure(&config, entry, errors);
+    }
+    if result
+        .failures
+        .iter()
+        .any(|(_, errors)| is_operator_error(errors))
+    {
+        offer_operator_fix(&config, &command_runner).await?;
+    }
+    if !result.failures.is_empty() {
+        std::process::exit(1);
+    }
 
     Ok(())
 }
 
-/// Checks if required commands are installed.
-fn check_required_commands(config: &Config) -> Result<(), Box<dyn Error>> {
-    if !is_command_installed("pinentry-gnome3") || !is_command_installed(&config.dmenu_cmd) {
-        panic!("pinentry-gnome3 or dmenu command missing");
+/// Reports a failed action on stderr and in a notification.
+fn report_failure(config: &Config, action: &str, errors: &[String]) {
+    let mut body = tr_args("action-failed", &[("action", action.trim())]);
+    for error in errors {
+        eprintln!("error: {error}");
+        body.push_str(&format!("\n{error}"));
     }
-    Ok(())
+    notify(config, "network-dmenu", &body);
 }
 
-/// Selects an action from the menu using dmenu.
+/// Notifies the result of several actions applied at once, listing the failed ones but
+/// not the cancelled ones.
+fn report_batch(config: &Config, result: &BatchResult) {
+    let mut body = tr_args(
+        "batch-applied",
+        &[
+            ("applied", &result.applied.to_string()),
+            ("total", &result.total.to_string()),
+        ],
+    );
+    for (entry, errors) in &result.failures {
+        for error in errors {
+            eprintln!("error: {error}");
+        }
+        let failed = tr_args("action-failed", &[("action", entry.trim())]);
+        body.push_str(&format!("\n{failed}"));
+    }
+    notify(config, "network-dmenu", &body);
+}
+
+/// Selects actions from the menu, with row icons and a status message in rofi, and
+/// previews of the actions in the terminal launchers.
+///
+/// Several actions can be selected at once with `multi_select`.
 fn select_action_from_menu(
     config: &Config,
+    wifi_interface: &str,
     actions: &[ActionType],
-) -> Result<String, Box<dyn Error>> {
-    let mut child = Command::new(&config.dmenu_cmd)
-        .args(config.dmenu_args.split_whitespace())
-        .stdin(Stdio::piped())
-        .stdout(Stdio::piped())
-        .spawn()?;
+    command_runner: &dyn CommandRunner,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let entries = actions
+        .iter()
+        .map(|action| action_to_entry(config, action))
+        .collect::<Vec<_>>();
+    let launcher = Launcher::detect(config);
+    let items = menu_items(launcher, &entries, actions);
+    let message = (launcher != Launcher::Dmenu)
+        .then(|| status_message(config, wifi_interface, command_runner))
+        .flatten();
+    if config.menu.multi_select {
+        return run_multi_menu(config, &items, message.as_deref());
+    }
+    let selection = run_menu(config, &items, message.as_deref())?;
+    Ok(if selection.is_empty() {
+        Vec::new()
+    } else {
+        vec![selection]
+    })
+}
 
-    {
-        let stdin = child.stdin.as_mut().ok_or("Failed to open stdin")?;
-        let actions_display = actions
+/// Shows the menu right away and adds each section once gathered, returning the actions
+/// gathered until the selection and the selected entries.
+///
+/// The actions are sorted by usage within their section only.
+fn stream_actions_to_menu(
+    context: &Context<'_>,
+) -> Result<(Vec<ActionType>, Vec<String>), Box<dyn Error>> {
+    let Context {
+        args,
+        config,
+        command_runner,
+        ..
+    } = *context;
+    let launcher = Launcher::detect(config);
+    let message = (launcher != Launcher::Dmenu)
+        .then(|| status_message(config, &args.wifi_interface, command_runner))
+        .flatten();
+    let mut menu = StreamingMenu::spawn(config, message.as_deref(), config.menu.multi_select)?;
+    let usage = load_usage_state();
+    // The quickest sections are shown first, to be picked while the others are gathered
+    let mut sections = config.menu.sections.clone();
+    sections.sort_by_key(|section| section.gather_cost());
+    let actions = gather_actions(context, &sections, |_, section| {
+        sort_by_usage(section, &usage, config.recency_weight, action_key);
+        let entries: Vec<String> = section
             .iter()
-            .map(action_to_string)
-            .collect::<Vec<_>>()
-            .join("\n");
-        write!(stdin, "{actions_display}")?;
+            .map(|action| action_to_entry(config, action))
+            .collect();
+        menu.push(&menu_items(launcher, &entries, section))
+    })?;
+    Ok((actions, menu.finish()?))
+}
+
+/// Builds the menu items of the actions, with row icons and terminal previews.
+fn menu_items<'a>(
+    launcher: Launcher,
+    entries: &'a [String],
+    actions: &[ActionType],
+) -> Vec<MenuItem<'a>> {
+    entries
+        .iter()
+        .zip(actions)
+        .map(|(entry, action)| MenuItem {
+            text: entry,
+            icon: row_icon(action),
+            details: launcher
+                .is_terminal()
+                .then(|| action_details(entry, action)),
+            id: Some(action_key(action)),
+            toggle: match action {
+                ActionType::Toggle(toggle_action) => Some(!toggle_action.enable),
+                _ => None,
+            },
+        })
+        .collect()
+}
+
+/// Returns the icon name of the row of an action, for launchers showing icons.
+fn row_icon(action: &ActionType) -> Option<&'static str> {
+    Some(match action {
+        ActionType::Wifi(WifiAction::Network(network)) => {
+            if network.contains('✅') {
+                "network-wireless-connected-symbolic"
+            } else {
+                match network
+                    .chars()
+                    .filter(|c| matches!(c, '▄' | '▆' | '█'))
+                    .count()
+                {
+                    3 => "network-wireless-signal-excellent-symbolic",
+                    2 => "network-wireless-signal-good-symbolic",
+                    1 => "network-wireless-signal-ok-symbolic",
+                    _ => "network-wireless-signal-weak-symbolic",
+                }
+            }
+        }
+        ActionType::Wifi(WifiAction::SavedOutOfRange(_)) => "network-wireless-offline-symbolic",
+        ActionType::Wifi(_) => "network-wireless-symbolic",
+        ActionType::Bluetooth(_) => "bluetooth-symbolic",
+        ActionType::Tailscale(_)
+        | ActionType::Mullvad(_)
+        | ActionType::Vpn(_)
+        | ActionType::Wireguard(_)
+        | ActionType::Tunnel(_)
+        | ActionType::Tor(_) => "network-vpn-symbolic",
+        ActionType::Firewall(_) | ActionType::KillSwitch(_) => "security-high-symbolic",
+        ActionType::System(_) => "preferences-system-symbolic",
+        _ => return None,
+    })
+}
+
+/// Describes an action for the preview of the terminal launchers.
+fn action_details(entry: &str, action: &ActionType) -> String {
+    let mut details = vec![entry.trim().to_string()];
+    if let ActionType::Wifi(WifiAction::Network(network)) = action {
+        if let Ok((ssid, security)) = parse_wifi_action(network) {
+            details.push(format!("SSID: {ssid}\nSecurity: {security}"));
+        }
     }
+    let key = action_key(action);
+    if key != entry {
+        details.push(format!("Key: {key}"));
+    }
+    if let Ok(json) = serde_json::to_string_pretty(action) {
+        details.push(json);
+    }
+    details.join("\n\n")
+}
 
-    let output = child.wait_with_output()?;
-    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+/// Builds the status message of the menu, showing the current network and exit node.
+fn status_message(
+    config: &Config,
+    wifi_interface: &str,
+    command_runner: &dyn CommandRunner,
+) -> Option<String> {
+    let mut status = Vec::new();
+    if let Ok(Some(ssid)) = get_current_ssid(config, wifi_interface, command_runner) {
+        status.push(tr_args("status-wifi", &[("ssid", &ssid)]));
+    }
+    if is_command_installed("tailscale") {
+        let exit_node = get_active_exit_node(command_runner);
+        if !exit_node.is_empty() {
+            status.push(tr_args("status-exit-node", &[("node", &exit_node)]));
+        }
+    }
+    (!status.is_empty()).then(|| status.join(" · "))
 }
 
-/// Converts an action to a string for display.
-fn action_to_string(action: &ActionType) -> String {
+/// Shows the given entries in dmenu and returns the selected one.
+fn select_from_menu(config: &Config, entries: &[String]) -> Result<String, Box<dyn Error>> {
+    let items: Vec<MenuItem> = entries
+        .iter()
+        .map(|entry| MenuItem {
+            text: entry,
+            icon: None,
+            details: None,
+            id: None,
+            toggle: None,
+        })
+        .collect();
+    run_menu(config, &items, None)
+}
+
+/// Asks the user for free-form input in dmenu, showing the given prompt.
+fn prompt_from_menu(config: &Config, prompt: &str) -> Result<String, Box<dyn Error>> {
+    run_prompt(config, prompt)
+}
+
+/// Asks the user to confirm an operation in dmenu.
+fn confirm(config: &Config, operation: &str) -> Result<bool, Box<dyn Error>> {
+    let yes = tr_args("confirm-yes", &[("operation", operation)]);
+    let answer = select_from_menu(config, &[yes.clone(), tr("confirm-no")])?;
+    Ok(answer == yes)
+}
+
+/// Converts an action to a string for display, with the icons of the configured theme.
+fn action_to_string(config: &Config, action: &ActionType) -> String {
+    let icon = |name| icon(name, config.icons, &config.icon_overrides);
+    // Entries built by the sections carry emoji glyphs
+    let themed = |entry: &str| apply_icon_theme(entry, config.icons, &config.icon_overrides);
     match action {
         ActionType::Custom(custom_action) => format_entry("action", "", &custom_action.display),
+        ActionType::Stale(action) => format!(
+            "{}\t{}",
+            action_to_string(config, action),
+            tr("entry-stale")
+        ),
         ActionType::System(system_action) => match system_action {
-            SystemAction::RfkillBlock => format_entry("system", "❌", "Radio wifi rfkill block"),
-            SystemAction::RfkillUnblock => {
-                format_entry("system", "📶", "Radio wifi rfkill unblock")
+            SystemAction::Diagnostics => {
+                format_entry("system", icon("diagnostics"), &tr("system-diagnostics"))
+            }
+            SystemAction::Info => format_entry("system", icon("info"), &tr("system-info")),
+            SystemAction::EditConfig => {
+                format_entry("system", icon("settings"), &tr("system-edit-config"))
+            }
+            SystemAction::EditConnections => {
+                format_entry("system", icon("wifi"), &tr("system-edit-connections"))
+            }
+            SystemAction::StartHotspot => {
+                format_entry("system", icon("hotspot"), &tr("system-start-hotspot"))
+            }
+            SystemAction::StopHotspot => {
+                format_entry("system", icon("inactive"), &tr("system-stop-hotspot"))
             }
-            SystemAction::EditConnections => format_entry("system", "📶", "Edit connections"),
         },
         ActionType::Tailscale(mullvad_action) => match mullvad_action {
-            TailscaleAction::SetExitNode(node) => node.to_string(),
-            TailscaleAction::DisableExitNode => {
-                format_entry("tailscale", "❌", "Disable exit-node")
+            TailscaleAction::SelectMullvadCountry(country) => format_entry(
+                "mullvad",
+                &if country.active {
+                    icon("active").to_string()
+                } else {
+                    flag(get_flag(&country.name), config.icons)
+                },
+                &tr_args(
+                    "mullvad-country",
+                    &[
+                        ("country", &country.name),
+                        ("nodes", &country.nodes.to_string()),
+                    ],
+                ),
+            ),
+            TailscaleAction::SetExitNode(node) => themed(node),
+            TailscaleAction::FilterMullvad(filter) => format_entry(
+                "mullvad",
+                icon("search"),
+                &tr_args("mullvad-filter", &[("filter", filter)]),
+            ),
+            TailscaleAction::SetRandomExitNode(country) => {
+                themed(&random_node_entry(country.as_deref()))
+            }
+            TailscaleAction::DisableExitNode => format_entry(
+                "tailscale",
+                icon("inactive"),
+                &tr("tailscale-disable-exit-node"),
+            ),
+            TailscaleAction::Login => {
+                format_entry("tailscale", icon("share"), &tr("tailscale-login"))
             }
-            TailscaleAction::SetEnable(enable) => format_entry(
+            TailscaleAction::Logout => {
+                format_entry("tailscale", icon("logout"), &tr("tailscale-logout"))
+            }
+            TailscaleAction::SetSuggestedExitNode => format_entry(
+                "mullvad",
+                icon("suggested"),
+                &tr("tailscale-suggested-exit-node"),
+            ),
+            TailscaleAction::SetEnable(enable, key_expiry) => format_entry(
+                "tailscale",
+                if *enable {
+                    icon("active")
+                } else {
+                    icon("inactive")
+                },
+                &match key_expiry {
+                    Some(date) => tr_args("tailscale-enable-key-expiry", &[("date", date)]),
+                    None => tr("tailscale-enable"),
+                },
+            ),
+            TailscaleAction::CopyPeerIp(peer) => format_entry(
+                "peer",
+                if peer.online {
+                    icon("active")
+                } else {
+                    icon("inactive")
+                },
+                &format!("{} {} - {}", pad(&peer.name, 25), pad(&peer.os, 8), peer.ip),
+            ),
+            TailscaleAction::PingPeer(peer) => format_entry(
+                "peer",
+                icon("diagnostics"),
+                &tr_args("tailscale-ping-peer", &[("peer", &peer.name)]),
+            ),
+            TailscaleAction::SetAdvertiseRoute(route, advertise) => format_entry(
+                "tailscale",
+                if *advertise {
+                    icon("inactive")
+                } else {
+                    icon("active")
+                },
+                &tr_args("tailscale-advertise-route", &[("route", route)]),
+            ),
+            TailscaleAction::SetHostname(hostname) => format_entry(
                 "tailscale",
-                if *enable { "✅" } else { "❌" },
-                "Enable tailscale",
+                icon("tag"),
+                &tr_args("tailscale-hostname", &[("hostname", hostname)]),
             ),
-            TailscaleAction::SetShields(enable) => format_entry(
+            TailscaleAction::SetOperator(user) => format_entry(
                 "tailscale",
-                if *enable { "🛡️" } else { "🛡️" },
+                icon("share"),
+                &tr_args("tailscale-set-operator", &[("user", user)]),
+            ),
+            TailscaleAction::SetDriveShare(name, share) => format_entry(
+                "tailscale",
+                if *share {
+                    icon("inactive")
+                } else {
+                    icon("active")
+                },
+                &tr_args("tailscale-drive-share", &[("name", name)]),
+            ),
+            TailscaleAction::StartFunnel(name) => format_entry(
+                "tailscale",
+                icon("dns"),
+                &tr_args("tailscale-start-funnel", &[("name", name)]),
+            ),
+            TailscaleAction::StopServe(serve) => format_entry(
+                "tailscale",
+                icon("inactive"),
+                &tr_args(
+                    if serve.funnel {
+                        "tailscale-stop-funnel"
+                    } else {
+                        "tailscale-stop-serve"
+                    },
+                    &[
+                        ("port", &serve.port.to_string()),
+                        ("protocol", &serve.protocol),
+                    ],
+                ),
+            ),
+        },
+        ActionType::Toggle(toggle_action) => themed(&toggle_entry(toggle_action)),
+        ActionType::Tor(tor_action) => match tor_action {
+            TorAction::CheckCircuit => format_entry("tor", icon("tor"), &tr("tor-check-circuit")),
+            TorAction::SetEnabled(enable) => format_entry(
+                "tor",
                 if *enable {
-                    "Shields up"
+                    icon("inactive")
+                } else {
+                    icon("active")
+                },
+                &tr("tor-service"),
+            ),
+        },
+        ActionType::Tunnel(tunnel_action) => match tunnel_action {
+            TunnelAction::Start(name) => format_entry("tunnel", icon("vpn"), name),
+            TunnelAction::Stop(name) => format_entry("tunnel", icon("active"), name),
+        },
+        ActionType::Dns(dns_action) => match dns_action {
+            DnsAction::FlushCache => format_entry("dns", icon("remove"), &tr("dns-flush-cache")),
+            DnsAction::SetPreset(name, active) => format_entry(
+                "dns",
+                if *active { icon("active") } else { icon("dns") },
+                name,
+            ),
+        },
+        ActionType::Mullvad(mullvad_action) => match mullvad_action {
+            MullvadAction::Connect => format_entry("vpn", icon("vpn"), &tr("mullvad-app-connect")),
+            MullvadAction::Disconnect(relay) => format_entry(
+                "vpn",
+                icon("active"),
+                &tr_args("mullvad-app-disconnect", &[("relay", relay)]),
+            ),
+            MullvadAction::SelectCountry(country) => format_entry(
+                "vpn",
+                &if country.active {
+                    icon("active").to_string()
                 } else {
-                    "Shields down"
+                    flag(get_flag(&country.name), config.icons)
                 },
+                &tr_args(
+                    "mullvad-app-country",
+                    &[
+                        ("country", &country.name),
+                        ("cities", &country.cities.len().to_string()),
+                    ],
+                ),
             ),
         },
+        ActionType::Firewall(
+            FirewallAction::SetZone(name, active) | FirewallAction::SetUfwProfile(name, active),
+        ) => format_entry(
+            "firewall",
+            if *active {
+                icon("active")
+            } else {
+                icon("shield")
+            },
+            name,
+        ),
+        ActionType::KillSwitch(KillSwitchAction::SetArmed(arm)) => format_entry(
+            "firewall",
+            if *arm {
+                icon("inactive")
+            } else {
+                icon("active")
+            },
+            &tr("kill-switch"),
+        ),
+        ActionType::Power(PowerAction::SetProfile(name, active)) => format_entry(
+            "power",
+            if *active {
+                icon("active")
+            } else {
+                icon("power")
+            },
+            name,
+        ),
+        ActionType::Profile(name) => format_entry("profile", icon("profile"), name),
+        ActionType::Proxy(ProxyAction::SetProfile(name, active)) => format_entry(
+            "proxy",
+            if *active { icon("active") } else { icon("dns") },
+            name,
+        ),
+        ActionType::IpProfile(IpProfileAction::Apply(name, active)) => format_entry(
+            "ip",
+            if *active { icon("active") } else { icon("ip") },
+            name,
+        ),
+        ActionType::Vpn(vpn_action) => match vpn_action {
+            VpnAction::Connect(name) => format_entry("vpn", icon("vpn"), name),
+            VpnAction::Disconnect(name) => format_entry("vpn", icon("active"), name),
+            VpnAction::StartSession(name) => format_entry("vpn", icon("vpn"), name),
+            VpnAction::StopSession(name) => format_entry("vpn", icon("active"), name),
+        },
+        ActionType::Wireguard(wireguard_action) => match wireguard_action {
+            WireguardAction::Up(name) => format_entry("wireguard", icon("vpn"), name),
+            WireguardAction::Down(name) => format_entry("wireguard", icon("active"), name),
+        },
         ActionType::Wifi(wifi_action) => match wifi_action {
-            WifiAction::Network(network) => format_entry("wifi", "", network),
-            WifiAction::Disconnect => format_entry("wifi", "❌", "Disconnect"),
-            WifiAction::Connect => format_entry("wifi", "📶", "Connect"),
+            WifiAction::Network(network) => format_entry("wifi", "", &themed(network)),
+            WifiAction::Policy(ssid, policy) => format_entry(
+                "wifi",
+                icon("shield"),
+                &tr_args("wifi-policy", &[("ssid", ssid), ("policy", policy)]),
+            ),
+            WifiAction::Disconnect => {
+                format_entry("wifi", icon("inactive"), &tr("wifi-disconnect"))
+            }
+            WifiAction::Connect => format_entry("wifi", icon("wifi"), &tr("wifi-connect")),
+            WifiAction::ConnectAccessPoint => {
+                format_entry("wifi", icon("wifi"), &tr("wifi-connect-access-point"))
+            }
+            WifiAction::ConnectHidden => {
+                format_entry("wifi", icon("wifi"), &tr("wifi-connect-hidden"))
+            }
+            WifiAction::ConnectWps => format_entry("wifi", icon("wifi"), &tr("wifi-connect-wps")),
+            WifiAction::Forget => format_entry("wifi", icon("remove"), &tr("wifi-forget")),
+            WifiAction::History => format_entry("wifi", icon("history"), &tr("wifi-history")),
+            WifiAction::Rescan => format_entry("wifi", icon("rescan"), &tr("wifi-rescan")),
+            WifiAction::SavedNetworks => {
+                format_entry("wifi", icon("wifi"), &tr("wifi-saved-networks"))
+            }
+            WifiAction::SavedOutOfRange(name) => format_entry(
+                "wifi",
+                icon("out_of_range"),
+                &tr_args("wifi-out-of-range", &[("name", name)]),
+            ),
+            WifiAction::SelectInterface(interface) => format_entry(
+                "wifi",
+                icon("wifi"),
+                &tr_args("wifi-interface", &[("interface", interface)]),
+            ),
+            WifiAction::ShareNetwork => format_entry("wifi", icon("share"), &tr("wifi-share")),
         },
         ActionType::Bluetooth(bluetooth_action) => match bluetooth_action {
-            BluetoothAction::ToggleConnect(device) => device.to_string(),
+            BluetoothAction::Pair => format_entry("bluetooth", icon("add"), &tr("bluetooth-pair")),
+            BluetoothAction::Remove(device) => themed(device),
+            BluetoothAction::SetProfile(card, profile) => format_entry(
+                "bluetooth",
+                icon("audio"),
+                &tr_args(
+                    "bluetooth-profile",
+                    &[("profile", &pad(profile, 18)), ("card", card)],
+                ),
+            ),
+            BluetoothAction::ToggleConnect(device) => themed(device),
+        },
+    }
+}
+
+/// Converts an action to its menu entry, applying the configured prefixes and icons.
+fn action_to_entry(config: &Config, action: &ActionType) -> String {
+    let entry = action_to_string(config, action);
+    match entry
+        .split_once("- ")
+        .and_then(|(prefix, text)| Some((config.menu.prefixes.get(prefix.trim())?, text)))
+    {
+        Some((prefix, text)) => format!("{}- {text}", pad(prefix, 10)),
+        None => entry,
+    }
+}
+
+/// Returns a key identifying an action across runs, ignoring volatile display details.
+fn action_key(action: &ActionType) -> String {
+    match action {
+        ActionType::Stale(action) => action_key(action),
+        ActionType::Bluetooth(bluetooth_action) => match bluetooth_action {
+            BluetoothAction::Pair => "bluetooth:pair".to_string(),
+            BluetoothAction::Remove(device) => format!(
+                "bluetooth-remove:{}",
+                extract_device_address(device).unwrap_or_else(|| id_part(device))
+            ),
+            BluetoothAction::SetProfile(card, profile) => {
+                format!("bluetooth-profile:{}:{}", id_part(card), id_part(profile))
+            }
+            BluetoothAction::ToggleConnect(device) => format!(
+                "bluetooth:{}",
+                extract_device_address(device).unwrap_or_else(|| id_part(device))
+            ),
+        },
+        ActionType::Custom(custom_action) => format!("custom:{}", id_part(&custom_action.display)),
+        ActionType::Dns(DnsAction::FlushCache) => "dns:flush".to_string(),
+        ActionType::Dns(DnsAction::SetPreset(name, _)) => format!("dns:{}", id_part(name)),
+        ActionType::Firewall(
+            FirewallAction::SetZone(name, _) | FirewallAction::SetUfwProfile(name, _),
+        ) => format!("firewall:{}", id_part(name)),
+        ActionType::IpProfile(IpProfileAction::Apply(name, _)) => format!("ip:{}", id_part(name)),
+        ActionType::KillSwitch(KillSwitchAction::SetArmed(_)) => "firewall:kill-switch".to_string(),
+        ActionType::Mullvad(mullvad_action) => match mullvad_action {
+            MullvadAction::Connect => "mullvad-app:connect".to_string(),
+            MullvadAction::Disconnect(_) => "mullvad-app:disconnect".to_string(),
+            MullvadAction::SelectCountry(country) => {
+                format!("mullvad-app:{}", id_part(&country.code))
+            }
+        },
+        ActionType::Power(PowerAction::SetProfile(name, _)) => format!("power:{}", id_part(name)),
+        ActionType::Profile(name) => format!("profile:{}", id_part(name)),
+        ActionType::Proxy(ProxyAction::SetProfile(name, _)) => format!("proxy:{}", id_part(name)),
+        ActionType::System(system_action) => match system_action {
+            SystemAction::Diagnostics => "system:diagnostics",
+            SystemAction::EditConfig => "system:edit-config",
+            SystemAction::EditConnections => "system:edit-connections",
+            SystemAction::Info => "system:info",
+            SystemAction::StartHotspot => "system:start-hotspot",
+            SystemAction::StopHotspot => "system:stop-hotspot",
+        }
+        .to_string(),
+        ActionType::Tailscale(tailscale_action) => match tailscale_action {
+            TailscaleAction::DisableExitNode => "exit-node:none".to_string(),
+            TailscaleAction::Login => "tailscale:login".to_string(),
+            TailscaleAction::Logout => "tailscale:logout".to_string(),
+            TailscaleAction::SetAdvertiseRoute(route, _) => format!("route:{}", id_part(route)),
+            TailscaleAction::SetDriveShare(name, _) => format!("drive:{}", id_part(name)),
+            TailscaleAction::SetEnable(..) => "tailscale:service".to_string(),
+            TailscaleAction::SetHostname(_) => "tailscale:hostname".to_string(),
+            TailscaleAction::SetOperator(_) => "tailscale:operator".to_string(),
+            TailscaleAction::FilterMullvad(_) => "mullvad:filter".to_string(),
+            TailscaleAction::SelectMullvadCountry(country) => {
+                format!("mullvad:{}", id_part(&country.name))
+            }
+            TailscaleAction::SetRandomExitNode(None) => "exit-node:random".to_string(),
+            TailscaleAction::SetRandomExitNode(Some(country)) => {
+                format!("exit-node:random:{}", id_part(country))
+            }
+            TailscaleAction::SetExitNode(node) => format!(
+                "exit-node:{}",
+                extract_node_ip(node).map_or_else(|| id_part(node), str::to_string)
+            ),
+            TailscaleAction::SetSuggestedExitNode => "exit-node:suggested".to_string(),
+            TailscaleAction::CopyPeerIp(peer) => format!("peer:{}", id_part(&peer.name)),
+            TailscaleAction::PingPeer(peer) => format!("ping:{}", id_part(&peer.name)),
+            TailscaleAction::StartFunnel(name) => format!("funnel:{}", id_part(name)),
+            TailscaleAction::StopServe(serve) => {
+                format!("serve:{}:{}", serve.port, id_part(&serve.protocol))
+            }
         },
+        ActionType::Toggle(toggle_action) => format!("toggle:{}", toggle_action.id.toggle().name),
+        ActionType::Tor(TorAction::CheckCircuit) => "tor:check-circuit".to_string(),
+        ActionType::Tor(TorAction::SetEnabled(_)) => "tor:service".to_string(),
+        ActionType::Tunnel(TunnelAction::Start(name) | TunnelAction::Stop(name)) => {
+            format!("tunnel:{}", id_part(name))
+        }
+        ActionType::Vpn(VpnAction::Connect(name) | VpnAction::Disconnect(name)) => {
+            format!("vpn:{}", id_part(name))
+        }
+        ActionType::Vpn(VpnAction::StartSession(name) | VpnAction::StopSession(name)) => {
+            format!("vpn-session:{}", id_part(name))
+        }
+        ActionType::Wifi(wifi_action) => match wifi_action {
+            WifiAction::Network(network) => format!(
+                "wifi:{}",
+                id_part(parse_wifi_action(network).map_or(network.as_str(), |(ssid, _)| ssid))
+            ),
+            WifiAction::SavedOutOfRange(name) => format!("wifi:{}", id_part(name)),
+            WifiAction::Policy(ssid, _) => format!("wifi-policy:{}", id_part(ssid)),
+            WifiAction::SelectInterface(interface) => {
+                format!("wifi-action:interface:{}", id_part(interface))
+            }
+            WifiAction::Connect => "wifi-action:connect".to_string(),
+            WifiAction::ConnectAccessPoint => "wifi-action:connect-access-point".to_string(),
+            WifiAction::ConnectHidden => "wifi-action:connect-hidden".to_string(),
+            WifiAction::ConnectWps => "wifi-action:connect-wps".to_string(),
+            WifiAction::Disconnect => "wifi-action:disconnect".to_string(),
+            WifiAction::Forget => "wifi-action:forget".to_string(),
+            WifiAction::History => "wifi-action:history".to_string(),
+            WifiAction::Rescan => "wifi-action:rescan".to_string(),
+            WifiAction::SavedNetworks => "wifi-action:saved-networks".to_string(),
+            WifiAction::ShareNetwork => "wifi-action:share".to_string(),
+        },
+        ActionType::Wireguard(WireguardAction::Up(name) | WireguardAction::Down(name)) => {
+            format!("wireguard:{}", id_part(name))
+        }
     }
 }
 
-/// Finds the selected action from the action list.
+/// Escapes a name for an action id, percent-encoding the whitespace, symbols such as
+/// emoji and `%` so that ids can be typed and passed as a single argument.
+fn id_part(name: &str) -> String {
+    name.chars().fold(String::new(), |mut part, c| {
+        if c.is_whitespace() || c == '%' || (!c.is_ascii() && !c.is_alphanumeric()) {
+            let mut bytes = [0; 4];
+            for byte in c.encode_utf8(&mut bytes).bytes() {
+                part.push_str(&format!("%{byte:02X}"));
+            }
+        } else {
+            part.push(c);
+        }
+        part
+    })
+}
+
+/// Finds the selected action from the action list, by id or else by typed entry.
 fn find_selected_action<'a>(
-    action: &str,
+    config: &Config,
+    selection: &str,
     actions: &'a [ActionType],
 ) -> Result<&'a ActionType, Box<dyn Error>> {
     actions
         .iter()
-        .find(|a| match a {
-            ActionType::Custom(custom_action) => {
-                format_entry("action", "", &custom_action.display) == action
+        .find(|action| action_key(action) == selection)
+        .or_else(|| {
+            actions
+                .iter()
+                .find(|action| action_to_entry(config, action) == selection)
+        })
+        .ok_or("Selected action not found".into())
+}
+
+/// Gets the actions from the daemon cache, or else gathers them.
+async fn get_current_actions(context: &Context<'_>) -> Result<Vec<ActionType>, Box<dyn Error>> {
+    if let Some(actions) = fetch_daemon_actions() {
+        return Ok(actions);
+    }
+    if !get_mullvad_filter(context.config).is_empty() {
+        spawn_relays_refresh(context.config);
+    }
+    get_actions(context)
+}
+
+/// Retrieves the available Wi-Fi networks as menu actions.
+fn get_wifi_networks(
+    config: &Config,
+    wifi_interface: &str,
+    notify_scan: bool,
+    command_runner: &dyn CommandRunner,
+) -> Result<Vec<WifiAction>, Box<dyn Error>> {
+    let history = load_wifi_history();
+    let now = now();
+    Ok(
+        get_backend_wifi_networks(config, wifi_interface, notify_scan, command_runner)?
+            .into_iter()
+            .map(|network| {
+                let failures = history.recent_failures(&network.ssid, now);
+                match (network.into_action(), failures_annotation(failures)) {
+                    (WifiAction::Network(display), Some(annotation)) => {
+                        WifiAction::Network(format!("{display}\t{annotation}"))
+                    }
+                    (action, _) => action,
+                }
+            })
+            .collect(),
+    )
+}
+
+/// Retrieves the available Wi-Fi networks from the first available backend.
+fn get_backend_wifi_networks(
+    config: &Config,
+    wifi_interface: &str,
+    notify_scan: bool,
+    command_runner: &dyn CommandRunner,
+) -> Result<Vec<WifiNetwork>, Box<dyn Error>> {
+    let networks: Vec<WifiNetwork> = if is_nm_dbus_available() {
+        get_nm_dbus_wifi_networks(wifi_interface)?
+    } else if is_command_installed("nmcli") {
+        get_nm_wifi_networks(wifi_interface, command_runner)?
+    } else if is_command_installed("iwctl") {
+        get_iwd_networks(config, wifi_interface, notify_scan, command_runner)?
+    } else {
+        Vec::new()
+    };
+
+    Ok(dedup_and_sort_networks(
+        networks,
+        config.wifi_dedup,
+        config.wifi_sort_by_signal,
+    ))
+}
+
+/// Retrieves the known Wi-Fi networks from the first available backend.
+fn get_known_networks(command_runner: &dyn CommandRunner) -> Result<Vec<String>, Box<dyn Error>> {
+    if is_command_installed("nmcli") {
+        get_nm_saved_networks(command_runner)
+    } else if is_command_installed("iwctl") {
+        get_iwd_known_networks(command_runner)
+    } else {
+        Ok(Vec::new())
+    }
+}
+
+/// Retrieves the known Wi-Fi networks with their SSID, the name of the IWD ones.
+fn get_known_network_ssids(
+    command_runner: &dyn CommandRunner,
+) -> Result<Vec<(String, String)>, Box<dyn Error>> {
+    if is_command_installed("nmcli") {
+        get_nm_saved_network_ssids(command_runner)
+    } else {
+        Ok(get_known_networks(command_runner)?
+            .into_iter()
+            .map(|name| (name.clone(), name))
+            .collect())
+    }
+}
+
+/// Checks if connecting to a Wi-Fi network would prompt for a password.
+fn needs_password(network: &str, known_networks: &[String]) -> bool {
+    parse_wifi_action(network).is_ok_and(|(ssid, security)| {
+        !network.starts_with('✅')
+            && !security.is_empty()
+            && !security.eq_ignore_ascii_case("open")
+            && !known_networks.iter().any(|known| known == ssid)
+    })
+}
+
+/// Retrieves the list of actions based on the command-line arguments and configuration.
+fn get_actions(context: &Context<'_>) -> Result<Vec<ActionType>, Box<dyn Error>> {
+    gather_actions(context, &context.config.menu.sections, |_, _| true)
+}
+
+/// Gathers the actions of the given sections one by one, handing each one over once
+/// gathered, and stopping early when `on_section` returns false.
+fn gather_actions(
+    context: &Context<'_>,
+    sections: &[MenuSection],
+    mut on_section: impl FnMut(MenuSection, &mut [ActionType]) -> bool,
+) -> Result<Vec<ActionType>, Box<dyn Error>> {
+    let Context {
+        args,
+        config,
+        capabilities,
+        command_runner,
+    } = *context;
+    let mut actions = Vec::new();
+    let mut reachable: HashMap<Backend, bool> = HashMap::new();
+    let mut snapshot = HashMap::new();
+
+    for section in sections {
+        // Sections of an unreachable daemon show their last entries instead of nothing
+        let backend = Backend::of(*section).filter(|_| match section {
+            MenuSection::Wifi => !args.no_wifi,
+            MenuSection::Tailscale | MenuSection::Peers => {
+                !args.no_tailscale && capabilities.tailscale
             }
-            ActionType::System(system_action) => match system_action {
-                SystemAction::RfkillBlock => {
-                    action == format_entry("system", "❌", "Radio wifi rfkill block")
+            _ => true,
+        });
+        if let Some(backend) = backend {
+            if !*reachable
+                .entry(backend)
+                .or_insert_with(|| backend.is_reachable(command_runner))
+            {
+                let start = actions.len();
+                actions.extend(get_stale_actions(*section));
+                if !on_section(*section, &mut actions[start..]) {
+                    break;
                 }
-                SystemAction::RfkillUnblock => {
-                    action == format_entry("system", "📶", "Radio wifi rfkill unblock")
+                continue;
+            }
+        }
+        let start = actions.len();
+        match section {
+            MenuSection::Profiles => actions.extend(
+                config
+                    .profiles
+                    .iter()
+                    .map(|profile| ActionType::Profile(profile.name.clone())),
+            ),
+            MenuSection::Custom => actions.extend(
+                config
+                    .actions
+                    .clone() // Clone the actions vector
+                    .into_iter()
+                    .map(ActionType::Custom),
+            ),
+            MenuSection::Wifi if !args.no_wifi => actions.extend(get_wifi_actions(context)?),
+            MenuSection::System => actions.extend(get_system_actions(args, command_runner)?),
+            MenuSection::Dns if is_command_installed("resolvectl") => actions.extend(
+                get_dns_actions(&config.dns_presets, command_runner)?
+                    .into_iter()
+                    .map(ActionType::Dns),
+            ),
+            MenuSection::Firewall => {
+                actions.extend(
+                    get_firewall_actions(&config.ufw_profiles, command_runner)?
+                        .into_iter()
+                        .map(ActionType::Firewall),
+                );
+                if config.kill_switch && is_command_installed("nft") {
+                    actions.extend(
+                        get_kill_switch_actions()
+                            .into_iter()
+                            .map(ActionType::KillSwitch),
+                    );
                 }
-                SystemAction::EditConnections => {
-                    action == format_entry("system", "📶", "Edit connections")
+            }
+            MenuSection::Power => actions.extend(
+                get_power_actions(command_runner)?
+                    .into_iter()
+                    .map(ActionType::Power),
+            ),
+            MenuSection::Ip if is_command_installed("nmcli") => actions.extend(
+                get_ip_profile_actions(&config.ip_profiles, command_runner)?
+                    .into_iter()
+                    .map(ActionType::IpProfile),
+            ),
+            MenuSection::Proxy => actions.extend(
+                get_proxy_actions(&config.proxy_profiles)
+                    .into_iter()
+                    .map(ActionType::Proxy),
+            ),
+            MenuSection::Vpn => {
+                if is_command_installed("nmcli") {
+                    actions.extend(
+                        get_nm_vpn_actions(command_runner)?
+                            .into_iter()
+                            .map(ActionType::Vpn),
+                    );
                 }
-            },
-            ActionType::Tailscale(mullvad_action) => match mullvad_action {
-                TailscaleAction::SetExitNode(node) => action == node,
-                TailscaleAction::DisableExitNode => {
-                    action == format_entry("tailscale", "❌", "Disable exit-node")
+                actions.extend(
+                    get_vpn_session_actions(&config.vpn_sessions, command_runner)
+                        .into_iter()
+                        .map(ActionType::Vpn),
+                );
+                if is_command_installed("ssh") {
+                    actions.extend(
+                        get_tunnel_actions(&config.tunnels)
+                            .into_iter()
+                            .map(ActionType::Tunnel),
+                    );
                 }
-                TailscaleAction::SetEnable(enable) => {
-                    action
-                        == format_entry(
-                            "tailscale",
-                            if *enable { "✅" } else { "❌" },
-                            "Enable tailscale",
-                        )
+                if is_command_installed("wg-quick") {
+                    actions.extend(
+                        get_wireguard_actions(command_runner)?
+                            .into_iter()
+                            .map(ActionType::Wireguard),
+                    );
                 }
-                TailscaleAction::SetShields(enable) => {
-                    action
-                        == format_entry(
-                            "tailscale",
-                            "🛡️",
-                            if *enable {
-                                "Shields up"
-                            } else {
-                                "Shields down"
-                            },
-                        )
+                if config.tor && is_command_installed("systemctl") {
+                    actions.extend(
+                        get_tor_actions(config, command_runner)
+                            .into_iter()
+                            .map(ActionType::Tor),
+                    );
                 }
-            },
-            ActionType::Wifi(wifi_action) => match wifi_action {
-                WifiAction::Network(network) => action == format_entry("wifi", "", network),
-                WifiAction::Disconnect => action == format_entry("wifi", "❌", "Disconnect"),
-                WifiAction::Connect => action == format_entry("wifi", "📶", "Connect"),
-            },
-            ActionType::Bluetooth(bluetooth_action) => match bluetooth_action {
-                BluetoothAction::ToggleConnect(device) => action == device,
-            },
-        })
-        .ok_or("Selected action not found".into())
+                if is_command_installed("mullvad") {
+                    actions.extend(
+                        get_mullvad_actions(command_runner)?
+                            .into_iter()
+                            .map(ActionType::Mullvad),
+                    );
+                }
+            }
+            MenuSection::Tailscale if !args.no_tailscale && capabilities.tailscale => {
+                actions.extend(get_tailscale_actions(config, command_runner)?)
+            }
+            MenuSection::Peers if !args.no_tailscale && capabilities.tailscale => {
+                actions.extend(get_peers(command_runner).into_iter().flat_map(|peer| {
+                    [
+                        ActionType::Tailscale(TailscaleAction::CopyPeerIp(peer.clone())),
+                        ActionType::Tailscale(TailscaleAction::PingPeer(peer)),
+                    ]
+                }))
+            }
+            MenuSection::Bluetooth if !args.no_bluetooth => {
+                actions.extend(get_bluetooth_actions(capabilities, command_runner)?)
+            }
+            _ => {}
+        }
+        if backend.is_some() {
+            snapshot.insert(*section, serde_json::to_value(&actions[start..])?);
+        }
+        if !on_section(*section, &mut actions[start..]) {
+            break;
+        }
+    }
+    save_snapshot(snapshot);
+
+    Ok(actions)
 }
 
-/// Gets the configuration file path.
-fn get_config_path() -> Result<PathBuf, Box<dyn Error>> {
-    let config_dir = config_dir().ok_or("Failed to find config directory")?;
-    Ok(config_dir.join("network-dmenu").join("config.toml"))
-}
+/// Retrieves the Wi-Fi networks and actions.
+fn get_wifi_actions(context: &Context<'_>) -> Result<Vec<ActionType>, Box<dyn Error>> {
+    let Context {
+        args,
+        config,
+        capabilities,
+        command_runner,
+    } = *context;
+    let mut networks =
+        get_wifi_networks(config, &args.wifi_interface, !args.daemon, command_runner)?;
+    if !capabilities.pinentry {
+        let known_networks = get_known_networks(command_runner)?;
+        networks.retain(|network| match network {
+            WifiAction::Network(display) => !needs_password(display, &known_networks),
+            _ => true,
+        });
+    }
+    if config.wifi_out_of_range {
+        let out_of_range =
+            get_out_of_range_networks(get_known_network_ssids(command_runner)?, &networks);
+        networks.extend(out_of_range.into_iter().map(WifiAction::SavedOutOfRange));
+    }
+    // The policy in force on the connected network, if any
+    let policy = networks.iter().find_map(|network| match network {
+        WifiAction::Network(display) if display.starts_with('✅') => {
+            let (ssid, _) = parse_wifi_action(display).ok()?;
+            let policy = describe_network_policy(config, ssid)?;
+            Some(WifiAction::Policy(ssid.to_string(), policy))
+        }
+        _ => None,
+    });
+    let mut actions: Vec<_> = networks.into_iter().map(ActionType::Wifi).collect();
+    actions.extend(policy.map(ActionType::Wifi));
+
+    if is_nm_dbus_available() {
+        if is_nm_dbus_connected(&args.wifi_interface)? {
+            actions.push(ActionType::Wifi(WifiAction::Disconnect));
+            if is_command_installed("nmcli") {
+                actions.push(ActionType::Wifi(WifiAction::ShareNetwork));
+            }
+        } else {
+            actions.push(ActionType::Wifi(WifiAction::Connect));
+        }
+    } else if is_command_installed("nmcli") {
+        if is_nm_connected(command_runner, &args.wifi_interface)? {
+            actions.push(ActionType::Wifi(WifiAction::Disconnect));
+            actions.push(ActionType::Wifi(WifiAction::ShareNetwork));
+        } else {
+            actions.push(ActionType::Wifi(WifiAction::Connect));
+        }
+    } else if is_command_installed("iwctl") {
+        if is_iwd_connected(command_runner, &args.wifi_interface)? {
+            actions.push(ActionType::Wifi(WifiAction::Disconnect));
+        } else {
+            actions.push(ActionType::Wifi(WifiAction::Connect));
+        }
+    }
+
+    if is_command_installed("nmcli") || is_command_installed("iwctl") {
+        actions.push(ActionType::Wifi(WifiAction::Rescan));
+        actions.push(ActionType::Wifi(WifiAction::Forget));
+        actions.push(ActionType::Wifi(WifiAction::SavedNetworks));
+    }
 
-/// Creates a default configuration file if it doesn't exist.
-fn create_default_config_if_missing() -> Result<(), Box<dyn Error>> {
-    let config_path = get_config_path()?;
+    if !load_wifi_history().is_empty() {
+        actions.push(ActionType::Wifi(WifiAction::History));
+    }
 
-    if !config_path.exists() {
-        if let Some(parent) = config_path.parent() {
-            fs::create_dir_all(parent)?;
-        }
+    if is_command_installed("nmcli") || is_command_installed("iwctl") {
+        actions.push(ActionType::Wifi(WifiAction::ConnectHidden));
+        actions.push(ActionType::Wifi(WifiAction::ConnectWps));
+    }
 
-        fs::write(&config_path, get_default_config())?;
+    if config.wifi_advanced && is_command_installed("nmcli") {
+        actions.push(ActionType::Wifi(WifiAction::ConnectAccessPoint));
     }
-    Ok(())
+
+    if get_wifi_interfaces(command_runner)?.len() > 1 {
+        actions.push(ActionType::Wifi(WifiAction::SelectInterface(
+            args.wifi_interface.clone(),
+        )));
+    }
+
+    Ok(actions)
 }
 
-/// Reads and returns the configuration.
-fn get_config() -> Result<Config, Box<dyn Error>> {
-    let config_path = get_config_path()?;
-    let config_content = fs::read_to_string(config_path)?;
-    let config = toml::from_str(&config_content)?;
-    Ok(config)
+/// Uses the wireless interface selected last from the menu if it is still present, or
+/// else the detected one, keeping `wlan0` when none is found.
+fn select_default_wifi_interface(
+    args: &mut Args,
+    command_runner: &dyn CommandRunner,
+) -> Result<(), Box<dyn Error>> {
+    let interfaces = get_wifi_interfaces(command_runner)?;
+    let selected = get_selected_wifi_interface()
+        .filter(|name| interfaces.iter().any(|interface| &interface.name == name));
+    if let Some(interface) =
+        selected.or_else(|| default_wifi_interface(&interfaces).map(str::to_string))
+    {
+        args.wifi_interface = interface;
+    }
+    Ok(())
 }
 
-/// Retrieves the list of actions based on the command-line arguments and configuration.
-fn get_actions(
+/// Retrieves the system actions.
+fn get_system_actions(
     args: &Args,
-    config: &Config, // Change to reference
     command_runner: &dyn CommandRunner,
 ) -> Result<Vec<ActionType>, Box<dyn Error>> {
-    let mut actions = config
-        .actions
-        .clone() // Clone the actions vector
+    let mut toggles = Vec::new();
+    if !args.no_wifi {
+        toggles.push(ToggleId::WifiRadio);
+        toggles.push(ToggleId::WifiPowerSave);
+    }
+    toggles.push(ToggleId::Ipv6);
+    toggles.push(ToggleId::Metered);
+    if !args.no_bluetooth {
+        toggles.push(ToggleId::BluetoothRadio);
+    }
+    toggles.push(ToggleId::AirplaneMode);
+    let context = ToggleContext::new(command_runner).with_wifi_interface(&args.wifi_interface);
+    let mut actions: Vec<_> = get_toggle_actions(&toggles, &context)
         .into_iter()
-        .map(ActionType::Custom)
-        .collect::<Vec<_>>();
+        .map(ActionType::Toggle)
+        .collect();
 
-    if !args.no_tailscale
-        && is_command_installed("tailscale")
-        && is_exit_node_active(command_runner)?
-    {
-        actions.push(ActionType::Tailscale(TailscaleAction::DisableExitNode));
+    if !args.no_wifi && is_command_installed("nm-connection-editor") {
+        actions.push(ActionType::System(SystemAction::EditConnections));
     }
 
-    if !args.no_wifi {
-        if is_command_installed("nmcli") {
-            actions.extend(
-                get_nm_wifi_networks(command_runner)?
-                    .into_iter()
-                    .map(ActionType::Wifi),
-            );
-        } else if is_command_installed("iwctl") {
-            actions.extend(
-                get_iwd_networks(&args.wifi_interface, command_runner)?
-                    .into_iter()
-                    .map(ActionType::Wifi),
-            );
-        }
+    if is_command_installed("ping") {
+        actions.push(ActionType::System(SystemAction::Diagnostics));
+    }
+    actions.push(ActionType::System(SystemAction::Info));
+    actions.push(ActionType::System(SystemAction::EditConfig));
 
-        if is_command_installed("nmcli") {
-            if is_nm_connected(command_runner, &args.wifi_interface)? {
-                actions.push(ActionType::Wifi(WifiAction::Disconnect));
-            } else {
-                actions.push(ActionType::Wifi(WifiAction::Connect));
-            }
-        } else if is_command_installed("iwctl") {
-            if is_iwd_connected(command_runner, &args.wifi_interface)? {
-                actions.push(ActionType::Wifi(WifiAction::Disconnect));
-            } else {
-                actions.push(ActionType::Wifi(WifiAction::Connect));
-            }
+    if !args.no_wifi && is_command_installed("nmcli") {
+        if is_nm_hotspot_active(command_runner)? {
+            actions.push(ActionType::System(SystemAction::StopHotspot));
+        } else {
+            actions.push(ActionType::System(SystemAction::StartHotspot));
         }
     }
 
-    if !args.no_wifi && is_command_installed("rfkill") {
-        actions.push(ActionType::System(SystemAction::RfkillBlock));
-        actions.push(ActionType::System(SystemAction::RfkillUnblock));
-    }
+    Ok(actions)
+}
 
-    if !args.no_wifi && is_command_installed("nm-connection-editor") {
-        actions.push(ActionType::System(SystemAction::EditConnections));
+/// Retrieves the Tailscale actions and exit nodes.
+fn get_tailscale_actions(
+    config: &Config,
+    command_runner: &dyn CommandRunner,
+) -> Result<Vec<ActionType>, Box<dyn Error>> {
+    let mut actions = vec![ActionType::Tailscale(TailscaleAction::SetSuggestedExitNode)];
+
+    if is_exit_node_active(command_runner)? {
+        actions.push(ActionType::Tailscale(TailscaleAction::DisableExitNode));
     }
 
-    if !args.no_tailscale && is_command_installed("tailscale") {
-        actions.push(ActionType::Tailscale(TailscaleAction::SetEnable(
-            !is_tailscale_enabled(command_runner)?,
+    actions.push(ActionType::Tailscale(TailscaleAction::SetEnable(
+        !is_tailscale_enabled(command_runner)?,
+        get_key_expiry(command_runner),
+    )));
+    actions.extend(
+        get_toggle_actions(
+            &[
+                ToggleId::AcceptRoutes,
+                ToggleId::AcceptDns,
+                ToggleId::ExitNodeAllowLan,
+                ToggleId::Shields,
+            ],
+            &ToggleContext::new(command_runner),
+        )
+        .into_iter()
+        .map(ActionType::Toggle),
+    );
+    actions.extend(
+        get_serve_ports(command_runner)
+            .into_iter()
+            .map(|serve| ActionType::Tailscale(TailscaleAction::StopServe(serve))),
+    );
+    actions.extend(
+        config
+            .funnel_presets
+            .iter()
+            .map(|preset| ActionType::Tailscale(TailscaleAction::StartFunnel(preset.name.clone()))),
+    );
+    if !config.drive_shares.is_empty() {
+        let shared = get_drive_shares(command_runner);
+        actions.extend(config.drive_shares.iter().map(|share| {
+            ActionType::Tailscale(TailscaleAction::SetDriveShare(
+                share.name.clone(),
+                !shared.contains(&share.name),
+            ))
+        }));
+        actions.extend(
+            shared
+                .into_iter()
+                .filter(|name| !config.drive_shares.iter().any(|share| &share.name == name))
+                .map(|name| ActionType::Tailscale(TailscaleAction::SetDriveShare(name, false))),
+        );
+    }
+    if !config.advertised_routes.is_empty() {
+        let advertised = get_advertised_routes(command_runner);
+        actions.extend(config.advertised_routes.iter().map(|route| {
+            ActionType::Tailscale(TailscaleAction::SetAdvertiseRoute(
+                route.clone(),
+                !advertised.contains(route),
+            ))
+        }));
+        actions.extend(
+            advertised
+                .into_iter()
+                .filter(|route| !config.advertised_routes.contains(route))
+                .map(|route| {
+                    ActionType::Tailscale(TailscaleAction::SetAdvertiseRoute(route, false))
+                }),
+        );
+    }
+    if let Some(hostname) = get_tailscale_hostname(command_runner) {
+        actions.push(ActionType::Tailscale(TailscaleAction::SetHostname(
+            hostname,
+        )));
+    }
+    if let Some(user) = get_operator_candidate(command_runner) {
+        actions.push(ActionType::Tailscale(TailscaleAction::SetOperator(user)));
+    }
+    actions.push(ActionType::Tailscale(TailscaleAction::Login));
+    actions.push(ActionType::Tailscale(TailscaleAction::Logout));
+    let exit_nodes = get_allowed_exit_nodes(config, command_runner);
+    if exit_nodes.iter().any(|node| node.is_mullvad()) {
+        actions.push(ActionType::Tailscale(TailscaleAction::SetRandomExitNode(
+            None,
+        )));
+        actions.push(ActionType::Tailscale(TailscaleAction::FilterMullvad(
+            describe_mullvad_filter(config),
         )));
-        actions.push(ActionType::Tailscale(TailscaleAction::SetShields(false)));
-        actions.push(ActionType::Tailscale(TailscaleAction::SetShields(true)));
+    }
+    if config.mullvad_by_country {
+        actions.extend(
+            exit_nodes
+                .iter()
+                .filter(|node| !node.is_mullvad())
+                .map(|node| ActionType::Tailscale(TailscaleAction::SetExitNode(node.to_entry()))),
+        );
+        actions.extend(
+            group_mullvad_countries(&exit_nodes)
+                .into_iter()
+                .map(|country| {
+                    ActionType::Tailscale(TailscaleAction::SelectMullvadCountry(country))
+                }),
+        );
+    } else {
         actions.extend(
-            get_mullvad_actions(command_runner, &config.exclude_exit_node)
+            group_mullvad_countries(&exit_nodes)
                 .into_iter()
-                .map(|m| ActionType::Tailscale(TailscaleAction::SetExitNode(m))),
+                .map(|country| {
+                    ActionType::Tailscale(TailscaleAction::SetRandomExitNode(Some(country.name)))
+                }),
+        );
+        actions.extend(
+            exit_nodes
+                .iter()
+                .map(|node| ActionType::Tailscale(TailscaleAction::SetExitNode(node.to_entry()))),
         );
     }
 
-    if !args.no_bluetooth && is_command_installed("bluetoothctl") {
+    Ok(actions)
+}
+
+/// Retrieves the Bluetooth actions, paired devices and audio profiles.
+fn get_bluetooth_actions(
+    capabilities: &Capabilities,
+    command_runner: &dyn CommandRunner,
+) -> Result<Vec<ActionType>, Box<dyn Error>> {
+    let mut actions: Vec<_> = get_toggle_actions(
+        &[ToggleId::BluetoothPower],
+        &ToggleContext::new(command_runner),
+    )
+    .into_iter()
+    .map(ActionType::Toggle)
+    .collect();
+
+    if is_command_installed("bluetoothctl") {
+        actions.push(ActionType::Bluetooth(BluetoothAction::Pair));
+    }
+
+    if capabilities.bluetooth {
         actions.extend(
             get_paired_bluetooth_devices(command_runner)?
                 .into_iter()
                 .map(ActionType::Bluetooth),
         );
+        actions.extend(
+            get_audio_profile_actions(&get_connected_devices(command_runner)?, command_runner)?
+                .into_iter()
+                .map(ActionType::Bluetooth),
+        );
     }
 
     Ok(actions)
 }
 
 /// Handles a custom action by executing its command.
-fn handle_custom_action(action: &CustomAction) -> Result<bool, Box<dyn Error>> {
-    let status = Command::new("sh").arg("-c").arg(&action.cmd).status()?;
+///
+/// The `{ssid}`, `{exit_node}`, `{interface}` and `{input}` placeholders are
+/// replaced by shell-quoted values, `{input}` being asked for when a prompt is set.
+fn handle_custom_action(
+    config: &Config,
+    action: &CustomAction,
+    wifi_interface: &str,
+    command_runner: &dyn CommandRunner,
+) -> Result<bool, Box<dyn Error>> {
+    let mut cmd = action.cmd.clone();
+
+    if let Some(prompt) = &action.prompt {
+        let input = prompt_from_menu(config, prompt)?;
+        if input.is_empty() {
+            return Ok(false);
+        }
+        cmd = cmd.replace("{input}", &shell_quote(&input));
+    }
+    if cmd.contains("{ssid}") {
+        let ssid = get_current_ssid(config, wifi_interface, command_runner)?.unwrap_or_default();
+        cmd = cmd.replace("{ssid}", &shell_quote(&ssid));
+    }
+    if cmd.contains("{exit_node}") {
+        let exit_node = if is_command_installed("tailscale") {
+            get_active_exit_node(command_runner)
+        } else {
+            String::new()
+        };
+        cmd = cmd.replace("{exit_node}", &shell_quote(&exit_node));
+    }
+    cmd = cmd.replace("{interface}", &shell_quote(wifi_interface));
+
+    let status = Command::new("sh").arg("-c").arg(&cmd).status()?;
     Ok(status.success())
 }
 
+/// Returns the SSID of the currently connected Wi-Fi network.
+fn get_current_ssid(
+    config: &Config,
+    wifi_interface: &str,
+    command_runner: &dyn CommandRunner,
+) -> Result<Option<String>, Box<dyn Error>> {
+    Ok(
+        get_wifi_networks(config, wifi_interface, false, command_runner)?
+            .iter()
+            .find_map(|network| match network {
+                WifiAction::Network(display) if display.starts_with('✅') => {
+                    parse_wifi_action(display)
+                        .ok()
+                        .map(|(ssid, _)| ssid.to_string())
+                }
+                _ => None,
+            }),
+    )
+}
+
 /// Handles a system action.
-fn handle_system_action(action: &SystemAction) -> Result<bool, Box<dyn Error>> {
+async fn handle_system_action(
+    config: &Config,
+    action: &SystemAction,
+    wifi_interface: &str,
+    command_runner: &dyn CommandRunner,
+) -> Result<bool, Box<dyn Error>> {
     match action {
-        SystemAction::RfkillBlock => {
-            let status = Command::new("rfkill").arg("block").arg("wlan").status()?;
-            Ok(status.success())
-        }
-        SystemAction::RfkillUnblock => {
-            let status = Command::new("rfkill").arg("unblock").arg("wlan").status()?;
-            Ok(status.success())
-        }
         SystemAction::EditConnections => {
             let status = Command::new("nm-connection-editor").status()?;
             Ok(status.success())
         }
+        SystemAction::Info => show_connection_info(config, wifi_interface, command_runner).await,
+        SystemAction::EditConfig => edit_config(config),
+        SystemAction::StartHotspot => {
+            let ssid = config
+                .hotspot_ssid
+                .clone()
+                .unwrap_or_else(|| "network-dmenu".to_string());
+            let password = match &config.hotspot_password {
+                Some(password) => password.clone(),
+                None => generate_password(12)?,
+            };
+            if !start_nm_hotspot(wifi_interface, &ssid, &password, command_runner)? {
+                return Ok(false);
+            }
+            notify_hotspot(config, &ssid, &password);
+            Ok(true)
+        }
+        SystemAction::StopHotspot => stop_nm_hotspot(command_runner),
+        SystemAction::Diagnostics => run_diagnostics(config, command_runner),
     }
 }
 
@@ -436,6 +1821,7 @@ fn parse_wifi_action(action: &str) -> Result<(&str, &str), Box<dyn Error>> {
 
 /// Handles a Wi-Fi action, such as connecting or disconnecting.
 async fn handle_wifi_action(
+    config: &Config,
     action: &WifiAction,
     wifi_interface: &str,
     command_runner: &dyn CommandRunner,
@@ -444,70 +1830,538 @@ async fn handle_wifi_action(
         WifiAction::Disconnect => {
             let status = if is_command_installed("nmcli") {
                 disconnect_nm_wifi(wifi_interface, command_runner)?
+            } else if is_nm_dbus_available() {
+                disconnect_nm_dbus_wifi(wifi_interface)?
             } else {
                 disconnect_iwd_wifi(wifi_interface, command_runner)?
             };
+            if status {
+                record_wifi_disconnection();
+            }
             Ok(status)
         }
         WifiAction::Connect => {
-            let status = Command::new("nmcli")
-                .arg("device")
-                .arg("connect")
-                .arg(wifi_interface)
-                .status()?;
-            check_mullvad().await?;
-            Ok(status.success())
+            let connected = if is_command_installed("nmcli") {
+                Command::new("nmcli")
+                    .arg("device")
+                    .arg("connect")
+                    .arg(wifi_interface)
+                    .status()?
+                    .success()
+            } else {
+                connect_nm_dbus_device(wifi_interface)?
+            };
+            if connected {
+                on_wifi_joined_in_use(config, wifi_interface, command_runner)?;
+            } else {
+                spawn_connectivity_check(config, None)?;
+            }
+            Ok(connected)
         }
         WifiAction::Network(network) => {
+            connect_to_wifi_network(config, wifi_interface, network, command_runner)
+        }
+        WifiAction::Rescan => {
+            if !rescan_wifi(config, wifi_interface, command_runner)? {
+                return Ok(false);
+            }
+            let network = select_wifi_network(config, wifi_interface, command_runner)?;
+            if network.is_empty() {
+                return Ok(true);
+            }
+            connect_to_wifi_network(config, wifi_interface, &network, command_runner)
+        }
+        WifiAction::SelectInterface(_) => {
+            let interfaces: Vec<String> = get_wifi_interfaces(command_runner)?
+                .into_iter()
+                .map(|interface| interface.name)
+                .collect();
+            let interface = select_from_menu(config, &interfaces)?;
+            if interface.is_empty() {
+                return Ok(false);
+            }
+            if let Err(err) = save_selected_wifi_interface(&interface) {
+                warn!("failed to remember the Wi-Fi interface {interface}: {err}");
+            }
+            let network = select_wifi_network(config, &interface, command_runner)?;
+            if network.is_empty() {
+                return Ok(true);
+            }
+            connect_to_wifi_network(config, &interface, &network, command_runner)
+        }
+        WifiAction::ConnectAccessPoint => {
+            let connected = connect_to_nm_access_point(config, wifi_interface, command_runner)?;
+            if connected {
+                on_wifi_joined_in_use(config, wifi_interface, command_runner)?;
+            }
+            Ok(connected)
+        }
+        WifiAction::ConnectHidden => {
+            let ssid = prompt_from_menu(config, &tr("prompt-hidden-ssid"))?;
+            if ssid.is_empty() {
+                return Ok(false);
+            }
+            let connected = if is_command_installed("nmcli") {
+                connect_to_nm_hidden(config, wifi_interface, &ssid, command_runner)?
+            } else {
+                connect_to_iwd_hidden(config, wifi_interface, &ssid, command_runner)?
+            };
+            if connected {
+                on_wifi_joined_in_use(config, wifi_interface, command_runner)?;
+            }
+            Ok(connected)
+        }
+        WifiAction::ConnectWps => {
+            let connected = if is_command_installed("nmcli") {
+                let network = select_wifi_network(config, wifi_interface, command_runner)?;
+                if network.is_empty() {
+                    return Ok(false);
+                }
+                let (ssid, _) = parse_wifi_action(&network)?;
+                connect_to_nm_wps(config, ssid, wifi_interface, command_runner)?
+            } else {
+                connect_to_iwd_wps(config, wifi_interface, command_runner)?
+            };
+            if connected {
+                on_wifi_joined_in_use(config, wifi_interface, command_runner)?;
+            }
+            Ok(connected)
+        }
+        WifiAction::Forget => {
+            let networks = if is_command_installed("nmcli") {
+                get_nm_saved_networks(command_runner)?
+            } else {
+                get_iwd_known_networks(command_runner)?
+            };
+            let network = select_from_menu(config, &networks)?;
+            if network.is_empty() {
+                return Ok(false);
+            }
             if is_command_installed("nmcli") {
-                connect_to_nm_wifi(network, command_runner)?;
-            } else if is_command_installed("iwctl") {
-                connect_to_iwd_wifi(wifi_interface, network, command_runner)?;
+                forget_nm_network(&network, command_runner)
+            } else {
+                forget_iwd_network(&network, command_runner)
+            }
+        }
+        WifiAction::Policy(ssid, _) => Ok(network_policy(config, ssid)
+            .is_some_and(|policy| apply_network_policy(config, ssid, policy, command_runner))),
+        WifiAction::History => {
+            select_from_menu(config, &load_wifi_history().summary(now()))?;
+            Ok(true)
+        }
+        WifiAction::SavedNetworks => {
+            let network = select_from_menu(config, &get_known_networks(command_runner)?)?;
+            if network.is_empty() {
+                return Ok(false);
             }
-            check_mullvad().await?;
+            manage_saved_network(config, &network, command_runner)
+        }
+        WifiAction::SavedOutOfRange(name) => {
+            connect_out_of_range_network(config, wifi_interface, name, command_runner)
+        }
+        WifiAction::ShareNetwork => {
+            let Some(name) = get_nm_active_wifi_connection(command_runner)? else {
+                return Ok(false);
+            };
+            let (ssid, password) = get_nm_wifi_credentials(&name, command_runner)?;
+            notify_wifi_credentials(config, &ssid, password.as_deref())?;
             Ok(true)
         }
     }
 }
 
+/// Requests a Wi-Fi scan with the available backend, showing a placeholder while it runs.
+fn rescan_wifi(
+    config: &Config,
+    wifi_interface: &str,
+    command_runner: &dyn CommandRunner,
+) -> Result<bool, Box<dyn Error>> {
+    let progress = Progress::show(config, &tr("wifi"), &tr("wifi-scanning"));
+    let result = if is_command_installed("nmcli") {
+        rescan_nm_wifi(wifi_interface, config.wifi_scan_timeout, command_runner)
+    } else if is_command_installed("iwctl") {
+        rescan_iwd_networks(config, wifi_interface, command_runner)
+    } else {
+        Ok(false)
+    };
+    if let Some(progress) = progress {
+        progress.close();
+    }
+    result
+}
+
+/// Connects an interface to a network of the menu with the available backend.
+fn connect_to_wifi_network(
+    config: &Config,
+    wifi_interface: &str,
+    network: &str,
+    command_runner: &dyn CommandRunner,
+) -> Result<bool, Box<dyn Error>> {
+    let connected = if is_command_installed("nmcli") {
+        connect_to_nm_wifi(config, wifi_interface, network, command_runner)?
+    } else if is_nm_dbus_available() {
+        connect_to_nm_dbus_wifi(config, wifi_interface, network)?
+    } else if is_command_installed("iwctl") {
+        connect_to_iwd_wifi(config, wifi_interface, network, command_runner)?
+    } else {
+        return Ok(false);
+    };
+    let (ssid, security) = parse_wifi_action(network)?;
+    if connected {
+        on_wifi_joined(config, ssid, security, command_runner)?;
+    } else {
+        record_wifi_connection(ssid, false);
+        spawn_connectivity_check(config, None)?;
+    }
+    Ok(connected)
+}
+
+/// Runs the steps shared by every way of joining a Wi-Fi network: records it in the
+/// history, applies its security policy and checks the connectivity.
+fn on_wifi_joined(
+    config: &Config,
+    ssid: &str,
+    security: &str,
+    command_runner: &dyn CommandRunner,
+) -> Result<(), Box<dyn Error>> {
+    record_wifi_connection(ssid, true);
+    apply_wifi_security(config, ssid, security, command_runner);
+    spawn_connectivity_check(config, None)
+}
+
+/// Runs the steps of a joined network for the connections that do not go through a
+/// scanned entry, such as hidden, WPS or out-of-range ones, looking up the network in use.
+fn on_wifi_joined_in_use(
+    config: &Config,
+    wifi_interface: &str,
+    command_runner: &dyn CommandRunner,
+) -> Result<(), Box<dyn Error>> {
+    let joined = get_backend_wifi_networks(config, wifi_interface, false, command_runner)
+        .unwrap_or_default()
+        .into_iter()
+        .find(|network| network.connected);
+    match joined {
+        Some(network) => on_wifi_joined(config, &network.ssid, &network.security, command_runner),
+        None => spawn_connectivity_check(config, None),
+    }
+}
+
+/// Shows the networks seen by an interface and returns the selected one.
+fn select_wifi_network(
+    config: &Config,
+    wifi_interface: &str,
+    command_runner: &dyn CommandRunner,
+) -> Result<String, Box<dyn Error>> {
+    let networks: Vec<String> = get_wifi_networks(config, wifi_interface, true, command_runner)?
+        .into_iter()
+        .filter_map(|network| match network {
+            WifiAction::Network(display) => Some(display),
+            _ => None,
+        })
+        .collect();
+    select_from_menu(config, &networks)
+}
+
+/// Activates a saved network not seen by the last scan, or lets it autoconnect once in range.
+fn connect_out_of_range_network(
+    config: &Config,
+    wifi_interface: &str,
+    name: &str,
+    command_runner: &dyn CommandRunner,
+) -> Result<bool, Box<dyn Error>> {
+    let icon = |name| icon(name, config.icons, &config.icon_overrides);
+    let when_available = format_entry("wifi", icon("waiting"), &tr("wifi-connect-when-available"));
+    let activate = format_entry("wifi", icon("wifi"), &tr("wifi-activate-profile"));
+    let choice = select_from_menu(config, &[when_available.clone(), activate.clone()])?;
+    if choice == when_available {
+        let enabled = if is_command_installed("nmcli") {
+            set_nm_autoconnect(name, true, command_runner)?
+        } else {
+            set_iwd_autoconnect(name, true, command_runner)?
+        };
+        if enabled {
+            notify(
+                config,
+                &tr("wifi"),
+                &tr_args("wifi-connect-once-in-range", &[("name", name)]),
+            );
+        }
+        Ok(enabled)
+    } else if choice == activate {
+        let connected = if is_command_installed("nmcli") {
+            activate_nm_wifi_connection(config, name, wifi_interface, command_runner)?
+        } else {
+            connect_to_iwd_known_network(config, wifi_interface, name, command_runner)?
+        };
+        if connected {
+            on_wifi_joined_in_use(config, wifi_interface, command_runner)?;
+        }
+        Ok(connected)
+    } else {
+        Ok(false)
+    }
+}
+
+/// Shows the autoconnect settings of a saved network and applies the selected change.
+fn manage_saved_network(
+    config: &Config,
+    name: &str,
+    command_runner: &dyn CommandRunner,
+) -> Result<bool, Box<dyn Error>> {
+    let (autoconnect, priority) = if is_command_installed("nmcli") {
+        let (autoconnect, priority) = get_nm_autoconnect(name, command_runner)?;
+        (autoconnect, Some(priority))
+    } else {
+        (get_iwd_autoconnect(name, command_runner)?, None)
+    };
+
+    let icon = |name| icon(name, config.icons, &config.icon_overrides);
+    let toggle = format_entry(
+        "wifi",
+        if autoconnect {
+            icon("active")
+        } else {
+            icon("inactive")
+        },
+        &tr("wifi-autoconnect"),
+    );
+    let mut entries = vec![toggle.clone()];
+    if let Some(priority) = priority {
+        entries.push(format_entry(
+            "wifi",
+            "",
+            &tr_args(
+                "wifi-autoconnect-priority",
+                &[("priority", &priority.to_string())],
+            ),
+        ));
+    }
+
+    let choice = select_from_menu(config, &entries)?;
+    if choice.is_empty() {
+        Ok(false)
+    } else if choice == toggle {
+        if is_command_installed("nmcli") {
+            set_nm_autoconnect(name, !autoconnect, command_runner)
+        } else {
+            set_iwd_autoconnect(name, !autoconnect, command_runner)
+        }
+    } else {
+        let priority = prompt_from_menu(config, &tr("prompt-autoconnect-priority"))?;
+        match priority.parse() {
+            Ok(priority) => set_nm_autoconnect_priority(name, priority, command_runner),
+            Err(_) => Ok(false),
+        }
+    }
+}
+
+/// Retrieves the connected Bluetooth devices when the action depends on them, so that the
+/// other actions run without BlueZ.
+fn get_action_devices(
+    action: &ActionType,
+    command_runner: &dyn CommandRunner,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    match action {
+        ActionType::Bluetooth(_) => get_connected_devices(command_runner),
+        ActionType::Stale(action) => get_action_devices(action, command_runner),
+        _ => Ok(Vec::new()),
+    }
+}
+
 /// Sets and handles the selected action.
 async fn set_action(
+    config: &Config,
     wifi_interface: &str,
     action: &ActionType,
     connected_devices: &[String],
     command_runner: &dyn CommandRunner,
 ) -> Result<bool, Box<dyn Error>> {
-    match action {
-        ActionType::Custom(custom_action) => handle_custom_action(custom_action),
-        ActionType::System(system_action) => handle_system_action(system_action),
+    let success = match action {
+        ActionType::Stale(action) => {
+            // Tried anyway, the daemon may be back
+            return Box::pin(set_action(
+                config,
+                wifi_interface,
+                action,
+                connected_devices,
+                command_runner,
+            ))
+            .await;
+        }
+        ActionType::Custom(custom_action) => {
+            handle_custom_action(config, custom_action, wifi_interface, command_runner)
+        }
+        ActionType::System(system_action) => {
+            handle_system_action(config, system_action, wifi_interface, command_runner).await
+        }
         ActionType::Tailscale(mullvad_action) => {
-            handle_tailscale_action(mullvad_action, command_runner).await
+            handle_tailscale_action(config, mullvad_action, command_runner).await
+        }
+        ActionType::Dns(dns_action) => {
+            handle_dns_action(dns_action, &config.dns_presets, command_runner)
+        }
+        ActionType::Toggle(toggle_action) => {
+            handle_toggle_action(toggle_action, wifi_interface, command_runner)
+        }
+        ActionType::Tor(tor_action) => handle_tor_action(config, tor_action, command_runner),
+        ActionType::Tunnel(tunnel_action) => {
+            handle_tunnel_action(&config.tunnels, tunnel_action, command_runner)
+        }
+        ActionType::Mullvad(mullvad_action) => {
+            handle_mullvad_action(config, mullvad_action, command_runner)
+        }
+        ActionType::Firewall(firewall_action) => {
+            handle_firewall_action(firewall_action, &config.ufw_profiles, command_runner)
+        }
+        ActionType::KillSwitch(kill_switch_action) => {
+            handle_kill_switch_action(config, kill_switch_action, command_runner)
+        }
+        ActionType::Power(power_action) => handle_power_action(power_action, command_runner),
+        // Expanded into their steps by apply_actions, never nested
+        ActionType::Profile(name) => Err(format!("profile {name} cannot be a step").into()),
+        ActionType::Proxy(proxy_action) => {
+            handle_proxy_action(proxy_action, &config.proxy_profiles, command_runner)
+        }
+        ActionType::IpProfile(ip_profile_action) => {
+            handle_ip_profile_action(ip_profile_action, &config.ip_profiles, command_runner)
+        }
+        ActionType::Vpn(vpn_action) => handle_vpn_action(config, vpn_action, command_runner),
+        ActionType::Wireguard(wireguard_action) => {
+            handle_wireguard_action(wireguard_action, command_runner)
         }
         ActionType::Wifi(wifi_action) => {
-            handle_wifi_action(wifi_action, wifi_interface, command_runner).await
+            handle_wifi_action(config, wifi_action, wifi_interface, command_runner).await
         }
         ActionType::Bluetooth(bluetooth_action) => {
-            handle_bluetooth_action(bluetooth_action, connected_devices, command_runner)
+            handle_bluetooth_action(config, bluetooth_action, connected_devices, command_runner)
         }
+    }?;
+
+    if success && config.kill_switch {
+        follow_tunnel(config, action, command_runner)?;
     }
+    run_hooks(config, wifi_interface, action, success, command_runner);
+    Ok(success)
 }
 
 /// Sends a notification about the Wi-Fi connection.
-fn notify_connection(ssid: &str) -> Result<(), Box<dyn Error>> {
-    Notification::new()
-        .summary("Wi-Fi")
-        .body(&format!("Connected to {ssid}"))
-        .show()?;
-    Ok(())
+fn notify_connection(config: &Config, ssid: &str) {
+    notify(
+        config,
+        &tr("wifi"),
+        &tr_args("wifi-connected", &[("ssid", ssid)]),
+    );
 }
 
-/// Prints the Tailscale status if the command is installed (for debugging).
-fn debug_tailscale_status_if_installed() -> Result<(), Box<dyn Error>> {
-    #[cfg(debug_assertions)]
-    {
-        if is_command_installed("tailscale") {
-            Command::new("tailscale").arg("status").status()?;
+/// Sends a notification asking to press the WPS button of the router.
+fn notify_wps(config: &Config) {
+    notify(config, &tr("wifi"), &tr("wifi-press-wps"));
+}
+
+/// Sends a notification with the hotspot credentials and a QR code when available.
+fn notify_hotspot(config: &Config, ssid: &str, password: &str) {
+    let mut notification = new_notification(
+        config,
+        &tr("wifi-hotspot"),
+        &tr_args(
+            "wifi-credentials",
+            &[("ssid", ssid), ("password", password)],
+        ),
+    );
+    let qr_code = generate_wifi_qr_code(ssid, "WPA", password);
+    if let Some(qr_code) = &qr_code {
+        notification.image_path(&qr_code.path().to_string_lossy());
+    }
+    show_notification(config, &notification);
+    if let Some(qr_code) = qr_code {
+        qr_code.remove_once_displayed();
+    }
+}
+
+/// Sends a notification with the current network credentials and opens its QR code.
+fn notify_wifi_credentials(
+    config: &Config,
+    ssid: &str,
+    password: Option<&str>,
+) -> Result<(), Box<dyn Error>> {
+    let mut notification = new_notification(
+        config,
+        &tr("wifi"),
+        &tr_args(
+            "wifi-credentials",
+            &[
+                ("ssid", ssid),
+                (
+                    "password",
+                    &password.map_or_else(|| tr("wifi-no-password"), str::to_string),
+                ),
+            ],
+        ),
+    );
+    let security = if password.is_some() { "WPA" } else { "nopass" };
+    let qr_code = generate_wifi_qr_code(ssid, security, password.unwrap_or_default());
+    if let Some(qr_code) = &qr_code {
+        notification.image_path(&qr_code.path().to_string_lossy());
+        if is_command_installed("xdg-open") {
+            Command::new("xdg-open").arg(qr_code.path()).spawn()?;
         }
     }
+    show_notification(config, &notification);
+    if let Some(qr_code) = qr_code {
+        qr_code.remove_once_displayed();
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tailscale::{MullvadCountry, TailscalePeer};
+
+    #[test]
+    fn action_ids_have_no_whitespace_or_emoji() {
+        let actions = [
+            ActionType::Wifi(WifiAction::Network(
+                "✅ Coffee Shop 🍵\tWPA2\t▂▄▆█".to_string(),
+            )),
+            ActionType::Wifi(WifiAction::Rescan),
+            ActionType::Custom(CustomAction {
+                display: "🔒 Lock screen".to_string(),
+                cmd: "loginctl lock-session".to_string(),
+                prompt: None,
+            }),
+            ActionType::Tailscale(TailscaleAction::SelectMullvadCountry(MullvadCountry {
+                name: "United Kingdom".to_string(),
+                nodes: 3,
+                active: false,
+            })),
+            ActionType::Tailscale(TailscaleAction::PingPeer(TailscalePeer {
+                name: "work laptop".to_string(),
+                ip: "100.64.0.2".to_string(),
+                os: "linux".to_string(),
+                online: true,
+            })),
+            ActionType::Stale(Box::new(ActionType::Vpn(VpnAction::Connect(
+                "Office VPN".to_string(),
+            )))),
+            ActionType::Profile("Coffee shop".to_string()),
+            ActionType::System(SystemAction::StartHotspot),
+        ];
+        let ids: Vec<String> = actions.iter().map(action_key).collect();
+        assert_eq!(ids[0], "wifi:Coffee%20Shop%20%F0%9F%8D%B5");
+        assert_eq!(ids[3], "mullvad:United%20Kingdom");
+        assert_eq!(ids[5], "vpn:Office%20VPN");
+        for id in ids {
+            assert!(
+                id.split_once(':')
+                    .is_some_and(|(section, name)| { !section.is_empty() && !name.is_empty() }),
+                "{id} is not section:name"
+            );
+            assert!(
+                !id.chars()
+                    .any(|c| c.is_whitespace() || (!c.is_ascii() && !c.is_alphanumeric())),
+                "{id} has whitespace or emoji"
+            );
+        }
+    }
+}
@@ -0,0 +1,243 @@
+use crate::command::{read_output_lines, CommandRunner};
+use crate::connectivity::spawn_connectivity_check;
+use crate::i18n::tr_args;
+use crate::tailscale::get_flag;
+use crate::{format_entry, select_from_menu, Config};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+
+/// Represents actions on the standalone Mullvad VPN app.
+#[derive(Debug, Deserialize, Serialize)]
+pub enum MullvadAction {
+    Connect,
+    Disconnect(String),
+    SelectCountry(RelayCountry),
+}
+
+/// Country of the Mullvad relay list, opening a second menu with its cities.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct RelayCountry {
+    pub name: String,
+    pub code: String,
+    pub cities: Vec<RelayCity>,
+    pub active: bool,
+}
+
+/// City of the Mullvad relay list.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct RelayCity {
+    pub name: String,
+    pub code: String,
+}
+
+impl RelayCity {
+    /// Formats the city as an entry of its country menu.
+    fn to_entry(&self, country: &RelayCountry) -> String {
+        format_entry(
+            "vpn",
+            get_flag(&country.name),
+            &tr_args(
+                "mullvad-app-city",
+                &[("city", &self.name), ("code", &self.code)],
+            ),
+        )
+    }
+}
+
+/// Retrieves the Mullvad connection entry and one relay entry per country.
+pub fn get_mullvad_actions(
+    command_runner: &dyn CommandRunner,
+) -> Result<Vec<MullvadAction>, Box<dyn Error>> {
+    let relay = get_connected_relay(command_runner)?;
+    let output = command_runner.run_query("mullvad", &["relay", "list"])?;
+    let countries = if output.status.success() {
+        parse_relay_list(&read_output_lines(&output)?, relay.as_deref())
+    } else {
+        Vec::new()
+    };
+
+    let mut actions = vec![match relay {
+        Some(relay) => MullvadAction::Disconnect(relay),
+        None => MullvadAction::Connect,
+    }];
+    actions.extend(countries.into_iter().map(MullvadAction::SelectCountry));
+    Ok(actions)
+}
+
+/// Retrieves the hostname of the relay Mullvad is connected to, if any.
+fn get_connected_relay(
+    command_runner: &dyn CommandRunner,
+) -> Result<Option<String>, Box<dyn Error>> {
+    let output = command_runner.run_query("mullvad", &["status"])?;
+    let status = String::from_utf8_lossy(&output.stdout);
+    Ok(parse_connected_relay(&status))
+}
+
+/// Extracts the relay hostname from `mullvad status`, whose layout varies between versions.
+fn parse_connected_relay(status: &str) -> Option<String> {
+    if !status.trim_start().starts_with("Connected") {
+        return None;
+    }
+    Regex::new(r"\b[a-z]{2}-[a-z]{3}-(?:wg|ovpn|br)-\d+\b")
+        .ok()?
+        .find(status)
+        .map(|relay| relay.as_str().to_string())
+}
+
+/// Parses the countries and cities of `mullvad relay list`.
+///
+/// Countries are unindented, cities indented once and relays indented twice.
+fn parse_relay_list(lines: &[String], relay: Option<&str>) -> Vec<RelayCountry> {
+    let mut countries: Vec<RelayCountry> = Vec::new();
+    for line in lines {
+        if line.trim().is_empty() || line.starts_with("\t\t") {
+            continue;
+        }
+        let (name, code) = line
+            .split_once(" @ ")
+            .unwrap_or((line.as_str(), ""))
+            .0
+            .trim()
+            .rsplit_once(" (")
+            .map(|(name, code)| (name, code.trim_end_matches(')')))
+            .unwrap_or((line.trim(), ""));
+        if line.starts_with('\t') {
+            if let Some(country) = countries.last_mut() {
+                country.cities.push(RelayCity {
+                    name: name.to_string(),
+                    code: code.to_string(),
+                });
+            }
+        } else {
+            countries.push(RelayCountry {
+                name: name.to_string(),
+                code: code.to_string(),
+                cities: Vec::new(),
+                active: relay.is_some_and(|relay| relay.starts_with(&format!("{code}-"))),
+            });
+        }
+    }
+    countries
+}
+
+/// Handles a Mullvad action, asking for the city when a country is selected.
+pub fn handle_mullvad_action(
+    config: &Config,
+    action: &MullvadAction,
+    command_runner: &dyn CommandRunner,
+) -> Result<bool, Box<dyn Error>> {
+    match action {
+        MullvadAction::Connect => connect(config, None, command_runner),
+        MullvadAction::Disconnect(_) => {
+            let status = command_runner
+                .run_command("mullvad", &["disconnect"])?
+                .status;
+            Ok(status.success())
+        }
+        MullvadAction::SelectCountry(country) => {
+            let any_city = format_entry(
+                "vpn",
+                get_flag(&country.name),
+                &tr_args("mullvad-app-any-city", &[("country", &country.name)]),
+            );
+            let mut entries = vec![any_city.clone()];
+            entries.extend(country.cities.iter().map(|city| city.to_entry(country)));
+            let selection = select_from_menu(config, &entries)?;
+
+            let mut location = vec![country.code.as_str()];
+            if selection != any_city {
+                match country
+                    .cities
+                    .iter()
+                    .find(|city| city.to_entry(country) == selection)
+                {
+                    Some(city) => location.push(&city.code),
+                    None => return Ok(false),
+                }
+            }
+
+            let mut args = vec!["relay", "set", "location"];
+            args.extend(location);
+            if !command_runner
+                .run_command("mullvad", &args)?
+                .status
+                .success()
+            {
+                return Ok(false);
+            }
+            connect(config, Some(&country.name), command_runner)
+        }
+    }
+}
+
+/// Connects Mullvad, then checks the public IP matches the expected country.
+fn connect(
+    config: &Config,
+    country: Option<&str>,
+    command_runner: &dyn CommandRunner,
+) -> Result<bool, Box<dyn Error>> {
+    let status = command_runner
+        .run_command("mullvad", &["connect", "--wait"])?
+        .status;
+    if status.success() {
+        spawn_connectivity_check(config, country)?;
+    }
+    Ok(status.success())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{fixture, MockCommandRunner};
+
+    #[test]
+    fn parses_relay_list() {
+        let runner = MockCommandRunner::new()
+            .expect_fixture("mullvad", &["status"], "mullvad/status-connected.txt")
+            .expect_fixture("mullvad", &["relay", "list"], "mullvad/relay-list.txt");
+        let actions = get_mullvad_actions(&runner).unwrap();
+
+        assert!(
+            matches!(&actions[0], MullvadAction::Disconnect(relay) if relay == "se-got-wg-001")
+        );
+        let countries: Vec<_> = actions
+            .iter()
+            .filter_map(|action| match action {
+                MullvadAction::SelectCountry(country) => Some(country),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(countries.len(), 2);
+        assert_eq!(countries[0].code, "al");
+        assert!(!countries[0].active);
+        assert_eq!(countries[1].name, "Sweden");
+        assert!(countries[1].active);
+        assert_eq!(
+            countries[1].cities,
+            [
+                RelayCity {
+                    name: "Gothenburg".to_string(),
+                    code: "got".to_string(),
+                },
+                RelayCity {
+                    name: "Stockholm".to_string(),
+                    code: "sto".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_connected_relay_of_each_status_layout() {
+        assert_eq!(
+            parse_connected_relay(&fixture("mullvad/status-connected.txt")).as_deref(),
+            Some("se-got-wg-001")
+        );
+        assert_eq!(
+            parse_connected_relay("Connected to al-tia-wg-002 in Tirana, Albania\n").as_deref(),
+            Some("al-tia-wg-002")
+        );
+        assert_eq!(parse_connected_relay("Disconnected\n"), None);
+    }
+}
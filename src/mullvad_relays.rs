@@ -0,0 +1,335 @@
+use crate::connectivity::get_json;
+use crate::i18n::tr;
+use crate::tailscale::ExitNode;
+use crate::utils::write_state_file;
+use crate::{format_entry, select_from_menu, Config};
+use dirs::{cache_dir, state_dir};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeSet, HashMap};
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+use tracing::{debug, warn};
+
+/// Public list of the mullvad WireGuard relays, with their ownership and provider.
+const RELAYS_URL: &str = "https://api.mullvad.net/www/relays/wireguard/";
+
+/// Age after which the cached relay list is fetched again.
+const RELAYS_MAX_AGE: Duration = Duration::from_secs(24 * 3600);
+
+/// Delay before fetching the relay list again after a failure, e.g. while offline.
+const RELAYS_RETRY_DELAY: Duration = Duration::from_secs(3600);
+
+/// Ownership and hosting provider of a mullvad relay.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct RelayInfo {
+    pub owned: bool,
+    pub provider: String,
+}
+
+/// Filter of the mullvad exit nodes by ownership and hosting provider.
+#[derive(Debug, Default, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct MullvadFilter {
+    /// Keeps only the servers owned by mullvad, not rented.
+    #[serde(default)]
+    pub owned_only: bool,
+    /// Keeps only the servers of these providers, if any.
+    #[serde(default)]
+    pub providers: Vec<String>,
+}
+
+impl MullvadFilter {
+    /// Checks if the filter keeps every server.
+    pub fn is_empty(&self) -> bool {
+        !self.owned_only && self.providers.is_empty()
+    }
+
+    /// Checks if a relay passes the filter; relays of unknown ownership are kept.
+    fn allows(&self, info: Option<&RelayInfo>) -> bool {
+        let Some(info) = info else {
+            return true;
+        };
+        (!self.owned_only || info.owned)
+            && (self.providers.is_empty()
+                || self
+                    .providers
+                    .iter()
+                    .any(|provider| provider.eq_ignore_ascii_case(&info.provider)))
+    }
+
+    /// Describes the filter in the menu.
+    fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if self.owned_only {
+            parts.push(tr("mullvad-filter-owned"));
+        }
+        if !self.providers.is_empty() {
+            parts.push(self.providers.join(", "));
+        }
+        if parts.is_empty() {
+            tr("mullvad-filter-all")
+        } else {
+            parts.join(", ")
+        }
+    }
+}
+
+/// Gets the path of the cached relay list.
+fn get_relays_path() -> Option<PathBuf> {
+    Some(
+        cache_dir()?
+            .join("network-dmenu")
+            .join("mullvad-relays.json"),
+    )
+}
+
+/// Gets the path of the file marking the last failed fetch of the relay list.
+fn get_failure_path() -> Option<PathBuf> {
+    Some(
+        cache_dir()?
+            .join("network-dmenu")
+            .join("mullvad-relays.failed"),
+    )
+}
+
+/// Gets the path of the filter selected in the menu, overriding the configured one.
+fn get_filter_path() -> Option<PathBuf> {
+    Some(
+        state_dir()?
+            .join("network-dmenu")
+            .join("mullvad-filter.json"),
+    )
+}
+
+/// Returns the filter selected in the menu, or else the configured one.
+pub fn get_mullvad_filter(config: &Config) -> MullvadFilter {
+    get_filter_path()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_else(|| config.mullvad_filter.clone())
+}
+
+/// Remembers the filter selected in the menu.
+fn write_mullvad_filter(filter: &MullvadFilter) -> Result<(), Box<dyn Error>> {
+    write_state_file(get_filter_path(), &serde_json::to_string(filter)?)
+}
+
+/// Loads the cached relays by hostname, e.g. `se-got-wg-002`.
+pub fn load_relays() -> HashMap<String, RelayInfo> {
+    get_relays_path()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+/// Returns the age of a file, if it exists.
+fn get_file_age(path: Option<PathBuf>) -> Option<Duration> {
+    fs::metadata(path?)
+        .ok()?
+        .modified()
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+}
+
+/// Checks if the cached relay list is missing or outdated, and no fetch failed lately.
+fn are_relays_stale() -> bool {
+    get_file_age(get_relays_path()).is_none_or(|age| age > RELAYS_MAX_AGE)
+        && get_file_age(get_failure_path()).is_none_or(|age| age > RELAYS_RETRY_DELAY)
+}
+
+/// Parses the relay list of the mullvad API.
+fn parse_relays(relays: &Value) -> HashMap<String, RelayInfo> {
+    relays
+        .as_array()
+        .map(|relays| {
+            relays
+                .iter()
+                .filter_map(|relay| {
+                    let info = RelayInfo {
+                        owned: relay["owned"].as_bool()?,
+                        provider: relay["provider"].as_str()?.to_string(),
+                    };
+                    Some((relay["hostname"].as_str()?.to_string(), info))
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Fetches the relay list again if outdated, keeping the cached one on failure.
+pub async fn refresh_relays(config: &Config) {
+    update_relays(Duration::from_secs(config.connectivity_timeout)).await;
+}
+
+/// Fetches the relay list again in the background if outdated, the cached one being used
+/// meanwhile.
+pub fn spawn_relays_refresh(config: &Config) {
+    tokio::spawn(update_relays(Duration::from_secs(
+        config.connectivity_timeout,
+    )));
+}
+
+/// Fetches the relay list again if outdated, recording the failures to retry later.
+async fn update_relays(timeout: Duration) {
+    if !are_relays_stale() {
+        return;
+    }
+    let result = async {
+        let json = get_json(RELAYS_URL, timeout, None).await?;
+        let relays = parse_relays(&json);
+        if relays.is_empty() {
+            return Err("no relay in the mullvad API answer".into());
+        }
+        write_state_file(get_relays_path(), &serde_json::to_string(&relays)?)?;
+        debug!("cached {} mullvad relays", relays.len());
+        Ok::<(), Box<dyn Error>>(())
+    }
+    .await;
+    if let Err(err) = result {
+        warn!("failed to fetch the mullvad relays: {err}");
+        let _ = write_state_file(get_failure_path(), "");
+    } else if let Some(path) = get_failure_path() {
+        let _ = fs::remove_file(path);
+    }
+}
+
+/// Drops the mullvad exit nodes not passing the filter, keeping the active one.
+pub fn filter_exit_nodes(
+    nodes: Vec<ExitNode>,
+    filter: &MullvadFilter,
+    relays: &HashMap<String, RelayInfo>,
+) -> Vec<ExitNode> {
+    if filter.is_empty() {
+        return nodes;
+    }
+    nodes
+        .into_iter()
+        .filter(|node| {
+            let hostname = node.name.split('.').next().unwrap_or_default();
+            !node.is_mullvad() || node.active || filter.allows(relays.get(hostname))
+        })
+        .collect()
+}
+
+/// Describes the filter in force, shown in the entry opening the filter menu.
+pub fn describe_mullvad_filter(config: &Config) -> String {
+    get_mullvad_filter(config).describe()
+}
+
+/// Lets the user change the filter: show every server, toggle the owned servers or a
+/// provider.
+pub async fn select_mullvad_filter(config: &Config) -> Result<bool, Box<dyn Error>> {
+    refresh_relays(config).await;
+    let relays = load_relays();
+    let mut filter = get_mullvad_filter(config);
+
+    let providers: BTreeSet<&str> = relays.values().map(|info| info.provider.as_str()).collect();
+    let check = |selected: bool| if selected { "✅" } else { "❌" };
+    let all = format_entry(
+        "mullvad",
+        check(filter.is_empty()),
+        &tr("mullvad-filter-all"),
+    );
+    let owned = format_entry(
+        "mullvad",
+        check(filter.owned_only),
+        &tr("mullvad-filter-owned"),
+    );
+    let mut entries = vec![all.clone(), owned.clone()];
+    let provider_entries: Vec<(String, &str)> = providers
+        .into_iter()
+        .map(|provider| {
+            let selected = filter
+                .providers
+                .iter()
+                .any(|selected| selected.eq_ignore_ascii_case(provider));
+            (format_entry("mullvad", check(selected), provider), provider)
+        })
+        .collect();
+    entries.extend(provider_entries.iter().map(|(entry, _)| entry.clone()));
+
+    let selection = select_from_menu(config, &entries)?;
+    if selection == all {
+        filter = MullvadFilter::default();
+    } else if selection == owned {
+        filter.owned_only = !filter.owned_only;
+    } else if let Some((_, provider)) = provider_entries
+        .iter()
+        .find(|(entry, _)| *entry == selection)
+    {
+        let count = filter.providers.len();
+        filter
+            .providers
+            .retain(|selected| !selected.eq_ignore_ascii_case(provider));
+        if filter.providers.len() == count {
+            filter.providers.push(provider.to_string());
+        }
+    } else {
+        return Ok(false);
+    }
+    write_mullvad_filter(&filter)?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filters_nodes_by_ownership_and_provider() {
+        let relays = parse_relays(&serde_json::json!([
+            { "hostname": "se-got-wg-001", "owned": true, "provider": "31173" },
+            { "hostname": "se-got-wg-002", "owned": false, "provider": "M247" },
+            { "hostname": "ch-zrh-wg-001", "owned": false, "provider": "xtom" },
+        ]));
+        let node = |name: &str, active: bool| ExitNode {
+            ip: String::new(),
+            name: format!("{name}.mullvad.ts.net"),
+            country: String::new(),
+            city: String::new(),
+            active,
+        };
+        let nodes = || {
+            vec![
+                node("se-got-wg-001", false),
+                node("se-got-wg-002", false),
+                node("ch-zrh-wg-001", true),
+                node("us-nyc-wg-999", false),
+            ]
+        };
+        let names = |nodes: Vec<ExitNode>| -> Vec<String> {
+            nodes.into_iter().map(|node| node.name).collect()
+        };
+
+        let owned = MullvadFilter {
+            owned_only: true,
+            providers: Vec::new(),
+        };
+        assert_eq!(
+            names(filter_exit_nodes(nodes(), &owned, &relays)),
+            [
+                "se-got-wg-001.mullvad.ts.net",
+                "ch-zrh-wg-001.mullvad.ts.net",
+                "us-nyc-wg-999.mullvad.ts.net",
+            ]
+        );
+        let m247 = MullvadFilter {
+            owned_only: false,
+            providers: vec!["m247".to_string()],
+        };
+        assert_eq!(
+            names(filter_exit_nodes(nodes(), &m247, &relays)),
+            [
+                "se-got-wg-002.mullvad.ts.net",
+                "ch-zrh-wg-001.mullvad.ts.net",
+                "us-nyc-wg-999.mullvad.ts.net",
+            ]
+        );
+        assert_eq!(
+            filter_exit_nodes(nodes(), &MullvadFilter::default(), &relays).len(),
+            4
+        );
+    }
+}
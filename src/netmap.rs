@@ -0,0 +1,86 @@
+use crate::command::CommandRunner;
+use crate::i18n::{tr, tr_args};
+use crate::notifications::notify;
+use crate::tailscale::{get_tailscale_status, parse_peers};
+use crate::Config;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Tailnet changes notified by the daemon.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct NetmapWatchConfig {
+    #[serde(default)]
+    pub peer_online: bool,
+    #[serde(default)]
+    pub peer_offline: bool,
+    #[serde(default = "crate::config::default_true")]
+    pub exit_node_offline: bool,
+    #[serde(default)]
+    pub peers: Vec<String>,
+}
+
+impl Default for NetmapWatchConfig {
+    fn default() -> Self {
+        Self {
+            peer_online: false,
+            peer_offline: false,
+            exit_node_offline: true,
+            peers: Vec::new(),
+        }
+    }
+}
+
+/// Snapshot of the tailnet state compared between two daemon refreshes.
+#[derive(Debug, Default)]
+pub struct NetmapState {
+    peers: HashMap<String, bool>,
+    exit_node_online: Option<bool>,
+}
+
+/// Retrieves the online state of the peers and of the exit node in use.
+pub fn get_netmap_state(command_runner: &dyn CommandRunner) -> Option<NetmapState> {
+    let status = get_tailscale_status(command_runner)?;
+    Some(NetmapState {
+        peers: parse_peers(&status)
+            .into_iter()
+            .map(|peer| (peer.name, peer.online))
+            .collect(),
+        exit_node_online: status["ExitNodeStatus"]["Online"].as_bool(),
+    })
+}
+
+/// Notifies the changes between two tailnet states allowed by the watch configuration.
+pub fn notify_netmap_changes(config: &Config, previous: &NetmapState, current: &NetmapState) {
+    let watch = &config.netmap_watch;
+
+    let mut peers: Vec<_> = current
+        .peers
+        .iter()
+        .filter(|(name, _)| watch.peers.is_empty() || watch.peers.contains(name))
+        .filter(|(name, online)| previous.peers.get(*name).is_some_and(|was| was != *online))
+        .collect();
+    peers.sort();
+    for (name, online) in peers {
+        if *online && watch.peer_online {
+            notify(
+                config,
+                &tr("tailscale"),
+                &tr_args("tailscale-peer-online", &[("peer", name)]),
+            );
+        } else if !online && watch.peer_offline {
+            notify(
+                config,
+                &tr("tailscale"),
+                &tr_args("tailscale-peer-offline", &[("peer", name)]),
+            );
+        }
+    }
+
+    // Disabling the exit node removes its status, only a configured but unreachable one is lost
+    if watch.exit_node_offline
+        && previous.exit_node_online == Some(true)
+        && current.exit_node_online == Some(false)
+    {
+        notify(config, &tr("tailscale"), &tr("tailscale-exit-node-offline"));
+    }
+}
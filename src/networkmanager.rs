@@ -1,52 +1,122 @@
+use crate::columns::pad;
 use crate::command::{read_output_lines, CommandRunner};
-use crate::utils::{convert_network_strength, prompt_for_password};
-use crate::{notify_connection, parse_wifi_action, WifiAction};
-use regex::Regex;
+use crate::i18n::tr_args;
+use crate::networkmanager_dbus::{
+    activate_nm_dbus_wifi, forget_nm_dbus_network, is_nm_dbus_available, is_nm_dbus_known_network,
+    set_nm_dbus_wifi_password,
+};
+use crate::secrets::connect_with_password_retry;
+use crate::utils::{prompt_with_pinentry, write_private_file};
+use crate::vpn::VpnAction;
+use crate::wifi::{ConnectionOutcome, WifiNetwork};
+use crate::{
+    notify_connection, notify_wps, parse_wifi_action, prompt_from_menu, select_from_menu, Config,
+};
 use std::error::Error;
-use std::io::{BufRead, BufReader};
+use std::fs;
+use tracing::{debug, warn};
 
-/// Retrieves available Wi-Fi networks using NetworkManager.
+/// Retrieves a list of VPN and WireGuard connections with their activation state.
+pub fn get_nm_vpn_actions(
+    command_runner: &dyn CommandRunner,
+) -> Result<Vec<VpnAction>, Box<dyn Error>> {
+    let output = command_runner.run_query(
+        "nmcli",
+        &[
+            "--colors",
+            "no",
+            "-t",
+            "-f",
+            "NAME,TYPE,ACTIVE",
+            "connection",
+            "show",
+        ],
+    )?;
+
+    if !output.status.success() {
+        return Ok(Vec::new());
+    }
+
+    let actions = read_output_lines(&output)?
+        .into_iter()
+        .filter_map(|line| {
+            let mut parts = line.rsplitn(3, ':');
+            let active = parts.next()?;
+            let kind = parts.next()?;
+            let name = parts.next()?.replace("\\:", ":");
+            match (kind, active) {
+                ("vpn" | "wireguard", "yes") => Some(VpnAction::Disconnect(name)),
+                ("vpn" | "wireguard", _) => Some(VpnAction::Connect(name)),
+                _ => None,
+            }
+        })
+        .collect();
+    Ok(actions)
+}
+
+/// Activates or deactivates a NetworkManager VPN connection.
+pub fn set_nm_vpn_active(
+    name: &str,
+    active: bool,
+    command_runner: &dyn CommandRunner,
+) -> Result<bool, Box<dyn Error>> {
+    let command = if active { "up" } else { "down" };
+    let status = command_runner
+        .run_command("nmcli", &["connection", command, "id", name])?
+        .status;
+    Ok(status.success())
+}
+
+/// Retrieves the Wi-Fi networks seen by an interface using NetworkManager.
 pub fn get_nm_wifi_networks(
+    interface: &str,
     command_runner: &dyn CommandRunner,
-) -> Result<Vec<WifiAction>, Box<dyn Error>> {
-    let mut actions = Vec::new();
+) -> Result<Vec<WifiNetwork>, Box<dyn Error>> {
+    let mut networks = Vec::new();
 
-    if let Some(lines) = fetch_wifi_lines(command_runner)? {
+    if let Some(lines) = fetch_wifi_lines(interface, command_runner)? {
         let has_in_use = lines.iter().any(|line| line.starts_with('*'));
 
         if !has_in_use {
-            let rescan_output = command_runner.run_command(
+            let rescan_output = command_runner.run_query(
                 "nmcli",
-                &["--colors", "no", "dev", "wifi", "list", "--rescan", "auto"],
+                &[
+                    "--colors", "no", "dev", "wifi", "list", "ifname", interface, "--rescan",
+                    "auto",
+                ],
             )?;
 
             if rescan_output.status.success() {
-                if let Some(rescan_lines) = fetch_wifi_lines(command_runner)? {
-                    parse_wifi_lines(&mut actions, rescan_lines);
+                if let Some(rescan_lines) = fetch_wifi_lines(interface, command_runner)? {
+                    parse_wifi_lines(&mut networks, rescan_lines);
                 }
             }
         } else {
-            parse_wifi_lines(&mut actions, lines);
+            parse_wifi_lines(&mut networks, lines);
         }
     }
 
-    Ok(actions)
+    Ok(networks)
 }
 
 /// Fetches raw Wi-Fi network data from NetworkManager.
 fn fetch_wifi_lines(
+    interface: &str,
     command_runner: &dyn CommandRunner,
 ) -> Result<Option<Vec<String>>, Box<dyn Error>> {
-    let output = command_runner.run_command(
+    let output = command_runner.run_query(
         "nmcli",
         &[
             "--colors",
             "no",
             "-t",
             "-f",
-            "IN-USE,SSID,BARS,SECURITY",
+            "IN-USE,SSID,SIGNAL,SECURITY",
             "device",
             "wifi",
+            "list",
+            "ifname",
+            interface,
         ],
     )?;
 
@@ -59,23 +129,21 @@ fn fetch_wifi_lines(
 }
 
 /// Parses the raw Wi-Fi network data into a structured format.
-fn parse_wifi_lines(actions: &mut Vec<WifiAction>, wifi_lines: Vec<String>) {
+fn parse_wifi_lines(networks: &mut Vec<WifiNetwork>, wifi_lines: Vec<String>) {
     wifi_lines.into_iter().for_each(|line| {
-        let parts: Vec<&str> = line.split(':').collect();
+        let parts = split_nmcli_fields(&line);
         if parts.len() == 4 {
             let in_use = parts[0].trim();
             let ssid = parts[1].trim();
             let signal = parts[2].trim();
             let security = parts[3].trim();
             if !ssid.is_empty() {
-                let display = format!(
-                    "{} {:<25}\t{:<11}\t{}",
-                    if in_use == "*" { "✅" } else { "📶" },
-                    ssid,
-                    security.to_uppercase(),
-                    convert_network_strength(signal),
-                );
-                actions.push(WifiAction::Network(display));
+                networks.push(WifiNetwork {
+                    ssid: ssid.to_string(),
+                    security: security.to_uppercase(),
+                    signal: signal.parse().unwrap_or_default(),
+                    connected: in_use == "*",
+                });
             }
         }
     });
@@ -83,44 +151,504 @@ fn parse_wifi_lines(actions: &mut Vec<WifiAction>, wifi_lines: Vec<String>) {
 
 /// Connects to a Wi-Fi network using NetworkManager.
 pub fn connect_to_nm_wifi(
+    config: &Config,
+    interface: &str,
     action: &str,
     command_runner: &dyn CommandRunner,
 ) -> Result<bool, Box<dyn Error>> {
     let (ssid, security) = parse_wifi_action(action)?;
-    #[cfg(debug_assertions)]
-    println!("Connecting to Wi-Fi network: {ssid} with security {security}");
+    debug!("Connecting {interface} to Wi-Fi network: {ssid} with security {security}");
+
+    connect_to_nm_network(config, interface, ssid, security, None, command_runner)
+}
+
+/// Connects to a Wi-Fi network over D-Bus, for systems without nmcli, prompting for the
+/// password of unknown secured networks.
+pub fn connect_to_nm_dbus_wifi(
+    config: &Config,
+    interface: &str,
+    action: &str,
+) -> Result<bool, Box<dyn Error>> {
+    let (ssid, security) = parse_wifi_action(action)?;
+    let is_known = is_nm_dbus_known_network(ssid)?;
+    let attempt = |password: Option<&str>| {
+        let outcome = activate_nm_dbus_wifi(interface, ssid, security, password)?;
+        if outcome == ConnectionOutcome::Connected {
+            notify_connection(config, ssid);
+        }
+        Ok(outcome)
+    };
+
+    if security.contains("802.1X") && !is_known {
+        Err("joining a new WPA-Enterprise network needs nmcli".into())
+    } else if security.contains("802.1X") {
+        // Enterprise credentials are not a password to prompt again
+        Ok(attempt(None)? == ConnectionOutcome::Connected)
+    } else {
+        connect_with_password_retry(
+            config,
+            ssid,
+            is_known || security.is_empty(),
+            attempt,
+            || {
+                let result = if is_known {
+                    set_nm_dbus_wifi_password(ssid, "")
+                } else {
+                    forget_nm_dbus_network(ssid)
+                };
+                if let Err(err) = result {
+                    warn!("failed to drop the rejected password of {ssid}: {err}");
+                }
+            },
+        )
+    }
+}
+
+/// Connects to a Wi-Fi network, optionally pinned to a BSSID, prompting for the
+/// password of unknown secured networks.
+fn connect_to_nm_network(
+    config: &Config,
+    interface: &str,
+    ssid: &str,
+    security: &str,
+    bssid: Option<&str>,
+    command_runner: &dyn CommandRunner,
+) -> Result<bool, Box<dyn Error>> {
+    let is_known = if is_nm_dbus_available() {
+        is_nm_dbus_known_network(ssid)?
+    } else {
+        is_known_network(ssid, command_runner)?
+    };
 
-    if is_known_network(ssid, command_runner)? || security.is_empty() {
-        attempt_connection(ssid, None, command_runner)
+    if security.contains("802.1X") && !is_known {
+        connect_to_nm_enterprise(config, interface, ssid, command_runner)
+    } else if security.contains("802.1X") {
+        // Enterprise credentials are not a password to prompt again
+        Ok(
+            attempt_connection(config, interface, ssid, bssid, None, command_runner)?
+                == ConnectionOutcome::Connected,
+        )
     } else {
-        let password = prompt_for_password(ssid)?;
-        attempt_connection(ssid, Some(password), command_runner)
+        connect_with_password_retry(
+            config,
+            ssid,
+            is_known || security.is_empty(),
+            |password| attempt_connection(config, interface, ssid, bssid, password, command_runner),
+            || {
+                // The rejected password is kept by the profile, which is only deleted when
+                // created by the failed attempt. It is found by its SSID, as its name may
+                // differ
+                let result = find_nm_wifi_uuid(ssid, command_runner).and_then(|uuid| {
+                    let uuid = uuid.ok_or_else(|| format!("no saved connection for {ssid}"))?;
+                    if is_known {
+                        clear_nm_wifi_password(&uuid, command_runner)
+                    } else {
+                        delete_nm_connection(&uuid, command_runner)
+                    }
+                });
+                if let Err(err) = result {
+                    warn!("failed to drop the rejected password of {ssid}: {err}");
+                }
+            },
+        )
+    }
+}
+
+/// Connects to a WPA-Enterprise network, creating a PEAP/MSCHAPv2 profile from the
+/// prompted identity and password.
+fn connect_to_nm_enterprise(
+    config: &Config,
+    interface: &str,
+    ssid: &str,
+    command_runner: &dyn CommandRunner,
+) -> Result<bool, Box<dyn Error>> {
+    let identity = prompt_from_menu(config, &tr_args("prompt-identity", &[("ssid", ssid)]))?;
+    if identity.is_empty() {
+        return Ok(false);
+    }
+    let password =
+        prompt_with_pinentry(config, &tr_args("prompt-wifi-password", &[("ssid", ssid)]))?;
+
+    let status = command_runner
+        .run_command(
+            "nmcli",
+            &[
+                "connection",
+                "add",
+                "type",
+                "wifi",
+                "con-name",
+                ssid,
+                "ssid",
+                ssid,
+                "wifi-sec.key-mgmt",
+                "wpa-eap",
+                "802-1x.eap",
+                "peap",
+                "802-1x.phase2-auth",
+                "mschapv2",
+                "802-1x.identity",
+                &identity,
+            ],
+        )?
+        .status;
+    if !status.success() {
+        return Ok(false);
+    }
+
+    // Given in a file rather than on the command line, where `ps` shows it, and saved to
+    // the profile by NetworkManager once used
+    let passwd_file = write_private_file(
+        "network-dmenu-wifi-secrets",
+        format!("802-1x.password:{password}\n").as_bytes(),
+    )?;
+    let output = command_runner.run_command(
+        "nmcli",
+        &[
+            "connection",
+            "up",
+            "id",
+            ssid,
+            "ifname",
+            interface,
+            "passwd-file",
+            &passwd_file.to_string_lossy(),
+        ],
+    );
+    let _ = fs::remove_file(&passwd_file);
+    if output?.status.success() {
+        notify_connection(config, ssid);
+        Ok(true)
+    } else {
+        command_runner.run_command("nmcli", &["connection", "delete", "id", ssid])?;
+        Ok(false)
+    }
+}
+
+/// Clears the saved password of a Wi-Fi connection, keeping its other settings.
+fn clear_nm_wifi_password(
+    uuid: &str,
+    command_runner: &dyn CommandRunner,
+) -> Result<bool, Box<dyn Error>> {
+    let status = command_runner
+        .run_command(
+            "nmcli",
+            &["connection", "modify", "uuid", uuid, "wifi-sec.psk", ""],
+        )?
+        .status;
+    Ok(status.success())
+}
+
+/// Deletes a connection by its UUID.
+fn delete_nm_connection(
+    uuid: &str,
+    command_runner: &dyn CommandRunner,
+) -> Result<bool, Box<dyn Error>> {
+    let status = command_runner
+        .run_command("nmcli", &["connection", "delete", "uuid", uuid])?
+        .status;
+    Ok(status.success())
+}
+
+/// Finds the UUID of the saved Wi-Fi connection of an SSID, whose name may differ from it.
+fn find_nm_wifi_uuid(
+    ssid: &str,
+    command_runner: &dyn CommandRunner,
+) -> Result<Option<String>, Box<dyn Error>> {
+    let output = command_runner.run_query(
+        "nmcli",
+        &[
+            "--colors",
+            "no",
+            "-t",
+            "-f",
+            "UUID,TYPE",
+            "connection",
+            "show",
+        ],
+    )?;
+    if !output.status.success() {
+        return Ok(None);
+    }
+
+    for line in read_output_lines(&output)? {
+        let Some((uuid, "802-11-wireless")) = line.split_once(':') else {
+            continue;
+        };
+        let output = command_runner.run_query(
+            "nmcli",
+            &[
+                "--colors",
+                "no",
+                "-g",
+                "802-11-wireless.ssid",
+                "connection",
+                "show",
+                "uuid",
+                uuid,
+            ],
+        )?;
+        if read_output_lines(&output)?.first().map(String::as_str) == Some(ssid) {
+            return Ok(Some(uuid.to_string()));
+        }
     }
+    Ok(None)
 }
 
-/// Attempts to connect to a Wi-Fi network, optionally using a password.
+/// Attempts to connect to a Wi-Fi network, optionally pinned to a BSSID and using a password.
 fn attempt_connection(
+    config: &Config,
+    interface: &str,
     ssid: &str,
-    password: Option<String>,
+    bssid: Option<&str>,
+    password: Option<&str>,
+    command_runner: &dyn CommandRunner,
+) -> Result<ConnectionOutcome, Box<dyn Error>> {
+    let mut command = vec!["device", "wifi", "connect", ssid, "ifname", interface];
+    if let Some(bssid) = bssid {
+        command.extend(["bssid", bssid]);
+    }
+    if let Some(pwd) = password {
+        command.extend(["password", pwd]);
+    }
+
+    let outcome = ConnectionOutcome::from_output(&command_runner.run_command("nmcli", &command)?);
+
+    if outcome == ConnectionOutcome::Connected {
+        notify_connection(config, ssid);
+    } else {
+        warn!("Failed to connect to Wi-Fi network: {ssid}");
+    }
+    Ok(outcome)
+}
+
+/// Wi-Fi access point as listed by `nmcli device wifi list`.
+#[derive(Debug)]
+struct AccessPoint {
+    in_use: bool,
+    bssid: String,
+    ssid: String,
+    channel: String,
+    band: &'static str,
+    signal: String,
+    security: String,
+}
+
+/// Splits an `nmcli -t` line into its fields, unescaping `\:`.
+fn split_nmcli_fields(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => field.extend(chars.next()),
+            ':' => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+/// Returns the band of a frequency such as `5180 MHz`.
+fn frequency_band(frequency: &str) -> &'static str {
+    match frequency
+        .split_whitespace()
+        .next()
+        .and_then(|mhz| mhz.parse::<u32>().ok())
+    {
+        Some(..=3000) => "2.4 GHz",
+        Some(3001..=5924) => "5 GHz",
+        Some(_) => "6 GHz",
+        None => "",
+    }
+}
+
+/// Retrieves the individual access points visible to NetworkManager.
+fn get_nm_access_points(
+    interface: &str,
+    command_runner: &dyn CommandRunner,
+) -> Result<Vec<AccessPoint>, Box<dyn Error>> {
+    let output = command_runner.run_query(
+        "nmcli",
+        &[
+            "--colors",
+            "no",
+            "-t",
+            "-f",
+            "IN-USE,BSSID,SSID,CHAN,FREQ,SIGNAL,SECURITY",
+            "device",
+            "wifi",
+            "list",
+            "ifname",
+            interface,
+        ],
+    )?;
+
+    if !output.status.success() {
+        return Ok(Vec::new());
+    }
+
+    let access_points = read_output_lines(&output)?
+        .iter()
+        .filter_map(|line| {
+            let fields = split_nmcli_fields(line);
+            let [in_use, bssid, ssid, channel, frequency, signal, security] = fields.as_slice()
+            else {
+                return None;
+            };
+            (!ssid.is_empty()).then(|| AccessPoint {
+                in_use: in_use == "*",
+                bssid: bssid.clone(),
+                ssid: ssid.clone(),
+                channel: channel.clone(),
+                band: frequency_band(frequency),
+                signal: signal.clone(),
+                security: security.to_uppercase(),
+            })
+        })
+        .collect();
+    Ok(access_points)
+}
+
+/// Lists the individual access points and connects pinned to the selected BSSID.
+pub fn connect_to_nm_access_point(
+    config: &Config,
+    interface: &str,
     command_runner: &dyn CommandRunner,
 ) -> Result<bool, Box<dyn Error>> {
-    let command = match password {
-        Some(ref pwd) => vec!["device", "wifi", "connect", ssid, "password", pwd],
-        None => vec!["device", "wifi", "connect", ssid],
+    let access_points = get_nm_access_points(interface, command_runner)?;
+    let entries: Vec<String> = access_points
+        .iter()
+        .map(|ap| {
+            format!(
+                "{} {} {} ch{:<4} {:<8} {:>3}%",
+                if ap.in_use { "✅" } else { "📶" },
+                pad(&ap.ssid, 25),
+                ap.bssid,
+                ap.channel,
+                ap.band,
+                ap.signal
+            )
+        })
+        .collect();
+
+    let selection = select_from_menu(config, &entries)?;
+    let Some(ap) = entries
+        .iter()
+        .position(|entry| *entry == selection)
+        .map(|index| &access_points[index])
+    else {
+        return Ok(false);
     };
 
+    connect_to_nm_network(
+        config,
+        interface,
+        &ap.ssid,
+        &ap.security,
+        Some(&ap.bssid),
+        command_runner,
+    )
+}
+
+/// Connects to a hidden Wi-Fi network, prompting for its password.
+pub fn connect_to_nm_hidden(
+    config: &Config,
+    interface: &str,
+    ssid: &str,
+    command_runner: &dyn CommandRunner,
+) -> Result<bool, Box<dyn Error>> {
+    let password =
+        prompt_with_pinentry(config, &tr_args("prompt-wifi-password", &[("ssid", ssid)]))?;
+    let mut command = vec![
+        "device", "wifi", "connect", ssid, "ifname", interface, "hidden", "yes",
+    ];
+    if !password.is_empty() {
+        command.extend(["password", &password]);
+    }
+
     let status = command_runner.run_command("nmcli", &command)?.status;
+    if status.success() {
+        notify_connection(config, ssid);
+    }
+    Ok(status.success())
+}
+
+/// Connects to a Wi-Fi network with WPS push button, creating a profile NetworkManager
+/// activates once the router button is pressed.
+pub fn connect_to_nm_wps(
+    config: &Config,
+    ssid: &str,
+    interface: &str,
+    command_runner: &dyn CommandRunner,
+) -> Result<bool, Box<dyn Error>> {
+    let status = command_runner
+        .run_command(
+            "nmcli",
+            &[
+                "connection",
+                "add",
+                "type",
+                "wifi",
+                "ifname",
+                interface,
+                "con-name",
+                ssid,
+                "ssid",
+                ssid,
+                "wifi-sec.key-mgmt",
+                "wpa-psk",
+                "wifi-sec.wps-method",
+                "pbc",
+            ],
+        )?
+        .status;
+    if !status.success() {
+        return Ok(false);
+    }
+
+    notify_wps(config);
+    let status = command_runner
+        .run_command("nmcli", &["connection", "up", "id", ssid])?
+        .status;
 
     if status.success() {
-        notify_connection(ssid)?;
+        notify_connection(config, ssid);
         Ok(true)
     } else {
-        #[cfg(debug_assertions)]
-        eprintln!("Failed to connect to Wi-Fi network: {ssid}");
+        command_runner.run_command("nmcli", &["connection", "delete", "id", ssid])?;
         Ok(false)
     }
 }
 
+/// Requests a Wi-Fi scan on an interface, waiting up to `timeout` seconds for the results.
+pub fn rescan_nm_wifi(
+    interface: &str,
+    timeout: u64,
+    command_runner: &dyn CommandRunner,
+) -> Result<bool, Box<dyn Error>> {
+    let status = command_runner
+        .run_command(
+            "nmcli",
+            &[
+                "--wait",
+                &timeout.to_string(),
+                "--colors",
+                "no",
+                "dev",
+                "wifi",
+                "list",
+                "ifname",
+                interface,
+                "--rescan",
+                "yes",
+            ],
+        )?
+        .status;
+    Ok(status.success())
+}
+
 /// Disconnects from a Wi-Fi network.
 pub fn disconnect_nm_wifi(
     interface: &str,
@@ -132,12 +660,386 @@ pub fn disconnect_nm_wifi(
     Ok(status.success())
 }
 
+/// Retrieves the names of saved Wi-Fi connections from NetworkManager.
+pub fn get_nm_saved_networks(
+    command_runner: &dyn CommandRunner,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let output = command_runner.run_query(
+        "nmcli",
+        &[
+            "--colors",
+            "no",
+            "-t",
+            "-f",
+            "NAME,TYPE",
+            "connection",
+            "show",
+        ],
+    )?;
+
+    if !output.status.success() {
+        return Ok(Vec::new());
+    }
+
+    let networks = read_output_lines(&output)?
+        .into_iter()
+        .filter_map(|line| {
+            let (name, kind) = line.rsplit_once(':')?;
+            (kind == "802-11-wireless").then(|| name.replace("\\:", ":"))
+        })
+        .collect();
+    Ok(networks)
+}
+
+/// Retrieves the saved Wi-Fi connections from NetworkManager along with their SSID, which
+/// their name may differ from.
+pub fn get_nm_saved_network_ssids(
+    command_runner: &dyn CommandRunner,
+) -> Result<Vec<(String, String)>, Box<dyn Error>> {
+    get_nm_saved_networks(command_runner)?
+        .into_iter()
+        .map(|name| {
+            let ssid = get_nm_connection_setting(&name, "802-11-wireless.ssid", command_runner)?;
+            Ok((name, ssid))
+        })
+        .collect()
+}
+
+/// Activates a saved Wi-Fi connection on an interface.
+pub fn activate_nm_wifi_connection(
+    config: &Config,
+    name: &str,
+    interface: &str,
+    command_runner: &dyn CommandRunner,
+) -> Result<bool, Box<dyn Error>> {
+    let status = command_runner
+        .run_command(
+            "nmcli",
+            &["connection", "up", "id", name, "ifname", interface],
+        )?
+        .status;
+    if status.success() {
+        notify_connection(config, name);
+    }
+    Ok(status.success())
+}
+
+/// Retrieves the name of the active Wi-Fi connection from NetworkManager.
+pub fn get_nm_active_wifi_connection(
+    command_runner: &dyn CommandRunner,
+) -> Result<Option<String>, Box<dyn Error>> {
+    let output = command_runner.run_query(
+        "nmcli",
+        &[
+            "--colors",
+            "no",
+            "-t",
+            "-f",
+            "NAME,TYPE",
+            "connection",
+            "show",
+            "--active",
+        ],
+    )?;
+
+    Ok(read_output_lines(&output)?.into_iter().find_map(|line| {
+        let (name, kind) = line.rsplit_once(':')?;
+        (kind == "802-11-wireless").then(|| name.replace("\\:", ":"))
+    }))
+}
+
+/// Connection types carrying the traffic, unlike VPNs, tunnels, bridges or loopback.
+const PRIMARY_CONNECTION_TYPES: &[&str] =
+    &["802-3-ethernet", "802-11-wireless", "gsm", "bluetooth"];
+
+/// Retrieves the name of the first active connection of a physical device.
+pub fn get_nm_primary_connection(
+    command_runner: &dyn CommandRunner,
+) -> Result<Option<String>, Box<dyn Error>> {
+    let output = command_runner.run_query(
+        "nmcli",
+        &[
+            "--colors",
+            "no",
+            "-t",
+            "-f",
+            "NAME,TYPE",
+            "connection",
+            "show",
+            "--active",
+        ],
+    )?;
+
+    Ok(read_output_lines(&output)?.into_iter().find_map(|line| {
+        let (name, kind) = line.rsplit_once(':')?;
+        PRIMARY_CONNECTION_TYPES
+            .contains(&kind)
+            .then(|| name.replace("\\:", ":"))
+    }))
+}
+
+/// Reads a setting of a connection, such as `ipv6.method`.
+pub fn get_nm_connection_setting(
+    name: &str,
+    setting: &str,
+    command_runner: &dyn CommandRunner,
+) -> Result<String, Box<dyn Error>> {
+    let output = command_runner.run_query(
+        "nmcli",
+        &[
+            "--colors",
+            "no",
+            "-g",
+            setting,
+            "connection",
+            "show",
+            "id",
+            name,
+        ],
+    )?;
+    Ok(read_output_lines(&output)?
+        .into_iter()
+        .next()
+        .unwrap_or_default())
+}
+
+/// Modifies settings of a connection, without applying them.
+fn save_nm_connection_settings(
+    name: &str,
+    settings: &[(&str, &str)],
+    command_runner: &dyn CommandRunner,
+) -> Result<bool, Box<dyn Error>> {
+    let mut args = vec!["connection", "modify", "id", name];
+    for (setting, value) in settings {
+        args.extend([*setting, *value]);
+    }
+    Ok(command_runner.run_command("nmcli", &args)?.status.success())
+}
+
+/// Modifies settings of a connection and activates it again to apply them.
+pub fn modify_nm_connection(
+    name: &str,
+    settings: &[(&str, &str)],
+    command_runner: &dyn CommandRunner,
+) -> Result<bool, Box<dyn Error>> {
+    if !save_nm_connection_settings(name, settings, command_runner)? {
+        return Ok(false);
+    }
+    let status = command_runner
+        .run_command("nmcli", &["connection", "up", "id", name])?
+        .status;
+    Ok(status.success())
+}
+
+/// Enables or disables IPv6 on a connection, activating it again.
+pub fn set_nm_ipv6(
+    name: &str,
+    enable: bool,
+    command_runner: &dyn CommandRunner,
+) -> Result<bool, Box<dyn Error>> {
+    modify_nm_connection(
+        name,
+        &[("ipv6.method", if enable { "auto" } else { "disabled" })],
+        command_runner,
+    )
+}
+
+/// Marks a connection as metered or not, reapplying it to its device without dropping
+/// the link.
+pub fn set_nm_metered(
+    name: &str,
+    metered: bool,
+    command_runner: &dyn CommandRunner,
+) -> Result<bool, Box<dyn Error>> {
+    let settings = [("connection.metered", if metered { "yes" } else { "no" })];
+    if !save_nm_connection_settings(name, &settings, command_runner)? {
+        return Ok(false);
+    }
+    let device = get_nm_connection_setting(name, "GENERAL.DEVICES", command_runner)?;
+    if device.is_empty() {
+        // Inactive, the setting applies on the next activation
+        return Ok(true);
+    }
+    let status = command_runner
+        .run_command("nmcli", &["device", "reapply", &device])?
+        .status;
+    Ok(status.success())
+}
+
+/// Retrieves the SSID and pre-shared key of a saved Wi-Fi connection.
+pub fn get_nm_wifi_credentials(
+    name: &str,
+    command_runner: &dyn CommandRunner,
+) -> Result<(String, Option<String>), Box<dyn Error>> {
+    let output = command_runner.run_query(
+        "nmcli",
+        &[
+            "-s",
+            "-g",
+            "802-11-wireless.ssid,802-11-wireless-security.psk",
+            "connection",
+            "show",
+            name,
+        ],
+    )?;
+
+    if !output.status.success() {
+        return Err(format!("Failed to read credentials of {name}").into());
+    }
+
+    let mut lines = read_output_lines(&output)?.into_iter();
+    let ssid = lines.next().unwrap_or_else(|| name.to_string());
+    let password = lines.next().filter(|psk| !psk.is_empty());
+    Ok((ssid, password))
+}
+
+/// Deletes a saved Wi-Fi connection from NetworkManager.
+pub fn forget_nm_network(
+    name: &str,
+    command_runner: &dyn CommandRunner,
+) -> Result<bool, Box<dyn Error>> {
+    let status = command_runner
+        .run_command("nmcli", &["connection", "delete", "id", name])?
+        .status;
+    Ok(status.success())
+}
+
+/// Retrieves the autoconnect flag and priority of a saved connection.
+pub fn get_nm_autoconnect(
+    name: &str,
+    command_runner: &dyn CommandRunner,
+) -> Result<(bool, i32), Box<dyn Error>> {
+    let output = command_runner.run_query(
+        "nmcli",
+        &[
+            "-g",
+            "connection.autoconnect,connection.autoconnect-priority",
+            "connection",
+            "show",
+            "id",
+            name,
+        ],
+    )?;
+
+    if !output.status.success() {
+        return Err(format!("Failed to read connection {name}").into());
+    }
+
+    let lines = read_output_lines(&output)?;
+    let autoconnect = lines.first().is_some_and(|value| value == "yes");
+    let priority = lines
+        .get(1)
+        .and_then(|value| value.parse().ok())
+        .unwrap_or_default();
+    Ok((autoconnect, priority))
+}
+
+/// Enables or disables automatic connection to a saved connection.
+pub fn set_nm_autoconnect(
+    name: &str,
+    autoconnect: bool,
+    command_runner: &dyn CommandRunner,
+) -> Result<bool, Box<dyn Error>> {
+    let status = command_runner
+        .run_command(
+            "nmcli",
+            &[
+                "connection",
+                "modify",
+                "id",
+                name,
+                "connection.autoconnect",
+                if autoconnect { "yes" } else { "no" },
+            ],
+        )?
+        .status;
+    Ok(status.success())
+}
+
+/// Sets the autoconnect priority of a saved connection.
+pub fn set_nm_autoconnect_priority(
+    name: &str,
+    priority: i32,
+    command_runner: &dyn CommandRunner,
+) -> Result<bool, Box<dyn Error>> {
+    let status = command_runner
+        .run_command(
+            "nmcli",
+            &[
+                "connection",
+                "modify",
+                "id",
+                name,
+                "connection.autoconnect-priority",
+                &priority.to_string(),
+            ],
+        )?
+        .status;
+    Ok(status.success())
+}
+
+/// Starts a Wi-Fi hotspot on the given interface using NetworkManager.
+pub fn start_nm_hotspot(
+    interface: &str,
+    ssid: &str,
+    password: &str,
+    command_runner: &dyn CommandRunner,
+) -> Result<bool, Box<dyn Error>> {
+    let status = command_runner
+        .run_command(
+            "nmcli",
+            &[
+                "device", "wifi", "hotspot", "ifname", interface, "ssid", ssid, "password",
+                password,
+            ],
+        )?
+        .status;
+    Ok(status.success())
+}
+
+/// Stops the NetworkManager Wi-Fi hotspot.
+pub fn stop_nm_hotspot(command_runner: &dyn CommandRunner) -> Result<bool, Box<dyn Error>> {
+    let status = command_runner
+        .run_command("nmcli", &["connection", "down", "Hotspot"])?
+        .status;
+    Ok(status.success())
+}
+
+/// Checks if the NetworkManager Wi-Fi hotspot is currently active.
+pub fn is_nm_hotspot_active(command_runner: &dyn CommandRunner) -> Result<bool, Box<dyn Error>> {
+    let output = command_runner.run_query(
+        "nmcli",
+        &[
+            "--colors",
+            "no",
+            "-t",
+            "-f",
+            "NAME",
+            "connection",
+            "show",
+            "--active",
+        ],
+    )?;
+    Ok(read_output_lines(&output)?
+        .iter()
+        .any(|name| name == "Hotspot"))
+}
+
+/// Checks if the NetworkManager daemon is running and answering.
+pub fn is_nm_running(command_runner: &dyn CommandRunner) -> bool {
+    command_runner
+        .run_query("nmcli", &["-t", "-f", "RUNNING", "general"])
+        .is_ok_and(|output| {
+            output.status.success() && String::from_utf8_lossy(&output.stdout).trim() == "running"
+        })
+}
+
 /// Checks if NetworkManager is currently connected to a network.
 pub fn is_nm_connected(
     command_runner: &dyn CommandRunner,
     interface: &str,
 ) -> Result<bool, Box<dyn Error>> {
-    let output = command_runner.run_command(
+    let output = command_runner.run_query(
         "nmcli",
         &[
             "--colors",
@@ -159,33 +1061,243 @@ pub fn is_nm_connected(
     Ok(false)
 }
 
-/// Checks if a Wi-Fi network is known (i.e., previously connected).
+/// Checks if a Wi-Fi network is known (i.e., previously connected), by the SSID of the
+/// saved connections rather than their name.
 pub fn is_known_network(
     ssid: &str,
     command_runner: &dyn CommandRunner,
 ) -> Result<bool, Box<dyn Error>> {
-    // Run the `nmcli connection show` command
-    let output = command_runner.run_command("nmcli", &["--colors", "no", "connection", "show"])?;
+    Ok(find_nm_wifi_uuid(ssid, command_runner)?.is_some())
+}
 
-    // Check if the command executed successfully
-    if output.status.success() {
-        // Create a buffered reader for the command output
-        let reader = BufReader::new(output.stdout.as_slice());
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockCommandRunner;
 
-        // Create a regex pattern to match the SSID exactly
-        let ssid_pattern = format!(r"^\s*{}\s+", regex::escape(ssid));
-        let re = Regex::new(&ssid_pattern)?;
+    const WIFI_LIST: &[&str] = &[
+        "--colors",
+        "no",
+        "-t",
+        "-f",
+        "IN-USE,SSID,SIGNAL,SECURITY",
+        "device",
+        "wifi",
+        "list",
+        "ifname",
+        "wlan0",
+    ];
 
-        // Iterate over each line in the output
-        for line in reader.lines() {
-            let line = line?;
+    #[test]
+    fn parses_wifi_networks() {
+        let runner =
+            MockCommandRunner::new().expect_fixture("nmcli", WIFI_LIST, "nmcli/device-wifi.txt");
+        let networks = get_nm_wifi_networks("wlan0", &runner).unwrap();
 
-            // Check if the line matches the SSID pattern
-            if re.is_match(&line) {
-                return Ok(true);
-            }
-        }
+        let summary: Vec<_> = networks
+            .iter()
+            .map(|network| {
+                (
+                    network.ssid.as_str(),
+                    network.security.as_str(),
+                    network.signal,
+                    network.connected,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("Home", "WPA2", 78, true),
+                ("Neighbour:5G", "WPA1 WPA2", 54, false),
+                ("Cafe", "", 32, false),
+                ("Office", "WPA2 802.1X", 65, false),
+            ]
+        );
     }
 
-    Ok(false)
+    #[test]
+    fn rescans_when_disconnected() {
+        let runner = MockCommandRunner::new()
+            .expect_fixture("nmcli", WIFI_LIST, "nmcli/device-wifi-disconnected.txt")
+            .expect(
+                "nmcli",
+                &[
+                    "--colors", "no", "dev", "wifi", "list", "ifname", "wlan0", "--rescan", "auto",
+                ],
+                "",
+            )
+            .expect_fixture("nmcli", WIFI_LIST, "nmcli/device-wifi-disconnected.txt");
+        let networks = get_nm_wifi_networks("wlan0", &runner).unwrap();
+
+        assert_eq!(networks.len(), 2);
+        assert!(networks.iter().all(|network| !network.connected));
+    }
+
+    #[test]
+    fn returns_no_network_when_nmcli_fails() {
+        let runner = MockCommandRunner::new().expect_failure("nmcli", WIFI_LIST);
+        assert!(get_nm_wifi_networks("wlan0", &runner).unwrap().is_empty());
+    }
+
+    #[test]
+    fn toggles_ipv6_of_primary_connection() {
+        let active = &[
+            "--colors",
+            "no",
+            "-t",
+            "-f",
+            "NAME,TYPE",
+            "connection",
+            "show",
+            "--active",
+        ];
+        let runner = MockCommandRunner::new()
+            .expect(
+                "nmcli",
+                active,
+                "tailscale0:tun\nHome\\:5G:802-11-wireless\nlo:loopback\n",
+            )
+            .expect(
+                "nmcli",
+                &[
+                    "--colors",
+                    "no",
+                    "-g",
+                    "ipv6.method",
+                    "connection",
+                    "show",
+                    "id",
+                    "Home:5G",
+                ],
+                "auto\n",
+            )
+            .expect(
+                "nmcli",
+                &[
+                    "connection",
+                    "modify",
+                    "id",
+                    "Home:5G",
+                    "ipv6.method",
+                    "disabled",
+                ],
+                "",
+            )
+            .expect("nmcli", &["connection", "up", "id", "Home:5G"], "");
+
+        let name = get_nm_primary_connection(&runner).unwrap().unwrap();
+        assert_eq!(name, "Home:5G");
+        assert_eq!(
+            get_nm_connection_setting(&name, "ipv6.method", &runner).unwrap(),
+            "auto"
+        );
+        assert!(set_nm_ipv6(&name, false, &runner).unwrap());
+    }
+
+    #[test]
+    fn reapplies_metered_without_reactivating() {
+        let runner = MockCommandRunner::new()
+            .expect(
+                "nmcli",
+                &[
+                    "connection",
+                    "modify",
+                    "id",
+                    "Phone",
+                    "connection.metered",
+                    "yes",
+                ],
+                "",
+            )
+            .expect(
+                "nmcli",
+                &[
+                    "--colors",
+                    "no",
+                    "-g",
+                    "GENERAL.DEVICES",
+                    "connection",
+                    "show",
+                    "id",
+                    "Phone",
+                ],
+                "wlan0\n",
+            )
+            .expect("nmcli", &["device", "reapply", "wlan0"], "");
+        assert!(set_nm_metered("Phone", true, &runner).unwrap());
+    }
+
+    #[test]
+    fn finds_saved_connection_by_ssid() {
+        let ssid_of = |uuid| {
+            [
+                "--colors",
+                "no",
+                "-g",
+                "802-11-wireless.ssid",
+                "connection",
+                "show",
+                "uuid",
+                uuid,
+            ]
+        };
+        let runner = MockCommandRunner::new()
+            .expect(
+                "nmcli",
+                &[
+                    "--colors",
+                    "no",
+                    "-t",
+                    "-f",
+                    "UUID,TYPE",
+                    "connection",
+                    "show",
+                ],
+                "0b5a6c1e-0d1f-4c2b-9a11-2f3e4d5c6b7a:802-3-ethernet\n\
+                 5d0c3a7e-8f5b-4f8e-a9c1-0b1d2e3f4a5b:802-11-wireless\n\
+                 9f1e2d3c-4b5a-4e6f-8a7b-6c5d4e3f2a1b:802-11-wireless\n",
+            )
+            .expect(
+                "nmcli",
+                &ssid_of("5d0c3a7e-8f5b-4f8e-a9c1-0b1d2e3f4a5b"),
+                "Office\n",
+            )
+            .expect(
+                "nmcli",
+                &ssid_of("9f1e2d3c-4b5a-4e6f-8a7b-6c5d4e3f2a1b"),
+                "HomeNet\n",
+            )
+            .expect(
+                "nmcli",
+                &[
+                    "connection",
+                    "modify",
+                    "uuid",
+                    "9f1e2d3c-4b5a-4e6f-8a7b-6c5d4e3f2a1b",
+                    "wifi-sec.psk",
+                    "",
+                ],
+                "",
+            );
+        // Found whatever the name of the profile, e.g. "Home Network"
+        let uuid = find_nm_wifi_uuid("HomeNet", &runner).unwrap().unwrap();
+        assert!(clear_nm_wifi_password(&uuid, &runner).unwrap());
+    }
+
+    #[test]
+    fn splits_escaped_fields() {
+        assert_eq!(
+            split_nmcli_fields(r"a\:b:c\\d:"),
+            ["a:b", r"c\d", ""].map(String::from)
+        );
+    }
+
+    #[test]
+    fn maps_frequencies_to_bands() {
+        assert_eq!(frequency_band("2437 MHz"), "2.4 GHz");
+        assert_eq!(frequency_band("5180 MHz"), "5 GHz");
+        assert_eq!(frequency_band("5955 MHz"), "6 GHz");
+        assert_eq!(frequency_band(""), "");
+    }
 }
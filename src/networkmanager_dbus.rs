@@ -0,0 +1,320 @@
+use crate::wifi::{ConnectionOutcome, WifiNetwork};
+use std::collections::HashMap;
+use std::error::Error;
+use std::thread;
+use std::time::{Duration, Instant};
+use zbus::blocking::{Connection, Proxy};
+use zbus::zvariant::{ObjectPath, OwnedObjectPath, OwnedValue, Value};
+
+const NM_SERVICE: &str = "org.freedesktop.NetworkManager";
+const NM_PATH: &str = "/org/freedesktop/NetworkManager";
+const NM_INTERFACE: &str = "org.freedesktop.NetworkManager";
+const NM_DEVICE_INTERFACE: &str = "org.freedesktop.NetworkManager.Device";
+const NM_WIRELESS_INTERFACE: &str = "org.freedesktop.NetworkManager.Device.Wireless";
+const NM_AP_INTERFACE: &str = "org.freedesktop.NetworkManager.AccessPoint";
+const NM_SETTINGS_PATH: &str = "/org/freedesktop/NetworkManager/Settings";
+const NM_SETTINGS_INTERFACE: &str = "org.freedesktop.NetworkManager.Settings";
+const NM_CONNECTION_INTERFACE: &str = "org.freedesktop.NetworkManager.Settings.Connection";
+const NM_ACTIVE_CONNECTION_INTERFACE: &str = "org.freedesktop.NetworkManager.Connection.Active";
+
+const NM_DEVICE_TYPE_WIFI: u32 = 2;
+const NM_DEVICE_STATE_ACTIVATED: u32 = 100;
+const NM_DEVICE_STATE_REASON_NO_SECRETS: u32 = 7;
+const NM_DEVICE_STATE_REASON_SUPPLICANT_DISCONNECT: u32 = 8;
+const NM_ACTIVE_CONNECTION_STATE_ACTIVATED: u32 = 2;
+const NM_ACTIVE_CONNECTION_STATE_DEACTIVATING: u32 = 3;
+
+const NM_802_11_AP_FLAGS_PRIVACY: u32 = 0x1;
+const NM_802_11_AP_SEC_KEY_MGMT_PSK: u32 = 0x100;
+const NM_802_11_AP_SEC_KEY_MGMT_802_1X: u32 = 0x200;
+const NM_802_11_AP_SEC_KEY_MGMT_SAE: u32 = 0x400;
+
+type ConnectionSettings = HashMap<String, HashMap<String, OwnedValue>>;
+
+/// Maximum time to wait for a connection to be activated.
+const ACTIVATION_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Delay between two checks of the activation state.
+const ACTIVATION_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Checks if NetworkManager is reachable on the system bus.
+pub fn is_nm_dbus_available() -> bool {
+    Connection::system()
+        .and_then(|connection| {
+            Proxy::new(&connection, NM_SERVICE, NM_PATH, NM_INTERFACE)?
+                .get_property::<String>("Version")
+        })
+        .is_ok()
+}
+
+/// Retrieves available Wi-Fi networks from NetworkManager over D-Bus.
+pub fn get_nm_dbus_wifi_networks(interface: &str) -> Result<Vec<WifiNetwork>, Box<dyn Error>> {
+    let connection = Connection::system()?;
+    let Some(device) = find_wifi_device(&connection, interface)? else {
+        return Ok(Vec::new());
+    };
+
+    let wireless = Proxy::new(&connection, NM_SERVICE, &device, NM_WIRELESS_INTERFACE)?;
+    let active_ap: OwnedObjectPath = wireless.get_property("ActiveAccessPoint")?;
+    let access_points: Vec<OwnedObjectPath> = wireless.call("GetAllAccessPoints", &())?;
+
+    let mut networks = Vec::new();
+    for path in access_points {
+        let ap = Proxy::new(&connection, NM_SERVICE, &path, NM_AP_INTERFACE)?;
+        let ssid = String::from_utf8_lossy(&ap.get_property::<Vec<u8>>("Ssid")?).to_string();
+        if ssid.is_empty() {
+            continue;
+        }
+        let security = ap_security(
+            ap.get_property("Flags")?,
+            ap.get_property("WpaFlags")?,
+            ap.get_property("RsnFlags")?,
+        );
+        networks.push(WifiNetwork {
+            connected: path == active_ap,
+            ssid,
+            security,
+            signal: ap.get_property("Strength")?,
+        });
+    }
+
+    Ok(networks)
+}
+
+/// Checks if the given interface is connected according to NetworkManager over D-Bus.
+pub fn is_nm_dbus_connected(interface: &str) -> Result<bool, Box<dyn Error>> {
+    let connection = Connection::system()?;
+    let Some(device) = find_wifi_device(&connection, interface)? else {
+        return Ok(false);
+    };
+    let state: u32 =
+        Proxy::new(&connection, NM_SERVICE, &device, NM_DEVICE_INTERFACE)?.get_property("State")?;
+    Ok(state == NM_DEVICE_STATE_ACTIVATED)
+}
+
+/// Checks if a saved NetworkManager connection exists for the given SSID over D-Bus.
+pub fn is_nm_dbus_known_network(ssid: &str) -> Result<bool, Box<dyn Error>> {
+    Ok(find_saved_connection(&Connection::system()?, ssid)?.is_some())
+}
+
+/// Connects an interface to a Wi-Fi network over D-Bus and waits for the outcome.
+///
+/// The saved connection of the network is activated, with the password if given, or else
+/// a connection is created.
+pub fn activate_nm_dbus_wifi(
+    interface: &str,
+    ssid: &str,
+    security: &str,
+    password: Option<&str>,
+) -> Result<ConnectionOutcome, Box<dyn Error>> {
+    let connection = Connection::system()?;
+    let device = find_wifi_device(&connection, interface)?
+        .ok_or_else(|| format!("Wi-Fi device {interface} not found"))?;
+    let nm = Proxy::new(&connection, NM_SERVICE, NM_PATH, NM_INTERFACE)?;
+    let any = ObjectPath::try_from("/")?;
+
+    let active: OwnedObjectPath = match find_saved_connection(&connection, ssid)? {
+        Some(profile) => {
+            if let Some(password) = password {
+                set_password(&connection, &profile, password)?;
+            }
+            nm.call("ActivateConnection", &(&profile, &device, &any))?
+        }
+        None => {
+            let mut settings: HashMap<&str, HashMap<&str, Value>> = HashMap::new();
+            settings.insert(
+                "802-11-wireless",
+                HashMap::from([("ssid", Value::from(ssid.as_bytes()))]),
+            );
+            if let Some(password) = password {
+                let key_mgmt = if security.contains("WPA3") {
+                    "sae"
+                } else {
+                    "wpa-psk"
+                };
+                settings.insert(
+                    "802-11-wireless-security",
+                    HashMap::from([
+                        ("key-mgmt", Value::from(key_mgmt)),
+                        ("psk", Value::from(password)),
+                    ]),
+                );
+            }
+            let (_, active): (OwnedObjectPath, OwnedObjectPath) =
+                nm.call("AddAndActivateConnection", &(settings, &device, &any))?;
+            active
+        }
+    };
+    wait_for_activation(&connection, &device, &active)
+}
+
+/// Lets NetworkManager activate the best available connection of an interface over D-Bus.
+pub fn connect_nm_dbus_device(interface: &str) -> Result<bool, Box<dyn Error>> {
+    let connection = Connection::system()?;
+    let Some(device) = find_wifi_device(&connection, interface)? else {
+        return Ok(false);
+    };
+    let any = ObjectPath::try_from("/")?;
+    let active: OwnedObjectPath = Proxy::new(&connection, NM_SERVICE, NM_PATH, NM_INTERFACE)?
+        .call("ActivateConnection", &(&any, &device, &any))?;
+    Ok(wait_for_activation(&connection, &device, &active)? == ConnectionOutcome::Connected)
+}
+
+/// Disconnects an interface over D-Bus.
+pub fn disconnect_nm_dbus_wifi(interface: &str) -> Result<bool, Box<dyn Error>> {
+    let connection = Connection::system()?;
+    let Some(device) = find_wifi_device(&connection, interface)? else {
+        return Ok(false);
+    };
+    Proxy::new(&connection, NM_SERVICE, &device, NM_DEVICE_INTERFACE)?
+        .call::<_, _, ()>("Disconnect", &())?;
+    Ok(true)
+}
+
+/// Replaces the password saved in the connection of a network over D-Bus, an empty one
+/// making NetworkManager ask for it again.
+pub fn set_nm_dbus_wifi_password(ssid: &str, password: &str) -> Result<bool, Box<dyn Error>> {
+    let connection = Connection::system()?;
+    let Some(profile) = find_saved_connection(&connection, ssid)? else {
+        return Ok(false);
+    };
+    set_password(&connection, &profile, password)?;
+    Ok(true)
+}
+
+/// Deletes the saved connection of a network over D-Bus.
+pub fn forget_nm_dbus_network(ssid: &str) -> Result<bool, Box<dyn Error>> {
+    let connection = Connection::system()?;
+    let Some(profile) = find_saved_connection(&connection, ssid)? else {
+        return Ok(false);
+    };
+    Proxy::new(&connection, NM_SERVICE, &profile, NM_CONNECTION_INTERFACE)?
+        .call::<_, _, ()>("Delete", &())?;
+    Ok(true)
+}
+
+/// Finds the saved connection of an SSID.
+fn find_saved_connection(
+    connection: &Connection,
+    ssid: &str,
+) -> Result<Option<OwnedObjectPath>, Box<dyn Error>> {
+    let settings = Proxy::new(
+        connection,
+        NM_SERVICE,
+        NM_SETTINGS_PATH,
+        NM_SETTINGS_INTERFACE,
+    )?;
+    let profiles: Vec<OwnedObjectPath> = settings.call("ListConnections", &())?;
+
+    for path in profiles {
+        let profile = Proxy::new(connection, NM_SERVICE, &path, NM_CONNECTION_INTERFACE)?;
+        let profile_settings: ConnectionSettings = profile.call("GetSettings", &())?;
+        let profile_ssid = profile_settings
+            .get("802-11-wireless")
+            .and_then(|wireless| wireless.get("ssid"))
+            .and_then(|value| Vec::<u8>::try_from(value.try_clone().ok()?).ok());
+        drop(profile);
+        if profile_ssid.as_deref() == Some(ssid.as_bytes()) {
+            return Ok(Some(path));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Saves the pre-shared key of a connection, keeping its other settings.
+fn set_password(
+    connection: &Connection,
+    profile: &OwnedObjectPath,
+    password: &str,
+) -> Result<(), Box<dyn Error>> {
+    let proxy = Proxy::new(connection, NM_SERVICE, profile, NM_CONNECTION_INTERFACE)?;
+    let mut settings: ConnectionSettings = proxy.call("GetSettings", &())?;
+    settings
+        .entry("802-11-wireless-security".to_string())
+        .or_default()
+        .insert("psk".to_string(), Value::from(password).try_into()?);
+    proxy.call::<_, _, ()>("Update", &(settings,))?;
+    Ok(())
+}
+
+/// Waits for an activation to succeed or fail, telling rejected passwords apart from the
+/// reason the device gives.
+fn wait_for_activation(
+    connection: &Connection,
+    device: &OwnedObjectPath,
+    active: &OwnedObjectPath,
+) -> Result<ConnectionOutcome, Box<dyn Error>> {
+    let deadline = Instant::now() + ACTIVATION_TIMEOUT;
+    loop {
+        // A failed activation is removed from the bus, failing to read its state
+        let state = Proxy::new(
+            connection,
+            NM_SERVICE,
+            active,
+            NM_ACTIVE_CONNECTION_INTERFACE,
+        )
+        .and_then(|proxy| proxy.get_property::<u32>("State"));
+        match state {
+            Ok(NM_ACTIVE_CONNECTION_STATE_ACTIVATED) => return Ok(ConnectionOutcome::Connected),
+            Ok(state) if state < NM_ACTIVE_CONNECTION_STATE_DEACTIVATING => {
+                if Instant::now() >= deadline {
+                    return Ok(ConnectionOutcome::Failed(
+                        "timed out waiting for the connection".to_string(),
+                    ));
+                }
+                thread::sleep(ACTIVATION_POLL_INTERVAL);
+            }
+            _ => break,
+        }
+    }
+
+    let (_, reason): (u32, u32) = Proxy::new(connection, NM_SERVICE, device, NM_DEVICE_INTERFACE)?
+        .get_property("StateReason")?;
+    let message = format!("device state reason {reason}");
+    Ok(match reason {
+        NM_DEVICE_STATE_REASON_NO_SECRETS | NM_DEVICE_STATE_REASON_SUPPLICANT_DISCONNECT => {
+            ConnectionOutcome::AuthFailed(message)
+        }
+        _ => ConnectionOutcome::Failed(message),
+    })
+}
+
+/// Finds the object path of the Wi-Fi device matching the interface name.
+fn find_wifi_device(
+    connection: &Connection,
+    interface: &str,
+) -> Result<Option<OwnedObjectPath>, Box<dyn Error>> {
+    let nm = Proxy::new(connection, NM_SERVICE, NM_PATH, NM_INTERFACE)?;
+    let devices: Vec<OwnedObjectPath> = nm.call("GetDevices", &())?;
+
+    for path in devices {
+        let device = Proxy::new(connection, NM_SERVICE, path.as_ref(), NM_DEVICE_INTERFACE)?;
+        let device_type: u32 = device.get_property("DeviceType")?;
+        let name: String = device.get_property("Interface")?;
+        drop(device);
+        if device_type == NM_DEVICE_TYPE_WIFI && name == interface {
+            return Ok(Some(path));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Converts the access point security flags to an nmcli-like security label.
+fn ap_security(flags: u32, wpa_flags: u32, rsn_flags: u32) -> String {
+    let key_mgmt = wpa_flags | rsn_flags;
+    if key_mgmt & NM_802_11_AP_SEC_KEY_MGMT_802_1X != 0 {
+        "802.1X".to_string()
+    } else if rsn_flags & NM_802_11_AP_SEC_KEY_MGMT_SAE != 0 {
+        "WPA3".to_string()
+    } else if rsn_flags & NM_802_11_AP_SEC_KEY_MGMT_PSK != 0 {
+        "WPA2".to_string()
+    } else if wpa_flags & NM_802_11_AP_SEC_KEY_MGMT_PSK != 0 {
+        "WPA1".to_string()
+    } else if flags & NM_802_11_AP_FLAGS_PRIVACY != 0 {
+        "WEP".to_string()
+    } else {
+        String::new()
+    }
+}
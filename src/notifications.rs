@@ -0,0 +1,114 @@
+use crate::launcher::MessageWindow;
+use crate::Config;
+use notify_rust::{Notification, NotificationHandle, Timeout, Urgency};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+/// Urgency of the notifications.
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum NotificationUrgency {
+    Low,
+    #[default]
+    Normal,
+    Critical,
+}
+
+impl From<NotificationUrgency> for Urgency {
+    fn from(urgency: NotificationUrgency) -> Self {
+        match urgency {
+            NotificationUrgency::Low => Urgency::Low,
+            NotificationUrgency::Normal => Urgency::Normal,
+            NotificationUrgency::Critical => Urgency::Critical,
+        }
+    }
+}
+
+/// Notification configuration.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct NotificationConfig {
+    #[serde(default = "crate::config::default_true")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub timeout: Option<u32>,
+    #[serde(default)]
+    pub urgency: NotificationUrgency,
+    #[serde(default)]
+    pub icon: Option<String>,
+}
+
+impl Default for NotificationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            timeout: None,
+            urgency: NotificationUrgency::default(),
+            icon: None,
+        }
+    }
+}
+
+/// Feedback shown while a long operation runs, such as a scan.
+pub enum Progress {
+    Launcher(MessageWindow),
+    Notification(Box<NotificationHandle>),
+}
+
+impl Progress {
+    /// Shows the body in the message bar of the launcher when it has one, or else in a
+    /// notification.
+    pub fn show(config: &Config, summary: &str, body: &str) -> Option<Self> {
+        match MessageWindow::show(config, body) {
+            Some(window) => Some(Progress::Launcher(window)),
+            None => show_notification(config, &new_notification(config, summary, body))
+                .map(|notification| Progress::Notification(Box::new(notification))),
+        }
+    }
+
+    /// Closes the feedback once the operation is over.
+    pub fn close(self) {
+        match self {
+            Progress::Launcher(window) => drop(window),
+            Progress::Notification(notification) => notification.close(),
+        }
+    }
+}
+
+/// Creates a notification with the configured timeout, urgency and icon.
+pub fn new_notification(config: &Config, summary: &str, body: &str) -> Notification {
+    let settings = &config.notifications;
+    let mut notification = Notification::new();
+    notification
+        .summary(summary)
+        .body(body)
+        .urgency(settings.urgency.into());
+    if let Some(timeout) = settings.timeout {
+        notification.timeout(Timeout::Milliseconds(timeout));
+    }
+    if let Some(icon) = &settings.icon {
+        notification.icon(icon);
+    }
+    notification
+}
+
+/// Shows a notification unless they are disabled.
+///
+/// Failures, e.g. without a notification daemon, are reported on stderr rather
+/// than failing the action.
+pub fn show_notification(
+    config: &Config,
+    notification: &Notification,
+) -> Option<NotificationHandle> {
+    if !config.notifications.enabled {
+        return None;
+    }
+    notification
+        .show()
+        .map_err(|err| warn!("failed to show notification: {err}"))
+        .ok()
+}
+
+/// Shows a notification with the given summary and body.
+pub fn notify(config: &Config, summary: &str, body: &str) {
+    show_notification(config, &new_notification(config, summary, body));
+}
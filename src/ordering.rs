@@ -0,0 +1,87 @@
+use crate::utils::write_state_file;
+use dirs::data_dir;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Usage statistics of a single menu entry.
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct Usage {
+    count: u64,
+    last_used: u64,
+}
+
+/// Persistent usage state of the menu entries.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct UsageState {
+    #[serde(default)]
+    entries: HashMap<String, Usage>,
+}
+
+/// Gets the state file path.
+fn get_state_path() -> Result<PathBuf, Box<dyn Error>> {
+    let data_dir = data_dir().ok_or("Failed to find data directory")?;
+    Ok(data_dir.join("network-dmenu").join("state.json"))
+}
+
+/// Returns the current UNIX timestamp in seconds.
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+/// Loads the usage state, falling back to an empty state.
+pub fn load_usage_state() -> UsageState {
+    get_state_path()
+        .ok()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+/// Records the selection of an entry in the usage state file.
+pub fn record_usage(key: &str) -> Result<(), Box<dyn Error>> {
+    let mut state = load_usage_state();
+    let usage = state.entries.entry(key.to_string()).or_default();
+    usage.count += 1;
+    usage.last_used = now();
+
+    write_state_file(Some(get_state_path()?), &serde_json::to_string(&state)?)
+}
+
+/// Sorts the items so the most frequently and recently used ones come first.
+///
+/// `recency_weight` ranges from 0.0 (frequency only) to 1.0 (recency only).
+/// Items that were never used keep their original order at the end.
+pub fn sort_by_usage<T>(
+    items: &mut [T],
+    state: &UsageState,
+    recency_weight: f64,
+    key: impl Fn(&T) -> String,
+) {
+    let max_count = state
+        .entries
+        .values()
+        .map(|usage| usage.count)
+        .max()
+        .unwrap_or(0)
+        .max(1) as f64;
+    let now = now();
+    let recency_weight = recency_weight.clamp(0.0, 1.0);
+
+    let score = |item: &T| -> f64 {
+        state.entries.get(&key(item)).map_or(0.0, |usage| {
+            let frequency = usage.count as f64 / max_count;
+            let age_hours = now.saturating_sub(usage.last_used) as f64 / 3600.0;
+            let recency = (-age_hours / 24.0).exp();
+            (1.0 - recency_weight) * frequency + recency_weight * recency
+        })
+    };
+
+    items.sort_by(|a, b| score(b).total_cmp(&score(a)));
+}
@@ -0,0 +1,97 @@
+use crate::builtin_ui::{filter_entries, group_by_section, toggle_label, Row};
+use eframe::egui;
+use std::cell::RefCell;
+use std::error::Error;
+use std::rc::Rc;
+
+/// Quick-settings window listing the entries by section.
+struct PopupApp {
+    entries: Vec<String>,
+    toggles: Vec<Option<bool>>,
+    query: String,
+    selection: Rc<RefCell<String>>,
+}
+
+/// Shows the entries in an always-on-top popup window and returns the clicked one.
+///
+/// Entries with a toggle state are shown as switches. Without entries, the popup is a
+/// prompt returning the typed text.
+pub fn run_popup_menu(
+    title: &str,
+    entries: &[String],
+    toggles: &[Option<bool>],
+) -> Result<String, Box<dyn Error>> {
+    let selection = Rc::new(RefCell::new(String::new()));
+    let app = PopupApp {
+        entries: entries.to_vec(),
+        toggles: toggles.to_vec(),
+        query: String::new(),
+        selection: Rc::clone(&selection),
+    };
+    let options = eframe::NativeOptions {
+        viewport: egui::ViewportBuilder::default()
+            .with_title(title)
+            .with_inner_size([420.0, 560.0])
+            .with_always_on_top(),
+        ..Default::default()
+    };
+    eframe::run_native(title, options, Box::new(|_| Ok(Box::new(app))))
+        .map_err(|err| err.to_string())?;
+    let selection = selection.borrow().clone();
+    Ok(selection)
+}
+
+impl eframe::App for PopupApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        let rows = group_by_section(&self.entries, &filter_entries(&self.entries, &self.query));
+        let mut selected = None;
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.text_edit_singleline(&mut self.query).request_focus();
+            ui.separator();
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                for row in &rows {
+                    match row {
+                        Row::Header(section) => {
+                            ui.add_space(6.0);
+                            ui.strong(section);
+                        }
+                        Row::Entry(index) => {
+                            // The section is shown by the header
+                            let entry = &self.entries[*index];
+                            let text = entry
+                                .split_once("- ")
+                                .map_or(entry.as_str(), |(_, text)| text);
+                            let clicked = match self.toggles.get(*index).copied().flatten() {
+                                Some(mut enabled) => {
+                                    ui.toggle_value(&mut enabled, toggle_label(text)).clicked()
+                                }
+                                None => ui.selectable_label(false, text).clicked(),
+                            };
+                            if clicked {
+                                selected = Some(entry.clone());
+                            }
+                        }
+                    }
+                }
+            });
+        });
+
+        // Enter picks the first match, or the typed text when nothing matches
+        if ctx.input(|input| input.key_pressed(egui::Key::Enter)) {
+            selected = Some(
+                rows.iter()
+                    .find_map(|row| match row {
+                        Row::Entry(index) => Some(self.entries[*index].clone()),
+                        Row::Header(_) => None,
+                    })
+                    .unwrap_or_else(|| self.query.clone()),
+            );
+        }
+        if let Some(selected) = selected {
+            *self.selection.borrow_mut() = selected;
+            ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+        } else if ctx.input(|input| input.key_pressed(egui::Key::Escape)) {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+        }
+    }
+}
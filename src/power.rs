@@ -0,0 +1,104 @@
+use crate::command::{is_command_installed, read_output_lines, CommandRunner};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+
+/// Represents actions switching the power profile.
+#[derive(Debug, Deserialize, Serialize)]
+pub enum PowerAction {
+    SetProfile(String, bool),
+}
+
+/// Retrieves the power profiles of power-profiles-daemon or tuned, marking the current one.
+pub fn get_power_actions(
+    command_runner: &dyn CommandRunner,
+) -> Result<Vec<PowerAction>, Box<dyn Error>> {
+    let profiles = if is_command_installed("powerprofilesctl") {
+        get_ppd_profiles(command_runner)?
+    } else if is_command_installed("tuned-adm") {
+        get_tuned_profiles(command_runner)?
+    } else {
+        Vec::new()
+    };
+    Ok(profiles
+        .into_iter()
+        .map(|(name, active)| PowerAction::SetProfile(name, active))
+        .collect())
+}
+
+/// Parses `powerprofilesctl list`, where the current profile is starred.
+fn get_ppd_profiles(
+    command_runner: &dyn CommandRunner,
+) -> Result<Vec<(String, bool)>, Box<dyn Error>> {
+    let output = command_runner.run_query("powerprofilesctl", &["list"])?;
+    if !output.status.success() {
+        return Ok(Vec::new());
+    }
+    Ok(read_output_lines(&output)?
+        .iter()
+        .filter_map(|line| {
+            let name = line.trim().strip_suffix(':')?;
+            let active = name.starts_with('*');
+            Some((name.trim_start_matches('*').trim().to_string(), active))
+        })
+        .collect())
+}
+
+/// Parses `tuned-adm list`, which ends with the current profile.
+fn get_tuned_profiles(
+    command_runner: &dyn CommandRunner,
+) -> Result<Vec<(String, bool)>, Box<dyn Error>> {
+    let output = command_runner.run_query("tuned-adm", &["list"])?;
+    if !output.status.success() {
+        return Ok(Vec::new());
+    }
+    let lines = read_output_lines(&output)?;
+    let current = lines
+        .iter()
+        .find_map(|line| line.strip_prefix("Current active profile:"))
+        .map(str::trim);
+    Ok(lines
+        .iter()
+        .filter_map(|line| line.strip_prefix("- ")?.split_whitespace().next())
+        .map(|name| (name.to_string(), Some(name) == current))
+        .collect())
+}
+
+/// Switches to the selected power profile.
+pub fn handle_power_action(
+    action: &PowerAction,
+    command_runner: &dyn CommandRunner,
+) -> Result<bool, Box<dyn Error>> {
+    let PowerAction::SetProfile(name, _) = action;
+    let output = if is_command_installed("powerprofilesctl") {
+        command_runner.run_command("powerprofilesctl", &["set", name])?
+    } else {
+        command_runner.run_command("tuned-adm", &["profile", name])?
+    };
+    Ok(output.status.success())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockCommandRunner;
+
+    #[test]
+    fn parses_profiles_and_current_one() {
+        let runner = MockCommandRunner::new()
+            .expect_fixture("powerprofilesctl", &["list"], "powerprofilesctl/list.txt")
+            .expect_fixture("tuned-adm", &["list"], "tuned-adm/list.txt");
+
+        assert_eq!(
+            get_ppd_profiles(&runner).unwrap(),
+            [
+                ("performance".to_string(), false),
+                ("balanced".to_string(), true),
+                ("power-saver".to_string(), false),
+            ]
+        );
+        let tuned = get_tuned_profiles(&runner).unwrap();
+        assert_eq!(tuned.len(), 5);
+        assert!(tuned.contains(&("balanced".to_string(), true)));
+        assert!(tuned.contains(&("powersave".to_string(), false)));
+    }
+}
@@ -0,0 +1,224 @@
+use crate::context::Context;
+use crate::{action_key, action_to_entry, get_action_devices, get_actions, set_action, ActionType};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+
+/// Named list of menu entries applied one after the other from a single entry, e.g. a
+/// network, an exit node and the shields for a coffee shop.
+///
+/// Steps are the entry ids listed by `--list-json`; toggles take an `:on` or `:off` suffix
+/// to be set rather than flipped, e.g. `toggle:shields:on`.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Profile {
+    pub name: String,
+    #[serde(default)]
+    pub steps: Vec<String>,
+}
+
+/// Step of a profile resolved to an entry of the menu.
+#[derive(Debug)]
+enum Step<'a> {
+    /// Entry to apply.
+    Apply(&'a ActionType),
+    /// Toggle already in the requested state.
+    Done,
+    /// Step matching no entry.
+    Missing,
+}
+
+/// Resolves a step of a profile to the entry with its id.
+fn resolve_step<'a>(step: &str, actions: &'a [ActionType]) -> Step<'a> {
+    let (id, state) = match step.rsplit_once(':') {
+        Some((id, "on")) if id.starts_with("toggle:") => (id, Some(true)),
+        Some((id, "off")) if id.starts_with("toggle:") => (id, Some(false)),
+        _ => (step, None),
+    };
+    let Some(action) = actions.iter().find(|action| action_key(action) == id) else {
+        return Step::Missing;
+    };
+    match (action, state) {
+        // The toggle entry sets the opposite of the current state
+        (ActionType::Toggle(toggle), Some(enable)) if toggle.enable != enable => Step::Done,
+        _ => Step::Apply(action),
+    }
+}
+
+/// Outcome of entries applied one after the other.
+#[derive(Debug, Default)]
+pub struct BatchResult {
+    /// Number of entries applied, profile steps counted one by one.
+    pub applied: usize,
+    /// Number of entries tried.
+    pub total: usize,
+    /// Number of entries cancelled at a prompt, not counted as failures.
+    pub cancelled: usize,
+    /// Failed entries with their errors.
+    pub failures: Vec<(String, Vec<String>)>,
+}
+
+impl BatchResult {
+    /// Records the result of an entry, along with the commands that failed for it.
+    fn record(&mut self, entry: String, result: Result<bool, Box<dyn Error>>, errors: Vec<String>) {
+        self.total += 1;
+        match result {
+            Ok(true) => self.applied += 1,
+            // Cancelled prompts also return false, but without any failed command
+            Ok(false) if errors.is_empty() => self.cancelled += 1,
+            Ok(false) => self.failures.push((entry, errors)),
+            Err(err) => self.failures.push((entry, vec![err.to_string()])),
+        }
+    }
+}
+
+/// Applies the selected entries one after the other, profiles step by step.
+///
+/// Steps are looked up in the given actions, and in freshly gathered ones when missing,
+/// e.g. from a section not streamed yet when the profile was selected.
+pub async fn apply_actions(
+    context: &Context<'_>,
+    selected: &[&ActionType],
+    actions: &[ActionType],
+) -> Result<BatchResult, Box<dyn Error>> {
+    let mut result = BatchResult::default();
+    let mut gathered: Option<Vec<ActionType>> = None;
+    for action in selected {
+        let ActionType::Profile(name) = action else {
+            apply_action(context, action, &mut result).await?;
+            continue;
+        };
+        let profile = context
+            .config
+            .profiles
+            .iter()
+            .find(|profile| &profile.name == name)
+            .ok_or_else(|| format!("profile {name} not found"))?;
+        for step in &profile.steps {
+            let mut resolved = resolve_step(step, actions);
+            if matches!(resolved, Step::Missing) {
+                if gathered.is_none() {
+                    gathered = Some(get_actions(context)?);
+                }
+                resolved = resolve_step(step, gathered.as_deref().unwrap_or_default());
+            }
+            match resolved {
+                Step::Apply(action) => apply_action(context, action, &mut result).await?,
+                Step::Done => result.record(step.clone(), Ok(true), Vec::new()),
+                Step::Missing => result.record(
+                    step.clone(),
+                    Err("no such entry in the menu".into()),
+                    Vec::new(),
+                ),
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// Applies a single entry, recording its result.
+async fn apply_action(
+    context: &Context<'_>,
+    action: &ActionType,
+    result: &mut BatchResult,
+) -> Result<(), Box<dyn Error>> {
+    let Context {
+        args,
+        config,
+        command_runner,
+        ..
+    } = *context;
+    let connected_devices = get_action_devices(action, command_runner)?;
+    command_runner.take_failures();
+    let success = set_action(
+        config,
+        &args.wifi_interface,
+        action,
+        &connected_devices,
+        command_runner,
+    )
+    .await;
+    result.record(
+        action_to_entry(config, action),
+        success,
+        command_runner.take_failures(),
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capabilities::Capabilities;
+    use crate::testing::MockCommandRunner;
+    use crate::toggle::{ToggleAction, ToggleId};
+    use crate::{Args, Config, CustomAction};
+    use clap::Parser;
+
+    #[test]
+    fn resolves_profile_steps() {
+        let actions = [ActionType::Toggle(ToggleAction {
+            id: ToggleId::Shields,
+            enable: true,
+        })];
+        assert!(matches!(
+            resolve_step("toggle:shields:on", &actions),
+            Step::Apply(ActionType::Toggle(_))
+        ));
+        assert!(matches!(
+            resolve_step("toggle:shields:off", &actions),
+            Step::Done
+        ));
+        assert!(matches!(resolve_step("wifi:Cafe", &actions), Step::Missing));
+    }
+
+    #[test]
+    fn counts_cancelled_entries_apart_from_failures() {
+        let mut result = BatchResult::default();
+        result.record("applied".to_string(), Ok(true), Vec::new());
+        result.record("cancelled".to_string(), Ok(false), Vec::new());
+        let errors = vec!["nmcli connection up: timed out".to_string()];
+        result.record("failed".to_string(), Ok(false), errors.clone());
+        result.record(
+            "missing".to_string(),
+            Err("no such entry".into()),
+            Vec::new(),
+        );
+
+        assert_eq!((result.applied, result.cancelled, result.total), (1, 1, 4));
+        assert_eq!(
+            result.failures,
+            [
+                ("failed".to_string(), errors),
+                ("missing".to_string(), vec!["no such entry".to_string()])
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn applies_entries_without_bluetooth() {
+        let args = Args::parse_from(["network-dmenu", "--no-bluetooth"]);
+        let config: Config = toml::from_str("").unwrap();
+        let capabilities = Capabilities {
+            pinentry: false,
+            wifi: false,
+            bluetooth: false,
+            tailscale: false,
+        };
+        // Querying the connected devices would be an unexpected invocation
+        let runner = MockCommandRunner::new();
+        let context = Context {
+            args: &args,
+            config: &config,
+            capabilities: &capabilities,
+            command_runner: &runner,
+        };
+        let action = ActionType::Custom(CustomAction {
+            display: "Nothing".to_string(),
+            cmd: "true".to_string(),
+            prompt: None,
+        });
+
+        let result = apply_actions(&context, &[&action], &[]).await.unwrap();
+        assert_eq!((result.applied, result.total), (1, 1));
+        assert!(result.failures.is_empty());
+    }
+}
@@ -0,0 +1,131 @@
+use crate::config::MenuSection;
+use crate::context::Context;
+use crate::gather_actions;
+use std::error::Error;
+use std::time::{Duration, Instant};
+
+/// Sections taking longer to gather are flagged with a suggestion.
+const SLOW_SECTION: Duration = Duration::from_millis(200);
+
+/// Launches taking longer are suggested the daemon cache or the streamed menu.
+const SLOW_STARTUP: Duration = Duration::from_millis(500);
+
+/// Time spent gathering a step of the menu startup.
+struct Timing {
+    name: String,
+    entries: Option<usize>,
+    elapsed: Duration,
+    suggestion: Option<&'static str>,
+}
+
+/// Returns how to skip or speed up a section.
+fn section_suggestion(section: MenuSection) -> &'static str {
+    match section {
+        MenuSection::Wifi => "--no-wifi skips it",
+        MenuSection::Tailscale => {
+            "--no-tailscale skips it, exclude_exit_node shortens the exit node list"
+        }
+        MenuSection::Peers => "--no-tailscale skips it",
+        MenuSection::Bluetooth => "--no-bluetooth skips it",
+        _ => "leaving it out of [menu] sections skips it",
+    }
+}
+
+/// Gathers the menu entries as a launch does, and prints the time taken by the tool
+/// detection and each section along with suggestions for the slow ones.
+pub fn profile_startup(context: &Context<'_>, detection: Duration) -> Result<(), Box<dyn Error>> {
+    let mut timings = vec![Timing {
+        name: "tool detection".to_string(),
+        entries: None,
+        elapsed: detection,
+        suggestion: None,
+    }];
+    let mut section_start = Instant::now();
+    gather_actions(
+        context,
+        &context.config.menu.sections,
+        |section, actions| {
+            let elapsed = section_start.elapsed();
+            timings.push(Timing {
+                name: format!("{section:?}").to_lowercase(),
+                entries: Some(actions.len()),
+                elapsed,
+                suggestion: (elapsed > SLOW_SECTION).then(|| section_suggestion(section)),
+            });
+            section_start = Instant::now();
+            true
+        },
+    )?;
+
+    print!("{}", format_profile(&timings));
+    Ok(())
+}
+
+/// Formats the timings as a table, with the total and the suggestions.
+fn format_profile(timings: &[Timing]) -> String {
+    let mut report = format!("{:<16} {:>8} {:>9}\n", "step", "entries", "time");
+    for timing in timings {
+        report.push_str(&format!(
+            "{:<16} {:>8} {:>6} ms",
+            timing.name,
+            timing
+                .entries
+                .map_or_else(|| "-".to_string(), |entries| entries.to_string()),
+            timing.elapsed.as_millis()
+        ));
+        if let Some(suggestion) = timing.suggestion {
+            report.push_str(&format!("  slow: {suggestion}"));
+        }
+        report.push('\n');
+    }
+
+    let total: Duration = timings.iter().map(|timing| timing.elapsed).sum();
+    report.push_str(&format!(
+        "{:<16} {:>8} {:>6} ms\n",
+        "total",
+        timings
+            .iter()
+            .filter_map(|timing| timing.entries)
+            .sum::<usize>(),
+        total.as_millis()
+    ));
+    if total > SLOW_STARTUP {
+        report.push_str(
+            "\nThe menu is slow to gather: --daemon caches the entries for instant launches, \
+             and [menu] stream = true shows the menu before they are gathered.\n",
+        );
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_timings_with_suggestions() {
+        let timings = [
+            Timing {
+                name: "custom".to_string(),
+                entries: Some(2),
+                elapsed: Duration::from_millis(1),
+                suggestion: None,
+            },
+            Timing {
+                name: "wifi".to_string(),
+                entries: Some(14),
+                elapsed: Duration::from_millis(842),
+                suggestion: Some(section_suggestion(MenuSection::Wifi)),
+            },
+        ];
+        let report = format_profile(&timings);
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(lines[1], "custom                  2      1 ms");
+        assert_eq!(
+            lines[2],
+            "wifi                   14    842 ms  slow: --no-wifi skips it"
+        );
+        assert_eq!(lines[3], "total                  16    843 ms");
+        assert!(report.contains("--daemon caches the entries"));
+    }
+}
@@ -0,0 +1,244 @@
+use crate::command::{is_command_installed, CommandRunner};
+use dirs::config_dir;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+
+/// Header of the environment.d file, naming the profile it was written for.
+const PROFILE_HEADER: &str = "# network-dmenu proxy profile: ";
+
+/// Proxy variables exported to the user session.
+const PROXY_VARIABLES: [&str; 4] = ["http_proxy", "https_proxy", "all_proxy", "no_proxy"];
+
+/// Proxy profile applied to GNOME settings and the session environment.
+///
+/// Proxies are given as `host:port`; a profile without any is a direct connection.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ProxyProfile {
+    pub name: String,
+    #[serde(default)]
+    pub http: Option<String>,
+    #[serde(default)]
+    pub https: Option<String>,
+    #[serde(default)]
+    pub socks: Option<String>,
+    #[serde(default)]
+    pub autoconfig_url: Option<String>,
+    #[serde(default)]
+    pub no_proxy: Vec<String>,
+}
+
+impl ProxyProfile {
+    /// Returns the GNOME proxy mode of the profile.
+    fn mode(&self) -> &'static str {
+        if self.autoconfig_url.is_some() {
+            "auto"
+        } else if self.http.is_some() || self.https.is_some() || self.socks.is_some() {
+            "manual"
+        } else {
+            "none"
+        }
+    }
+
+    /// Returns the session environment variables of the profile.
+    fn environment(&self) -> Vec<(&'static str, String)> {
+        let mut variables = Vec::new();
+        if let Some(http) = &self.http {
+            variables.push(("http_proxy", format!("http://{http}")));
+        }
+        if let Some(https) = self.https.as_ref().or(self.http.as_ref()) {
+            variables.push(("https_proxy", format!("http://{https}")));
+        }
+        if let Some(socks) = &self.socks {
+            variables.push(("all_proxy", format!("socks5://{socks}")));
+        }
+        if !variables.is_empty() && !self.no_proxy.is_empty() {
+            variables.push(("no_proxy", self.no_proxy.join(",")));
+        }
+        variables
+    }
+}
+
+/// Represents actions switching the system proxy.
+#[derive(Debug, Deserialize, Serialize)]
+pub enum ProxyAction {
+    SetProfile(String, bool),
+}
+
+/// Gets the path of the environment.d file holding the proxy variables.
+fn get_environment_path() -> Result<PathBuf, Box<dyn Error>> {
+    let config_dir = config_dir().ok_or("Failed to find config directory")?;
+    Ok(config_dir
+        .join("environment.d")
+        .join("60-network-dmenu-proxy.conf"))
+}
+
+/// Reads the name of the profile applied last.
+fn get_active_profile() -> Option<String> {
+    let content = fs::read_to_string(get_environment_path().ok()?).ok()?;
+    content
+        .lines()
+        .next()?
+        .strip_prefix(PROFILE_HEADER)
+        .map(str::to_string)
+}
+
+/// Retrieves the proxy actions, marking the profile applied last, or else the direct one.
+pub fn get_proxy_actions(profiles: &[ProxyProfile]) -> Vec<ProxyAction> {
+    let active = get_active_profile()
+        .filter(|name| profiles.iter().any(|profile| &profile.name == name))
+        .or_else(|| {
+            profiles
+                .iter()
+                .find(|profile| profile.mode() == "none")
+                .map(|profile| profile.name.clone())
+        });
+    profiles
+        .iter()
+        .map(|profile| {
+            ProxyAction::SetProfile(profile.name.clone(), active.as_ref() == Some(&profile.name))
+        })
+        .collect()
+}
+
+/// Applies the selected proxy profile.
+pub fn handle_proxy_action(
+    action: &ProxyAction,
+    profiles: &[ProxyProfile],
+    command_runner: &dyn CommandRunner,
+) -> Result<bool, Box<dyn Error>> {
+    let ProxyAction::SetProfile(name, _) = action;
+    let profile = profiles
+        .iter()
+        .find(|profile| &profile.name == name)
+        .ok_or_else(|| format!("proxy profile {name} not found"))?;
+
+    write_environment_file(profile)?;
+    let mut success = true;
+    if is_command_installed("systemctl") {
+        success &= set_session_environment(profile, command_runner)?;
+    }
+    if is_command_installed("gsettings") {
+        success &= set_gnome_proxy(profile, command_runner)?;
+    }
+    Ok(success)
+}
+
+/// Writes the proxy variables read by the next sessions from environment.d.
+fn write_environment_file(profile: &ProxyProfile) -> Result<(), Box<dyn Error>> {
+    let path = get_environment_path()?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut content = format!("{PROFILE_HEADER}{}\n", profile.name);
+    for (name, value) in profile.environment() {
+        content.push_str(&format!("{name}={value}\n"));
+    }
+    fs::write(path, content)?;
+    Ok(())
+}
+
+/// Updates the environment of the running user session, for the services started from now on.
+fn set_session_environment(
+    profile: &ProxyProfile,
+    command_runner: &dyn CommandRunner,
+) -> Result<bool, Box<dyn Error>> {
+    let mut unset = vec!["--user", "unset-environment"];
+    unset.extend(PROXY_VARIABLES);
+    let mut success = command_runner
+        .run_command("systemctl", &unset)?
+        .status
+        .success();
+
+    let variables: Vec<String> = profile
+        .environment()
+        .into_iter()
+        .map(|(name, value)| format!("{name}={value}"))
+        .collect();
+    if !variables.is_empty() {
+        let mut set = vec!["--user", "set-environment"];
+        set.extend(variables.iter().map(String::as_str));
+        success &= command_runner
+            .run_command("systemctl", &set)?
+            .status
+            .success();
+    }
+    Ok(success)
+}
+
+/// Applies the profile to the GNOME proxy settings, also used by most GTK applications.
+fn set_gnome_proxy(
+    profile: &ProxyProfile,
+    command_runner: &dyn CommandRunner,
+) -> Result<bool, Box<dyn Error>> {
+    let ignore_hosts = format!(
+        "[{}]",
+        profile
+            .no_proxy
+            .iter()
+            .map(|host| format!("'{host}'"))
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    let mut settings = vec![
+        ("org.gnome.system.proxy", "mode", profile.mode().to_string()),
+        ("org.gnome.system.proxy", "ignore-hosts", ignore_hosts),
+        (
+            "org.gnome.system.proxy",
+            "autoconfig-url",
+            profile.autoconfig_url.clone().unwrap_or_default(),
+        ),
+    ];
+    for (schema, proxy) in [
+        ("org.gnome.system.proxy.http", &profile.http),
+        (
+            "org.gnome.system.proxy.https",
+            &profile.https.clone().or(profile.http.clone()),
+        ),
+        ("org.gnome.system.proxy.socks", &profile.socks),
+    ] {
+        let (host, port) = proxy
+            .as_deref()
+            .and_then(|proxy| proxy.rsplit_once(':'))
+            .unwrap_or(("", "0"));
+        settings.push((schema, "host", host.to_string()));
+        settings.push((schema, "port", port.to_string()));
+    }
+
+    let mut success = true;
+    for (schema, key, value) in &settings {
+        success &= command_runner
+            .run_command("gsettings", &["set", schema, key, value])?
+            .status
+            .success();
+    }
+    Ok(success)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_proxy_environment() {
+        let profile = ProxyProfile {
+            name: "Office".to_string(),
+            http: Some("proxy.corp:3128".to_string()),
+            https: None,
+            socks: Some("127.0.0.1:9050".to_string()),
+            autoconfig_url: None,
+            no_proxy: vec!["localhost".to_string(), ".corp".to_string()],
+        };
+        assert_eq!(profile.mode(), "manual");
+        assert_eq!(
+            profile.environment(),
+            [
+                ("http_proxy", "http://proxy.corp:3128".to_string()),
+                ("https_proxy", "http://proxy.corp:3128".to_string()),
+                ("all_proxy", "socks5://127.0.0.1:9050".to_string()),
+                ("no_proxy", "localhost,.corp".to_string()),
+            ]
+        );
+    }
+}
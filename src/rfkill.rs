@@ -0,0 +1,93 @@
+use crate::command::{read_output_lines, CommandRunner};
+use std::error::Error;
+
+/// Radio types toggled together by the airplane mode.
+pub const AIRPLANE_RADIOS: [&str; 3] = ["wlan", "bluetooth", "wwan"];
+
+/// Retrieves the soft-block state of each radio as `(type, blocked)` pairs.
+pub fn get_rfkill_states(
+    command_runner: &dyn CommandRunner,
+) -> Result<Vec<(String, bool)>, Box<dyn Error>> {
+    let output = command_runner.run_query("rfkill", &["-n", "-o", "TYPE,SOFT", "list"])?;
+
+    if !output.status.success() {
+        return Ok(Vec::new());
+    }
+
+    let states = read_output_lines(&output)?
+        .into_iter()
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            let kind = parts.next()?.to_string();
+            let soft = parts.next()?;
+            Some((kind, soft == "blocked"))
+        })
+        .collect();
+    Ok(states)
+}
+
+/// Checks if every radio of the given type is soft-blocked.
+pub fn is_rfkill_blocked(states: &[(String, bool)], kind: &str) -> bool {
+    let mut radios = states.iter().filter(|(radio, _)| radio == kind).peekable();
+    radios.peek().is_some() && radios.all(|(_, blocked)| *blocked)
+}
+
+/// Checks if airplane mode is on, i.e. all present airplane radios are soft-blocked.
+pub fn is_airplane_mode_on(states: &[(String, bool)]) -> bool {
+    let mut radios = states
+        .iter()
+        .filter(|(radio, _)| AIRPLANE_RADIOS.contains(&radio.as_str()))
+        .peekable();
+    radios.peek().is_some() && radios.all(|(_, blocked)| *blocked)
+}
+
+/// Blocks or unblocks the given radio types.
+pub fn set_rfkill_blocked(
+    kinds: &[&str],
+    block: bool,
+    command_runner: &dyn CommandRunner,
+) -> Result<bool, Box<dyn Error>> {
+    let mut success = true;
+    for kind in kinds {
+        let status = command_runner
+            .run_privileged("rfkill", &[if block { "block" } else { "unblock" }, kind])?
+            .status;
+        success &= status.success();
+    }
+    Ok(success)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockCommandRunner;
+
+    const LIST: &[&str] = &["-n", "-o", "TYPE,SOFT", "list"];
+
+    #[test]
+    fn reads_radio_states() {
+        let runner = MockCommandRunner::new().expect_fixture("rfkill", LIST, "rfkill/list.txt");
+        let states = get_rfkill_states(&runner).unwrap();
+
+        assert!(is_rfkill_blocked(&states, "bluetooth"));
+        assert!(!is_rfkill_blocked(&states, "wlan"));
+        assert!(!is_rfkill_blocked(&states, "nfc"));
+        assert!(!is_airplane_mode_on(&states));
+    }
+
+    #[test]
+    fn detects_airplane_mode() {
+        let runner =
+            MockCommandRunner::new().expect_fixture("rfkill", LIST, "rfkill/list-airplane.txt");
+        assert!(is_airplane_mode_on(&get_rfkill_states(&runner).unwrap()));
+        assert!(!is_airplane_mode_on(&[]));
+    }
+
+    #[test]
+    fn blocks_each_radio() {
+        let runner = MockCommandRunner::new()
+            .expect("rfkill", &["block", "wlan"], "")
+            .expect_failure("rfkill", &["block", "bluetooth"]);
+        assert!(!set_rfkill_blocked(&["wlan", "bluetooth"], true, &runner).unwrap());
+    }
+}
@@ -0,0 +1,132 @@
+use crate::i18n::{tr, tr_args};
+use crate::notifications::notify;
+use crate::utils::prompt_for_password;
+use crate::wifi::ConnectionOutcome;
+use crate::{confirm, Config};
+use secret_service::blocking::SecretService;
+use secret_service::EncryptionType;
+use std::collections::HashMap;
+use std::error::Error;
+use tracing::warn;
+
+/// Returns the Secret Service attributes identifying the password of an SSID.
+fn wifi_attributes(ssid: &str) -> HashMap<&str, &str> {
+    HashMap::from([("application", "network-dmenu"), ("ssid", ssid)])
+}
+
+/// Looks up the stored password of an SSID in the keyring.
+pub fn lookup_wifi_secret(ssid: &str) -> Option<String> {
+    let service = SecretService::connect(EncryptionType::Dh).ok()?;
+    let items = service.search_items(wifi_attributes(ssid)).ok()?;
+    let item = items.unlocked.into_iter().chain(items.locked).next()?;
+    item.unlock().ok()?;
+    let secret = item.get_secret().ok()?;
+    String::from_utf8(secret).ok()
+}
+
+/// Stores the password of an SSID in the default keyring collection.
+pub fn store_wifi_secret(ssid: &str, password: &str) -> Result<(), Box<dyn Error>> {
+    let service = SecretService::connect(EncryptionType::Dh)?;
+    let collection = service.get_default_collection()?;
+    collection.ensure_unlocked()?;
+    collection.create_item(
+        &format!("Wi-Fi password for {ssid}"),
+        wifi_attributes(ssid),
+        password.as_bytes(),
+        true,
+        "text/plain",
+    )?;
+    Ok(())
+}
+
+/// Deletes the stored passwords of an SSID from the keyring.
+pub fn delete_wifi_secret(ssid: &str) -> Result<(), Box<dyn Error>> {
+    let service = SecretService::connect(EncryptionType::Dh)?;
+    let items = service.search_items(wifi_attributes(ssid))?;
+    for item in items.unlocked.into_iter().chain(items.locked) {
+        item.delete()?;
+    }
+    Ok(())
+}
+
+/// Returns the keyring password of an SSID, or prompts for it.
+///
+/// The boolean is true when the password was prompted rather than found in the keyring.
+pub fn get_or_prompt_password(
+    config: &Config,
+    ssid: &str,
+) -> Result<(String, bool), Box<dyn Error>> {
+    match lookup_wifi_secret(ssid) {
+        Some(password) => Ok((password, false)),
+        None => Ok((prompt_for_password(config, ssid)?, true)),
+    }
+}
+
+/// Offers to remember a prompted password in the keyring.
+pub fn offer_to_remember(
+    config: &Config,
+    ssid: &str,
+    password: &str,
+) -> Result<(), Box<dyn Error>> {
+    if confirm(
+        config,
+        &tr_args("confirm-remember-password", &[("ssid", ssid)]),
+    )? {
+        store_wifi_secret(ssid, password)?;
+    }
+    Ok(())
+}
+
+/// Connects to a network, with its saved profile when known or else with the keyring
+/// password or a prompted one.
+///
+/// When the password is rejected, it is forgotten from the saved profile and the keyring
+/// and the password is prompted once more. The reason of a final failure is notified.
+pub fn connect_with_password_retry(
+    config: &Config,
+    ssid: &str,
+    known: bool,
+    attempt: impl Fn(Option<&str>) -> Result<ConnectionOutcome, Box<dyn Error>>,
+    forget: impl Fn(),
+) -> Result<bool, Box<dyn Error>> {
+    let (mut outcome, mut password, mut prompted) = if known {
+        (attempt(None)?, None, false)
+    } else {
+        let (password, prompted) = get_or_prompt_password(config, ssid)?;
+        (attempt(Some(&password))?, Some(password), prompted)
+    };
+
+    if let ConnectionOutcome::AuthFailed(reason) = &outcome {
+        warn!("password of {ssid} rejected: {reason}");
+        forget();
+        if let Err(err) = delete_wifi_secret(ssid) {
+            warn!("failed to delete the stored password of {ssid}: {err}");
+        }
+        let retried = prompt_for_password(config, ssid)?;
+        if retried.is_empty() {
+            return Ok(false);
+        }
+        outcome = attempt(Some(&retried))?;
+        password = Some(retried);
+        prompted = true;
+    }
+
+    match (outcome, password) {
+        (ConnectionOutcome::Connected, Some(password)) if prompted => {
+            offer_to_remember(config, ssid, &password)?;
+            Ok(true)
+        }
+        (ConnectionOutcome::Connected, _) => Ok(true),
+        (ConnectionOutcome::AuthFailed(reason) | ConnectionOutcome::Failed(reason), _) => {
+            notify(
+                config,
+                &tr("wifi"),
+                &tr_args(
+                    "wifi-connection-failed",
+                    &[("ssid", ssid), ("reason", &reason)],
+                ),
+            );
+            Ok(false)
+        }
+    }
+}
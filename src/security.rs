@@ -0,0 +1,293 @@
+use crate::command::CommandRunner;
+use crate::i18n::{tr, tr_args};
+use crate::networkmanager::set_nm_vpn_active;
+use crate::notifications::notify;
+use crate::tailscale::{set_exit_node, set_random_mullvad_exit_node};
+use crate::utils::write_state_file;
+use crate::Config;
+use dirs::state_dir;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+use tracing::warn;
+
+/// Key of the policy applied to the networks neither trusted nor having their own policy.
+pub const ANY_UNTRUSTED_NETWORK: &str = "*";
+
+/// Wi-Fi security settings.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct SecurityConfig {
+    #[serde(default)]
+    pub untrusted_wifi_action: UntrustedWifiAction,
+    /// Open networks not needing the untrusted Wi-Fi action, e.g. a home network.
+    #[serde(default)]
+    pub trusted_networks: Vec<String>,
+    /// Tailscale policies applied when connecting to the networks, by SSID or
+    /// [`ANY_UNTRUSTED_NETWORK`].
+    #[serde(default)]
+    pub network_policies: HashMap<String, TailscalePolicy>,
+}
+
+/// Tailscale behavior applied when connecting to a network.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TailscalePolicy {
+    /// Stop using an exit node.
+    DisableExitNode,
+    /// Use the given exit node.
+    ExitNode(String),
+    /// Use a random mullvad exit node of the given country.
+    Mullvad(String),
+}
+
+impl TailscalePolicy {
+    /// Describes the policy in the menu and notifications.
+    pub fn describe(&self) -> String {
+        match self {
+            TailscalePolicy::DisableExitNode => tr("policy-disable-exit-node"),
+            TailscalePolicy::ExitNode(node) => tr_args("policy-exit-node", &[("node", node)]),
+            TailscalePolicy::Mullvad(country) => tr_args("policy-mullvad", &[("country", country)]),
+        }
+    }
+
+    /// Applies the policy, returning whether it succeeded.
+    pub fn apply(
+        &self,
+        config: &Config,
+        command_runner: &dyn CommandRunner,
+    ) -> Result<bool, Box<dyn Error>> {
+        match self {
+            TailscalePolicy::DisableExitNode => Ok(command_runner
+                .run_command("tailscale", &["set", "--exit-node="])?
+                .status
+                .success()),
+            TailscalePolicy::ExitNode(node) => Ok(set_exit_node(config, node)),
+            TailscalePolicy::Mullvad(country) => {
+                set_random_mullvad_exit_node(config, Some(country), command_runner)
+            }
+        }
+    }
+}
+
+/// Action taken when joining an open Wi-Fi network.
+#[derive(Debug, Default, Deserialize, Serialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum UntrustedWifiAction {
+    /// Neither warn nor protect.
+    None,
+    /// Warn about the unencrypted network.
+    #[default]
+    Warn,
+    /// Warn and route the traffic through a Tailscale exit node.
+    ExitNode(String),
+    /// Warn and activate a NetworkManager VPN connection.
+    Vpn(String),
+}
+
+/// Checks if a Wi-Fi security type leaves the traffic unencrypted.
+pub fn is_open_security(security: &str) -> bool {
+    let security = security.trim();
+    security.is_empty() || security == "--" || security.eq_ignore_ascii_case("open")
+}
+
+/// Gets the path of the file remembering the protection enabled on an open network.
+fn get_protection_path() -> Option<PathBuf> {
+    Some(
+        state_dir()?
+            .join("network-dmenu")
+            .join("untrusted-wifi-protection.json"),
+    )
+}
+
+/// Reads the protection enabled automatically and not reverted yet, if any.
+fn read_protection() -> Option<UntrustedWifiAction> {
+    let content = fs::read_to_string(get_protection_path()?).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+/// Remembers the protection enabled automatically, to revert it later.
+fn write_protection(action: &UntrustedWifiAction) -> Result<(), Box<dyn Error>> {
+    write_state_file(get_protection_path(), &serde_json::to_string(action)?)
+}
+
+/// Checks if a network is listed as trusted.
+fn is_trusted(config: &Config, ssid: &str) -> bool {
+    config
+        .security
+        .trusted_networks
+        .iter()
+        .any(|trusted| trusted == ssid)
+}
+
+/// Gets the Tailscale policy of a network, falling back to the one of untrusted networks.
+pub fn network_policy<'a>(config: &'a Config, ssid: &str) -> Option<&'a TailscalePolicy> {
+    let policies = &config.security.network_policies;
+    policies.get(ssid).or_else(|| {
+        (!is_trusted(config, ssid))
+            .then(|| policies.get(ANY_UNTRUSTED_NETWORK))
+            .flatten()
+    })
+}
+
+/// Describes the policy in force on a network, if it has one or is trusted.
+pub fn describe_network_policy(config: &Config, ssid: &str) -> Option<String> {
+    match network_policy(config, ssid) {
+        Some(policy) => Some(policy.describe()),
+        None => is_trusted(config, ssid).then(|| tr("policy-trusted")),
+    }
+}
+
+/// Applies the Tailscale policy of a network just joined, or else warns about an open
+/// untrusted network and enables the configured protection, reverting that protection
+/// once on a secured or trusted network.
+pub fn apply_wifi_security(
+    config: &Config,
+    ssid: &str,
+    security: &str,
+    command_runner: &dyn CommandRunner,
+) {
+    if let Some(policy) = network_policy(config, ssid) {
+        // The policy sets the exit node itself, superseding any protection
+        if let Some(path) = get_protection_path() {
+            let _ = fs::remove_file(path);
+        }
+        apply_network_policy(config, ssid, policy, command_runner);
+    } else if is_open_security(security) && !is_trusted(config, ssid) {
+        protect_untrusted_wifi(config, ssid, command_runner);
+    } else if let Some(protection) = read_protection() {
+        revert_protection(config, &protection, command_runner);
+    }
+}
+
+/// Applies the Tailscale policy of a network, notifying it.
+pub fn apply_network_policy(
+    config: &Config,
+    ssid: &str,
+    policy: &TailscalePolicy,
+    command_runner: &dyn CommandRunner,
+) -> bool {
+    let applied = policy
+        .apply(config, command_runner)
+        .map_err(|err| warn!("failed to apply the policy of {ssid}: {err}"))
+        .unwrap_or(false);
+    if applied {
+        notify(
+            config,
+            &tr("security"),
+            &tr_args(
+                "policy-applied",
+                &[("ssid", ssid), ("policy", &policy.describe())],
+            ),
+        );
+    }
+    applied
+}
+
+/// Warns about an open network and enables the configured protection.
+fn protect_untrusted_wifi(config: &Config, ssid: &str, command_runner: &dyn CommandRunner) {
+    let action = &config.security.untrusted_wifi_action;
+    let enabled = match action {
+        UntrustedWifiAction::None => return,
+        UntrustedWifiAction::Warn => false,
+        UntrustedWifiAction::ExitNode(node) => set_exit_node(config, node),
+        UntrustedWifiAction::Vpn(name) => set_nm_vpn_active(name, true, command_runner)
+            .map_err(|err| warn!("failed to activate {name}: {err}"))
+            .unwrap_or(false),
+    };
+    if enabled {
+        if let Err(err) = write_protection(action) {
+            warn!("failed to remember the protection of {ssid}: {err}");
+        }
+    }
+
+    let message = match action {
+        UntrustedWifiAction::ExitNode(name) | UntrustedWifiAction::Vpn(name) if enabled => {
+            tr_args("security-open-protected", &[("ssid", ssid), ("name", name)])
+        }
+        _ => tr_args("security-open-network", &[("ssid", ssid)]),
+    };
+    notify(config, &tr("security"), &message);
+}
+
+/// Disables the protection enabled on an open network.
+fn revert_protection(
+    config: &Config,
+    protection: &UntrustedWifiAction,
+    command_runner: &dyn CommandRunner,
+) {
+    let reverted = match protection {
+        UntrustedWifiAction::ExitNode(_) => command_runner
+            .run_command("tailscale", &["set", "--exit-node="])
+            .is_ok_and(|output| output.status.success()),
+        UntrustedWifiAction::Vpn(name) => {
+            set_nm_vpn_active(name, false, command_runner).unwrap_or(false)
+        }
+        UntrustedWifiAction::None | UntrustedWifiAction::Warn => true,
+    };
+    if !reverted {
+        warn!("failed to revert the open network protection");
+        return;
+    }
+    if let Some(path) = get_protection_path() {
+        let _ = fs::remove_file(path);
+    }
+    notify(config, &tr("security"), &tr("security-protection-reverted"));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_untrusted_wifi_actions() {
+        let config: SecurityConfig =
+            toml::from_str("untrusted_wifi_action = { exit_node = \"100.64.0.1\" }").unwrap();
+        assert_eq!(
+            config.untrusted_wifi_action,
+            UntrustedWifiAction::ExitNode("100.64.0.1".to_string())
+        );
+        let config: SecurityConfig = toml::from_str("untrusted_wifi_action = \"none\"").unwrap();
+        assert_eq!(config.untrusted_wifi_action, UntrustedWifiAction::None);
+        assert_eq!(
+            SecurityConfig::default().untrusted_wifi_action,
+            UntrustedWifiAction::Warn
+        );
+
+        let config: SecurityConfig = toml::from_str(
+            "trusted_networks = [\"Home\"]\n\
+             [network_policies]\n\
+             Home = \"disable_exit_node\"\n\
+             Airport = { mullvad = \"Sweden\" }",
+        )
+        .unwrap();
+        assert_eq!(config.trusted_networks, ["Home"]);
+        assert_eq!(
+            config.network_policies["Home"],
+            TailscalePolicy::DisableExitNode
+        );
+        assert_eq!(
+            config.network_policies["Airport"],
+            TailscalePolicy::Mullvad("Sweden".to_string())
+        );
+
+        let config: Config = toml::from_str(
+            "[security]\n\
+             trusted_networks = [\"Home\"]\n\
+             network_policies = { Office = \"disable_exit_node\", \"*\" = { exit_node = \"relay\" } }",
+        )
+        .unwrap();
+        let relay = TailscalePolicy::ExitNode("relay".to_string());
+        assert_eq!(network_policy(&config, "Cafe"), Some(&relay));
+        assert_eq!(
+            network_policy(&config, "Office"),
+            Some(&TailscalePolicy::DisableExitNode)
+        );
+        assert_eq!(network_policy(&config, "Home"), None);
+
+        assert!(is_open_security(""));
+        assert!(is_open_security("open"));
+        assert!(!is_open_security("WPA2"));
+    }
+}
@@ -0,0 +1,112 @@
+use crate::command::{is_command_installed, CommandRunner};
+use crate::config::MenuSection;
+use crate::networkmanager::is_nm_running;
+use crate::tailscale::is_tailscaled_running;
+use crate::utils::write_state_file;
+use crate::ActionType;
+use dirs::state_dir;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use tracing::warn;
+
+/// Daemon some menu sections are read from, whose last entries are kept for its restarts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Backend {
+    NetworkManager,
+    Tailscale,
+}
+
+impl Backend {
+    /// Returns the daemon a section is read from, if snapshotted.
+    pub fn of(section: MenuSection) -> Option<Backend> {
+        match section {
+            MenuSection::Wifi | MenuSection::Ip => Some(Backend::NetworkManager),
+            MenuSection::Tailscale | MenuSection::Peers => Some(Backend::Tailscale),
+            _ => None,
+        }
+    }
+
+    /// Checks if the daemon answers; without `nmcli`, the Wi-Fi is not read from
+    /// NetworkManager.
+    pub fn is_reachable(self, command_runner: &dyn CommandRunner) -> bool {
+        match self {
+            Backend::NetworkManager => {
+                !is_command_installed("nmcli") || is_nm_running(command_runner)
+            }
+            Backend::Tailscale => is_tailscaled_running(command_runner),
+        }
+    }
+}
+
+/// Gets the path of the file holding the last entries of the snapshotted sections.
+fn get_snapshot_path() -> Option<PathBuf> {
+    Some(state_dir()?.join("network-dmenu").join("snapshot.json"))
+}
+
+/// Loads the last entries of the sections, as serialized actions.
+fn load_snapshot() -> HashMap<MenuSection, Value> {
+    get_snapshot_path()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+/// Returns the last entries of a section, marked as stale.
+pub fn get_stale_actions(section: MenuSection) -> Vec<ActionType> {
+    load_snapshot()
+        .remove(&section)
+        .map(mark_stale)
+        .unwrap_or_default()
+}
+
+/// Reads serialized actions back, marking them as stale.
+fn mark_stale(actions: Value) -> Vec<ActionType> {
+    serde_json::from_value::<Vec<ActionType>>(actions)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|action| ActionType::Stale(Box::new(action)))
+        .collect()
+}
+
+/// Replaces the last entries of the freshly read sections, logging failures.
+pub fn save_snapshot(sections: HashMap<MenuSection, Value>) {
+    if sections.is_empty() {
+        return;
+    }
+    let mut snapshot = load_snapshot();
+    if sections
+        .iter()
+        .all(|(section, entries)| snapshot.get(section) == Some(entries))
+    {
+        return;
+    }
+    snapshot.extend(sections);
+    let result = serde_json::to_string(&snapshot)
+        .map_err(Into::into)
+        .and_then(|json| write_state_file(get_snapshot_path(), &json));
+    if let Err(err) = result {
+        warn!("failed to save the menu snapshot: {err}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tailscale::TailscaleAction;
+
+    #[test]
+    fn restores_snapshotted_actions_as_stale() {
+        assert_eq!(Backend::of(MenuSection::Peers), Some(Backend::Tailscale));
+        assert_eq!(Backend::of(MenuSection::Custom), None);
+
+        let actions = [ActionType::Tailscale(TailscaleAction::DisableExitNode)];
+        let stale = mark_stale(serde_json::to_value(&actions).unwrap());
+        assert!(matches!(
+            stale.as_slice(),
+            [ActionType::Stale(action)]
+                if matches!(**action, ActionType::Tailscale(TailscaleAction::DisableExitNode))
+        ));
+    }
+}
@@ -1,111 +1,319 @@
+use crate::columns::pad;
 use crate::command::{execute_command, is_command_installed, read_output_lines, CommandRunner};
-use crate::format_entry;
+use crate::connectivity::{get_expected_country, spawn_connectivity_check};
+use crate::i18n::{tr, tr_args};
+use crate::mullvad_relays::{
+    filter_exit_nodes, get_mullvad_filter, load_relays, select_mullvad_filter,
+};
+use crate::notifications::{new_notification, notify};
+use crate::utils::{copy_to_clipboard, random_index, write_private_file};
+use crate::{confirm, format_entry, prompt_from_menu, select_from_menu, Config};
 use notify_rust::Notification;
 use regex::Regex;
-use reqwest::Client;
-use reqwest_middleware::{ClientBuilder, ClientWithMiddleware};
-use reqwest_retry::{policies::ExponentialBackoff, RetryTransientMiddleware};
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::{HashMap, HashSet};
 use std::error::Error;
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::thread;
+use tracing::debug;
 
 /// Enum representing various Tailscale actions.
-#[derive(Debug)]
+#[derive(Debug, Deserialize, Serialize)]
 pub enum TailscaleAction {
     DisableExitNode,
-    SetEnable(bool),
+    Login,
+    Logout,
+    SetAdvertiseRoute(String, bool),
+    SetDriveShare(String, bool),
+    SetEnable(bool, Option<String>),
+    SetHostname(String),
+    SetOperator(String),
+    FilterMullvad(String),
+    SelectMullvadCountry(MullvadCountry),
+    SetRandomExitNode(Option<String>),
     SetExitNode(String),
-    SetShields(bool),
+    SetSuggestedExitNode,
+    CopyPeerIp(TailscalePeer),
+    PingPeer(TailscalePeer),
+    StartFunnel(String),
+    StopServe(ServePort),
 }
 
-/// Add a new parameter to pass the excluded exit nodes.
-pub fn get_mullvad_actions(
-    command_runner: &dyn CommandRunner,
-    exclude_exit_nodes: &[String],
-) -> Vec<String> {
-    let output = command_runner
-        .run_command("tailscale", &["exit-node", "list"])
-        .expect("Failed to execute command");
+/// Tailnet peer as reported by `tailscale status --json`.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct TailscalePeer {
+    pub name: String,
+    pub ip: String,
+    pub os: String,
+    pub online: bool,
+}
 
-    let active_exit_node = get_active_exit_node(command_runner);
+/// Port shared by `tailscale serve`, exposed to the internet when funneled.
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct ServePort {
+    pub port: u16,
+    pub protocol: String,
+    pub funnel: bool,
+}
 
-    let exclude_set: HashSet<_> = exclude_exit_nodes.iter().collect();
+/// Funnel started from the menu, serving `target` on the given port and path.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct FunnelPreset {
+    pub name: String,
+    #[serde(default = "default_funnel_port")]
+    pub port: u16,
+    #[serde(default = "default_funnel_path")]
+    pub path: String,
+    pub target: String,
+}
 
-    if output.status.success() {
-        let reader = read_output_lines(&output).unwrap_or_default();
-        let regex = Regex::new(r"\s{2,}").unwrap();
-
-        let mut actions: Vec<String> = reader
-            .into_iter()
-            .filter(|line| line.contains("mullvad.ts.net"))
-            .filter(|line| !exclude_set.contains(&extract_node_name(line)))
-            .map(|line| parse_mullvad_line(&line, &regex, &active_exit_node))
-            .collect();
+/// Directory shared with the tailnet through `tailscale drive`.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct DriveShare {
+    pub name: String,
+    pub path: String,
+}
 
-        let reader = read_output_lines(&output).unwrap_or_default();
-        actions.extend(
-            reader
-                .into_iter()
-                .filter(|line| line.contains("ts.net") && !line.contains("mullvad.ts.net"))
-                .filter(|line| !exclude_set.contains(&extract_node_name(line)))
-                .map(|line| parse_exit_node_line(&line, &regex, &active_exit_node)),
-        );
+/// Returns the default funnel port.
+fn default_funnel_port() -> u16 {
+    443
+}
 
-        actions.sort_by(|a, b| {
-            a.split_whitespace()
-                .next()
-                .cmp(&b.split_whitespace().next())
-        });
-        actions
-    } else {
-        Vec::new()
+/// Returns the default funnel mount path.
+fn default_funnel_path() -> String {
+    "/".to_string()
+}
+
+/// Retrieves the ports currently shared with `tailscale serve` or `tailscale funnel`.
+pub fn get_serve_ports(command_runner: &dyn CommandRunner) -> Vec<ServePort> {
+    command_runner
+        .run_query("tailscale", &["serve", "status", "--json"])
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| serde_json::from_slice::<Value>(&output.stdout).ok())
+        .map(|status| parse_serve_status(&status))
+        .unwrap_or_default()
+}
+
+/// Retrieves the names of the directories currently shared with `tailscale drive`.
+pub fn get_drive_shares(command_runner: &dyn CommandRunner) -> Vec<String> {
+    command_runner
+        .run_query("tailscale", &["drive", "list"])
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| read_output_lines(&output).ok())
+        .map(|lines| {
+            lines
+                .iter()
+                .skip_while(|line| !line.starts_with("---"))
+                .skip(1)
+                .filter_map(|line| line.split_whitespace().next())
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Default routes advertised along with the exit node, not handled as subnet routes.
+const EXIT_NODE_ROUTES: [&str; 2] = ["0.0.0.0/0", "::/0"];
+
+/// Retrieves the subnet routes advertised by this machine.
+pub fn get_advertised_routes(command_runner: &dyn CommandRunner) -> Vec<String> {
+    get_tailscale_prefs(command_runner)
+        .map(|prefs| parse_advertised_routes(&prefs))
+        .unwrap_or_default()
+}
+
+/// Reads the advertised subnet routes from the Tailscale preferences.
+fn parse_advertised_routes(prefs: &Value) -> Vec<String> {
+    prefs["AdvertiseRoutes"]
+        .as_array()
+        .map(|routes| {
+            routes
+                .iter()
+                .filter_map(Value::as_str)
+                .filter(|route| !EXIT_NODE_ROUTES.contains(route))
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Parses the output of `tailscale serve status --json`.
+fn parse_serve_status(status: &Value) -> Vec<ServePort> {
+    let Some(tcp) = status["TCP"].as_object() else {
+        return Vec::new();
+    };
+    let funnels: Vec<&str> = status["AllowFunnel"]
+        .as_object()
+        .map(|funnels| {
+            funnels
+                .iter()
+                .filter(|(_, allowed)| allowed.as_bool() == Some(true))
+                .map(|(host_port, _)| host_port.as_str())
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let mut ports: Vec<ServePort> = tcp
+        .iter()
+        .filter_map(|(port, handler)| {
+            let port: u16 = port.parse().ok()?;
+            let protocol = if handler["HTTPS"].as_bool() == Some(true) {
+                "https"
+            } else if handler["HTTP"].as_bool() == Some(true) {
+                "http"
+            } else {
+                "tcp"
+            };
+            Some(ServePort {
+                port,
+                protocol: protocol.to_string(),
+                funnel: funnels
+                    .iter()
+                    .any(|host_port| host_port.ends_with(&format!(":{port}"))),
+            })
+        })
+        .collect();
+    ports.sort_by_key(|serve| serve.port);
+    ports
+}
+
+/// Exit node as listed by `tailscale exit-node list`.
+#[derive(Debug, Clone)]
+pub struct ExitNode {
+    pub ip: String,
+    pub name: String,
+    pub country: String,
+    pub city: String,
+    pub active: bool,
+}
+
+impl ExitNode {
+    /// Checks if the exit node is a mullvad one.
+    pub fn is_mullvad(&self) -> bool {
+        self.name.contains("mullvad.ts.net")
+    }
+
+    /// Formats the exit node as a menu entry.
+    pub fn to_entry(&self) -> String {
+        if self.is_mullvad() {
+            format_entry(
+                "mullvad",
+                if self.active {
+                    "✅"
+                } else {
+                    get_flag(&self.country)
+                },
+                &format!("{} - {:<16} {}", pad(&self.country, 15), self.ip, self.name),
+            )
+        } else {
+            format_entry(
+                "exit-node",
+                if self.active { "✅" } else { "🌿" },
+                &format!(
+                    "{} - {:<16} {}",
+                    pad(extract_short_name(&self.name), 15),
+                    self.ip,
+                    self.name
+                ),
+            )
+        }
+    }
+
+    /// Formats the exit node as an entry of its country menu.
+    fn to_city_entry(&self) -> String {
+        format_entry(
+            "mullvad",
+            if self.active {
+                "✅"
+            } else {
+                get_flag(&self.country)
+            },
+            &format!("{} - {:<16} {}", pad(&self.city, 15), self.ip, self.name),
+        )
     }
 }
 
-/// Helper function to extract node name from the action line.
-fn extract_node_name(line: &str) -> String {
-    let parts: Vec<&str> = line.split_whitespace().collect();
-    parts.get(1).unwrap_or(&"").to_string()
+/// Mullvad country grouping its exit nodes in a second menu.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct MullvadCountry {
+    pub name: String,
+    pub nodes: usize,
+    pub active: bool,
 }
 
-/// Checks Mullvad connection status and sends a notification.
-pub async fn check_mullvad() -> Result<(), Box<dyn Error>> {
-    // Create a retry policy with exponential backoff
-    let retry_policy = ExponentialBackoff::builder().build_with_max_retries(3);
+/// Retrieves the exit nodes, other nodes first then mullvad ones, without the excluded ones.
+pub fn get_exit_nodes(
+    command_runner: &dyn CommandRunner,
+    exclude_exit_nodes: &[String],
+) -> Vec<ExitNode> {
+    let Ok(output) = command_runner.run_query("tailscale", &["exit-node", "list"]) else {
+        return Vec::new();
+    };
+    if !output.status.success() {
+        return Vec::new();
+    }
 
-    // Build a client with retry middleware
-    let client: ClientWithMiddleware = ClientBuilder::new(Client::new())
-        .with(RetryTransientMiddleware::new_with_policy(retry_policy))
-        .build();
+    let active_exit_node = get_active_exit_node(command_runner);
+    let exclude_set: HashSet<_> = exclude_exit_nodes.iter().collect();
+    let regex = Regex::new(r"\s{2,}").unwrap();
 
-    // Make a request and handle retries automatically
-    let response = client
-        .get("https://am.i.mullvad.net/connected")
-        .send()
-        .await?
-        .text()
-        .await?;
+    let mut nodes: Vec<ExitNode> = read_output_lines(&output)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|line| line.contains("ts.net"))
+        .filter(|line| !exclude_set.contains(&extract_node_name(line)))
+        .map(|line| parse_exit_node_line(&line, &regex, &active_exit_node))
+        .collect();
+    nodes.sort_by_key(ExitNode::is_mullvad);
+    nodes
+}
 
-    Notification::new()
-        .summary("Connected Status")
-        .body(response.trim())
-        .show()?;
+/// Retrieves the exit nodes, without the excluded ones and the mullvad ones filtered out.
+pub fn get_allowed_exit_nodes(
+    config: &Config,
+    command_runner: &dyn CommandRunner,
+) -> Vec<ExitNode> {
+    let nodes = get_exit_nodes(command_runner, &config.exclude_exit_node);
+    let filter = get_mullvad_filter(config);
+    if filter.is_empty() {
+        return nodes;
+    }
+    filter_exit_nodes(nodes, &filter, &load_relays())
+}
 
-    Ok(())
+/// Groups the mullvad exit nodes by country, in alphabetical order.
+pub fn group_mullvad_countries(nodes: &[ExitNode]) -> Vec<MullvadCountry> {
+    let mut countries: Vec<MullvadCountry> = Vec::new();
+    for node in nodes.iter().filter(|node| node.is_mullvad()) {
+        match countries
+            .iter_mut()
+            .find(|country| country.name == node.country)
+        {
+            Some(country) => {
+                country.nodes += 1;
+                country.active |= node.active;
+            }
+            None => countries.push(MullvadCountry {
+                name: node.country.clone(),
+                nodes: 1,
+                active: node.active,
+            }),
+        }
+    }
+    countries.sort_by(|a, b| a.name.cmp(&b.name));
+    countries
 }
 
-/// Parses a Mullvad line from the Tailscale exit-node list output.
-fn parse_mullvad_line(line: &str, regex: &Regex, active_exit_node: &str) -> String {
-    let parts: Vec<&str> = regex.split(line).collect();
-    let node_ip = parts.first().unwrap_or(&"").trim();
-    let node_name = parts.get(1).unwrap_or(&"").trim();
-    let country = parts.get(2).unwrap_or(&"").trim();
-    let is_active = active_exit_node == node_name;
-    format_entry(
-        "mullvad",
-        if is_active { "✅" } else { get_flag(country) },
-        &format!("{country:<15} - {node_ip:<16} {node_name}"),
-    )
+/// Helper function to extract node name from the action line.
+fn extract_node_name(line: &str) -> String {
+    let parts: Vec<&str> = line.split_whitespace().collect();
+    parts.get(1).unwrap_or(&"").to_string()
 }
 
 /// Extracts the short name from a node name.
@@ -114,26 +322,28 @@ fn extract_short_name(node_name: &str) -> &str {
 }
 
 /// Parses an exit node line from the Tailscale exit-node list output.
-fn parse_exit_node_line(line: &str, regex: &Regex, active_exit_node: &str) -> String {
-    let parts: Vec<&str> = regex.split(line).collect();
-    let node_ip = parts.first().unwrap_or(&"").trim();
-    let node_name = parts.get(1).unwrap_or(&"").trim();
-    let node_short_name = extract_short_name(node_name);
-    let is_active = active_exit_node == node_name;
-    format_entry(
-        "exit-node",
-        if is_active { "✅" } else { "🌿" },
-        &format!("{node_short_name:<15} - {node_ip:<16} {node_name}"),
-    )
+fn parse_exit_node_line(line: &str, regex: &Regex, active_exit_node: &str) -> ExitNode {
+    let parts: Vec<&str> = regex.split(line.trim()).collect();
+    let part = |index: usize| parts.get(index).unwrap_or(&"").trim().to_string();
+    let name = part(1);
+    ExitNode {
+        active: active_exit_node == name,
+        ip: part(0),
+        name,
+        country: part(2),
+        city: part(3),
+    }
 }
 
 /// Retrieves the currently active exit node for Tailscale.
-fn get_active_exit_node(command_runner: &dyn CommandRunner) -> String {
-    let output = command_runner
-        .run_command("tailscale", &["status", "--json"])
-        .expect("failed to execute process");
-
-    let json: Value = serde_json::from_slice(&output.stdout).expect("failed to parse JSON");
+pub fn get_active_exit_node(command_runner: &dyn CommandRunner) -> String {
+    let Some(json) = command_runner
+        .run_query("tailscale", &["status", "--json"])
+        .ok()
+        .and_then(|output| serde_json::from_slice::<Value>(&output.stdout).ok())
+    else {
+        return String::new();
+    };
 
     if let Some(peers) = json.get("Peer") {
         if let Some(peers_map) = peers.as_object() {
@@ -152,32 +362,157 @@ fn get_active_exit_node(command_runner: &dyn CommandRunner) -> String {
     String::new()
 }
 
+/// Retrieves the output of `tailscale status --json`.
+pub fn get_tailscale_status(command_runner: &dyn CommandRunner) -> Option<Value> {
+    command_runner
+        .run_query("tailscale", &["status", "--json"])
+        .ok()
+        .and_then(|output| serde_json::from_slice(&output.stdout).ok())
+}
+
+/// Retrieves the tailnet peers, online ones first, leaving out mullvad exit nodes.
+pub fn get_peers(command_runner: &dyn CommandRunner) -> Vec<TailscalePeer> {
+    get_tailscale_status(command_runner)
+        .map(|status| parse_peers(&status))
+        .unwrap_or_default()
+}
+
+/// Parses the tailnet peers of `tailscale status --json`.
+pub fn parse_peers(json: &Value) -> Vec<TailscalePeer> {
+    let mut peers: Vec<TailscalePeer> = json["Peer"]
+        .as_object()
+        .map(|peers| {
+            peers
+                .values()
+                .filter(|peer| {
+                    !peer["DNSName"]
+                        .as_str()
+                        .is_some_and(|name| name.contains("mullvad.ts.net"))
+                })
+                .filter_map(|peer| {
+                    Some(TailscalePeer {
+                        name: peer["HostName"].as_str()?.to_string(),
+                        ip: peer["TailscaleIPs"].get(0)?.as_str()?.to_string(),
+                        os: peer["OS"].as_str().unwrap_or_default().to_string(),
+                        online: peer["Online"].as_bool().unwrap_or(false),
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    peers.sort_by(|a, b| b.online.cmp(&a.online).then_with(|| a.name.cmp(&b.name)));
+    peers
+}
+
+/// Summarizes the path and latency of the last pong of `tailscale ping`.
+fn parse_ping_output(output: &str) -> Option<String> {
+    let pong = output
+        .lines()
+        .rev()
+        .find(|line| line.starts_with("pong from"))?;
+    let (_, path) = pong.split_once(" via ")?;
+    let (via, latency) = path.split_once(" in ")?;
+    Some(if via.starts_with("DERP(") {
+        format!("relayed through {via}, {latency}")
+    } else {
+        format!("direct via {via}, {latency}")
+    })
+}
+
+/// Retrieves the exit node suggested by `tailscale exit-node suggest`.
+fn get_suggested_exit_node(command_runner: &dyn CommandRunner) -> Option<String> {
+    let output = command_runner
+        .run_query("tailscale", &["exit-node", "suggest"])
+        .ok()
+        .filter(|output| output.status.success())?;
+    read_output_lines(&output)
+        .ok()?
+        .iter()
+        .find_map(|line| line.strip_prefix("Suggested exit node:"))
+        .map(|node| node.trim().trim_end_matches('.').to_string())
+        .filter(|node| !node.is_empty())
+}
+
+/// Formats the entry picking a random mullvad node, in the given country or anywhere.
+pub fn random_node_entry(country: Option<&str>) -> String {
+    format_entry(
+        "mullvad",
+        "🎲",
+        &match country {
+            Some(country) => tr_args("mullvad-random-node-in", &[("country", country)]),
+            None => tr("mullvad-random-node"),
+        },
+    )
+}
+
+/// Sets a random exit node among the given ones, avoiding the active one to rotate the IP.
+fn apply_random_exit_node(config: &Config, nodes: Vec<ExitNode>) -> Result<bool, Box<dyn Error>> {
+    let candidates: Vec<ExitNode> = if nodes.len() > 1 {
+        nodes.into_iter().filter(|node| !node.active).collect()
+    } else {
+        nodes
+    };
+    if candidates.is_empty() {
+        notify(config, &tr("tailscale"), &tr("tailscale-no-exit-node"));
+        return Ok(false);
+    }
+    let node = &candidates[random_index(candidates.len())?];
+    apply_exit_node(config, &node.to_entry())
+}
+
+/// Sets a random mullvad exit node, in the given country or anywhere.
+pub fn set_random_mullvad_exit_node(
+    config: &Config,
+    country: Option<&str>,
+    command_runner: &dyn CommandRunner,
+) -> Result<bool, Box<dyn Error>> {
+    let nodes = get_allowed_exit_nodes(config, command_runner)
+        .into_iter()
+        .filter(|node| node.is_mullvad())
+        .filter(|node| country.is_none_or(|name| node.country.eq_ignore_ascii_case(name)))
+        .collect();
+    apply_random_exit_node(config, nodes)
+}
+
+/// Sets the exit node and checks the connectivity through it.
+fn apply_exit_node(config: &Config, node: &str) -> Result<bool, Box<dyn Error>> {
+    if set_exit_node(config, node) {
+        spawn_connectivity_check(config, get_expected_country(node).as_deref())?;
+        Ok(true)
+    } else {
+        spawn_connectivity_check(config, None)?;
+        Ok(false)
+    }
+}
+
 /// Sets the exit node for Tailscale.
-fn set_exit_node(action: &str) -> bool {
-    let Some(node_ip) = extract_node_ip(action) else {
+pub fn set_exit_node(config: &Config, action: &str) -> bool {
+    let node_ip = extract_node_ip(action).unwrap_or(action.trim());
+    if node_ip.is_empty() {
         return false;
-    };
+    }
 
-    #[cfg(debug_assertions)]
-    println!("Exit-node ip address: {node_ip}");
+    debug!("Exit-node ip address: {node_ip}");
 
     if !execute_command("tailscale", &["up"]) {
         return false;
     }
 
-    execute_command(
-        "tailscale",
-        &[
-            "set",
-            "--exit-node",
-            node_ip,
-            "--exit-node-allow-lan-access=true",
-        ],
-    )
+    let mut args = vec![
+        "set".to_string(),
+        "--exit-node".to_string(),
+        node_ip.to_string(),
+    ];
+    // Unset, the LAN access is left as toggled from the menu
+    if let Some(allow) = config.exit_node_allow_lan_access {
+        args.push(format!("--exit-node-allow-lan-access={allow}"));
+    }
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    execute_command("tailscale", &args)
 }
 
 /// Extracts the IP address from the action string.
-fn extract_node_ip(action: &str) -> Option<&str> {
+pub fn extract_node_ip(action: &str) -> Option<&str> {
     Regex::new(r"\b\d{1,3}\.\d{1,3}\.\d{1,3}\.\d{1,3}\b")
         .ok()?
         .captures(action)
@@ -186,7 +521,7 @@ fn extract_node_ip(action: &str) -> Option<&str> {
 }
 
 /// Returns the flag emoji for a given country.
-fn get_flag(country: &str) -> &'static str {
+pub fn get_flag(country: &str) -> &'static str {
     let country_flags: HashMap<&str, &str> = [
         ("Albania", "🇦🇱"),
         ("Australia", "🇦🇺"),
@@ -243,7 +578,7 @@ fn get_flag(country: &str) -> &'static str {
 
 /// Checks if an exit node is currently active for Tailscale.
 pub fn is_exit_node_active(command_runner: &dyn CommandRunner) -> Result<bool, Box<dyn Error>> {
-    let output = command_runner.run_command("tailscale", &["status"])?;
+    let output = command_runner.run_query("tailscale", &["status"])?;
 
     if output.status.success() {
         let reader = read_output_lines(&output)?;
@@ -256,8 +591,97 @@ pub fn is_exit_node_active(command_runner: &dyn CommandRunner) -> Result<bool, B
     Ok(false)
 }
 
+/// Login server and auth key arguments of `tailscale up` and `tailscale login`.
+///
+/// The auth key is passed in a file only readable by the user, rather than on the command
+/// line seen by every user, and removed once the arguments are dropped.
+struct AuthArgs {
+    args: Vec<String>,
+    key_file: Option<PathBuf>,
+}
+
+impl Drop for AuthArgs {
+    fn drop(&mut self) {
+        if let Some(path) = &self.key_file {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+/// Returns the login server and auth key arguments, running `tailscale_auth_key_cmd`.
+fn get_auth_args(config: &Config) -> Result<AuthArgs, Box<dyn Error>> {
+    let mut auth = AuthArgs {
+        args: Vec::new(),
+        key_file: None,
+    };
+    if let Some(login_server) = &config.tailscale_login_server {
+        auth.args.push(format!("--login-server={login_server}"));
+    }
+    if let Some(auth_key_cmd) = &config.tailscale_auth_key_cmd {
+        let output = Command::new("sh").arg("-c").arg(auth_key_cmd).output()?;
+        if !output.status.success() {
+            return Err("Failed to run tailscale_auth_key_cmd".into());
+        }
+        let auth_key = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        let path = write_private_file("network-dmenu-tailscale-authkey", auth_key.as_bytes())?;
+        auth.args
+            .push(format!("--authkey=file:{}", path.to_string_lossy()));
+        auth.key_file = Some(path);
+    }
+    Ok(auth)
+}
+
+/// Logs in to Tailscale, surfacing the authentication URL as a notification.
+fn login(config: &Config) -> Result<bool, Box<dyn Error>> {
+    let auth = get_auth_args(config)?;
+    let mut child = Command::new("tailscale")
+        .arg("login")
+        .args(&auth.args)
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    let stderr = child.stderr.take().ok_or("Failed to open stderr")?;
+    for line in BufReader::new(stderr).lines() {
+        let line = line?;
+        if let Some(url) = line
+            .split_whitespace()
+            .find(|word| word.starts_with("https://"))
+        {
+            let notification = new_notification(
+                config,
+                &tr("tailscale"),
+                &tr_args("tailscale-authenticate", &[("url", url)]),
+            );
+            if config.notifications.enabled {
+                let url = url.to_string();
+                thread::spawn(move || notify_auth_url(notification, &url));
+            }
+        }
+    }
+
+    Ok(child.wait()?.success())
+}
+
+/// Sends a notification with the Tailscale authentication URL, opened on click.
+fn notify_auth_url(
+    mut notification: Notification,
+    url: &str,
+) -> Result<(), notify_rust::error::Error> {
+    notification
+        .action("default", "Open")
+        .show()?
+        .wait_for_action(|action| {
+            if action == "default" {
+                let _ = Command::new("xdg-open").arg(url).spawn();
+            }
+        });
+    Ok(())
+}
+
 /// Handles a Tailscale action.
 pub async fn handle_tailscale_action(
+    config: &Config,
     action: &TailscaleAction,
     command_runner: &dyn CommandRunner,
 ) -> Result<bool, Box<dyn Error>> {
@@ -270,32 +694,196 @@ pub async fn handle_tailscale_action(
             let status = command_runner
                 .run_command("tailscale", &["set", "--exit-node="])?
                 .status;
-            check_mullvad().await?;
+            spawn_connectivity_check(config, None)?;
+            Ok(status.success())
+        }
+        TailscaleAction::Login => login(config),
+        TailscaleAction::Logout => {
+            if !confirm(config, &tr("confirm-logout"))? {
+                return Ok(false);
+            }
+            let status = command_runner.run_command("tailscale", &["logout"])?.status;
+            Ok(status.success())
+        }
+        TailscaleAction::SetEnable(true, _) => {
+            let auth = get_auth_args(config)?;
+            let mut args = vec!["up"];
+            args.extend(auth.args.iter().map(String::as_str));
+            let status = command_runner.run_command("tailscale", &args)?.status;
             Ok(status.success())
         }
-        TailscaleAction::SetEnable(enable) => {
+        TailscaleAction::SetHostname(current) => {
+            let hostname = prompt_from_menu(
+                config,
+                &tr_args("prompt-tailscale-hostname", &[("hostname", current)]),
+            )?;
+            let hostname = hostname.trim();
+            if hostname.is_empty() {
+                return Ok(false);
+            }
             let status = command_runner
-                .run_command("tailscale", &[if *enable { "up" } else { "down" }])?
+                .run_command("tailscale", &["set", &format!("--hostname={hostname}")])?
                 .status;
             Ok(status.success())
         }
-        TailscaleAction::SetExitNode(node) => {
-            if set_exit_node(node) {
-                check_mullvad().await?;
-                Ok(true)
-            } else {
-                check_mullvad().await?;
-                Ok(false)
+        TailscaleAction::SetOperator(user) => {
+            if !confirm(
+                config,
+                &tr_args("confirm-tailscale-operator", &[("user", user)]),
+            )? {
+                return Ok(false);
+            }
+            // Setting the operator needs root, unless already the operator
+            let status = command_runner
+                .run_privileged("tailscale", &["set", &format!("--operator={user}")])?
+                .status;
+            if status.success() {
+                notify(
+                    config,
+                    &tr("tailscale"),
+                    &tr_args("tailscale-operator-set", &[("user", user)]),
+                );
             }
+            Ok(status.success())
+        }
+        TailscaleAction::SetEnable(false, _) => {
+            let status = command_runner.run_command("tailscale", &["down"])?.status;
+            Ok(status.success())
         }
-        TailscaleAction::SetShields(enable) => {
+        TailscaleAction::SelectMullvadCountry(country) => {
+            let mut nodes: Vec<ExitNode> = get_allowed_exit_nodes(config, command_runner)
+                .into_iter()
+                .filter(|node| node.is_mullvad() && node.country == country.name)
+                .collect();
+            nodes.sort_by(|a, b| a.city.cmp(&b.city));
+            let random = random_node_entry(Some(&country.name));
+            let mut entries = vec![random.clone()];
+            entries.extend(nodes.iter().map(ExitNode::to_city_entry));
+            let selection = select_from_menu(config, &entries)?;
+            if selection == random {
+                return apply_random_exit_node(config, nodes);
+            }
+            match nodes.iter().find(|node| node.to_city_entry() == selection) {
+                Some(node) => apply_exit_node(config, &node.to_entry()),
+                None => Ok(false),
+            }
+        }
+        TailscaleAction::FilterMullvad(_) => select_mullvad_filter(config).await,
+        TailscaleAction::SetRandomExitNode(country) => {
+            set_random_mullvad_exit_node(config, country.as_deref(), command_runner)
+        }
+        TailscaleAction::SetExitNode(node) => apply_exit_node(config, node),
+        TailscaleAction::SetSuggestedExitNode => {
+            let Some(node) = get_suggested_exit_node(command_runner) else {
+                notify(config, &tr("tailscale"), &tr("tailscale-no-suggestion"));
+                return Ok(false);
+            };
+            let success = set_exit_node(config, &node);
+            let expected_country = get_expected_country(&node).filter(|_| success);
+            spawn_connectivity_check(config, expected_country.as_deref())?;
+            Ok(success)
+        }
+        TailscaleAction::CopyPeerIp(peer) => {
+            let copied = copy_to_clipboard(&peer.ip)?;
+            notify(
+                config,
+                &tr("tailscale"),
+                &tr_args(
+                    if copied {
+                        "tailscale-peer-ip-copied"
+                    } else {
+                        "tailscale-peer-ip"
+                    },
+                    &[("peer", &peer.name), ("ip", &peer.ip)],
+                ),
+            );
+            Ok(copied)
+        }
+        TailscaleAction::PingPeer(peer) => {
+            let output = command_runner.run_command("tailscale", &["ping", &peer.ip])?;
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            match parse_ping_output(&stdout) {
+                Some(summary) => {
+                    notify(
+                        config,
+                        &tr("tailscale"),
+                        &tr_args(
+                            "tailscale-ping-result",
+                            &[("peer", &peer.name), ("summary", &summary)],
+                        ),
+                    );
+                    Ok(true)
+                }
+                None => {
+                    notify(
+                        config,
+                        &tr("tailscale"),
+                        &tr_args("tailscale-no-pong", &[("peer", &peer.name)]),
+                    );
+                    Ok(false)
+                }
+            }
+        }
+        TailscaleAction::SetAdvertiseRoute(route, advertise) => {
+            let mut routes = get_advertised_routes(command_runner);
+            routes.retain(|advertised| advertised != route);
+            if *advertise {
+                routes.push(route.clone());
+            }
+            // The exit node routes are kept by tailscale when only the subnets are set
+            let status = command_runner
+                .run_command(
+                    "tailscale",
+                    &["set", &format!("--advertise-routes={}", routes.join(","))],
+                )?
+                .status;
+            Ok(status.success())
+        }
+        TailscaleAction::SetDriveShare(name, true) => {
+            let share = config
+                .drive_shares
+                .iter()
+                .find(|share| &share.name == name)
+                .ok_or_else(|| format!("Drive share {name} not found"))?;
+            let status = command_runner
+                .run_command("tailscale", &["drive", "share", name, &share.path])?
+                .status;
+            Ok(status.success())
+        }
+        TailscaleAction::SetDriveShare(name, false) => {
+            let status = command_runner
+                .run_command("tailscale", &["drive", "unshare", name])?
+                .status;
+            Ok(status.success())
+        }
+        TailscaleAction::StartFunnel(name) => {
+            let preset = config
+                .funnel_presets
+                .iter()
+                .find(|preset| &preset.name == name)
+                .ok_or_else(|| format!("Funnel preset {name} not found"))?;
             let status = command_runner
                 .run_command(
                     "tailscale",
                     &[
-                        "set",
-                        "--shields-up",
-                        if *enable { "true" } else { "false" },
+                        "funnel",
+                        "--bg",
+                        &format!("--https={}", preset.port),
+                        &format!("--set-path={}", preset.path),
+                        &preset.target,
+                    ],
+                )?
+                .status;
+            Ok(status.success())
+        }
+        TailscaleAction::StopServe(serve) => {
+            let status = command_runner
+                .run_command(
+                    "tailscale",
+                    &[
+                        if serve.funnel { "funnel" } else { "serve" },
+                        &format!("--{}={}", serve.protocol, serve.port),
+                        "off",
                     ],
                 )?
                 .status;
@@ -304,9 +892,16 @@ pub async fn handle_tailscale_action(
     }
 }
 
+/// Checks if the tailscaled daemon answers, whether Tailscale is enabled or not.
+pub fn is_tailscaled_running(command_runner: &dyn CommandRunner) -> bool {
+    command_runner
+        .run_query("tailscale", &["status", "--json"])
+        .is_ok_and(|output| output.status.success())
+}
+
 /// Checks if Tailscale is currently enabled.
 pub fn is_tailscale_enabled(command_runner: &dyn CommandRunner) -> Result<bool, Box<dyn Error>> {
-    let output = command_runner.run_command("tailscale", &["status"])?;
+    let output = command_runner.run_query("tailscale", &["status"])?;
 
     if output.status.success() {
         let stdout = String::from_utf8_lossy(&output.stdout);
@@ -314,3 +909,237 @@ pub fn is_tailscale_enabled(command_runner: &dyn CommandRunner) -> Result<bool,
     }
     Ok(false)
 }
+
+/// Retrieves the expiry date of the node key from `tailscale status --json`.
+pub fn get_key_expiry(command_runner: &dyn CommandRunner) -> Option<String> {
+    let output = command_runner
+        .run_query("tailscale", &["status", "--json"])
+        .ok()?;
+    let json: Value = serde_json::from_slice(&output.stdout).ok()?;
+    let expiry = json.get("Self")?.get("KeyExpiry")?.as_str()?;
+    Some(expiry.chars().take(10).collect())
+}
+
+/// Retrieves the hostname of this machine in the tailnet from `tailscale status --json`.
+pub fn get_tailscale_hostname(command_runner: &dyn CommandRunner) -> Option<String> {
+    let output = command_runner
+        .run_query("tailscale", &["status", "--json"])
+        .ok()?;
+    let json: Value = serde_json::from_slice(&output.stdout).ok()?;
+    let hostname = json.get("Self")?.get("HostName")?.as_str()?;
+    Some(hostname.to_string())
+}
+
+/// Returns the current user when not already the Tailscale operator, to offer making it one.
+pub fn get_operator_candidate(command_runner: &dyn CommandRunner) -> Option<String> {
+    let user = std::env::var("USER").ok().filter(|user| user != "root")?;
+    let prefs = get_tailscale_prefs(command_runner).ok()?;
+    (prefs["OperatorUser"].as_str() != Some(user.as_str())).then_some(user)
+}
+
+/// Checks if a failed `tailscale` command asked for an operator to run without root.
+pub fn is_operator_error(failures: &[String]) -> bool {
+    failures
+        .iter()
+        .any(|failure| failure.starts_with("tailscale ") && failure.contains("--operator"))
+}
+
+/// Offers to make the current user the Tailscale operator after a permission error.
+pub async fn offer_operator_fix(
+    config: &Config,
+    command_runner: &dyn CommandRunner,
+) -> Result<bool, Box<dyn Error>> {
+    let Some(user) = get_operator_candidate(command_runner) else {
+        return Ok(false);
+    };
+    handle_tailscale_action(config, &TailscaleAction::SetOperator(user), command_runner).await
+}
+
+/// Retrieves the current Tailscale preferences from `tailscale debug prefs`.
+pub fn get_tailscale_prefs(command_runner: &dyn CommandRunner) -> Result<Value, Box<dyn Error>> {
+    let output = command_runner.run_query("tailscale", &["debug", "prefs"])?;
+
+    if !output.status.success() {
+        return Err("Failed to read Tailscale preferences".into());
+    }
+
+    Ok(serde_json::from_slice(&output.stdout)?)
+}
+
+/// Reads a boolean Tailscale preference, defaulting to false when unavailable.
+pub fn get_tailscale_pref(prefs: &Value, key: &str) -> bool {
+    prefs.get(key).and_then(Value::as_bool).unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{fixture, MockCommandRunner};
+
+    fn status() -> Value {
+        serde_json::from_str(&fixture("tailscale/status.json")).unwrap()
+    }
+
+    #[test]
+    fn parses_exit_nodes() {
+        let runner = MockCommandRunner::new()
+            .expect_fixture(
+                "tailscale",
+                &["exit-node", "list"],
+                "tailscale/exit-node-list.txt",
+            )
+            .expect_fixture("tailscale", &["status", "--json"], "tailscale/status.json");
+        let nodes = get_exit_nodes(&runner, &["se-sto-wg-005.mullvad.ts.net".to_string()]);
+
+        let summary: Vec<_> = nodes
+            .iter()
+            .map(|node| {
+                (
+                    node.ip.as_str(),
+                    node.country.as_str(),
+                    node.city.as_str(),
+                    node.active,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("100.101.102.103", "-", "-", false),
+                ("100.86.12.40", "Switzerland", "Zurich", false),
+                ("100.92.3.17", "Sweden", "Gothenburg", true),
+            ]
+        );
+        assert!(!nodes[0].is_mullvad() && nodes[1].is_mullvad());
+
+        let countries = group_mullvad_countries(&nodes);
+        assert_eq!(countries.len(), 2);
+        assert_eq!(countries[0].name, "Sweden");
+        assert!(countries[0].active);
+        assert!(!countries[1].active);
+    }
+
+    #[test]
+    fn reads_active_exit_node_and_key_expiry() {
+        let runner = MockCommandRunner::new()
+            .expect_fixture("tailscale", &["status", "--json"], "tailscale/status.json")
+            .expect_fixture("tailscale", &["status", "--json"], "tailscale/status.json");
+        assert_eq!(
+            get_active_exit_node(&runner),
+            "se-got-wg-002.mullvad.ts.net"
+        );
+        assert_eq!(get_key_expiry(&runner).as_deref(), Some("2027-03-14"));
+    }
+
+    #[test]
+    fn reads_hostname_and_detects_operator_errors() {
+        let runner = MockCommandRunner::new().expect_fixture(
+            "tailscale",
+            &["status", "--json"],
+            "tailscale/status.json",
+        );
+        assert_eq!(get_tailscale_hostname(&runner).as_deref(), Some("laptop"));
+
+        let failures = [
+            "tailscale set --exit-node=: Access denied: prefs write access denied\n\n\
+             Use 'sudo tailscale set' or 'sudo tailscale set --operator=$USER' once."
+                .to_string(),
+        ];
+        assert!(is_operator_error(&failures));
+        assert!(!is_operator_error(&[
+            "nmcli connection up Home: failed".to_string()
+        ]));
+    }
+
+    #[test]
+    fn parses_peers_without_mullvad_nodes() {
+        let peers = parse_peers(&status());
+        let summary: Vec<_> = peers
+            .iter()
+            .map(|peer| (peer.name.as_str(), peer.ip.as_str(), peer.online))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("desktop", "100.70.1.3", true),
+                ("homeserver", "100.101.102.103", true),
+                ("phone", "100.70.1.2", false),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_serve_status() {
+        let status = serde_json::from_str(&fixture("tailscale/serve-status.json")).unwrap();
+        assert_eq!(
+            parse_serve_status(&status),
+            [
+                ServePort {
+                    port: 443,
+                    protocol: "https".to_string(),
+                    funnel: true,
+                },
+                ServePort {
+                    port: 2222,
+                    protocol: "tcp".to_string(),
+                    funnel: false,
+                },
+                ServePort {
+                    port: 8443,
+                    protocol: "https".to_string(),
+                    funnel: false,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn summarizes_last_pong() {
+        assert_eq!(
+            parse_ping_output(&fixture("tailscale/ping.txt")).as_deref(),
+            Some("direct via 192.168.1.20:41641, 3ms")
+        );
+        assert_eq!(
+            parse_ping_output("pong from homeserver (100.101.102.103) via DERP(fra) in 48ms")
+                .as_deref(),
+            Some("relayed through DERP(fra), 48ms")
+        );
+        assert_eq!(parse_ping_output("timeout"), None);
+    }
+
+    #[test]
+    fn parses_advertised_routes() {
+        let prefs = serde_json::json!({
+            "AdvertiseRoutes": ["192.168.1.0/24", "0.0.0.0/0", "::/0", "10.0.0.0/8"]
+        });
+        assert_eq!(
+            parse_advertised_routes(&prefs),
+            ["192.168.1.0/24", "10.0.0.0/8"]
+        );
+        assert!(
+            parse_advertised_routes(&serde_json::json!({ "AdvertiseRoutes": null })).is_empty()
+        );
+    }
+
+    #[test]
+    fn reports_stopped_tailscale() {
+        let runner = MockCommandRunner::new()
+            .expect("tailscale", &["status"], "Tailscale is stopped.\n")
+            .expect_failure("tailscale", &["status"]);
+        assert!(!is_tailscale_enabled(&runner).unwrap());
+        assert!(!is_tailscale_enabled(&runner).unwrap());
+    }
+
+    #[test]
+    fn tolerates_missing_tailscale() {
+        let runner = MockCommandRunner::new()
+            .expect_missing("tailscale", &["exit-node", "list"])
+            .expect_missing("tailscale", &["status", "--json"])
+            .expect_failure("tailscale", &["status", "--json"])
+            .expect_missing("tailscale", &["status"]);
+        assert!(get_exit_nodes(&runner, &[]).is_empty());
+        assert_eq!(get_active_exit_node(&runner), "");
+        assert_eq!(get_key_expiry(&runner), None);
+        assert!(is_tailscale_enabled(&runner).is_err());
+    }
+}
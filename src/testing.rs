@@ -0,0 +1,118 @@
+use crate::command::CommandRunner;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::io::ErrorKind;
+use std::os::unix::process::ExitStatusExt;
+use std::path::PathBuf;
+use std::process::{ExitStatus, Output};
+
+/// Invocation expected by the mock, answered with a canned output.
+struct Expectation {
+    command: String,
+    args: Vec<String>,
+    output: Result<Output, ErrorKind>,
+}
+
+/// Command runner replaying canned outputs for an expected sequence of invocations.
+///
+/// Panics on any invocation that is not the next expected one, and when dropped with
+/// expectations left, so a test fails whenever the commands run differ from the recorded ones.
+#[derive(Default)]
+pub struct MockCommandRunner {
+    expectations: RefCell<VecDeque<Expectation>>,
+}
+
+impl MockCommandRunner {
+    /// Creates a mock expecting no invocation.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Expects an invocation, answering it with a successful output.
+    pub fn expect(self, command: &str, args: &[&str], stdout: &str) -> Self {
+        self.expect_output(command, args, output(0, stdout))
+    }
+
+    /// Expects an invocation, answering it with the content of a fixture file.
+    pub fn expect_fixture(self, command: &str, args: &[&str], name: &str) -> Self {
+        self.expect(command, args, &fixture(name))
+    }
+
+    /// Expects an invocation, answering it with a failure and an empty output.
+    pub fn expect_failure(self, command: &str, args: &[&str]) -> Self {
+        self.expect_output(command, args, output(1, ""))
+    }
+
+    /// Expects an invocation of a command that is not installed.
+    pub fn expect_missing(self, command: &str, args: &[&str]) -> Self {
+        self.push(command, args, Err(ErrorKind::NotFound))
+    }
+
+    /// Expects an invocation, answering it with the given output.
+    pub fn expect_output(self, command: &str, args: &[&str], output: Output) -> Self {
+        self.push(command, args, Ok(output))
+    }
+
+    fn push(self, command: &str, args: &[&str], output: Result<Output, ErrorKind>) -> Self {
+        self.expectations.borrow_mut().push_back(Expectation {
+            command: command.to_string(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+            output,
+        });
+        self
+    }
+}
+
+impl CommandRunner for MockCommandRunner {
+    fn run_command(&self, command: &str, args: &[&str]) -> Result<Output, std::io::Error> {
+        let invocation = format!("{command} {}", args.join(" "));
+        let expectation = self
+            .expectations
+            .borrow_mut()
+            .pop_front()
+            .unwrap_or_else(|| panic!("unexpected invocation: {invocation}"));
+        assert_eq!(
+            invocation,
+            format!("{} {}", expectation.command, expectation.args.join(" ")),
+            "invocation differs from the expected one"
+        );
+        expectation.output.map_err(std::io::Error::from)
+    }
+}
+
+impl Drop for MockCommandRunner {
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            return;
+        }
+        let remaining: Vec<_> = self
+            .expectations
+            .get_mut()
+            .iter()
+            .map(|expectation| format!("{} {}", expectation.command, expectation.args.join(" ")))
+            .collect();
+        assert!(
+            remaining.is_empty(),
+            "expected invocations not run: {remaining:?}"
+        );
+    }
+}
+
+/// Builds a command output with the given exit code and standard output.
+pub fn output(code: i32, stdout: &str) -> Output {
+    Output {
+        status: ExitStatus::from_raw(code << 8),
+        stdout: stdout.as_bytes().to_vec(),
+        stderr: Vec::new(),
+    }
+}
+
+/// Reads a fixture file from `tests/fixtures`, e.g. `nmcli/device-wifi.txt`.
+pub fn fixture(name: &str) -> String {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("fixtures")
+        .join(name);
+    std::fs::read_to_string(&path)
+        .unwrap_or_else(|err| panic!("failed to read fixture {}: {err}", path.display()))
+}
@@ -0,0 +1,292 @@
+use crate::bluetooth::is_bluetooth_powered;
+use crate::command::{is_command_installed, CommandRunner};
+use crate::format_entry;
+use crate::i18n::tr;
+use crate::networkmanager::{
+    get_nm_connection_setting, get_nm_primary_connection, set_nm_ipv6, set_nm_metered,
+};
+use crate::rfkill::{
+    get_rfkill_states, is_airplane_mode_on, is_rfkill_blocked, set_rfkill_blocked, AIRPLANE_RADIOS,
+};
+use crate::tailscale::{get_tailscale_pref, get_tailscale_prefs};
+use crate::wifi::{get_wifi_power_save, set_wifi_power_save};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::cell::OnceCell;
+use std::error::Error;
+
+/// Boolean settings shown with their current state, offering the opposite.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ToggleId {
+    AcceptDns,
+    AcceptRoutes,
+    AirplaneMode,
+    BluetoothPower,
+    BluetoothRadio,
+    ExitNodeAllowLan,
+    Ipv6,
+    Metered,
+    Shields,
+    WifiPowerSave,
+    WifiRadio,
+}
+
+/// Setter of a toggle, given the state and the Wi-Fi interface, returning whether the
+/// state was applied.
+type ToggleSetter = fn(bool, &str, &dyn CommandRunner) -> Result<bool, Box<dyn Error>>;
+
+/// Declaration of a toggle: its entry, how to read its state and how to set it.
+pub struct Toggle {
+    pub name: &'static str,
+    pub prefix: &'static str,
+    pub get: fn(&ToggleContext) -> Option<bool>,
+    pub set: ToggleSetter,
+}
+
+/// Action setting a toggle to the given state.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ToggleAction {
+    pub id: ToggleId,
+    pub enable: bool,
+}
+
+/// State shared by the toggle getters, read once per menu launch.
+pub struct ToggleContext<'a> {
+    command_runner: &'a dyn CommandRunner,
+    wifi_interface: &'a str,
+    tailscale_prefs: OnceCell<Option<Value>>,
+    rfkill_states: OnceCell<Option<Vec<(String, bool)>>>,
+    primary_connection: OnceCell<Option<String>>,
+}
+
+impl<'a> ToggleContext<'a> {
+    /// Creates a context reading the state through the given command runner.
+    pub fn new(command_runner: &'a dyn CommandRunner) -> Self {
+        Self {
+            command_runner,
+            wifi_interface: "",
+            tailscale_prefs: OnceCell::new(),
+            rfkill_states: OnceCell::new(),
+            primary_connection: OnceCell::new(),
+        }
+    }
+
+    /// Sets the Wi-Fi interface read by the Wi-Fi toggles.
+    pub fn with_wifi_interface(mut self, wifi_interface: &'a str) -> Self {
+        self.wifi_interface = wifi_interface;
+        self
+    }
+
+    /// Reads a boolean Tailscale preference, if the preferences are available.
+    fn tailscale_pref(&self, key: &str) -> Option<bool> {
+        self.tailscale_prefs
+            .get_or_init(|| get_tailscale_prefs(self.command_runner).ok())
+            .as_ref()
+            .map(|prefs| get_tailscale_pref(prefs, key))
+    }
+
+    /// Reads the rfkill radio states, if rfkill is installed.
+    fn rfkill_states(&self) -> Option<&[(String, bool)]> {
+        self.rfkill_states
+            .get_or_init(|| {
+                is_command_installed("rfkill")
+                    .then(|| get_rfkill_states(self.command_runner).ok())
+                    .flatten()
+            })
+            .as_deref()
+    }
+
+    /// Reads the NetworkManager connection carrying the traffic, if any.
+    fn primary_connection(&self) -> Option<&str> {
+        self.primary_connection
+            .get_or_init(|| {
+                is_command_installed("nmcli")
+                    .then(|| get_nm_primary_connection(self.command_runner).ok())
+                    .flatten()
+                    .flatten()
+            })
+            .as_deref()
+    }
+
+    /// Checks if a radio type is unblocked, if such a radio is present.
+    fn rfkill_unblocked(&self, kind: &str) -> Option<bool> {
+        let states = self.rfkill_states()?;
+        states
+            .iter()
+            .any(|(radio, _)| radio == kind)
+            .then(|| !is_rfkill_blocked(states, kind))
+    }
+}
+
+impl ToggleId {
+    /// Returns the declaration of the toggle.
+    pub fn toggle(self) -> Toggle {
+        match self {
+            ToggleId::AcceptDns => Toggle {
+                name: "accept-dns",
+                prefix: "tailscale",
+                get: |context| context.tailscale_pref("CorpDNS"),
+                set: |enable, _, command_runner| {
+                    set_tailscale_flag("accept-dns", enable, command_runner)
+                },
+            },
+            ToggleId::AcceptRoutes => Toggle {
+                name: "accept-routes",
+                prefix: "tailscale",
+                get: |context| context.tailscale_pref("RouteAll"),
+                set: |enable, _, command_runner| {
+                    set_tailscale_flag("accept-routes", enable, command_runner)
+                },
+            },
+            ToggleId::AirplaneMode => Toggle {
+                name: "airplane-mode",
+                prefix: "system",
+                get: |context| context.rfkill_states().map(is_airplane_mode_on),
+                set: |enable, _, command_runner| {
+                    set_rfkill_blocked(&AIRPLANE_RADIOS, enable, command_runner)
+                },
+            },
+            ToggleId::BluetoothPower => Toggle {
+                name: "bluetooth-power",
+                prefix: "bluetooth",
+                get: |context| {
+                    is_command_installed("bluetoothctl")
+                        .then(|| is_bluetooth_powered(context.command_runner).ok())
+                        .flatten()
+                },
+                set: |enable, _, command_runner| {
+                    let status = command_runner
+                        .run_command(
+                            "bluetoothctl",
+                            &["power", if enable { "on" } else { "off" }],
+                        )?
+                        .status;
+                    Ok(status.success())
+                },
+            },
+            ToggleId::BluetoothRadio => Toggle {
+                name: "bluetooth-radio",
+                prefix: "system",
+                get: |context| context.rfkill_unblocked("bluetooth"),
+                set: |enable, _, command_runner| {
+                    set_rfkill_blocked(&["bluetooth"], !enable, command_runner)
+                },
+            },
+            ToggleId::ExitNodeAllowLan => Toggle {
+                name: "exit-node-allow-lan",
+                prefix: "tailscale",
+                get: |context| context.tailscale_pref("ExitNodeAllowLANAccess"),
+                set: |enable, _, command_runner| {
+                    set_tailscale_flag("exit-node-allow-lan-access", enable, command_runner)
+                },
+            },
+            ToggleId::Ipv6 => Toggle {
+                name: "ipv6",
+                prefix: "system",
+                get: |context| {
+                    let name = context.primary_connection()?;
+                    get_nm_connection_setting(name, "ipv6.method", context.command_runner)
+                        .ok()
+                        .map(|method| method != "disabled" && method != "ignore")
+                },
+                set: |enable, _, command_runner| {
+                    let name =
+                        get_nm_primary_connection(command_runner)?.ok_or("No active connection")?;
+                    set_nm_ipv6(&name, enable, command_runner)
+                },
+            },
+            ToggleId::Metered => Toggle {
+                name: "metered",
+                prefix: "system",
+                get: |context| {
+                    let name = context.primary_connection()?;
+                    get_nm_connection_setting(name, "connection.metered", context.command_runner)
+                        .ok()
+                        .map(|metered| metered == "yes")
+                },
+                set: |enable, _, command_runner| {
+                    let name =
+                        get_nm_primary_connection(command_runner)?.ok_or("No active connection")?;
+                    set_nm_metered(&name, enable, command_runner)
+                },
+            },
+            ToggleId::Shields => Toggle {
+                name: "shields",
+                prefix: "tailscale",
+                get: |context| context.tailscale_pref("ShieldsUp"),
+                set: |enable, _, command_runner| {
+                    set_tailscale_flag("shields-up", enable, command_runner)
+                },
+            },
+            ToggleId::WifiPowerSave => Toggle {
+                name: "wifi-power-save",
+                prefix: "wifi",
+                get: |context| {
+                    is_command_installed("iw")
+                        .then(|| {
+                            get_wifi_power_save(context.wifi_interface, context.command_runner)
+                                .ok()
+                                .flatten()
+                        })
+                        .flatten()
+                },
+                set: |enable, wifi_interface, command_runner| {
+                    set_wifi_power_save(wifi_interface, enable, command_runner)
+                },
+            },
+            ToggleId::WifiRadio => Toggle {
+                name: "wifi-radio",
+                prefix: "system",
+                get: |context| context.rfkill_unblocked("wlan"),
+                set: |enable, _, command_runner| {
+                    set_rfkill_blocked(&["wlan"], !enable, command_runner)
+                },
+            },
+        }
+    }
+}
+
+/// Applies a boolean `tailscale set` flag.
+fn set_tailscale_flag(
+    flag: &str,
+    enable: bool,
+    command_runner: &dyn CommandRunner,
+) -> Result<bool, Box<dyn Error>> {
+    let status = command_runner
+        .run_command("tailscale", &["set", &format!("--{flag}={enable}")])?
+        .status;
+    Ok(status.success())
+}
+
+/// Builds the actions of the toggles whose state can be read, offering the opposite state.
+pub fn get_toggle_actions(ids: &[ToggleId], context: &ToggleContext) -> Vec<ToggleAction> {
+    ids.iter()
+        .filter_map(|id| {
+            let enabled = (id.toggle().get)(context)?;
+            Some(ToggleAction {
+                id: *id,
+                enable: !enabled,
+            })
+        })
+        .collect()
+}
+
+/// Formats the entry of a toggle action, showing the current state.
+pub fn toggle_entry(action: &ToggleAction) -> String {
+    let toggle = action.id.toggle();
+    format_entry(
+        toggle.prefix,
+        if action.enable { "❌" } else { "✅" },
+        &tr(&format!("toggle-{}", toggle.name)),
+    )
+}
+
+/// Sets a toggle to the state of the action.
+pub fn handle_toggle_action(
+    action: &ToggleAction,
+    wifi_interface: &str,
+    command_runner: &dyn CommandRunner,
+) -> Result<bool, Box<dyn Error>> {
+    (action.id.toggle().set)(action.enable, wifi_interface, command_runner)
+}
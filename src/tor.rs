@@ -0,0 +1,117 @@
+use crate::command::CommandRunner;
+use crate::connectivity::{get_geolocation, get_json};
+use crate::i18n::{tr, tr_args};
+use crate::notifications::{new_notification, notify, show_notification};
+use crate::Config;
+use notify_rust::Urgency;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+/// Endpoint telling whether a request came through Tor.
+const TOR_CHECK_URL: &str = "https://check.torproject.org/api/ip";
+
+/// Represents actions on the Tor service.
+#[derive(Debug, Deserialize, Serialize)]
+pub enum TorAction {
+    CheckCircuit,
+    SetEnabled(bool),
+}
+
+/// Returns the default systemd unit running Tor, `arti` being the other common one.
+pub fn default_tor_service() -> String {
+    "tor".to_string()
+}
+
+/// Returns the default SOCKS proxy of Tor, resolving names through Tor too.
+pub fn default_tor_proxy() -> String {
+    "socks5h://127.0.0.1:9050".to_string()
+}
+
+/// Checks if the Tor service is running.
+fn is_tor_running(config: &Config, command_runner: &dyn CommandRunner) -> bool {
+    command_runner
+        .run_query("systemctl", &["is-active", "--quiet", &config.tor_service])
+        .is_ok_and(|output| output.status.success())
+}
+
+/// Retrieves the Tor actions, offering the circuit check while the service runs.
+pub fn get_tor_actions(config: &Config, command_runner: &dyn CommandRunner) -> Vec<TorAction> {
+    let running = is_tor_running(config, command_runner);
+    let mut actions = vec![TorAction::SetEnabled(!running)];
+    if running {
+        actions.push(TorAction::CheckCircuit);
+    }
+    actions
+}
+
+/// Handles a Tor action.
+pub fn handle_tor_action(
+    config: &Config,
+    action: &TorAction,
+    command_runner: &dyn CommandRunner,
+) -> Result<bool, Box<dyn Error>> {
+    match action {
+        TorAction::CheckCircuit => {
+            spawn_tor_check()?;
+            Ok(true)
+        }
+        TorAction::SetEnabled(enable) => {
+            let status = command_runner
+                .run_command(
+                    "systemctl",
+                    &[if *enable { "start" } else { "stop" }, &config.tor_service],
+                )?
+                .status;
+            if status.success() && *enable {
+                spawn_tor_check()?;
+            }
+            Ok(status.success())
+        }
+    }
+}
+
+/// Checks that traffic sent to the Tor proxy leaves through Tor, and notifies the exit location.
+pub async fn check_tor(config: &Config) -> Result<(), Box<dyn Error>> {
+    let timeout = Duration::from_secs(config.connectivity_timeout.max(15));
+    let proxy = Some(config.tor_proxy.as_str());
+    let check = get_json(TOR_CHECK_URL, timeout, proxy).await?;
+
+    if check["IsTor"].as_bool() != Some(true) {
+        let mut notification = new_notification(
+            config,
+            &tr("tor-not-in-effect"),
+            &tr_args("tor-not-in-effect-body", &[("proxy", &config.tor_proxy)]),
+        );
+        notification.urgency(Urgency::Critical);
+        show_notification(config, &notification);
+        return Ok(());
+    }
+
+    let exit_ip = check["IP"].as_str().unwrap_or_default();
+    let body = match get_geolocation(&config.connectivity_url, timeout, proxy).await {
+        Ok(location) => tr_args(
+            "tor-exit-location",
+            &[
+                ("ip", exit_ip),
+                ("city", &location.city),
+                ("country", &location.country),
+            ],
+        ),
+        Err(_) => tr_args("tor-exit", &[("ip", exit_ip)]),
+    };
+    notify(config, &tr("tor-connected"), &body);
+    Ok(())
+}
+
+/// Runs the Tor check in a detached process, as the circuit may take a while to build.
+fn spawn_tor_check() -> Result<(), Box<dyn Error>> {
+    Command::new(std::env::current_exe()?)
+        .arg("check-tor")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    Ok(())
+}
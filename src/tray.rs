@@ -0,0 +1,140 @@
+use crate::builtin_ui::{group_by_section, Row};
+use crate::daemon::{Event, Status};
+use crate::i18n::{tr, tr_args};
+use ksni::menu::{StandardItem, SubMenu};
+use ksni::{MenuItem, ToolTip, TrayMethods};
+use std::error::Error;
+use std::process::Command;
+use std::sync::mpsc::{self, Sender};
+use tracing::warn;
+
+/// Handle updating the tray once the daemon refreshed the state.
+pub type TrayHandle = ksni::Handle<NetworkTray>;
+
+/// Status notifier item showing the connection state, with the actions in its menu.
+pub struct NetworkTray {
+    pub status: Status,
+    events: Sender<Event>,
+}
+
+/// Shows the tray icon, applying the actions selected in its menu through the daemon.
+pub async fn spawn_tray(
+    status: Status,
+    events: Sender<Event>,
+) -> Result<TrayHandle, Box<dyn Error>> {
+    Ok(NetworkTray { status, events }.spawn().await?)
+}
+
+/// Opens the menu in a new process, as when run from a key binding.
+fn open_menu() {
+    let result = std::env::current_exe().and_then(|path| Command::new(path).spawn());
+    if let Err(err) = result {
+        warn!("failed to open the menu: {err}");
+    }
+}
+
+impl NetworkTray {
+    /// Builds the menu item applying the action at the given index.
+    fn action_item(&self, index: usize) -> MenuItem<Self> {
+        let action = &self.status.actions[index];
+        let text = action
+            .entry
+            .split_once("- ")
+            .map_or(action.entry.as_str(), |(_, text)| text);
+        let id = action.id.clone();
+        let events = self.events.clone();
+        StandardItem {
+            // Underscores would otherwise mark access keys
+            label: text.trim().replace('_', "__"),
+            activate: Box::new(move |_| {
+                // The answer is not awaited, the daemon refreshes the tray once done
+                let (answer_sender, _) = mpsc::channel();
+                let _ = events.send(Event::Apply(id.clone(), answer_sender));
+            }),
+            ..Default::default()
+        }
+        .into()
+    }
+}
+
+impl ksni::Tray for NetworkTray {
+    fn id(&self) -> String {
+        env!("CARGO_PKG_NAME").to_string()
+    }
+
+    fn title(&self) -> String {
+        env!("CARGO_PKG_NAME").to_string()
+    }
+
+    fn icon_name(&self) -> String {
+        if self.status.exit_node.is_some() {
+            "network-vpn-symbolic"
+        } else if self.status.wifi.is_some() {
+            "network-wireless-symbolic"
+        } else {
+            "network-offline-symbolic"
+        }
+        .to_string()
+    }
+
+    fn tool_tip(&self) -> ToolTip {
+        let mut description = Vec::new();
+        if let Some(ssid) = &self.status.wifi {
+            description.push(tr_args("status-wifi", &[("ssid", ssid)]));
+        }
+        if let Some(exit_node) = &self.status.exit_node {
+            description.push(tr_args("status-exit-node", &[("node", exit_node)]));
+        }
+        ToolTip {
+            title: self.title(),
+            description: description.join("\n"),
+            ..Default::default()
+        }
+    }
+
+    fn activate(&mut self, _x: i32, _y: i32) {
+        open_menu();
+    }
+
+    /// Mirrors the menu, with a submenu per section.
+    fn menu(&self) -> Vec<MenuItem<Self>> {
+        let mut menu = vec![
+            StandardItem {
+                label: tr("tray-open-menu"),
+                activate: Box::new(|_| open_menu()),
+                ..Default::default()
+            }
+            .into(),
+            MenuItem::Separator,
+        ];
+
+        let entries: Vec<String> = self
+            .status
+            .actions
+            .iter()
+            .map(|action| action.entry.clone())
+            .collect();
+        let indices: Vec<usize> = (0..entries.len()).collect();
+        let mut section: Option<SubMenu<Self>> = None;
+        for row in group_by_section(&entries, &indices) {
+            match row {
+                Row::Header(label) => {
+                    menu.extend(section.take().map(MenuItem::from));
+                    section = Some(SubMenu {
+                        label: label.replace('_', "__"),
+                        ..Default::default()
+                    });
+                }
+                Row::Entry(index) => {
+                    let item = self.action_item(index);
+                    match &mut section {
+                        Some(section) => section.submenu.push(item),
+                        None => menu.push(item),
+                    }
+                }
+            }
+        }
+        menu.extend(section.map(MenuItem::from));
+        menu
+    }
+}
@@ -0,0 +1,135 @@
+use crate::command::CommandRunner;
+use dirs::state_dir;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::Duration;
+
+/// Time given to ssh to fail, e.g. on an unreachable host, before the tunnel is considered up.
+const STARTUP_DELAY: Duration = Duration::from_secs(1);
+
+/// Represents actions on the configured SSH tunnels.
+#[derive(Debug, Deserialize, Serialize)]
+pub enum TunnelAction {
+    Start(String),
+    Stop(String),
+}
+
+/// SSH tunnel preset, opening a SOCKS proxy and/or local port forwards through a host.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct TunnelPreset {
+    pub name: String,
+    pub host: String,
+    #[serde(default)]
+    pub socks: Option<String>,
+    #[serde(default)]
+    pub forwards: Vec<String>,
+}
+
+/// Retrieves the tunnel actions, offering to stop the running tunnels and start the others.
+pub fn get_tunnel_actions(tunnels: &[TunnelPreset]) -> Vec<TunnelAction> {
+    tunnels
+        .iter()
+        .map(|tunnel| {
+            if read_pid(&get_pid_path(&tunnel.name)).is_some() {
+                TunnelAction::Stop(tunnel.name.clone())
+            } else {
+                TunnelAction::Start(tunnel.name.clone())
+            }
+        })
+        .collect()
+}
+
+/// Gets the path of the pid file of a tunnel.
+fn get_pid_path(name: &str) -> PathBuf {
+    state_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("network-dmenu")
+        .join("tunnels")
+        .join(format!("{name}.pid"))
+}
+
+/// Reads the pid of a running tunnel, ignoring pid files left over by dead processes,
+/// including zombies not reaped yet.
+fn read_pid(path: &Path) -> Option<u32> {
+    let pid: u32 = fs::read_to_string(path).ok()?.trim().parse().ok()?;
+    let command = fs::read_to_string(format!("/proc/{pid}/comm")).ok()?;
+    let stat = fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+    // The state follows the command name, which is in parentheses
+    let state = stat.rsplit_once(')')?.1.split_whitespace().next()?;
+    (command.trim() == "ssh" && state != "Z").then_some(pid)
+}
+
+/// Starts or stops a tunnel.
+pub fn handle_tunnel_action(
+    tunnels: &[TunnelPreset],
+    action: &TunnelAction,
+    command_runner: &dyn CommandRunner,
+) -> Result<bool, Box<dyn Error>> {
+    match action {
+        TunnelAction::Start(name) => {
+            let tunnel = tunnels
+                .iter()
+                .find(|tunnel| &tunnel.name == name)
+                .ok_or_else(|| format!("tunnel {name} not found"))?;
+            start_tunnel(tunnel)
+        }
+        TunnelAction::Stop(name) => {
+            let path = get_pid_path(name);
+            let Some(pid) = read_pid(&path) else {
+                return Ok(false);
+            };
+            let status = command_runner
+                .run_command("kill", &[&pid.to_string()])?
+                .status;
+            if status.success() {
+                let _ = fs::remove_file(path);
+            }
+            Ok(status.success())
+        }
+    }
+}
+
+/// Spawns ssh in the background, reaped by a thread once it exits, and records its pid.
+///
+/// ssh runs in batch mode since there is no terminal to answer prompts, so the host
+/// must be reachable with a key or an agent.
+fn start_tunnel(tunnel: &TunnelPreset) -> Result<bool, Box<dyn Error>> {
+    let mut args = vec![
+        "-N".to_string(),
+        "-o".to_string(),
+        "BatchMode=yes".to_string(),
+        "-o".to_string(),
+        "ExitOnForwardFailure=yes".to_string(),
+    ];
+    if let Some(socks) = &tunnel.socks {
+        args.extend(["-D".to_string(), socks.clone()]);
+    }
+    for forward in &tunnel.forwards {
+        args.extend(["-L".to_string(), forward.clone()]);
+    }
+    args.push(tunnel.host.clone());
+
+    let mut child = Command::new("ssh")
+        .args(&args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    thread::sleep(STARTUP_DELAY);
+    if child.try_wait()?.is_some() {
+        return Ok(false);
+    }
+
+    let path = get_pid_path(&tunnel.name);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, child.id().to_string())?;
+    // Waited on so that a tunnel dying while the daemon runs does not linger as a zombie
+    thread::spawn(move || child.wait());
+    Ok(true)
+}
@@ -1,5 +1,18 @@
-use std::io::Write;
-use std::process::{Command, Stdio};
+use crate::capabilities::CapabilityError;
+use crate::command::is_command_installed;
+use crate::i18n::tr_args;
+use crate::Config;
+use dirs::runtime_dir;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::Duration;
+
+/// Time given to the notification daemon and the image viewer to load a QR code image.
+const QR_CODE_LIFETIME: Duration = Duration::from_secs(5);
 
 /// Converts network strength to a visual representation.
 pub fn convert_network_strength(line: &str) -> String {
@@ -21,21 +34,76 @@ pub fn convert_network_strength(line: &str) -> String {
     network_strength
 }
 
-/// Prompts the user for a password using `pinentry-gnome3`.
-pub fn prompt_for_password(ssid: &str) -> Result<String, Box<dyn std::error::Error>> {
-    let mut child = Command::new("pinentry-gnome3")
+/// Pinentry programs tried in order when `pinentry_cmd` is not configured.
+const PINENTRY_PROGRAMS: [&str; 6] = [
+    "pinentry-gnome3",
+    "pinentry-qt",
+    "pinentry-gtk-2",
+    "pinentry-gtk",
+    "pinentry-curses",
+    "pinentry",
+];
+
+/// Prompts the user for a Wi-Fi password.
+pub fn prompt_for_password(
+    config: &Config,
+    ssid: &str,
+) -> Result<String, Box<dyn std::error::Error>> {
+    prompt_with_pinentry(config, &tr_args("prompt-wifi-password", &[("ssid", ssid)]))
+}
+
+/// Returns the configured pinentry command, or the first installed pinentry program.
+pub fn get_pinentry_command(config: &Config) -> Result<String, Box<dyn std::error::Error>> {
+    if let Some(command) = &config.pinentry_cmd {
+        return Ok(command.clone());
+    }
+
+    PINENTRY_PROGRAMS
+        .iter()
+        .find(|program| is_command_installed(program))
+        .map(|program| program.to_string())
+        .ok_or_else(|| CapabilityError::MissingPinentry.into())
+}
+
+/// Prompts the user for a secret with the given description.
+///
+/// Programs named `pinentry*` are driven through the Assuan protocol. Any other
+/// command, e.g. `rofi -dmenu -password -p {prompt}`, is run as a launcher whose
+/// standard output is the secret, with `{prompt}` replaced by the description.
+pub fn prompt_with_pinentry(
+    config: &Config,
+    description: &str,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let pinentry_command = get_pinentry_command(config)?;
+    let mut parts = pinentry_command.split_whitespace();
+    let program = parts.next().ok_or("Empty pinentry command")?;
+    let args: Vec<String> = parts
+        .map(|arg| arg.replace("{prompt}", description))
+        .collect();
+
+    let is_pinentry = Path::new(program)
+        .file_name()
+        .is_some_and(|name| name.to_string_lossy().starts_with("pinentry"));
+
+    let mut child = Command::new(program)
+        .args(&args)
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
         .spawn()?;
 
-    {
+    if is_pinentry {
         let stdin = child.stdin.as_mut().ok_or("Failed to open stdin")?;
-        write!(stdin, "SETDESC Enter {ssid} password\nGETPIN\n")?;
+        write!(stdin, "SETDESC {description}\nGETPIN\n")?;
     }
+    drop(child.stdin.take());
 
     let output = child.wait_with_output()?;
-
     let stdout = String::from_utf8_lossy(&output.stdout);
+
+    if !is_pinentry {
+        return Ok(stdout.trim_end_matches('\n').to_string());
+    }
+
     let password_line = stdout
         .lines()
         .find(|line| line.starts_with("D "))
@@ -44,3 +112,189 @@ pub fn prompt_for_password(ssid: &str) -> Result<String, Box<dyn std::error::Err
 
     Ok(password)
 }
+
+/// Quotes a value for safe interpolation into a `sh -c` command line.
+pub fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// Copies a text to the clipboard with `wl-copy`, `xclip` or `xsel`.
+///
+/// Returns false when none of them is installed.
+pub fn copy_to_clipboard(text: &str) -> Result<bool, Box<dyn std::error::Error>> {
+    let (command, args): (&str, &[&str]) =
+        if std::env::var_os("WAYLAND_DISPLAY").is_some() && is_command_installed("wl-copy") {
+            ("wl-copy", &[])
+        } else if is_command_installed("xclip") {
+            ("xclip", &["-selection", "clipboard"])
+        } else if is_command_installed("xsel") {
+            ("xsel", &["--clipboard", "--input"])
+        } else {
+            return Ok(false);
+        };
+
+    let mut child = Command::new(command)
+        .args(args)
+        .stdin(Stdio::piped())
+        .spawn()?;
+    child
+        .stdin
+        .take()
+        .ok_or("Failed to open stdin")?
+        .write_all(text.as_bytes())?;
+    Ok(child.wait()?.success())
+}
+
+/// Terminals tried in order when `$TERMINAL` is not set.
+const TERMINALS: [&str; 5] = ["foot", "alacritty", "kitty", "wezterm", "xterm"];
+
+/// Runs a command in `$TERMINAL`, or else the first installed of the common terminals.
+pub fn open_in_terminal(command: &[&str]) -> Result<Child, Box<dyn std::error::Error>> {
+    let terminal = std::env::var("TERMINAL")
+        .ok()
+        .or_else(|| {
+            TERMINALS
+                .iter()
+                .find(|terminal| is_command_installed(terminal))
+                .map(|terminal| terminal.to_string())
+        })
+        .ok_or("No terminal found, set $TERMINAL")?;
+    let mut terminal_command = Command::new(&terminal);
+    match terminal.rsplit('/').next() {
+        // Both take the command as arguments, without -e.
+        Some("foot" | "kitty") => {}
+        Some("wezterm") => {
+            terminal_command.args(["start", "--"]);
+        }
+        _ => {
+            terminal_command.arg("-e");
+        }
+    }
+    Ok(terminal_command.args(command).spawn()?)
+}
+
+/// Generates a random alphanumeric password of the given length.
+pub fn generate_password(length: usize) -> Result<String, Box<dyn std::error::Error>> {
+    const CHARSET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz23456789";
+    let mut bytes = vec![0u8; length];
+    File::open("/dev/urandom")?.read_exact(&mut bytes)?;
+    Ok(bytes
+        .iter()
+        .map(|byte| CHARSET[*byte as usize % CHARSET.len()] as char)
+        .collect())
+}
+
+/// Picks a random index below the given length.
+pub fn random_index(length: usize) -> Result<usize, Box<dyn std::error::Error>> {
+    let mut bytes = [0u8; 8];
+    File::open("/dev/urandom")?.read_exact(&mut bytes)?;
+    Ok((u64::from_ne_bytes(bytes) % length.max(1) as u64) as usize)
+}
+
+/// Writes a state or cache file, creating its directory first.
+pub fn write_state_file(
+    path: Option<PathBuf>,
+    contents: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let path = path.ok_or("Failed to find state directory")?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, contents)?;
+    Ok(())
+}
+
+/// Writes a file only readable by the user to the runtime directory, replacing the
+/// previous one.
+pub fn write_private_file(
+    name: &str,
+    contents: &[u8],
+) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let path = runtime_dir().unwrap_or_else(std::env::temp_dir).join(name);
+    // A file created by someone else in the shared temporary directory is never reused
+    let _ = fs::remove_file(&path);
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(&path)?
+        .write_all(contents)?;
+    Ok(path)
+}
+
+/// QR code image holding a Wi-Fi password, removed once dropped.
+pub struct QrCode {
+    path: PathBuf,
+}
+
+impl QrCode {
+    /// Returns the path of the image.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Removes the image once the notification and the image viewer had time to load it.
+    ///
+    /// The removal is left to a detached process, so that the action returns right away and
+    /// the image is still removed once the menu has exited.
+    pub fn remove_once_displayed(mut self) {
+        let delay = QR_CODE_LIFETIME.as_secs().to_string();
+        let remover = Command::new("sh")
+            .args(["-c", "sleep \"$0\" && rm -f -- \"$1\"", &delay])
+            .arg(&self.path)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        if let Ok(mut remover) = remover {
+            self.path = PathBuf::new();
+            thread::spawn(move || remover.wait());
+        }
+    }
+}
+
+impl Drop for QrCode {
+    fn drop(&mut self) {
+        if !self.path.as_os_str().is_empty() {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// Renders a `WIFI:` QR code image with `qrencode`, if installed.
+pub fn generate_wifi_qr_code(ssid: &str, security: &str, password: &str) -> Option<QrCode> {
+    if !is_command_installed("qrencode") {
+        return None;
+    }
+
+    let escape = |value: &str| {
+        value.chars().fold(String::new(), |mut escaped, c| {
+            if matches!(c, '\\' | ';' | ',' | ':' | '"') {
+                escaped.push('\\');
+            }
+            escaped.push(c);
+            escaped
+        })
+    };
+    let payload = format!(
+        "WIFI:T:{security};S:{};P:{};;",
+        escape(ssid),
+        escape(password)
+    );
+
+    // Read from the standard input, so that the password does not show up in `ps`
+    let mut qrencode = Command::new("qrencode")
+        .args(["-o", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .ok()?;
+    qrencode.stdin.take()?.write_all(payload.as_bytes()).ok()?;
+    let output = qrencode
+        .wait_with_output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    write_private_file("network-dmenu-wifi-qr.png", &output.stdout)
+        .map(|path| QrCode { path })
+        .ok()
+}
@@ -0,0 +1,224 @@
+use crate::command::{escalate, CommandRunner};
+use crate::i18n::tr_args;
+use crate::networkmanager::set_nm_vpn_active;
+use crate::utils::prompt_with_pinentry;
+use crate::Config;
+use dirs::runtime_dir;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Represents actions on NetworkManager VPN connections and configured VPN sessions.
+#[derive(Debug, Deserialize, Serialize)]
+pub enum VpnAction {
+    Connect(String),
+    Disconnect(String),
+    StartSession(String),
+    StopSession(String),
+}
+
+/// VPN session defined in the config, started outside of NetworkManager.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct VpnSession {
+    pub name: String,
+    #[serde(flatten)]
+    pub provider: VpnProvider,
+}
+
+/// Program running a VPN session.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(tag = "provider", rename_all = "lowercase")]
+pub enum VpnProvider {
+    /// OpenVPN client managed by a systemd unit, such as `openvpn-client@work`.
+    Openvpn { unit: String },
+    /// OpenConnect client, run as root in the background.
+    Openconnect {
+        server: String,
+        #[serde(default)]
+        user: Option<String>,
+        #[serde(default = "default_openconnect_protocol")]
+        protocol: String,
+        #[serde(default)]
+        otp: bool,
+    },
+}
+
+/// Returns the default OpenConnect protocol, Cisco AnyConnect.
+fn default_openconnect_protocol() -> String {
+    "anyconnect".to_string()
+}
+
+/// Retrieves the configured sessions, offering to stop the running ones and start the others.
+pub fn get_vpn_session_actions(
+    sessions: &[VpnSession],
+    command_runner: &dyn CommandRunner,
+) -> Vec<VpnAction> {
+    sessions
+        .iter()
+        .map(|session| {
+            if is_session_running(session, command_runner) {
+                VpnAction::StopSession(session.name.clone())
+            } else {
+                VpnAction::StartSession(session.name.clone())
+            }
+        })
+        .collect()
+}
+
+/// Checks if a session is running.
+fn is_session_running(session: &VpnSession, command_runner: &dyn CommandRunner) -> bool {
+    match &session.provider {
+        VpnProvider::Openvpn { unit } => command_runner
+            .run_query("systemctl", &["is-active", "--quiet", unit])
+            .is_ok_and(|output| output.status.success()),
+        VpnProvider::Openconnect { .. } => read_pid(&get_pid_path(&session.name)).is_some(),
+    }
+}
+
+/// Handles a VPN action with the handler of its provider.
+pub fn handle_vpn_action(
+    config: &Config,
+    action: &VpnAction,
+    command_runner: &dyn CommandRunner,
+) -> Result<bool, Box<dyn Error>> {
+    match action {
+        VpnAction::Connect(name) => set_nm_vpn_active(name, true, command_runner),
+        VpnAction::Disconnect(name) => set_nm_vpn_active(name, false, command_runner),
+        VpnAction::StartSession(name) | VpnAction::StopSession(name) => {
+            let start = matches!(action, VpnAction::StartSession(_));
+            let session = config
+                .vpn_sessions
+                .iter()
+                .find(|session| &session.name == name)
+                .ok_or_else(|| format!("VPN session {name} not found"))?;
+            match &session.provider {
+                VpnProvider::Openvpn { unit } => {
+                    let status = command_runner
+                        .run_command("systemctl", &[if start { "start" } else { "stop" }, unit])?
+                        .status;
+                    Ok(status.success())
+                }
+                VpnProvider::Openconnect { .. } if start => start_openconnect(config, session),
+                VpnProvider::Openconnect { .. } => stop_openconnect(&session.name, command_runner),
+            }
+        }
+    }
+}
+
+/// Gets the path of the pid file of an OpenConnect session.
+fn get_pid_path(name: &str) -> PathBuf {
+    runtime_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join(format!("network-dmenu-openconnect-{name}.pid"))
+}
+
+/// Reads the pid of a running session, ignoring pid files left over by dead processes.
+fn read_pid(path: &Path) -> Option<u32> {
+    let pid = fs::read_to_string(path).ok()?.trim().parse().ok()?;
+    Path::new(&format!("/proc/{pid}")).exists().then_some(pid)
+}
+
+/// Starts an OpenConnect session, prompting for the password and the one-time password.
+fn start_openconnect(config: &Config, session: &VpnSession) -> Result<bool, Box<dyn Error>> {
+    let VpnProvider::Openconnect {
+        server,
+        user,
+        protocol,
+        otp,
+    } = &session.provider
+    else {
+        return Ok(false);
+    };
+
+    let mut secrets = prompt_with_pinentry(
+        config,
+        &tr_args("prompt-vpn-password", &[("name", &session.name)]),
+    )?;
+    if *otp {
+        let code = prompt_with_pinentry(
+            config,
+            &tr_args("prompt-vpn-otp", &[("name", &session.name)]),
+        )?;
+        secrets = format!("{secrets}\n{code}");
+    }
+
+    let mut args = vec![
+        "--background".to_string(),
+        format!("--pid-file={}", get_pid_path(&session.name).display()),
+        format!("--protocol={protocol}"),
+        "--passwd-on-stdin".to_string(),
+    ];
+    args.extend(user.iter().map(|user| format!("--user={user}")));
+    args.push(server.clone());
+
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let command_line = escalate(&config.escalation, "openconnect", &args)?;
+    let mut child = Command::new(&command_line[0])
+        .args(&command_line[1..])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        writeln!(stdin, "{secrets}")?;
+    }
+    Ok(child.wait()?.success())
+}
+
+/// Stops an OpenConnect session by signaling its process.
+fn stop_openconnect(
+    name: &str,
+    command_runner: &dyn CommandRunner,
+) -> Result<bool, Box<dyn Error>> {
+    let path = get_pid_path(name);
+    let Some(pid) = read_pid(&path) else {
+        return Ok(false);
+    };
+    let status = command_runner
+        .run_privileged("kill", &[&pid.to_string()])?
+        .status;
+    if status.success() {
+        let _ = fs::remove_file(path);
+    }
+    Ok(status.success())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Deserialize)]
+    struct Sessions {
+        vpn_sessions: Vec<VpnSession>,
+    }
+
+    #[test]
+    fn parses_sessions_of_each_provider() {
+        let sessions: Sessions = toml::from_str(
+            r#"
+            [[vpn_sessions]]
+            name = "Home"
+            provider = "openvpn"
+            unit = "openvpn-client@home"
+
+            [[vpn_sessions]]
+            name = "Work"
+            provider = "openconnect"
+            server = "vpn.example.com"
+            user = "jdoe"
+            otp = true
+            "#,
+        )
+        .unwrap();
+
+        assert!(
+            matches!(&sessions.vpn_sessions[0].provider, VpnProvider::Openvpn { unit } if unit == "openvpn-client@home")
+        );
+        assert!(matches!(
+            &sessions.vpn_sessions[1].provider,
+            VpnProvider::Openconnect { protocol, otp: true, .. } if protocol == "anyconnect"
+        ));
+    }
+}
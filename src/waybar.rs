@@ -0,0 +1,71 @@
+use crate::connectivity::get_expected_country;
+use crate::context::Context;
+use crate::get_backend_wifi_networks;
+use crate::icons::apply_icon_theme;
+use crate::tailscale::{get_active_exit_node, is_tailscale_enabled};
+use serde_json::json;
+use std::error::Error;
+
+/// Prints the network state as a waybar custom module JSON object.
+pub fn print_waybar_status(context: &Context<'_>) -> Result<(), Box<dyn Error>> {
+    let Context {
+        args,
+        config,
+        capabilities,
+        command_runner,
+    } = *context;
+    let mut text = Vec::new();
+    let mut tooltip = Vec::new();
+    let mut class = Vec::new();
+
+    if !args.no_wifi {
+        let network =
+            get_backend_wifi_networks(config, &args.wifi_interface, false, command_runner)?
+                .into_iter()
+                .find(|network| network.connected);
+        match network {
+            Some(network) => {
+                text.push(format!("📶 {} {}%", network.ssid, network.signal));
+                tooltip.push(format!("Wi-Fi: {} ({}%)", network.ssid, network.signal));
+                class.push("wifi-connected");
+            }
+            None => {
+                text.push("❌ Wi-Fi".to_string());
+                tooltip.push("Wi-Fi: disconnected".to_string());
+                class.push("wifi-disconnected");
+            }
+        }
+    }
+
+    if !args.no_tailscale && capabilities.tailscale {
+        if is_tailscale_enabled(command_runner)? {
+            tooltip.push("Tailscale: enabled".to_string());
+            class.push("tailscale-enabled");
+
+            let exit_node = get_active_exit_node(command_runner);
+            if !exit_node.is_empty() {
+                let country = get_expected_country(&exit_node);
+                text.push(format!(
+                    "🌿 {}",
+                    country.as_deref().unwrap_or(exit_node.as_str())
+                ));
+                tooltip.push(match &country {
+                    Some(country) => format!("Exit node: {exit_node} ({country})"),
+                    None => format!("Exit node: {exit_node}"),
+                });
+                class.push("exit-node");
+            }
+        } else {
+            tooltip.push("Tailscale: disabled".to_string());
+            class.push("tailscale-disabled");
+        }
+    }
+
+    let status = json!({
+        "text": apply_icon_theme(&text.join(" "), config.icons, &config.icon_overrides),
+        "tooltip": tooltip.join("\n"),
+        "class": class,
+    });
+    println!("{status}");
+    Ok(())
+}
@@ -0,0 +1,388 @@
+use crate::columns::pad;
+use crate::command::{is_command_installed, read_output_lines, CommandRunner};
+use crate::networkmanager::get_nm_active_wifi_connection;
+use crate::utils::{convert_network_strength, write_state_file};
+use crate::{parse_wifi_action, WifiAction};
+use dirs::state_dir;
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Output;
+
+/// Wi-Fi network as reported by a backend.
+#[derive(Debug, Clone)]
+pub struct WifiNetwork {
+    pub ssid: String,
+    pub security: String,
+    pub signal: u8,
+    pub connected: bool,
+}
+
+impl WifiNetwork {
+    /// Converts the network into its menu action.
+    pub fn into_action(self) -> WifiAction {
+        WifiAction::Network(format!(
+            "{} {}\t{}\t{}",
+            if self.connected { "✅" } else { "📶" },
+            pad(&self.ssid, 25),
+            pad(&self.security, 11),
+            convert_network_strength(&strength_to_bars(self.signal)),
+        ))
+    }
+}
+
+/// Messages of nmcli and iwctl reporting a rejected password, in lowercase.
+const AUTH_FAILURE_MESSAGES: &[&str] = &[
+    "secrets were required",
+    "no-secrets",
+    "psk: property is invalid",
+    "invalid passphrase",
+    "invalid format",
+];
+
+/// Result of a connection attempt, with the reason reported by the backend on failure.
+#[derive(Debug, PartialEq)]
+pub enum ConnectionOutcome {
+    Connected,
+    AuthFailed(String),
+    Failed(String),
+}
+
+impl ConnectionOutcome {
+    /// Classifies the output of a connection command, detecting rejected passwords.
+    pub fn from_output(output: &Output) -> Self {
+        if output.status.success() {
+            return ConnectionOutcome::Connected;
+        }
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let reason = [stderr.trim(), stdout.trim()]
+            .into_iter()
+            .find(|text| !text.is_empty())
+            .unwrap_or_default()
+            .trim_start_matches("Error: ")
+            .to_string();
+        let lowercase = reason.to_lowercase();
+        if AUTH_FAILURE_MESSAGES
+            .iter()
+            .any(|message| lowercase.contains(message))
+        {
+            ConnectionOutcome::AuthFailed(reason)
+        } else {
+            ConnectionOutcome::Failed(reason)
+        }
+    }
+}
+
+/// Keeps the names of the saved networks whose SSID is absent from the scan results.
+pub fn get_out_of_range_networks(
+    known_networks: Vec<(String, String)>,
+    visible: &[WifiAction],
+) -> Vec<String> {
+    let visible: Vec<&str> = visible
+        .iter()
+        .filter_map(|network| match network {
+            WifiAction::Network(display) => parse_wifi_action(display).ok().map(|(ssid, _)| ssid),
+            _ => None,
+        })
+        .collect();
+    known_networks
+        .into_iter()
+        .filter(|(_, ssid)| !visible.contains(&ssid.as_str()))
+        .map(|(name, _)| name)
+        .collect()
+}
+
+/// Converts a signal strength percentage to nmcli-style star bars.
+pub fn strength_to_bars(strength: u8) -> String {
+    let bars = match strength {
+        81.. => 4,
+        56..=80 => 3,
+        31..=55 => 2,
+        6..=30 => 1,
+        _ => 0,
+    };
+    "*".repeat(bars)
+}
+
+/// Deduplicates networks by SSID and sorts them by connection state and signal.
+///
+/// A deduplicated network keeps the strongest signal of its access points and is
+/// connected if any of them is.
+pub fn dedup_and_sort_networks(
+    networks: Vec<WifiNetwork>,
+    dedup: bool,
+    sort: bool,
+) -> Vec<WifiNetwork> {
+    let mut result: Vec<WifiNetwork> = Vec::with_capacity(networks.len());
+    for network in networks {
+        match result
+            .iter_mut()
+            .find(|existing| dedup && existing.ssid == network.ssid)
+        {
+            Some(existing) => {
+                existing.connected |= network.connected;
+                if network.signal > existing.signal {
+                    existing.signal = network.signal;
+                    existing.security = network.security;
+                }
+            }
+            None => result.push(network),
+        }
+    }
+
+    if sort {
+        result.sort_by(|a, b| b.connected.cmp(&a.connected).then(b.signal.cmp(&a.signal)));
+    }
+    result
+}
+
+/// Wireless interface with its NetworkManager device state, e.g. `connected` or `unmanaged`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WifiInterface {
+    pub name: String,
+    pub state: String,
+}
+
+/// Lists the wireless interfaces from NetworkManager, or else from `iw dev`.
+///
+/// `iw` interfaces are reported `connected` when associated, and `disconnected` in
+/// managed mode, as NetworkManager would.
+pub fn get_wifi_interfaces(
+    command_runner: &dyn CommandRunner,
+) -> Result<Vec<WifiInterface>, Box<dyn Error>> {
+    if let Ok(output) = command_runner.run_query(
+        "nmcli",
+        &[
+            "--colors",
+            "no",
+            "-t",
+            "-f",
+            "DEVICE,TYPE,STATE",
+            "device",
+            "status",
+        ],
+    ) {
+        if output.status.success() {
+            return Ok(read_output_lines(&output)?
+                .iter()
+                .filter_map(|line| {
+                    let mut fields = line.splitn(3, ':');
+                    let name = fields.next()?;
+                    (fields.next()? == "wifi").then(|| WifiInterface {
+                        name: name.to_string(),
+                        state: fields.next().unwrap_or_default().to_string(),
+                    })
+                })
+                .collect());
+        }
+    }
+
+    let output = match command_runner.run_query("iw", &["dev"]) {
+        Ok(output) if output.status.success() => output,
+        _ => return Ok(Vec::new()),
+    };
+    let mut interfaces: Vec<WifiInterface> = Vec::new();
+    for line in read_output_lines(&output)? {
+        let line = line.trim();
+        if let Some(name) = line.strip_prefix("Interface ") {
+            interfaces.push(WifiInterface {
+                name: name.to_string(),
+                state: "unmanaged".to_string(),
+            });
+        } else if let Some(interface) = interfaces.last_mut() {
+            if line == "type managed" && interface.state != "connected" {
+                interface.state = "disconnected".to_string();
+            } else if line.starts_with("ssid ") {
+                interface.state = "connected".to_string();
+            }
+        }
+    }
+    Ok(interfaces)
+}
+
+/// Picks the interface to use by default: the connected one, or else the first managed one.
+pub fn default_wifi_interface(interfaces: &[WifiInterface]) -> Option<&str> {
+    interfaces
+        .iter()
+        .min_by_key(|interface| match interface.state.as_str() {
+            "connected" => 0,
+            "unmanaged" | "unavailable" => 2,
+            _ => 1,
+        })
+        .map(|interface| interface.name.as_str())
+}
+
+/// Gets the path of the file recording the wireless interface selected last.
+fn get_interface_state_path() -> Option<PathBuf> {
+    Some(state_dir()?.join("network-dmenu").join("wifi-interface"))
+}
+
+/// Reads the wireless interface selected last.
+pub fn get_selected_wifi_interface() -> Option<String> {
+    let name = fs::read_to_string(get_interface_state_path()?).ok()?;
+    Some(name.trim().to_string())
+}
+
+/// Records the selected wireless interface, used by the next launches without `--wifi-interface`.
+pub fn save_selected_wifi_interface(interface: &str) -> Result<(), Box<dyn Error>> {
+    write_state_file(get_interface_state_path(), interface)
+}
+
+/// Reads whether power saving is enabled on a wireless interface.
+pub fn get_wifi_power_save(
+    interface: &str,
+    command_runner: &dyn CommandRunner,
+) -> Result<Option<bool>, Box<dyn Error>> {
+    let output = command_runner.run_query("iw", &["dev", interface, "get", "power_save"])?;
+    if !output.status.success() {
+        return Ok(None);
+    }
+    Ok(read_output_lines(&output)?.iter().find_map(|line| {
+        match line.trim().strip_prefix("Power save:")?.trim() {
+            "on" => Some(true),
+            "off" => Some(false),
+            _ => None,
+        }
+    }))
+}
+
+/// Enables or disables power saving on a wireless interface.
+///
+/// The active NetworkManager connection is updated too, as NetworkManager applies its
+/// own setting again on every connection.
+pub fn set_wifi_power_save(
+    interface: &str,
+    enable: bool,
+    command_runner: &dyn CommandRunner,
+) -> Result<bool, Box<dyn Error>> {
+    let mut success = true;
+    if is_command_installed("nmcli") {
+        if let Some(connection) = get_nm_active_wifi_connection(command_runner)? {
+            success &= command_runner
+                .run_command(
+                    "nmcli",
+                    &[
+                        "connection",
+                        "modify",
+                        &connection,
+                        "802-11-wireless.powersave",
+                        if enable { "3" } else { "2" },
+                    ],
+                )?
+                .status
+                .success();
+        }
+    }
+    success &= command_runner
+        .run_privileged(
+            "iw",
+            &[
+                "dev",
+                interface,
+                "set",
+                "power_save",
+                if enable { "on" } else { "off" },
+            ],
+        )?
+        .status
+        .success();
+    Ok(success)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{output, MockCommandRunner};
+
+    #[test]
+    fn reads_power_save() {
+        let args = &["dev", "wlan0", "get", "power_save"];
+        let runner = MockCommandRunner::new()
+            .expect("iw", args, "Power save: on\n")
+            .expect_failure("iw", args);
+
+        assert_eq!(get_wifi_power_save("wlan0", &runner).unwrap(), Some(true));
+        assert_eq!(get_wifi_power_save("wlan0", &runner).unwrap(), None);
+    }
+
+    #[test]
+    fn detects_rejected_passwords() {
+        let failure = |stderr: &str| Output {
+            stderr: stderr.as_bytes().to_vec(),
+            ..output(4, "")
+        };
+        assert_eq!(
+            ConnectionOutcome::from_output(&failure(
+                "Error: Connection activation failed: Secrets were required, but not provided.\n"
+            )),
+            ConnectionOutcome::AuthFailed(
+                "Connection activation failed: Secrets were required, but not provided."
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            ConnectionOutcome::from_output(&failure("Error: No network with SSID 'Cafe' found.")),
+            ConnectionOutcome::Failed("No network with SSID 'Cafe' found.".to_string())
+        );
+        assert_eq!(
+            ConnectionOutcome::from_output(&output(0, "")),
+            ConnectionOutcome::Connected
+        );
+    }
+
+    #[test]
+    fn lists_out_of_range_networks() {
+        let visible = [
+            WifiNetwork {
+                ssid: "Cafe Wifi".to_string(),
+                security: "WPA2".to_string(),
+                signal: 50,
+                connected: false,
+            }
+            .into_action(),
+            WifiAction::Disconnect,
+        ];
+        let known = vec![
+            ("Home".to_string(), "Home".to_string()),
+            ("Cafe".to_string(), "Cafe Wifi".to_string()),
+        ];
+        assert_eq!(get_out_of_range_networks(known, &visible), ["Home"]);
+    }
+
+    #[test]
+    fn detects_wifi_interfaces() {
+        let nmcli_args = &[
+            "--colors",
+            "no",
+            "-t",
+            "-f",
+            "DEVICE,TYPE,STATE",
+            "device",
+            "status",
+        ];
+        let runner = MockCommandRunner::new()
+            .expect(
+                "nmcli",
+                nmcli_args,
+                "wlp3s0:wifi:disconnected\nwlx00c0ca:wifi:connected\n\
+                 p2p-dev-wlp3s0:wifi-p2p:disconnected\nlo:loopback:unmanaged\n",
+            )
+            .expect_failure("nmcli", nmcli_args)
+            .expect(
+                "iw",
+                &["dev"],
+                "phy#0\n\tInterface wlan1\n\t\ttype monitor\n\
+                 \tInterface wlan0\n\t\ttype managed\n",
+            );
+
+        let interfaces = get_wifi_interfaces(&runner).unwrap();
+        let names: Vec<_> = interfaces.iter().map(|i| i.name.as_str()).collect();
+        assert_eq!(names, ["wlp3s0", "wlx00c0ca"]);
+        assert_eq!(default_wifi_interface(&interfaces), Some("wlx00c0ca"));
+
+        let interfaces = get_wifi_interfaces(&runner).unwrap();
+        assert_eq!(interfaces[0].state, "unmanaged");
+        assert_eq!(default_wifi_interface(&interfaces), Some("wlan0"));
+    }
+}
@@ -0,0 +1,127 @@
+use crate::command::{is_command_installed, read_output_lines, CommandRunner};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::fs;
+
+/// Directory of the wg-quick configurations.
+const WIREGUARD_DIR: &str = "/etc/wireguard";
+
+/// Represents actions on wg-quick tunnels.
+#[derive(Debug, Deserialize, Serialize)]
+pub enum WireguardAction {
+    Down(String),
+    Up(String),
+}
+
+/// Retrieves the known wg-quick tunnels, offering to tear down the active ones and bring up the others.
+pub fn get_wireguard_actions(
+    command_runner: &dyn CommandRunner,
+) -> Result<Vec<WireguardAction>, Box<dyn Error>> {
+    let mut tunnels = get_config_tunnels();
+    if is_command_installed("systemctl") {
+        let output = command_runner.run_query(
+            "systemctl",
+            &[
+                "list-units",
+                "--all",
+                "--plain",
+                "--no-legend",
+                "--type=service",
+                "wg-quick@*",
+            ],
+        )?;
+        tunnels.extend(parse_unit_tunnels(&read_output_lines(&output)?));
+    }
+
+    let output =
+        command_runner.run_query("ip", &["-brief", "link", "show", "type", "wireguard"])?;
+    let active: BTreeSet<String> = read_output_lines(&output)?
+        .iter()
+        .filter_map(|line| line.split_whitespace().next())
+        .map(str::to_string)
+        .collect();
+
+    Ok(tunnels
+        .into_iter()
+        .map(|name| {
+            if active.contains(&name) {
+                WireguardAction::Down(name)
+            } else {
+                WireguardAction::Up(name)
+            }
+        })
+        .collect())
+}
+
+/// Lists the tunnels configured in `/etc/wireguard`, when it is readable.
+fn get_config_tunnels() -> BTreeSet<String> {
+    fs::read_dir(WIREGUARD_DIR)
+        .map(|entries| {
+            entries
+                .filter_map(Result::ok)
+                .filter_map(|entry| {
+                    let name = entry.file_name().into_string().ok()?;
+                    name.strip_suffix(".conf").map(str::to_string)
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Extracts the tunnel names of the `wg-quick@` units listed by systemctl.
+fn parse_unit_tunnels(lines: &[String]) -> BTreeSet<String> {
+    lines
+        .iter()
+        .filter_map(|line| {
+            line.split_whitespace()
+                .next()?
+                .strip_prefix("wg-quick@")?
+                .strip_suffix(".service")
+                .filter(|name| !name.is_empty())
+                .map(str::to_string)
+        })
+        .collect()
+}
+
+/// Brings a tunnel up or down through its systemd unit, or wg-quick as root without systemd.
+pub fn handle_wireguard_action(
+    action: &WireguardAction,
+    command_runner: &dyn CommandRunner,
+) -> Result<bool, Box<dyn Error>> {
+    let (up, name) = match action {
+        WireguardAction::Up(name) => (true, name),
+        WireguardAction::Down(name) => (false, name),
+    };
+    let output = if is_command_installed("systemctl") {
+        command_runner.run_command(
+            "systemctl",
+            &[
+                if up { "start" } else { "stop" },
+                &format!("wg-quick@{name}.service"),
+            ],
+        )?
+    } else {
+        command_runner.run_privileged("wg-quick", &[if up { "up" } else { "down" }, name])?
+    };
+    Ok(output.status.success())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_unit_tunnels() {
+        let lines = [
+            "wg-quick@home.service loaded active exited WireGuard via wg-quick(8) for home",
+            "wg-quick@work.service loaded inactive dead WireGuard via wg-quick(8) for work",
+            "wg-quick@.service not-found inactive dead wg-quick@.service",
+        ]
+        .map(String::from);
+        assert_eq!(
+            parse_unit_tunnels(&lines).into_iter().collect::<Vec<_>>(),
+            ["home", "work"]
+        );
+    }
+}